@@ -45,6 +45,52 @@ fn bench_kmer_speed(c: &mut Criterion) {
         });
     });
 
+    group.bench_function("Workspace kmer", |bench| {
+        use needletail::parser::FastaReader;
+        use needletail::sequence::Workspace;
+        bench.iter(|| {
+            let mut n_total = 0;
+            let mut n_canonical = 0;
+            let fasta_data = Cursor::new(data.clone());
+            let mut reader = FastaReader::new(fasta_data);
+            let mut ws = Workspace::new();
+            while let Some(record) = reader.next() {
+                let rec = record.unwrap();
+                let (seq, rc) = ws.normalize_and_reverse_complement(&rec, true);
+                for (_, _kmer, was_rc) in seq.canonical_kmers(ksize, rc) {
+                    if !was_rc {
+                        n_canonical += 1;
+                    }
+                    n_total += 1;
+                }
+            }
+            assert_eq!(718_007, n_total);
+            assert_eq!(350_983, n_canonical);
+        });
+    });
+
+    group.bench_function("Normalized kmer", |bench| {
+        use needletail::parser::FastaReader;
+        bench.iter(|| {
+            let mut n_total = 0;
+            let mut n_canonical = 0;
+            let fasta_data = Cursor::new(data.clone());
+            let mut reader = FastaReader::new(fasta_data);
+            while let Some(record) = reader.next() {
+                let rec = record.unwrap();
+                let mut kmers = rec.normalized_kmers(ksize, true);
+                while let Some((_, _kmer, was_rc)) = kmers.next() {
+                    if !was_rc {
+                        n_canonical += 1;
+                    }
+                    n_total += 1;
+                }
+            }
+            assert_eq!(718_007, n_total);
+            assert_eq!(350_983, n_canonical);
+        });
+    });
+
     group.bench_function("Bitkmer", |bench| {
         use needletail::parser::FastaReader;
         bench.iter(|| {
@@ -182,6 +228,68 @@ fn bench_fasta_file(c: &mut Criterion) {
     });
 }
 
-criterion_group!(io, bench_fasta_file, bench_fastq_file);
+fn bench_strip_returns(c: &mut Criterion) {
+    use needletail::parser::FastaReader;
+
+    let mut data: Vec<u8> = vec![];
+    let mut f = File::open("tests/data/28S.fasta").unwrap();
+    let _ = f.read_to_end(&mut data);
+
+    let mut group = c.benchmark_group("Newline stripping");
+
+    // scanning the raw sequence for line endings
+    group.bench_function("Scan", |bench| {
+        bench.iter(|| {
+            let fasta_data = Cursor::new(data.clone());
+            let mut reader = FastaReader::new(fasta_data);
+            let mut n_bases = 0;
+            while let Some(result) = reader.next() {
+                let record = result.unwrap();
+                n_bases += record.raw_seq().strip_returns().len();
+            }
+            assert_eq!(738_580, n_bases);
+        });
+    });
+
+    // copying the lines found by the parser
+    group.bench_function("Line blocks", |bench| {
+        bench.iter(|| {
+            let fasta_data = Cursor::new(data.clone());
+            let mut reader = FastaReader::new(fasta_data);
+            let mut n_bases = 0;
+            while let Some(result) = reader.next() {
+                let record = result.unwrap();
+                n_bases += record.seq().len();
+            }
+            assert_eq!(738_580, n_bases);
+        });
+    });
+}
+
+criterion_group!(io, bench_fasta_file, bench_fastq_file, bench_strip_returns);
+
+#[cfg(feature = "parallel")]
+fn bench_parallel_stats(c: &mut Criterion) {
+    use needletail::stats::parallel_file_stats;
+
+    let mut group = c.benchmark_group("Parallel FASTQ stats");
+    group.sample_size(10);
+    for n_threads in [1, 2, 4, 8] {
+        group.bench_function(format!("{n_threads} threads"), |bench| {
+            bench.iter(|| {
+                let stats =
+                    parallel_file_stats("tests/data/PRJNA271013_head.fq", n_threads).unwrap();
+                assert_eq!(stats.records, 2000);
+            });
+        });
+    }
+    group.finish();
+}
+
+#[cfg(feature = "parallel")]
+criterion_group!(parallel, bench_parallel_stats);
 
+#[cfg(feature = "parallel")]
+criterion_main!(kmers, io, parallel);
+#[cfg(not(feature = "parallel"))]
 criterion_main!(kmers, io);
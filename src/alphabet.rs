@@ -0,0 +1,155 @@
+//! Sets of symbols sequences are made of, to validate and normalize protein or RNA
+//! sequences without them being treated as DNA.
+use std::borrow::Cow;
+
+use crate::sequence::tables::IUPAC_DNA;
+
+/// Whether each byte is one of `symbols`, in upper or lower case
+const fn symbol_table(symbols: &[u8]) -> [bool; 256] {
+    let mut table = [false; 256];
+    let mut i = 0;
+    while i < symbols.len() {
+        table[symbols[i].to_ascii_uppercase() as usize] = true;
+        table[symbols[i].to_ascii_lowercase() as usize] = true;
+        i += 1;
+    }
+    table
+}
+
+const DNA: [bool; 256] = symbol_table(b"ACGTN");
+const RNA: [bool; 256] = symbol_table(b"ACGUN");
+const PROTEIN: [bool; 256] = symbol_table(b"ACDEFGHIKLMNPQRSTVWYBZJUOX*");
+
+/// The symbols a sequence can be made of, in upper or lower case. Gaps (`-`) aren't
+/// part of any alphabet but are kept by [`Alphabet::normalize`].
+///
+/// ```
+/// use needletail::alphabet::Alphabet;
+///
+/// assert!(Alphabet::Protein.is_valid(b"MKWVTFISLL*"));
+/// assert!(!Alphabet::Dna.is_valid(b"MKWV"));
+/// assert_eq!(Alphabet::Protein.normalize(b"mkw\nv.j#").as_ref(), b"MKWV-JX");
+/// assert_eq!(Alphabet::Dna.normalize(b"acgu\nry").as_ref(), b"ACGTNN");
+/// assert_eq!(Alphabet::Rna.normalize(b"ACGT").as_ref(), b"ACGU");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Alphabet {
+    /// `ACGT` and `N`
+    #[default]
+    Dna,
+    /// `ACGU` and `N`
+    Rna,
+    /// `ACGTN` and the IUPAC ambiguity codes `RYSWKMBDHV`
+    IupacDna,
+    /// The 20 standard amino acids, `U` (selenocysteine), `O` (pyrrolysine), the
+    /// ambiguity codes `B`, `Z`, `J` and `X` and `*` for stop codons
+    Protein,
+    /// Any other set of symbols, matched in either case, with the symbol that
+    /// replaces the others when normalizing
+    Custom { symbols: Vec<u8>, unknown: u8 },
+}
+
+impl Alphabet {
+    /// Whether `symbol` is part of the alphabet
+    #[inline]
+    pub fn contains(&self, symbol: u8) -> bool {
+        match self {
+            Alphabet::Dna => DNA[symbol as usize],
+            Alphabet::Rna => RNA[symbol as usize],
+            Alphabet::IupacDna => IUPAC_DNA[symbol as usize],
+            Alphabet::Protein => PROTEIN[symbol as usize],
+            Alphabet::Custom { symbols, .. } => {
+                symbols.iter().any(|s| s.eq_ignore_ascii_case(&symbol))
+            }
+        }
+    }
+
+    /// The symbol replacing the ones outside of the alphabet when normalizing: `N` for
+    /// nucleic acids and `X` for proteins
+    pub fn unknown(&self) -> u8 {
+        match self {
+            Alphabet::Dna | Alphabet::Rna | Alphabet::IupacDna => b'N',
+            Alphabet::Protein => b'X',
+            Alphabet::Custom { unknown, .. } => unknown.to_ascii_uppercase(),
+        }
+    }
+
+    /// Whether this is a DNA or RNA alphabet
+    pub fn is_nucleic(&self) -> bool {
+        matches!(self, Alphabet::Dna | Alphabet::Rna | Alphabet::IupacDna)
+    }
+
+    /// Position of the first symbol of `seq` outside of the alphabet, if any
+    pub fn first_invalid(&self, seq: &[u8]) -> Option<usize> {
+        seq.iter().position(|c| !self.contains(*c))
+    }
+
+    /// Whether all the symbols of `seq` are part of the alphabet
+    pub fn is_valid(&self, seq: &[u8]) -> bool {
+        self.first_invalid(seq).is_none()
+    }
+
+    /// Normalizes a sequence for this alphabet:
+    ///  - whitespace and line endings are removed
+    ///  - symbols are uppercased
+    ///  - `.` and `~` gaps are converted to `-`, which is kept
+    ///  - `U` is converted to `T` for DNA and `T` to `U` for RNA
+    ///  - anything else outside of the alphabet becomes [`Alphabet::unknown`]
+    ///
+    /// The sequence is only copied if something changed.
+    pub fn normalize<'a>(&self, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        let normalize_symbol = |c: u8| {
+            let upper = match (self, c.to_ascii_uppercase()) {
+                (Alphabet::Dna | Alphabet::IupacDna, b'U') => b'T',
+                (Alphabet::Rna, b'T') => b'U',
+                (_, upper) => upper,
+            };
+            match upper {
+                b'-' | b'.' | b'~' => b'-',
+                _ if self.contains(upper) => upper,
+                _ => self.unknown(),
+            }
+        };
+        let is_removed = |c: &u8| c.is_ascii_whitespace();
+        if !seq
+            .iter()
+            .any(|c| is_removed(c) || normalize_symbol(*c) != *c)
+        {
+            return seq.into();
+        }
+        seq.iter()
+            .filter(|c| !is_removed(c))
+            .map(|c| normalize_symbol(*c))
+            .collect::<Vec<u8>>()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alphabets() {
+        assert!(Alphabet::Dna.is_valid(b"ACGTNacgtn"));
+        assert_eq!(Alphabet::Dna.first_invalid(b"ACGR"), Some(3));
+        assert!(Alphabet::IupacDna.is_valid(b"ACGRYkm"));
+        assert!(!Alphabet::Rna.is_valid(b"ACGT"));
+        assert!(Alphabet::Protein.is_valid(b"ACDEFGHIKLMNPQRSTVWYBZJUOX*"));
+        assert!(!Alphabet::Protein.is_nucleic());
+
+        let custom = Alphabet::Custom {
+            symbols: b"01".to_vec(),
+            unknown: b'?',
+        };
+        assert!(custom.is_valid(b"0110"));
+        assert_eq!(custom.normalize(b"01 2").as_ref(), b"01?");
+
+        assert!(matches!(
+            Alphabet::Protein.normalize(b"MKWV-"),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(Alphabet::Dna.normalize(b"acgt"), Cow::Owned(_)));
+        assert_eq!(Alphabet::IupacDna.normalize(b"ryu~").as_ref(), b"RYT-");
+    }
+}
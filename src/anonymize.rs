@@ -0,0 +1,119 @@
+//! Replacing read names with stable tokens before sharing data, e.g. for clinical
+//! samples where read names can leak information about the run.
+use std::io::Write;
+
+use hmac_sha256::HMAC;
+
+use crate::errors::ParseError;
+use crate::parser::{id_key, mate_key, write_fasta, write_fastq, FastxReader, Format};
+
+/// Number of bytes of the HMAC kept in tokens, 128 bits to avoid collisions
+const TOKEN_BYTES: usize = 16;
+
+/// Returns the token replacing `id`: the hex encoded HMAC-SHA256 (truncated to 128 bits)
+/// keyed by `salt` of the id up to the first whitespace, without its `/1` or `/2`
+/// suffix. The suffix is kept so mates of a pair still share their token.
+///
+/// The rest of the header (after the first whitespace) is dropped.
+///
+/// ```
+/// use needletail::anonymize::anonymize_id;
+///
+/// let r1 = anonymize_id(b"M00123:1:000-A:1:1101:15589:1331/1 sample_42", b"secret");
+/// let r2 = anonymize_id(b"M00123:1:000-A:1:1101:15589:1331/2", b"secret");
+/// assert_eq!(r1.len(), 34);
+/// assert_eq!(r1[..32], r2[..32]);
+/// assert!(r1.ends_with(b"/1") && r2.ends_with(b"/2"));
+/// ```
+pub fn anonymize_id(id: &[u8], salt: &[u8]) -> Vec<u8> {
+    let name = mate_key(id);
+    let suffix = &id_key(id)[name.len()..];
+
+    let mac = HMAC::mac(name, salt);
+    let mut token = Vec::with_capacity(TOKEN_BYTES * 2 + suffix.len());
+    for byte in &mac[..TOKEN_BYTES] {
+        token.extend_from_slice(format!("{byte:02x}").as_bytes());
+    }
+    token.extend_from_slice(suffix);
+    token
+}
+
+/// Writes the records of `reader` to `writer` with their ids replaced by the tokens
+/// of [`anonymize_id`]. If `mapping` is set, a `original header<TAB>token` line is
+/// written to it for each record so the original names can be recovered by whoever
+/// holds it.
+///
+/// The same salt gives the same tokens across files and runs: it should be kept
+/// secret, otherwise tokens of known read names can be recomputed.
+///
+/// Returns the number of records written.
+pub fn anonymize(
+    reader: &mut dyn FastxReader,
+    writer: &mut dyn Write,
+    salt: &[u8],
+    mut mapping: Option<&mut dyn Write>,
+) -> Result<usize, ParseError> {
+    let mut count = 0;
+    while let Some(record) = reader.next() {
+        let record = record?;
+        let token = anonymize_id(record.id(), salt);
+        let line_ending = record.line_ending();
+        match record.format() {
+            Format::Fasta => write_fasta(&token, &record.seq(), writer, line_ending)?,
+            Format::Fastq => {
+                write_fastq(&token, &record.seq(), record.qual(), writer, line_ending)?
+            }
+        }
+        if let Some(mapping) = mapping.as_mut() {
+            mapping.write_all(record.id())?;
+            mapping.write_all(b"\t")?;
+            mapping.write_all(&token)?;
+            mapping.write_all(b"\n")?;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+    use crate::parser::LineEnding;
+
+    #[test]
+    fn test_anonymize() {
+        let fastq = b"@read1/1 1:N:0:ACGT\nACGT\n+\nIIII\n@read2\nTT\n+\nII\n";
+        let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+        let mut out = Vec::new();
+        let mut mapping = Vec::new();
+        let count = anonymize(&mut reader, &mut out, b"salt", Some(&mut mapping)).unwrap();
+        assert_eq!(count, 2);
+
+        let token1 = anonymize_id(b"read1/1", b"salt");
+        let token2 = anonymize_id(b"read2", b"salt");
+        let mut expected = Vec::new();
+        write_fastq(
+            &token1,
+            b"ACGT",
+            Some(b"IIII"),
+            &mut expected,
+            LineEnding::Unix,
+        )
+        .unwrap();
+        write_fastq(&token2, b"TT", Some(b"II"), &mut expected, LineEnding::Unix).unwrap();
+        assert_eq!(out, expected);
+        assert!(!String::from_utf8(out).unwrap().contains("read"));
+
+        let mapping = String::from_utf8(mapping).unwrap();
+        let lines: Vec<_> = mapping.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            format!("read1/1 1:N:0:ACGT\t{}", String::from_utf8_lossy(&token1))
+        );
+
+        // another salt gives other tokens
+        assert_ne!(anonymize_id(b"read2", b"pepper"), token2);
+    }
+}
@@ -1,46 +1,127 @@
 //! Compact binary representations of nucleic acid kmers
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{BitAnd, BitOr, BitXor, Not, Range, Shl, Shr};
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::sequence::tables::NUC2BIT;
+
 pub type BitKmerSeq = u64;
-pub type BitKmer = (BitKmerSeq, u8);
+/// A kmer packed in 2 bits per base, the first base in the highest bits, and its size.
+/// Backed by a `u64` for kmers of up to 32 bases, use `BitKmer<u128>` for up to 64.
+pub type BitKmer<T = BitKmerSeq> = (T, u8);
+
+/// Integer a `BitKmer` can be packed in: `u64` for up to 32-mers or `u128` for up
+/// to 64-mers
+pub trait KmerWord:
+    Copy
+    + Default
+    + Eq
+    + Ord
+    + Hash
+    + Debug
+    + Send
+    + Sync
+    + From<u8>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+{
+    const BITS: u32;
+    const MAX: Self;
+    /// Every other bit set, starting from the lowest one
+    const LOW_BITS: Self;
+    /// Largest kmer size that fits
+    const MAX_K: u8 = (Self::BITS / 2) as u8;
+
+    fn reverse_bits(self) -> Self;
+
+    /// The lowest 8 bits
+    fn low_byte(self) -> u8;
+
+    /// Folds the word into a `u64`, for hashing
+    fn fold_u64(self) -> u64;
+}
+
+impl KmerWord for u64 {
+    const BITS: u32 = u64::BITS;
+    const MAX: Self = u64::MAX;
+    const LOW_BITS: Self = 0x5555_5555_5555_5555;
+
+    fn reverse_bits(self) -> Self {
+        u64::reverse_bits(self)
+    }
+
+    fn low_byte(self) -> u8 {
+        self as u8
+    }
+
+    fn fold_u64(self) -> u64 {
+        self
+    }
+}
 
-const NUC2BIT_LOOKUP: [Option<u8>; 256] = {
-    let mut lookup = [None; 256];
+impl KmerWord for u128 {
+    const BITS: u32 = u128::BITS;
+    const MAX: Self = u128::MAX;
+    const LOW_BITS: Self = 0x5555_5555_5555_5555_5555_5555_5555_5555;
 
-    lookup[b'A' as usize] = Some(0);
-    lookup[b'C' as usize] = Some(1);
-    lookup[b'G' as usize] = Some(2);
-    lookup[b'T' as usize] = Some(3);
-    lookup[b'a' as usize] = Some(0);
-    lookup[b'c' as usize] = Some(1);
-    lookup[b'g' as usize] = Some(2);
-    lookup[b't' as usize] = Some(3);
+    fn reverse_bits(self) -> Self {
+        u128::reverse_bits(self)
+    }
+
+    fn low_byte(self) -> u8 {
+        self as u8
+    }
 
-    lookup
-};
+    fn fold_u64(self) -> u64 {
+        (self as u64) ^ ((self >> 64) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+}
+
+/// Mask of the bits used by a kmer of size `k` (without overflowing when `k` fills
+/// the whole word)
+#[inline]
+fn kmer_mask<T: KmerWord>(k: u8) -> T {
+    T::MAX >> (T::BITS - 2 * u32::from(k))
+}
 
 fn nuc2bti_lookup_nocheck(nuc: u8) -> Option<u8> {
-    unsafe { *NUC2BIT_LOOKUP.get_unchecked(nuc as usize) }
+    unsafe { *NUC2BIT.get_unchecked(nuc as usize) }
 }
 
 /// Takes a `BitKmer` and adds a new base on the end, optionally loping off the
 /// first base if the resulting kmer is too long.
-fn extend_kmer(kmer: &mut BitKmer, new_char: u8) -> bool {
+fn extend_kmer<T: KmerWord>(kmer: &mut BitKmer<T>, new_char: u8) -> bool {
     if let Some(new_char_int) = nuc2bti_lookup_nocheck(new_char) {
-        let new_kmer = (kmer.0 << 2) + new_char_int as BitKmerSeq;
+        let new_kmer = (kmer.0 << 2) | T::from(new_char_int);
 
         // mask out any overflowed bits
-        kmer.0 = new_kmer & (BitKmerSeq::pow(2, u32::from(2 * kmer.1)) - 1) as BitKmerSeq;
+        kmer.0 = new_kmer & kmer_mask(kmer.1);
         true
     } else {
         false
     }
 }
 
+/// Adds the ambiguous base at `pos` to the runs, merging it with the last one
+fn record_ambiguous(runs: &mut Vec<Range<usize>>, pos: usize) {
+    match runs.last_mut() {
+        Some(run) if run.end == pos => run.end += 1,
+        _ => runs.push(pos..pos + 1),
+    }
+}
+
 /// Used for the `BitNuclKmer` iterator to handle skipping invalid bases.
-fn update_position(
+fn update_position<T: KmerWord>(
     start_pos: &mut usize,
-    kmer: &mut BitKmer,
+    kmer: &mut BitKmer<T>,
     buffer: &[u8],
     initial: bool,
+    ambiguous: &mut Vec<Range<usize>>,
 ) -> bool {
     // check if we have enough "physical" space for one more kmer
     if *start_pos + kmer.1 as usize > buffer.len() {
@@ -58,9 +139,12 @@ fn update_position(
         if extend_kmer(cur_kmer, buffer[*start_pos + kmer_len]) {
             kmer_len += 1;
         } else {
+            // restart right after the invalid base
+            let invalid_pos = *start_pos + kmer_len;
+            record_ambiguous(ambiguous, invalid_pos);
             kmer_len = 0;
-            *cur_kmer = (0u64, cur_kmer.1);
-            *start_pos += kmer_len + 1;
+            *cur_kmer = (T::default(), cur_kmer.1);
+            *start_pos = invalid_pos + 1;
             if *start_pos + cur_kmer.1 as usize > buffer.len() {
                 return false;
             }
@@ -69,67 +153,245 @@ fn update_position(
     true
 }
 
-pub struct BitNuclKmer<'a> {
+/// What `BitNuclKmer` does with the kmers containing bases other than `ACGT` (`N`,
+/// IUPAC codes...), which can't be packed in 2 bits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AmbiguousPolicy {
+    /// Skip those kmers
+    #[default]
+    SkipKmer,
+    /// Pack the ambiguous bases as `A` and yield the kmers as any other
+    MaskAsA,
+    /// Same as `MaskAsA`, with `BitNuclKmer::is_ambiguous` telling whether the last
+    /// kmer returned contained an ambiguous base
+    YieldWithFlag,
+    /// Stop the iteration at the first kmer containing an ambiguous base, which
+    /// `BitNuclKmer::try_next` returns as an error
+    Error,
+}
+
+/// Iterator over the kmers of a sequence made only of `ACGT`, yielding their
+/// position, the kmer and whether it was reverse complemented to be canonical.
+///
+/// Kmers of more than 32 bases need a `u128` backing:
+///
+/// ```
+/// use needletail::bitkmer::{bitmer_to_bytes_generic, BitNuclKmer};
+///
+/// let seq = b"ACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTAC";
+/// let mut kmers = BitNuclKmer::<u128>::new_generic(seq, 40, false);
+/// let (pos, kmer, _) = kmers.nth(2).unwrap();
+/// assert_eq!(bitmer_to_bytes_generic(kmer), &seq[pos..pos + 40]);
+/// ```
+///
+/// The kmers with other bases are skipped by default, see [`AmbiguousPolicy`] for the
+/// alternatives:
+///
+/// ```
+/// use needletail::bitkmer::{bitmer_to_bytes, AmbiguousPolicy, BitNuclKmer};
+///
+/// let mut kmers = BitNuclKmer::new(b"ACNGT", 2, false);
+/// assert_eq!(kmers.by_ref().map(|(pos, _, _)| pos).collect::<Vec<_>>(), [0, 3]);
+/// assert_eq!(kmers.ambiguous_runs(), [2..3]);
+///
+/// let mut kmers =
+///     BitNuclKmer::new(b"ACNGT", 2, false).with_policy(AmbiguousPolicy::YieldWithFlag);
+/// let mut flagged = Vec::new();
+/// while let Some((_, kmer, _)) = kmers.next() {
+///     flagged.push((bitmer_to_bytes(kmer), kmers.is_ambiguous()));
+/// }
+/// assert_eq!(flagged[1], (b"CA".to_vec(), true));
+/// assert_eq!(flagged[3], (b"GT".to_vec(), false));
+///
+/// let mut kmers = BitNuclKmer::new(b"ACNGT", 2, false).with_policy(AmbiguousPolicy::Error);
+/// assert!(kmers.try_next().unwrap().is_ok());
+/// assert!(kmers.try_next().unwrap().is_err());
+/// assert!(kmers.try_next().is_none());
+/// ```
+pub struct BitNuclKmer<'a, T = BitKmerSeq> {
     start_pos: usize,
-    cur_kmer: BitKmer,
+    cur_kmer: BitKmer<T>,
     buffer: &'a [u8],
     canonical: bool,
+    policy: AmbiguousPolicy,
+    ambiguous: Vec<Range<usize>>,
+    /// Position of the next base to read when ambiguous bases are masked
+    next_base: usize,
+    /// Position of the last ambiguous base read when they are masked
+    last_ambiguous: Option<usize>,
+    last_is_ambiguous: bool,
+    /// Position of the ambiguous base that stopped the iteration with `AmbiguousPolicy::Error`
+    error: Option<usize>,
 }
 
 impl<'a> BitNuclKmer<'a> {
     pub fn new(slice: &'a [u8], k: u8, canonical: bool) -> BitNuclKmer<'a> {
-        let mut kmer = (0u64, k);
-        let mut start_pos = 0;
-        update_position(&mut start_pos, &mut kmer, slice, true);
+        Self::new_generic(slice, k, canonical)
+    }
+}
 
-        BitNuclKmer {
-            start_pos,
-            cur_kmer: kmer,
+impl<'a, T: KmerWord> BitNuclKmer<'a, T> {
+    /// Same as `BitNuclKmer::new` with the kmers packed in a `T`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is more than `T::MAX_K`.
+    pub fn new_generic(slice: &'a [u8], k: u8, canonical: bool) -> Self {
+        assert!(
+            k <= T::MAX_K,
+            "k can be at most {} with this backing, got {k}",
+            T::MAX_K
+        );
+        let mut kmers = BitNuclKmer {
+            start_pos: 0,
+            cur_kmer: (T::default(), k),
             buffer: slice,
             canonical,
+            policy: AmbiguousPolicy::SkipKmer,
+            ambiguous: Vec::new(),
+            next_base: 0,
+            last_ambiguous: None,
+            last_is_ambiguous: false,
+            error: None,
+        };
+        update_position(
+            &mut kmers.start_pos,
+            &mut kmers.cur_kmer,
+            slice,
+            true,
+            &mut kmers.ambiguous,
+        );
+        kmers
+    }
+
+    /// Sets what to do with the kmers containing bases other than `ACGT`, to call
+    /// before iterating
+    pub fn with_policy(self, policy: AmbiguousPolicy) -> Self {
+        let mut kmers = Self::new_generic(self.buffer, self.cur_kmer.1, self.canonical);
+        if policy != AmbiguousPolicy::SkipKmer {
+            // the bases are read one by one instead
+            kmers.start_pos = 0;
+            kmers.cur_kmer.0 = T::default();
+            kmers.ambiguous.clear();
         }
+        kmers.policy = policy;
+        kmers
     }
-}
 
-impl Iterator for BitNuclKmer<'_> {
-    type Item = (usize, BitKmer, bool);
+    /// The runs of bases other than `ACGT` read so far, in order
+    pub fn ambiguous_runs(&self) -> &[Range<usize>] {
+        &self.ambiguous
+    }
+
+    /// Whether the last kmer returned contained a base other than `ACGT`, only with
+    /// `AmbiguousPolicy::YieldWithFlag`
+    pub fn is_ambiguous(&self) -> bool {
+        self.last_is_ambiguous
+    }
+
+    /// Same as `next` but, with `AmbiguousPolicy::Error`, returns an error for the first
+    /// kmer containing an ambiguous base instead of just stopping there.
+    /// Returns `None` after that.
+    pub fn try_next(&mut self) -> Option<Result<<Self as Iterator>::Item, ParseError>> {
+        match self.next() {
+            Some(kmer) => Some(Ok(kmer)),
+            None => self.error.take().map(|pos| {
+                let msg = format!(
+                    "Ambiguous base '{}' at position {pos}",
+                    char::from(self.buffer[pos]).escape_default()
+                );
+                Err(ParseError::new_invalid_record(
+                    msg,
+                    ErrorPosition::default(),
+                ))
+            }),
+        }
+    }
 
-    fn next(&mut self) -> Option<(usize, BitKmer, bool)> {
-        if !update_position(&mut self.start_pos, &mut self.cur_kmer, self.buffer, false) {
-            return None;
+    /// Next kmer when the ambiguous bases aren't skipped
+    fn next_masked(&mut self) -> Option<BitKmer<T>> {
+        let k = usize::from(self.cur_kmer.1);
+        while self.next_base < self.start_pos + k {
+            let pos = self.next_base;
+            let base = *self.buffer.get(pos)?;
+            let code = match nuc2bti_lookup_nocheck(base) {
+                Some(code) => code,
+                None => {
+                    record_ambiguous(&mut self.ambiguous, pos);
+                    if self.policy == AmbiguousPolicy::Error {
+                        self.error = Some(pos);
+                        self.start_pos = self.buffer.len();
+                        self.next_base = self.buffer.len();
+                        return None;
+                    }
+                    self.last_ambiguous = Some(pos);
+                    0
+                }
+            };
+            self.cur_kmer.0 = ((self.cur_kmer.0 << 2) | T::from(code)) & kmer_mask(self.cur_kmer.1);
+            self.next_base += 1;
         }
+        self.last_is_ambiguous = self.policy == AmbiguousPolicy::YieldWithFlag
+            && self.last_ambiguous.is_some_and(|pos| pos >= self.start_pos);
+        Some(self.cur_kmer)
+    }
+}
+
+impl<T: KmerWord> Iterator for BitNuclKmer<'_, T> {
+    type Item = (usize, BitKmer<T>, bool);
+
+    fn next(&mut self) -> Option<(usize, BitKmer<T>, bool)> {
+        let kmer = if self.policy == AmbiguousPolicy::SkipKmer {
+            if !update_position(
+                &mut self.start_pos,
+                &mut self.cur_kmer,
+                self.buffer,
+                false,
+                &mut self.ambiguous,
+            ) {
+                return None;
+            }
+            self.cur_kmer
+        } else {
+            self.next_masked()?
+        };
         self.start_pos += 1;
         if self.canonical {
-            let (kmer, was_rc) = canonical(self.cur_kmer);
+            let (kmer, was_rc) = canonical_generic(kmer);
             Some((self.start_pos - 1, kmer, was_rc))
         } else {
-            Some((self.start_pos - 1, self.cur_kmer, false))
+            Some((self.start_pos - 1, kmer, false))
         }
     }
 }
 
 /// Reverse complement a `BitKmer` (reverses the sequence and swaps A<>T and G<>C)
 pub fn reverse_complement(kmer: BitKmer) -> BitKmer {
-    // FIXME: this is not going to work with BitKmers of u128 or u32
-    // inspired from https://www.biostars.org/p/113640/
-    let mut new_kmer = kmer.0;
-    // reverse it
-    new_kmer = (new_kmer >> 2 & 0x3333_3333_3333_3333) | (new_kmer & 0x3333_3333_3333_3333) << 2;
-    new_kmer = (new_kmer >> 4 & 0x0F0F_0F0F_0F0F_0F0F) | (new_kmer & 0x0F0F_0F0F_0F0F_0F0F) << 4;
-    new_kmer = (new_kmer >> 8 & 0x00FF_00FF_00FF_00FF) | (new_kmer & 0x00FF_00FF_00FF_00FF) << 8;
-    new_kmer = (new_kmer >> 16 & 0x0000_FFFF_0000_FFFF) | (new_kmer & 0x0000_FFFF_0000_FFFF) << 16;
-    new_kmer = (new_kmer >> 32 & 0x0000_0000_FFFF_FFFF) | (new_kmer & 0x0000_0000_FFFF_FFFF) << 32;
+    reverse_complement_generic(kmer)
+}
+
+/// Same as `reverse_complement` for kmers packed in any `KmerWord`
+pub fn reverse_complement_generic<T: KmerWord>(kmer: BitKmer<T>) -> BitKmer<T> {
+    // reversing the bits reverses the order of the bases but also the 2 bits of each
+    // base, which are swapped back
+    let reversed = kmer.0.reverse_bits();
+    let mut new_kmer = ((reversed >> 1) & T::LOW_BITS) | ((reversed & T::LOW_BITS) << 1);
     // complement it
-    new_kmer ^= 0xFFFF_FFFF_FFFF_FFFF;
+    new_kmer = !new_kmer;
     // shift it to the right size
-    new_kmer >>= 2 * (32 - kmer.1);
+    new_kmer = new_kmer >> (T::BITS - 2 * u32::from(kmer.1));
     (new_kmer, kmer.1)
 }
 
 /// Return the lexigraphically lowest of the `BitKmer` and its reverse complement and
 /// whether the returned kmer is the `reverse_complement` (true) or the original (false)
 pub fn canonical(kmer: BitKmer) -> (BitKmer, bool) {
-    let rc = reverse_complement(kmer);
+    canonical_generic(kmer)
+}
+
+/// Same as `canonical` for kmers packed in any `KmerWord`
+pub fn canonical_generic<T: KmerWord>(kmer: BitKmer<T>) -> (BitKmer<T>, bool) {
+    let rc = reverse_complement_generic(kmer);
     if kmer.0 > rc.0 {
         (rc, true)
     } else {
@@ -138,51 +400,94 @@ pub fn canonical(kmer: BitKmer) -> (BitKmer, bool) {
 }
 
 /// Find the lexicographically lowest substring of a given length in the `BitKmer`
+///
+/// The substrings are reverse complemented as if they were of the size of the whole
+/// kmer and the size of the kmer is returned, see `minimizer_generic` for the minimizer
+/// on either strand as a kmer of size `minmer_size`.
+#[deprecated(note = "use minimizer_generic")]
 pub fn minimizer(kmer: BitKmer, minmer_size: u8) -> BitKmer {
     let mut new_kmer = kmer.0;
     let mut lowest = !0;
-    let bitmask = (BitKmerSeq::pow(2, u32::from(2 * minmer_size)) - 1) as BitKmerSeq;
+    let bitmask = kmer_mask::<BitKmerSeq>(minmer_size);
     for _ in 0..=(kmer.1 - minmer_size) {
         let cur = bitmask & new_kmer;
-        if cur < lowest {
-            lowest = cur;
-        }
-        let cur_rev = reverse_complement((bitmask & new_kmer, kmer.1));
-        if cur_rev.0 < lowest {
-            lowest = cur_rev.0;
-        }
+        let cur_rev = reverse_complement((cur, kmer.1));
+        lowest = lowest.min(cur).min(cur_rev.0);
         new_kmer >>= 2;
     }
     (lowest, kmer.1)
 }
 
-pub fn bitmer_to_bytes(kmer: BitKmer) -> Vec<u8> {
+/// Find the lexicographically lowest substring of a given length in the `BitKmer`,
+/// on either strand, returned as a kmer of size `minmer_size`
+pub fn minimizer_generic<T: KmerWord>(kmer: BitKmer<T>, minmer_size: u8) -> BitKmer<T> {
     let mut new_kmer = kmer.0;
-    let mut new_kmer_str = Vec::new();
-    // we're reading the bases off from the "high" end of the integer so we need to do some
-    // math to figure out where they start (this helps us just pop the bases on the end
-    // of the working buffer as we read them off "left to right")
-    let offset = (kmer.1 - 1) * 2;
-    let bitmask = BitKmerSeq::pow(2, u32::from(2 * kmer.1 - 1))
-        + BitKmerSeq::pow(2, u32::from(2 * kmer.1 - 2));
-
-    for _ in 0..kmer.1 {
-        let new_char = (new_kmer & bitmask) >> offset;
-        new_kmer <<= 2;
-        new_kmer_str.push(match new_char {
-            0 => b'A',
-            1 => b'C',
-            2 => b'G',
-            3 => b'T',
-            _ => unreachable!("Mathematical impossibility"),
-        });
-    }
-    new_kmer_str
+    let mut lowest = T::MAX;
+    let bitmask = kmer_mask::<T>(minmer_size);
+    for _ in 0..=(kmer.1 - minmer_size) {
+        let cur = (bitmask & new_kmer, minmer_size);
+        lowest = lowest.min(cur.0).min(reverse_complement_generic(cur).0);
+        new_kmer = new_kmer >> 2;
+    }
+    (lowest, minmer_size)
+}
+
+pub fn bitmer_to_bytes(kmer: BitKmer) -> Vec<u8> {
+    bitmer_to_bytes_generic(kmer)
+}
+
+/// Same as `bitmer_to_bytes` for kmers packed in any `KmerWord`
+pub fn bitmer_to_bytes_generic<T: KmerWord>(kmer: BitKmer<T>) -> Vec<u8> {
+    // the first base is in the highest bits
+    (0..kmer.1)
+        .map(|i| {
+            let shift = 2 * u32::from(kmer.1 - 1 - i);
+            b"ACGT"[usize::from((kmer.0 >> shift).low_byte() & 0b11)]
+        })
+        .collect()
+}
+
+/// Same as `bitmer_to_bytes` but without allocating, returns `None` if the kmer
+/// isn't of size `K`
+pub fn bitmer_to_array<const K: usize>(kmer: BitKmer) -> Option<[u8; K]> {
+    bitmer_to_array_generic(kmer)
+}
+
+/// Same as `bitmer_to_array` for kmers packed in any `KmerWord`
+pub fn bitmer_to_array_generic<const K: usize, T: KmerWord>(kmer: BitKmer<T>) -> Option<[u8; K]> {
+    if kmer.1 as usize != K {
+        return None;
+    }
+    let mut bases = [0; K];
+    for (i, base) in bases.iter_mut().enumerate() {
+        // the first base is in the highest bits
+        let shift = 2 * (K - 1 - i) as u32;
+        *base = b"ACGT"[usize::from((kmer.0 >> shift).low_byte() & 0b11)];
+    }
+    Some(bases)
+}
+
+/// Hashes a `BitKmer` into a well-mixed `u64` (the finalizer of MurmurHash3), e.g. to
+/// bucket or sample kmers. Kmers of different sizes hash differently.
+pub fn bitmer_hash(kmer: BitKmer) -> u64 {
+    bitmer_hash_generic(kmer)
+}
+
+/// Same as `bitmer_hash` for kmers packed in any `KmerWord`
+pub fn bitmer_hash_generic<T: KmerWord>(kmer: BitKmer<T>) -> u64 {
+    let mut h = kmer.0.fold_u64() ^ (u64::from(kmer.1) << 58);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    h
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Sequence;
 
     #[test]
     fn can_kmerize() {
@@ -245,6 +550,50 @@ mod tests {
         assert_eq!(kmer_iter.next(), None);
     }
 
+    #[test]
+    fn test_ambiguous_policies() {
+        let seq = b"ACGNNTACRGT";
+        let mut skipped = BitNuclKmer::new(seq, 3, false);
+        let positions: Vec<_> = skipped.by_ref().map(|(pos, _, _)| pos).collect();
+        assert_eq!(positions, [0, 5]);
+        assert_eq!(skipped.ambiguous_runs(), [3..5, 8..9]);
+
+        let masked: Vec<_> = BitNuclKmer::new(seq, 3, false)
+            .with_policy(AmbiguousPolicy::MaskAsA)
+            .map(|(pos, kmer, _)| (pos, bitmer_to_bytes(kmer)))
+            .collect();
+        assert_eq!(masked.len(), seq.len() - 2);
+        assert_eq!(masked[2], (2, b"GAA".to_vec()));
+        assert_eq!(masked[8], (8, b"AGT".to_vec()));
+
+        let mut flagged =
+            BitNuclKmer::new(seq, 3, true).with_policy(AmbiguousPolicy::YieldWithFlag);
+        let mut flags = Vec::new();
+        while flagged.next().is_some() {
+            flags.push(flagged.is_ambiguous());
+        }
+        assert_eq!(
+            flags,
+            [false, true, true, true, true, false, true, true, true]
+        );
+        assert_eq!(flagged.ambiguous_runs(), [3..5, 8..9]);
+
+        let mut strict = BitNuclKmer::new(seq, 3, false).with_policy(AmbiguousPolicy::Error);
+        assert_eq!(strict.by_ref().count(), 1);
+        assert_eq!(strict.ambiguous_runs().first(), Some(&(3..4)));
+        assert_eq!(strict.next(), None);
+
+        let mut strict = BitNuclKmer::new(seq, 3, false).with_policy(AmbiguousPolicy::Error);
+        assert_eq!(strict.try_next().unwrap().unwrap().0, 0);
+        let err = strict.try_next().unwrap().unwrap_err();
+        assert_eq!(err.kind, crate::errors::ParseErrorKind::InvalidRecord);
+        assert!(err.msg.contains("'N' at position 3"), "{}", err.msg);
+        assert!(strict.try_next().is_none());
+        // the other policies never fail
+        let mut skipped = BitNuclKmer::new(seq, 3, false);
+        assert_eq!(std::iter::from_fn(|| skipped.try_next()).count(), 2);
+    }
+
     #[test]
     fn test_reverse_complement() {
         assert_eq!(reverse_complement((0b00_0000, 3)).0, 0b11_1111);
@@ -254,11 +603,71 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_minimizer() {
         assert_eq!(minimizer((0b00_1011, 3), 2).0, 0b0010);
         assert_eq!(minimizer((0b00_1011, 3), 1).0, 0b00);
         assert_eq!(minimizer((0b1100_0011, 4), 2).0, 0b0000);
         assert_eq!(minimizer((0b11_0001, 3), 2).0, 0b0001);
+
+        // in AGT, GT is reverse complemented as the 3-mer AGT: ACT is higher than AG,
+        // which comes out as a 3-mer, while the reverse complement of GT is AC
+        assert_eq!(minimizer((0b00_1011, 3), 2), (0b0010, 3));
+        assert_eq!(minimizer_generic((0b00_1011u64, 3), 2), (0b0001, 2));
+    }
+
+    #[test]
+    fn test_generic() {
+        assert_eq!(
+            reverse_complement_generic((0b0001_1011u128, 4)).0,
+            0b0001_1011
+        );
+        // AGT: GT is the reverse complement of AC
+        assert_eq!(minimizer_generic((0b00_1011u64, 3), 2), (0b0001, 2));
+        assert_eq!(minimizer_generic((0b11_0001u128, 3), 2), (0b0001, 2));
+        assert_eq!(bitmer_hash_generic((60u64, 3)), bitmer_hash((60, 3)));
+        assert_ne!(bitmer_hash_generic((60u128, 3)), bitmer_hash((60, 4)));
+    }
+
+    #[test]
+    fn test_u128_kmers() {
+        let seq: Vec<u8> = (0..100).map(|i| b"ACGTTGCA"[(i * 3 + i / 7) % 8]).collect();
+        for k in [33, 50, 64] {
+            let kmers: Vec<_> = BitNuclKmer::<u128>::new_generic(&seq, k, false).collect();
+            assert_eq!(kmers.len(), seq.len() - usize::from(k) + 1);
+            for (pos, kmer, _) in kmers {
+                let bytes = &seq[pos..pos + usize::from(k)];
+                assert_eq!(bitmer_to_bytes_generic(kmer), bytes);
+                let rc = reverse_complement_generic(kmer);
+                assert_eq!(bitmer_to_bytes_generic(rc), bytes.reverse_complement());
+                assert_eq!(reverse_complement_generic(rc), kmer);
+            }
+        }
+
+        // the same kmers as with a u64 backing when they fit
+        let wide: Vec<_> = BitNuclKmer::<u128>::new_generic(&seq, 21, true)
+            .map(|(pos, kmer, rc)| (pos, kmer.0 as u64, rc))
+            .collect();
+        let narrow: Vec<_> = BitNuclKmer::new(&seq, 21, true)
+            .map(|(pos, kmer, rc)| (pos, kmer.0, rc))
+            .collect();
+        assert_eq!(wide, narrow);
+
+        let (_, kmer, _) = BitNuclKmer::<u128>::new_generic(&seq, 40, false)
+            .next()
+            .unwrap();
+        let lowest = BitNuclKmer::new(&seq[..40], 30, true)
+            .map(|(_, mmer, _)| mmer.0)
+            .min()
+            .unwrap();
+        assert_eq!(minimizer_generic(kmer, 30), (u128::from(lowest), 30));
+        assert_eq!(bitmer_to_array_generic::<40, _>(kmer).unwrap(), seq[..40]);
+    }
+
+    #[test]
+    #[should_panic(expected = "k can be at most 32")]
+    fn test_k_too_large() {
+        BitNuclKmer::new(b"ACGT", 33, false);
     }
 
     #[test]
@@ -280,6 +689,25 @@ mod tests {
         assert_eq!(bitmer_to_bytes((0 as BitKmerSeq, 3)), b"AAA");
     }
 
+    #[test]
+    fn test_bitmer_to_array() {
+        assert_eq!(bitmer_to_array::<3>((60, 3)), Some(*b"TTA"));
+        assert_eq!(bitmer_to_array::<1>((1, 1)), Some(*b"C"));
+        assert_eq!(bitmer_to_array::<2>((60, 3)), None);
+        let kmer = bytes_to_bitmer(b"ACGTACGTACGTACGTACGTACGTACGTACGT");
+        assert_eq!(
+            &bitmer_to_array::<32>(kmer).unwrap(),
+            &bitmer_to_bytes(kmer)[..]
+        );
+    }
+
+    #[test]
+    fn test_bitmer_hash() {
+        assert_ne!(bitmer_hash((0, 3)), bitmer_hash((0, 4)));
+        assert_ne!(bitmer_hash((1, 3)), bitmer_hash((2, 3)));
+        assert_eq!(bitmer_hash((60, 3)), bitmer_hash(bytes_to_bitmer(b"TTA")));
+    }
+
     pub fn bytes_to_bitmer(kmer: &[u8]) -> BitKmer {
         let k = kmer.len() as u8;
 
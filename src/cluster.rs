@@ -0,0 +1,157 @@
+//! Greedy clustering of sequences (CD-HIT-like) on MinHash sketches, e.g. to
+//! collapse near-duplicate amplicons.
+use crate::errors::ParseError;
+use crate::parser::{FastxReader, OwnedRecord};
+use crate::sketch::MinHash;
+
+/// How the similarity of a sequence to a cluster representative is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Similarity {
+    /// Jaccard similarity of their kmers
+    Jaccard,
+    /// Fraction of the kmers of the sequence found in the representative, so
+    /// sequences contained in a longer representative join its cluster
+    Containment,
+}
+
+/// Parameters of [`greedy_cluster`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClusterOptions {
+    /// Kmer size, 21 by default
+    pub k: u8,
+    /// Number of hashes kept in the sketches, 1000 by default
+    pub sketch_size: usize,
+    /// Minimum similarity to join a cluster, 0.9 by default
+    pub threshold: f64,
+    /// `Containment` by default
+    pub similarity: Similarity,
+}
+
+impl Default for ClusterOptions {
+    fn default() -> Self {
+        Self {
+            k: 21,
+            sketch_size: 1000,
+            threshold: 0.9,
+            similarity: Similarity::Containment,
+        }
+    }
+}
+
+/// The result of a clustering
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clusters {
+    /// The cluster of every record, in the order they were given. Clusters are
+    /// numbered in the order their representatives were picked, longest first.
+    pub assignments: Vec<usize>,
+    /// The representative record of every cluster
+    pub representatives: Vec<OwnedRecord>,
+}
+
+/// Clusters records greedily: they are considered from the longest to the shortest and
+/// join the first cluster whose representative is similar enough, or become the
+/// representative of a new cluster otherwise.
+///
+/// Every record is compared to the representatives so this is meant for up to
+/// a few hundred thousand records, like the amplicons of a sample.
+///
+/// ```
+/// use needletail::cluster::{greedy_cluster, ClusterOptions};
+/// use needletail::parser::OwnedRecord;
+///
+/// let record = |id: &[u8], seq: &[u8]| OwnedRecord { id: id.to_vec(), seq: seq.to_vec(), qual: None };
+/// let records = vec![
+///     record(b"a", b"ACGTTGCATGCAGTCGATCGATCGTAGCTAGCTAGCTGATC"),
+///     record(b"b", b"GGGGGGCCCCCCAAAAATTTTTGGGGGCCCCC"),
+///     // a prefix of a
+///     record(b"c", b"ACGTTGCATGCAGTCGATCGATCGTAGCTAG"),
+/// ];
+/// let options = ClusterOptions { k: 11, ..Default::default() };
+/// let clusters = greedy_cluster(records, &options);
+/// assert_eq!(clusters.assignments, vec![0, 1, 0]);
+/// assert_eq!(clusters.representatives[0].id, b"a");
+/// ```
+pub fn greedy_cluster(records: Vec<OwnedRecord>, options: &ClusterOptions) -> Clusters {
+    let sketches: Vec<_> = records
+        .iter()
+        .map(|r| MinHash::from_seq(r, options.k, options.sketch_size))
+        .collect();
+    // longest first, keeping the input order for the same length
+    let mut order: Vec<_> = (0..records.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(records[*i].seq.len()));
+
+    let mut assignments = vec![0; records.len()];
+    // index of the representative record of each cluster
+    let mut representatives: Vec<usize> = Vec::new();
+    for i in order {
+        let sketch = &sketches[i];
+        let cluster = representatives.iter().position(|rep| {
+            let similarity = match options.similarity {
+                Similarity::Jaccard => sketch.jaccard(&sketches[*rep]),
+                Similarity::Containment => sketch.containment(&sketches[*rep]),
+            };
+            similarity >= options.threshold
+        });
+        assignments[i] = match cluster {
+            Some(cluster) => cluster,
+            None => {
+                representatives.push(i);
+                representatives.len() - 1
+            }
+        };
+    }
+
+    let mut records: Vec<_> = records.into_iter().map(Some).collect();
+    Clusters {
+        assignments,
+        representatives: representatives
+            .into_iter()
+            .map(|i| {
+                records[i]
+                    .take()
+                    .expect("a record represents at most one cluster")
+            })
+            .collect(),
+    }
+}
+
+/// Clusters all the records of a reader, see [`greedy_cluster`]
+pub fn cluster_reader(
+    reader: &mut dyn FastxReader,
+    options: &ClusterOptions,
+) -> Result<Clusters, ParseError> {
+    let mut records = Vec::new();
+    while let Some(record) = reader.next() {
+        records.push(record?.to_owned_record());
+    }
+    Ok(greedy_cluster(records, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_cluster_reader() {
+        let fasta = b">short\nACGTTGCATGCAGTCGATCG\n>long\nACGTTGCATGCAGTCGATCGATCGTAGCTAGC\n>other\nTTTTTTTTTTGGGGGGGGGGAAAAAAAAAA\n";
+        let options = ClusterOptions {
+            k: 9,
+            ..Default::default()
+        };
+        let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+        let clusters = cluster_reader(&mut reader, &options).unwrap();
+        // the longest sequence represents the cluster
+        assert_eq!(clusters.assignments, vec![0, 0, 1]);
+        assert_eq!(clusters.representatives[0].id, b"long");
+
+        // short isn't similar enough to long by Jaccard
+        let options = ClusterOptions {
+            similarity: Similarity::Jaccard,
+            ..options
+        };
+        let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+        let clusters = cluster_reader(&mut reader, &options).unwrap();
+        assert_eq!(clusters.representatives.len(), 3);
+    }
+}
@@ -0,0 +1,80 @@
+//! Base-by-base comparisons of sequences of the same length, e.g. amplicons
+//! against their consensus
+use std::borrow::Cow;
+
+use crate::Sequence;
+
+/// Returns `a` with the bases differing from the ones of `b` at the same position
+/// replaced by an `N`, or `None` if the sequences don't have the same length.
+///
+/// Newlines are stripped from both sequences first and bases are compared
+/// case-insensitively. If the sequences are identical, no allocation is made.
+///
+/// ```
+/// use needletail::compare::mask_mismatches;
+///
+/// let masked = mask_mismatches(&b"ACGTACGT"[..], &b"ACCTACGa"[..]).unwrap();
+/// assert_eq!(masked.as_ref(), b"ACNTACGN");
+/// assert!(mask_mismatches(&b"ACGT"[..], &b"ACG"[..]).is_none());
+/// ```
+pub fn mask_mismatches<'a, 'b, A, B>(a: &'a A, b: &'b B) -> Option<Cow<'a, [u8]>>
+where
+    A: Sequence<'a> + ?Sized,
+    B: Sequence<'b> + ?Sized,
+{
+    let mut seq = a.strip_returns();
+    let other = b.strip_returns();
+    if seq.len() != other.len() {
+        return None;
+    }
+    let differs = |(x, y): (&u8, &u8)| !x.eq_ignore_ascii_case(y);
+    if !seq.iter().zip(other.iter()).any(differs) {
+        return Some(seq);
+    }
+
+    for (x, y) in seq.to_mut().iter_mut().zip(other.iter()) {
+        if differs((x, y)) {
+            *x = b'N';
+        }
+    }
+    Some(seq)
+}
+
+/// Number of positions where the bases of `a` and `b` differ (case-insensitively),
+/// or `None` if the sequences don't have the same length.
+pub fn count_mismatches<'a, 'b, A, B>(a: &'a A, b: &'b B) -> Option<usize>
+where
+    A: Sequence<'a> + ?Sized,
+    B: Sequence<'b> + ?Sized,
+{
+    let seq = a.strip_returns();
+    let other = b.strip_returns();
+    if seq.len() != other.len() {
+        return None;
+    }
+    Some(
+        seq.iter()
+            .zip(other.iter())
+            .filter(|(x, y)| !x.eq_ignore_ascii_case(y))
+            .count(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_mask_mismatches_records() {
+        let mut reader = parse_fastx_reader(&b">a\nACG\nTAC\n>b\nACGAAC\n"[..]).unwrap();
+        let a = reader.next().unwrap().unwrap().to_owned_record();
+        let b = reader.next().unwrap().unwrap().to_owned_record();
+        assert_eq!(mask_mismatches(&a, &b).unwrap().as_ref(), b"ACGNAC");
+        assert_eq!(count_mismatches(&a, &b), Some(1));
+
+        let same = mask_mismatches(&b, &b).unwrap();
+        assert!(matches!(same, Cow::Borrowed(_)));
+        assert_eq!(count_mismatches(&a, &b"ACGT"[..]), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! Collapsing groups of reads (e.g. sharing a UMI) into a consensus read
+use crate::parser::OwnedRecord;
+
+const BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+/// Highest Phred score given to a consensus base
+const MAX_QUALITY: u64 = 93;
+
+fn base_index(base: u8) -> Option<usize> {
+    match base {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' => Some(3),
+        _ => None,
+    }
+}
+
+/// Builds the consensus of a group of reads by a majority vote at each position where
+/// each read votes with the Phred score of its base (or 1 for reads without quality).
+/// Positions are aligned from the start of the reads and non-ACGT bases don't vote.
+///
+/// The quality of a consensus base is its total score minus the total score of the
+/// other bases (capped to 93): it drops when reads disagree. Positions without votes
+/// or where bases tie get an `N` with a quality of 0. The consensus has qualities only
+/// if every read has some and takes the id of the first read.
+///
+/// Returns `None` for an empty group.
+///
+/// ```
+/// use needletail::consensus::by_group;
+/// use needletail::parser::OwnedRecord;
+///
+/// let read = |seq: &[u8], qual: &[u8]| OwnedRecord {
+///     id: b"umi1".to_vec(),
+///     seq: seq.to_vec(),
+///     qual: Some(qual.to_vec()),
+/// };
+/// let group = [read(b"ACGT", b"IIII"), read(b"ACTT", b"II#I"), read(b"ACGA", b"III5")];
+/// let consensus = by_group(&group).unwrap();
+/// assert_eq!(consensus.seq, b"ACGT");
+/// // 3 * Q40 capped to Q93 ('~'), then G: 40 + 40 - 2 = Q78 and T: 40 + 40 - 20 = Q60
+/// assert_eq!(consensus.qual.unwrap(), b"~~o]");
+/// ```
+pub fn by_group(records: &[OwnedRecord]) -> Option<OwnedRecord> {
+    let first = records.first()?;
+    let len = records.iter().map(|r| r.seq.len()).max().unwrap_or(0);
+    let with_quality = records.iter().all(|r| r.qual.is_some());
+
+    let mut votes = vec![[0u64; 4]; len];
+    for record in records {
+        for (pos, base) in record.seq.iter().enumerate() {
+            if let Some(index) = base_index(*base) {
+                let weight = record
+                    .qual
+                    .as_ref()
+                    .and_then(|q| q.get(pos))
+                    .map_or(1, |q| u64::from(q.saturating_sub(33)));
+                votes[pos][index] += weight;
+            }
+        }
+    }
+
+    let mut seq = Vec::with_capacity(len);
+    let mut qual = Vec::with_capacity(len);
+    for counts in votes {
+        let total: u64 = counts.iter().sum();
+        let best = *counts.iter().max().unwrap_or(&0);
+        let winners: Vec<_> = (0..4).filter(|i| counts[*i] == best).collect();
+        if best == 0 || winners.len() > 1 {
+            seq.push(b'N');
+            qual.push(b'!');
+        } else {
+            seq.push(BASES[winners[0]]);
+            let score = best.saturating_sub(total - best).min(MAX_QUALITY);
+            qual.push(score as u8 + 33);
+        }
+    }
+
+    Some(OwnedRecord {
+        id: first.id.clone(),
+        seq,
+        qual: with_quality.then_some(qual),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_by_group() {
+        assert!(by_group(&[]).is_none());
+
+        let fasta = |seq: &[u8]| OwnedRecord {
+            id: b"r".to_vec(),
+            seq: seq.to_vec(),
+            qual: None,
+        };
+        // ties and positions without votes give Ns, shorter reads only vote where they have bases
+        let consensus = by_group(&[fasta(b"ACGN"), fasta(b"ACTN"), fasta(b"acgNA")]).unwrap();
+        assert_eq!(consensus.seq, b"ACGNA");
+        assert_eq!(consensus.qual, None);
+
+        let consensus = by_group(&[fasta(b"AT"), fasta(b"AC")]).unwrap();
+        assert_eq!(consensus.seq, b"AN");
+    }
+}
@@ -0,0 +1,166 @@
+//! Converting FASTA/FASTQ files between formats, line endings and line widths while
+//! streaming them, one record at a time
+use std::io::Write;
+use std::path::Path;
+
+use crate::errors::ParseError;
+use crate::parser::{
+    create_file, parse_fastx_file, CompressedWriter, Compression, FastxReader, FastxWriter, Format,
+    LineEnding,
+};
+use crate::quality::Phred;
+
+/// How [`convert`] writes the records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvertOptions {
+    /// Format of the output, the format of each record by default. FASTQ records
+    /// written as FASTA lose their quality.
+    pub format: Option<Format>,
+    /// Quality of every base of the FASTA records written as FASTQ, Q40 (`I`) by default
+    pub fake_quality: Phred,
+    /// Line ending of the output, the one of the first record by default
+    pub line_ending: Option<LineEnding>,
+    /// Width of the lines of FASTA sequences, 0 (on a single line) by default
+    pub line_width: usize,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            fake_quality: Phred::new(40).unwrap(),
+            line_ending: None,
+            line_width: 0,
+        }
+    }
+}
+
+/// Writes all the remaining records of `reader` to `writer` according to `options`,
+/// keeping their ids, and returns `writer` once everything was written to it.
+///
+/// ```
+/// use needletail::convert::{convert, ConvertOptions};
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::{Format, LineEnding};
+///
+/// let mut reader = parse_fastx_reader(&b"@r1 desc\r\nACGTAC\r\n+\r\nIIIIII\r\n"[..]).unwrap();
+/// let options = ConvertOptions {
+///     format: Some(Format::Fasta),
+///     line_ending: Some(LineEnding::Unix),
+///     line_width: 4,
+///     ..Default::default()
+/// };
+/// let fasta = convert(&mut reader, Vec::new(), &options).unwrap();
+/// assert_eq!(fasta, b">r1 desc\nACGT\nAC\n");
+/// ```
+pub fn convert<W: Write>(
+    reader: &mut dyn FastxReader,
+    writer: W,
+    options: &ConvertOptions,
+) -> Result<W, ParseError> {
+    let mut writer = Some(writer);
+    // created with the first record, whose line ending is used by default
+    let mut fastx_writer = None;
+    let mut fake_quality = Vec::new();
+    while let Some(record) = reader.next() {
+        let record = record?;
+        let out = fastx_writer.get_or_insert_with(|| {
+            let line_ending = options.line_ending.unwrap_or(record.line_ending());
+            FastxWriter::new(writer.take().unwrap())
+                .with_line_ending(line_ending)
+                .with_line_width(options.line_width)
+        });
+        let format = options.format.unwrap_or(record.format());
+        match (format, record.qual()) {
+            (Format::Fasta, _) => out.write_fasta(record.id(), &record.seq())?,
+            (Format::Fastq, Some(qual)) => out.write_fastq(record.id(), &record.seq(), qual)?,
+            (Format::Fastq, None) => {
+                let seq = record.seq();
+                fake_quality.resize(seq.len(), options.fake_quality.to_ascii());
+                out.write_fastq(record.id(), &seq, &fake_quality)?;
+            }
+        }
+    }
+    match fastx_writer {
+        Some(fastx_writer) => fastx_writer.finish(),
+        None => Ok(writer.unwrap()),
+    }
+}
+
+/// Converts the (possibly compressed) file at `input` into the file at `output`,
+/// compressed according to its extension. Without a format in `options`, the format
+/// is the one of the extension of `output` if it has one (e.g. `.fa.gz`).
+///
+/// ```no_run
+/// use needletail::convert::{convert_file, ConvertOptions};
+///
+/// convert_file("reads.fastq.gz", "reads.fasta", &ConvertOptions::default()).unwrap();
+/// ```
+pub fn convert_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    options: &ConvertOptions,
+) -> Result<(), ParseError> {
+    let output = output.as_ref();
+    let mut reader = parse_fastx_file(input)?;
+    let options = ConvertOptions {
+        format: options.format.or(Format::from_path_extension(output)),
+        ..*options
+    };
+    let file = create_file(output)?;
+    let compression = Compression::from_path_extension(output);
+    let writer = CompressedWriter::new(file, compression, None)?;
+    convert(&mut reader, writer, &options)?.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_convert() {
+        let fasta = b">r1\r\nAC\r\nGT\r\n>r2\r\nA\r\n";
+        let convert_with = |options: &ConvertOptions| {
+            let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+            convert(&mut reader, Vec::new(), options).unwrap()
+        };
+        assert_eq!(
+            convert_with(&ConvertOptions::default()),
+            b">r1\r\nACGT\r\n>r2\r\nA\r\n"
+        );
+        let options = ConvertOptions {
+            format: Some(Format::Fastq),
+            fake_quality: Phred::new(20).unwrap(),
+            line_ending: Some(LineEnding::Unix),
+            ..Default::default()
+        };
+        assert_eq!(
+            convert_with(&options),
+            b"@r1\nACGT\n+\n5555\n@r2\nA\n+\n5\n"
+        );
+
+        let mut reader = parse_fastx_reader(&b">r1\nACGT\n"[..]).unwrap();
+        reader.next().unwrap().unwrap();
+        let empty = convert(&mut reader, Vec::new(), &options).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_convert_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("28S.fastq");
+        convert_file("tests/data/28S.fasta", &output, &ConvertOptions::default()).unwrap();
+        let mut original = parse_fastx_file("tests/data/28S.fasta").unwrap();
+        let mut converted = parse_fastx_file(&output).unwrap();
+        while let Some(record) = original.next() {
+            let record = record.unwrap();
+            let copy = converted.next().unwrap().unwrap();
+            assert_eq!(copy.id(), record.id());
+            assert_eq!(copy.seq(), record.seq());
+            assert_eq!(copy.qual().unwrap(), vec![b'I'; record.num_bases()]);
+        }
+        assert!(converted.next().is_none());
+    }
+}
@@ -0,0 +1,207 @@
+//! Experimental quality-based correction of sequencing errors.
+//!
+//! Nanopore reads notably get homopolymer lengths wrong, mostly by calling too
+//! few bases in long runs. [`homopolymer`] adjusts the length of every run using
+//! its mean quality and a [`HomopolymerModel`]. The default model is a simple
+//! table that can be replaced by a better one learned on your own data.
+use crate::parser::{OwnedRecord, SequenceRecord};
+use crate::quality::Phred;
+use crate::sequence::Base;
+
+/// Something that can tell the most likely length of a homopolymer run
+pub trait HomopolymerModel {
+    /// Returns the corrected length of a run of `observed_len` times `base`
+    /// (uppercase) whose bases have a mean score of `mean_quality`
+    fn corrected_length(&self, base: Base, observed_len: usize, mean_quality: Phred) -> usize;
+}
+
+/// A table of corrected lengths by observed run length and mean quality, the
+/// qualities being grouped in buckets of `bucket_size`.
+///
+/// Runs longer than the table are left untouched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HomopolymerTable {
+    bucket_size: u8,
+    // corrected lengths indexed by [observed length - 1][mean quality / bucket_size]
+    lengths: Vec<Vec<usize>>,
+}
+
+impl HomopolymerTable {
+    /// A table that doesn't change anything, for runs of up to `max_len` bases
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bucket_size` is 0.
+    pub fn identity(max_len: usize, bucket_size: u8) -> Self {
+        assert!(bucket_size > 0, "bucket_size needs to be at least 1");
+        let num_buckets = (Phred::MAX.score() / bucket_size) as usize + 1;
+        Self {
+            bucket_size,
+            lengths: (1..=max_len).map(|len| vec![len; num_buckets]).collect(),
+        }
+    }
+
+    /// Set the corrected length of runs of `observed_len` bases whose mean
+    /// quality falls in the same bucket as `mean_quality`
+    pub fn set(&mut self, observed_len: usize, mean_quality: Phred, corrected_len: usize) {
+        let bucket = (mean_quality.score() / self.bucket_size) as usize;
+        if let Some(row) = self.lengths.get_mut(observed_len.wrapping_sub(1)) {
+            row[bucket] = corrected_len;
+        }
+    }
+}
+
+impl Default for HomopolymerTable {
+    /// Adds one base to the runs of at least 4 bases with a mean quality below 10,
+    /// where a deletion is the most likely error.
+    fn default() -> Self {
+        let mut table = Self::identity(20, 5);
+        for len in 4..=20 {
+            for quality in [Phred::MIN, Phred::new(5).unwrap()] {
+                table.set(len, quality, len + 1);
+            }
+        }
+        table
+    }
+}
+
+impl HomopolymerModel for HomopolymerTable {
+    fn corrected_length(&self, _base: Base, observed_len: usize, mean_quality: Phred) -> usize {
+        let bucket = (mean_quality.score() / self.bucket_size) as usize;
+        self.lengths
+            .get(observed_len.wrapping_sub(1))
+            .map_or(observed_len, |row| row[bucket])
+    }
+}
+
+/// Returns a copy of the record with the length of its homopolymer runs
+/// adjusted by `model`. Bases added to a run get its mean quality.
+///
+/// Runs of `N` or of bytes that aren't nucleotides and records without quality (FASTA)
+/// are left untouched.
+///
+/// ```
+/// use needletail::correct::{homopolymer, HomopolymerTable};
+/// use needletail::parse_fastx_reader;
+///
+/// let mut reader = parse_fastx_reader(&b"@r\nCAAAAG\n+\nI((((I\n"[..]).unwrap();
+/// let record = reader.next().unwrap().unwrap();
+/// let corrected = homopolymer(&record, &HomopolymerTable::default());
+/// assert_eq!(corrected.seq, b"CAAAAAG");
+/// assert_eq!(corrected.qual.unwrap(), b"I(((((I");
+/// ```
+pub fn homopolymer<M: HomopolymerModel + ?Sized>(
+    record: &SequenceRecord,
+    model: &M,
+) -> OwnedRecord {
+    let owned = record.to_owned_record();
+    let qual = match &owned.qual {
+        Some(q) => q,
+        None => return owned,
+    };
+
+    let seq = &owned.seq;
+    let mut new_seq = Vec::with_capacity(seq.len());
+    let mut new_qual = Vec::with_capacity(seq.len());
+    let mut start = 0;
+    while start < seq.len() {
+        let base = seq[start].to_ascii_uppercase();
+        let end = start
+            + seq[start..]
+                .iter()
+                .take_while(|b| b.to_ascii_uppercase() == base)
+                .count();
+        let run_qual = &qual[start..end];
+        let observed_len = end - start;
+        let corrected_len = match Base::new(base) {
+            Some(base) if base != Base::N => {
+                let total: usize = run_qual
+                    .iter()
+                    .map(|q| q.saturating_sub(Phred::OFFSET) as usize)
+                    .sum();
+                let mean_quality = u8::try_from(total / observed_len)
+                    .ok()
+                    .and_then(Phred::new)
+                    .unwrap_or(Phred::MAX);
+                model.corrected_length(base, observed_len, mean_quality)
+            }
+            _ => observed_len,
+        };
+
+        if corrected_len <= observed_len {
+            new_seq.extend_from_slice(&seq[start..start + corrected_len]);
+            new_qual.extend_from_slice(&run_qual[..corrected_len]);
+        } else {
+            let mean_qual =
+                (run_qual.iter().map(|q| *q as usize).sum::<usize>() / observed_len) as u8;
+            new_seq.extend_from_slice(&seq[start..end]);
+            new_seq.resize(new_seq.len() + corrected_len - observed_len, seq[start]);
+            new_qual.extend_from_slice(run_qual);
+            new_qual.resize(new_qual.len() + corrected_len - observed_len, mean_qual);
+        }
+        start = end;
+    }
+
+    OwnedRecord {
+        id: owned.id,
+        seq: new_seq,
+        qual: Some(new_qual),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    struct Shorten;
+
+    impl HomopolymerModel for Shorten {
+        fn corrected_length(&self, base: Base, observed_len: usize, _mean_quality: Phred) -> usize {
+            if base == Base::T {
+                observed_len.min(2)
+            } else {
+                observed_len
+            }
+        }
+    }
+
+    #[test]
+    fn test_homopolymer_custom_model() {
+        let mut reader = parse_fastx_reader(&b"@r\nACttttGNNNN\n+\nABCDEFGHIJK\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let corrected = homopolymer(&record, &Shorten);
+        assert_eq!(corrected.seq, b"ACttGNNNN");
+        assert_eq!(corrected.qual.unwrap(), b"ABCDGHIJK");
+
+        // FASTA records are left as is
+        let mut reader = parse_fastx_reader(&b">f\nTTTT\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(homopolymer(&record, &Shorten).seq, b"TTTT");
+    }
+
+    fn q(score: u8) -> Phred {
+        Phred::new(score).unwrap()
+    }
+
+    #[test]
+    fn test_homopolymer_table() {
+        let mut table = HomopolymerTable::identity(3, 10);
+        table.set(3, q(25), 4);
+        assert_eq!(table.corrected_length(Base::A, 3, q(20)), 4);
+        assert_eq!(table.corrected_length(Base::A, 3, q(30)), 3);
+        assert_eq!(table.corrected_length(Base::A, 10, q(20)), 10);
+        assert_eq!(table.corrected_length(Base::A, 0, q(20)), 0);
+
+        let default = HomopolymerTable::default();
+        assert_eq!(default.corrected_length(Base::A, 5, q(9)), 6);
+        assert_eq!(default.corrected_length(Base::A, 5, q(10)), 5);
+        assert_eq!(default.corrected_length(Base::A, 3, q(2)), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "bucket_size needs to be at least 1")]
+    fn test_homopolymer_table_empty_bucket() {
+        HomopolymerTable::identity(3, 0);
+    }
+}
@@ -0,0 +1,299 @@
+//! Removing duplicate records in a single pass, by sequence or by id, e.g. to
+//! dereplicate amplicon reads.
+//!
+//! The first record with a given key is kept and the following ones are dropped.
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+
+use crate::errors::ParseError;
+use crate::parser::{id_key, FastxReader, SequenceRecord};
+
+/// What makes two records duplicates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupeKey {
+    /// The whole sequence, without line endings. Sequences that only differ by case
+    /// are different: normalize them first if needed.
+    #[default]
+    Sequence,
+    /// The first bases of the sequence, the whole sequence if it's shorter
+    Prefix(usize),
+    /// The id up to the first whitespace
+    Id,
+}
+
+/// How the keys already seen are remembered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupeHashing {
+    /// Keep a copy of each key: no record is ever dropped by mistake
+    #[default]
+    Exact,
+    /// Only keep a 64-bit hash of each key, much smaller for long sequences. Two
+    /// different keys with the same hash, which is very unlikely, would be seen as
+    /// duplicates.
+    Hash64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupeOptions {
+    pub key: DedupeKey,
+    pub hashing: DedupeHashing,
+    /// Approximate number of bytes the keys seen can take. Past it, they are moved to a
+    /// Bloom filter of that size: the memory stays bounded but some records that
+    /// aren't duplicates will be dropped, more and more as the filter fills up.
+    pub max_memory: Option<usize>,
+}
+
+impl Default for DedupeOptions {
+    fn default() -> Self {
+        Self {
+            key: DedupeKey::Sequence,
+            hashing: DedupeHashing::Exact,
+            max_memory: None,
+        }
+    }
+}
+
+/// Number of hash functions of the Bloom filter
+const BLOOM_HASHES: u64 = 4;
+/// Estimated memory used by each entry of a hash set, on top of the key itself
+const ENTRY_OVERHEAD: usize = 32;
+
+fn hash_key(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone)]
+enum SeenKeys {
+    Exact(HashSet<Vec<u8>>),
+    Hashes(HashSet<u64>),
+    Bloom(Vec<u64>),
+}
+
+/// Remembers the keys seen so far to tell whether a record is a duplicate
+///
+/// ```
+/// use needletail::dedupe::{DedupeKey, DedupeOptions, Deduplicator};
+///
+/// let mut dedupe = Deduplicator::new(DedupeOptions {
+///     key: DedupeKey::Prefix(4),
+///     ..Default::default()
+/// });
+/// assert!(dedupe.insert_key(b"ACGTAAA"));
+/// assert!(!dedupe.insert_key(b"ACGTCCC"));
+/// assert!(dedupe.insert_key(b"ACG"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deduplicator {
+    options: DedupeOptions,
+    seen: SeenKeys,
+    memory: usize,
+}
+
+impl Deduplicator {
+    pub fn new(options: DedupeOptions) -> Self {
+        let seen = match options.hashing {
+            DedupeHashing::Exact => SeenKeys::Exact(HashSet::new()),
+            DedupeHashing::Hash64 => SeenKeys::Hashes(HashSet::new()),
+        };
+        Self {
+            options,
+            seen,
+            memory: 0,
+        }
+    }
+
+    /// The key of a record for these options
+    pub fn key<'a>(&self, record: &'a SequenceRecord) -> Cow<'a, [u8]> {
+        match self.options.key {
+            DedupeKey::Id => id_key(record.id()).into(),
+            DedupeKey::Sequence | DedupeKey::Prefix(_) => record.seq(),
+        }
+    }
+
+    /// Remembers `key` (cut to the prefix length if needed) and returns whether it
+    /// wasn't seen before
+    pub fn insert_key(&mut self, key: &[u8]) -> bool {
+        let key = match self.options.key {
+            DedupeKey::Prefix(len) => &key[..len.min(key.len())],
+            _ => key,
+        };
+        let is_new = match &mut self.seen {
+            SeenKeys::Exact(keys) => {
+                let is_new = !keys.contains(key);
+                if is_new {
+                    keys.insert(key.to_vec());
+                    self.memory += key.len() + ENTRY_OVERHEAD;
+                }
+                is_new
+            }
+            SeenKeys::Hashes(hashes) => {
+                let is_new = hashes.insert(hash_key(key));
+                if is_new {
+                    self.memory += ENTRY_OVERHEAD;
+                }
+                is_new
+            }
+            SeenKeys::Bloom(bits) => return bloom_insert(bits, hash_key(key)),
+        };
+        if matches!(self.options.max_memory, Some(max) if self.memory > max) {
+            self.spill();
+        }
+        is_new
+    }
+
+    /// Whether the record wasn't seen before, remembering it
+    pub fn insert(&mut self, record: &SequenceRecord) -> bool {
+        let key = self.key(record);
+        self.insert_key(&key)
+    }
+
+    /// Whether the keys were moved to a Bloom filter, i.e. whether records that
+    /// aren't duplicates may have been dropped since
+    pub fn is_approximate(&self) -> bool {
+        matches!(self.seen, SeenKeys::Bloom(_))
+    }
+
+    /// Moves the keys to a Bloom filter of `max_memory` bytes
+    fn spill(&mut self) {
+        let words = (self.options.max_memory.unwrap_or(0) / 8).max(1);
+        let mut bits = vec![0; words];
+        match &self.seen {
+            SeenKeys::Exact(keys) => {
+                for key in keys {
+                    bloom_insert(&mut bits, hash_key(key));
+                }
+            }
+            SeenKeys::Hashes(hashes) => {
+                for hash in hashes {
+                    bloom_insert(&mut bits, *hash);
+                }
+            }
+            SeenKeys::Bloom(_) => return,
+        }
+        self.seen = SeenKeys::Bloom(bits);
+        self.memory = words * 8;
+    }
+}
+
+/// Sets the bits of `hash` in the filter and returns whether one of them wasn't set
+fn bloom_insert(bits: &mut [u64], hash: u64) -> bool {
+    let n_bits = bits.len() as u64 * 64;
+    // double hashing: the bits are at h1 + i * h2
+    let (h1, h2) = (hash, hash.rotate_left(32) | 1);
+    let mut is_new = false;
+    for i in 0..BLOOM_HASHES {
+        let bit = h1.wrapping_add(i.wrapping_mul(h2)) % n_bits;
+        let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+        is_new |= bits[word] & mask == 0;
+        bits[word] |= mask;
+    }
+    is_new
+}
+
+/// What was seen and kept while removing duplicates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupeCounts {
+    pub seen: usize,
+    pub kept: usize,
+    /// Whether the memory cap was reached, see [`DedupeOptions::max_memory`]
+    pub approximate: bool,
+}
+
+/// Writes the first record of each key of `reader` to `writer`, dropping the
+/// duplicates that follow
+///
+/// ```
+/// use needletail::dedupe::{dedupe_to_writer, DedupeOptions};
+/// use needletail::parse_fastx_reader;
+///
+/// let mut reader = parse_fastx_reader(&b">a\nACGT\n>b\nAC\nGT\n>c\nTTTT\n"[..]).unwrap();
+/// let mut out = Vec::new();
+/// let counts = dedupe_to_writer(&mut reader, DedupeOptions::default(), &mut out).unwrap();
+/// assert_eq!((counts.seen, counts.kept), (3, 2));
+/// assert_eq!(out, b">a\nACGT\n>c\nTTTT\n");
+/// ```
+pub fn dedupe_to_writer(
+    reader: &mut dyn FastxReader,
+    options: DedupeOptions,
+    writer: &mut dyn Write,
+) -> Result<DedupeCounts, ParseError> {
+    let mut dedupe = Deduplicator::new(options);
+    let mut counts = DedupeCounts::default();
+    while let Some(record) = reader.next() {
+        let record = record?;
+        counts.seen += 1;
+        if dedupe.insert(&record) {
+            record.write(writer, None)?;
+            counts.kept += 1;
+        }
+    }
+    counts.approximate = dedupe.is_approximate();
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    fn kept_ids(fasta: &[u8], options: DedupeOptions) -> Vec<Vec<u8>> {
+        let mut out = Vec::new();
+        dedupe_to_writer(&mut parse_fastx_reader(fasta).unwrap(), options, &mut out).unwrap();
+        let mut reader = parse_fastx_reader(&out[..]).unwrap();
+        let mut ids = Vec::new();
+        while let Some(record) = reader.next() {
+            ids.push(record.unwrap().id().to_vec());
+        }
+        ids
+    }
+
+    #[test]
+    fn test_dedupe_keys() {
+        let fasta = b">r1 x\nACGTA\n>r2\nACGTT\n>r1 y\nGG\n>r3\nACGTA\n";
+        for hashing in [DedupeHashing::Exact, DedupeHashing::Hash64] {
+            let options = DedupeOptions {
+                hashing,
+                ..Default::default()
+            };
+            assert_eq!(kept_ids(fasta, options), [&b"r1 x"[..], b"r2", b"r1 y"]);
+            let options = DedupeOptions {
+                key: DedupeKey::Prefix(4),
+                hashing,
+                ..Default::default()
+            };
+            assert_eq!(kept_ids(fasta, options), [&b"r1 x"[..], b"r1 y"]);
+            let options = DedupeOptions {
+                key: DedupeKey::Id,
+                hashing,
+                ..Default::default()
+            };
+            assert_eq!(kept_ids(fasta, options), [&b"r1 x"[..], b"r2", b"r3"]);
+        }
+    }
+
+    #[test]
+    fn test_memory_cap() {
+        let mut dedupe = Deduplicator::new(DedupeOptions {
+            max_memory: Some(2048),
+            ..Default::default()
+        });
+        let keys: Vec<_> = (0..1000).map(|i| format!("key{i}").into_bytes()).collect();
+        for key in &keys[..100] {
+            assert!(dedupe.insert_key(key));
+        }
+        assert!(dedupe.is_approximate());
+        // the keys seen before spilling are still known
+        assert!(keys[..100].iter().all(|key| !dedupe.insert_key(key)));
+        let kept = keys[100..]
+            .iter()
+            .filter(|key| dedupe.insert_key(key))
+            .count();
+        assert!(kept > 850, "{kept}");
+        assert!(!dedupe.insert_key(&keys[500]));
+    }
+}
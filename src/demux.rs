@@ -0,0 +1,448 @@
+//! Splitting reads into samples from inline barcodes, with optional extraction of UMIs
+//! (unique molecular identifiers) into the read ids.
+use std::io::{BufRead, BufReader, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::{id_key, open_file, FastxReader, OwnedRecord, SequenceRecord};
+
+/// A barcode identifying a sample
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Barcode {
+    pub sequence: Vec<u8>,
+    /// Position of the first base of the barcode in the read
+    pub position: usize,
+    /// Number of bases that can differ from the read, `N`s included
+    pub max_mismatches: usize,
+}
+
+/// The barcodes of each sample, a sample having one or more.
+///
+/// ```
+/// use needletail::demux::{Barcode, BarcodeTable};
+///
+/// let mut table = BarcodeTable::new();
+/// for (sample, sequence) in [("s1", "ACGT"), ("s2", "TTTT"), ("s2", "GGGG")] {
+///     let sequence = sequence.as_bytes().to_vec();
+///     table.add(sample, Barcode { sequence, position: 2, max_mismatches: 1 });
+/// }
+/// assert_eq!(table.samples(), ["s1", "s2"]);
+/// assert_eq!(table.assign(b"NNACGTAAAA"), Some(0));
+/// assert_eq!(table.assign(b"NNGGCGAAAA"), Some(1));
+/// assert_eq!(table.assign(b"NNCCCCAAAA"), None);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BarcodeTable {
+    samples: Vec<String>,
+    // each barcode with the index of its sample
+    barcodes: Vec<(Barcode, usize)>,
+}
+
+impl BarcodeTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a barcode of `sample` and returns the index of the sample. Barcodes match
+    /// reads regardless of case.
+    pub fn add(&mut self, sample: &str, mut barcode: Barcode) -> usize {
+        barcode.sequence.make_ascii_uppercase();
+        let index = match self.samples.iter().position(|s| s == sample) {
+            Some(index) => index,
+            None => {
+                self.samples.push(sample.to_string());
+                self.samples.len() - 1
+            }
+        };
+        self.barcodes.push((barcode, index));
+        index
+    }
+
+    /// Reads the barcode table at `path`, see [`BarcodeTable::from_reader`]
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Self::from_reader(BufReader::new(open_file(path.as_ref())?))
+    }
+
+    /// Reads a barcode table with one barcode per line: the sample name, the barcode and
+    /// optionally its position in the read (0 by default) and the number of mismatches
+    /// allowed (1 by default), separated by tabs. Empty lines and lines starting with
+    /// `#` are skipped.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, ParseError> {
+        let mut table = Self::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let columns: Vec<_> = line.split('\t').collect();
+            let number = |col: usize, default: usize| {
+                columns.get(col).map_or(Some(default), |n| n.parse().ok())
+            };
+            let (Some(position), Some(max_mismatches)) = (number(2, 0), number(3, 1)) else {
+                let msg = String::from("Expected numbers for the position and mismatches");
+                return Err(table_error(msg, i));
+            };
+            match columns[..] {
+                [sample, sequence, ..] if !sample.is_empty() && !sequence.is_empty() => {
+                    let barcode = Barcode {
+                        sequence: sequence.as_bytes().to_vec(),
+                        position,
+                        max_mismatches,
+                    };
+                    table.add(sample, barcode);
+                }
+                _ => {
+                    let msg =
+                        String::from("Expected a sample name and a barcode separated by a tab");
+                    return Err(table_error(msg, i));
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// The names of the samples, in the order they were added
+    pub fn samples(&self) -> &[String] {
+        &self.samples
+    }
+
+    /// The index of the sample whose barcode is the closest to `seq`, if it's within its
+    /// allowed mismatches. Reads as close to the barcodes of two samples aren't assigned.
+    pub fn assign(&self, seq: &[u8]) -> Option<usize> {
+        let mut best: Option<(usize, usize)> = None;
+        let mut tied = false;
+        for (barcode, sample) in &self.barcodes {
+            let Some(mismatches) = mismatches(barcode, seq) else {
+                continue;
+            };
+            match best {
+                Some((fewest, best_sample)) if mismatches == fewest => {
+                    tied |= best_sample != *sample;
+                }
+                Some((fewest, _)) if mismatches > fewest => {}
+                _ => {
+                    best = Some((mismatches, *sample));
+                    tied = false;
+                }
+            }
+        }
+        best.filter(|_| !tied).map(|(_, sample)| sample)
+    }
+}
+
+fn table_error(msg: String, line: usize) -> ParseError {
+    ParseError::new_invalid_record(
+        msg,
+        ErrorPosition {
+            line: line as u64 + 1,
+            id: None,
+        },
+    )
+}
+
+/// Number of bases of `seq` differing from the barcode, `None` if there are too many or
+/// if the read is too short to contain it
+fn mismatches(barcode: &Barcode, seq: &[u8]) -> Option<usize> {
+    let end = barcode.position + barcode.sequence.len();
+    let count = seq
+        .get(barcode.position..end)?
+        .iter()
+        .zip(&barcode.sequence)
+        .map(|(base, expected)| (base.to_ascii_uppercase(), *expected))
+        .filter(|(base, expected)| base != expected || *base == b'N')
+        .count();
+    (count <= barcode.max_mismatches).then_some(count)
+}
+
+/// What to do with the reads on top of assigning them to samples
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DemuxOptions {
+    /// Bases of the UMI in the read (R1 for pairs). They are added to the id of the
+    /// read (and of its mate) after a `:`, e.g. `read1:ACGTAC` or `read1:ACGTAC/1`.
+    pub umi: Option<Range<usize>>,
+    /// Whether to remove the bases up to the end of the barcode and UMI from the
+    /// reads assigned to a sample (R1 for pairs)
+    pub trim: bool,
+}
+
+/// How many reads (or pairs of reads) went to each sample
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DemuxCounts {
+    /// In the order of [`BarcodeTable::samples`]
+    pub samples: Vec<usize>,
+    pub unassigned: usize,
+}
+
+/// Adds `umi` to the id of a read, before its `/1` or `/2` suffix if there is one so
+/// that mates still have the same name
+fn tag_umi(id: &[u8], umi: &[u8]) -> Vec<u8> {
+    let name = id_key(id);
+    let name_end = name.len();
+    let insert_at = if name.ends_with(b"/1") || name.ends_with(b"/2") {
+        name_end - 2
+    } else {
+        name_end
+    };
+    let mut tagged = Vec::with_capacity(id.len() + umi.len() + 1);
+    tagged.extend_from_slice(&id[..insert_at]);
+    tagged.push(b':');
+    tagged.extend_from_slice(umi);
+    tagged.extend_from_slice(&id[insert_at..]);
+    tagged
+}
+
+/// Processes a read assigned to a sample: tags its id with the UMI (from `barcoded`,
+/// which is the read itself for single-end reads) and trims it if `trim` is set
+fn prepare(
+    record: &SequenceRecord,
+    barcoded: &[u8],
+    table: &BarcodeTable,
+    sample: usize,
+    options: &DemuxOptions,
+    trim: bool,
+) -> OwnedRecord {
+    let mut edit = record.edit();
+    let umi = options
+        .umi
+        .as_ref()
+        .map(|umi| &barcoded[umi.start.min(barcoded.len())..umi.end.min(barcoded.len())]);
+    if let Some(umi) = umi {
+        edit = edit.set_id(&tag_umi(id_key(record.id()), umi));
+    }
+    if trim {
+        let barcode_end = table
+            .barcodes
+            .iter()
+            .filter(|(_, s)| *s == sample)
+            .map(|(barcode, _)| barcode.position + barcode.sequence.len())
+            .max()
+            .unwrap_or(0);
+        let start = barcode_end.max(options.umi.as_ref().map_or(0, |umi| umi.end));
+        edit = edit.slice(start..usize::MAX);
+    }
+    edit.finish()
+}
+
+/// Writes each read of `reader` to the writer of its sample, in the order of
+/// [`BarcodeTable::samples`], or to `unassigned` if it doesn't match a barcode.
+/// Unassigned reads are written as they were read.
+///
+/// Panics if there are fewer writers than samples.
+///
+/// ```
+/// use needletail::demux::{demux_to_writers, BarcodeTable, DemuxOptions};
+/// use needletail::parse_fastx_reader;
+///
+/// let table = BarcodeTable::from_reader(&b"s1\tACGT\ns2\tTTTT\n"[..]).unwrap();
+/// let fastq = b"@r1\nACGTGGCCAA\n+\nIIIIIIIIII\n@r2\nTTATGGCCAA\n+\nIIIIIIIIII\n";
+/// let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+/// let (mut s1, mut s2, mut unassigned) = (Vec::new(), Vec::new(), Vec::new());
+/// // a 4bp UMI right after the barcode
+/// let options = DemuxOptions { umi: Some(4..8), trim: true };
+/// let counts = demux_to_writers(
+///     &mut reader,
+///     &table,
+///     &options,
+///     &mut [&mut s1, &mut s2],
+///     &mut unassigned,
+/// )
+/// .unwrap();
+/// assert_eq!(counts.samples, [1, 1]);
+/// assert_eq!(s1, b"@r1:GGCC\nAA\n+\nII\n");
+/// assert_eq!(s2, b"@r2:GGCC\nAA\n+\nII\n");
+/// ```
+pub fn demux_to_writers(
+    reader: &mut dyn FastxReader,
+    table: &BarcodeTable,
+    options: &DemuxOptions,
+    writers: &mut [&mut dyn Write],
+    unassigned: &mut dyn Write,
+) -> Result<DemuxCounts, ParseError> {
+    assert!(
+        writers.len() >= table.samples.len(),
+        "{} writers for {} samples",
+        writers.len(),
+        table.samples.len()
+    );
+    let mut counts = DemuxCounts {
+        samples: vec![0; table.samples.len()],
+        unassigned: 0,
+    };
+    while let Some(record) = reader.next() {
+        let record = record?;
+        let seq = record.seq();
+        match table.assign(&seq) {
+            Some(sample) => {
+                let prepared = prepare(&record, &seq, table, sample, options, options.trim);
+                prepared.write(writers[sample], None)?;
+                counts.samples[sample] += 1;
+            }
+            None => {
+                record.write(unassigned, None)?;
+                counts.unassigned += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Paired-end version of [`demux_to_writers`]: the barcodes and UMIs are read from R1,
+/// both mates get the UMI and only R1 is trimmed. R1 goes to the first writer of each
+/// tuple and R2 to the second one.
+///
+/// Returns an error of kind `UnpairedRecords` if one of the readers runs out of
+/// records before the other.
+pub fn demux_paired_to_writers(
+    reader1: &mut dyn FastxReader,
+    reader2: &mut dyn FastxReader,
+    table: &BarcodeTable,
+    options: &DemuxOptions,
+    writers: &mut [(&mut dyn Write, &mut dyn Write)],
+    unassigned: (&mut dyn Write, &mut dyn Write),
+) -> Result<DemuxCounts, ParseError> {
+    assert!(
+        writers.len() >= table.samples.len(),
+        "{} writers for {} samples",
+        writers.len(),
+        table.samples.len()
+    );
+    let mut counts = DemuxCounts {
+        samples: vec![0; table.samples.len()],
+        unassigned: 0,
+    };
+    loop {
+        let (record1, record2) = match (reader1.next(), reader2.next()) {
+            (None, None) => break,
+            (Some(r1), Some(r2)) => (r1?, r2?),
+            (Some(r), None) | (None, Some(r)) => {
+                let record = r?;
+                return Err(ParseError::new_unpaired_records(
+                    ErrorPosition {
+                        line: record.start_line_number(),
+                        id: Some(String::from_utf8_lossy(record.id()).into()),
+                    },
+                    record.format(),
+                ));
+            }
+        };
+        let seq = record1.seq();
+        match table.assign(&seq) {
+            Some(sample) => {
+                let (writer1, writer2) = &mut writers[sample];
+                prepare(&record1, &seq, table, sample, options, options.trim)
+                    .write(*writer1, None)?;
+                prepare(&record2, &seq, table, sample, options, false).write(*writer2, None)?;
+                counts.samples[sample] += 1;
+            }
+            None => {
+                record1.write(unassigned.0, None)?;
+                record2.write(unassigned.1, None)?;
+                counts.unassigned += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_barcode_table() {
+        let tsv = b"# sample\tbarcode\n\ns1\tacgt\t0\t0\ns2\tACGA\t0\t1\ns3\tGGGG\t4\n";
+        let table = BarcodeTable::from_reader(&tsv[..]).unwrap();
+        assert_eq!(table.samples(), ["s1", "s2", "s3"]);
+        assert_eq!(table.assign(b"ACGTAAAA"), Some(0));
+        // ACGA matches exactly, ACGT would need a mismatch it isn't allowed
+        assert_eq!(table.assign(b"ACGAAAAA"), Some(1));
+        // one mismatch from both s1 and s2, only allowed for s2
+        assert_eq!(table.assign(b"ACGCAAAA"), Some(1));
+        assert_eq!(table.assign(b"TTTTGGGN"), Some(2));
+        assert_eq!(table.assign(b"TTTTGGG"), None);
+
+        let mut tied = BarcodeTable::new();
+        for (sample, seq) in [("a", b"AAAA"), ("b", b"AAAT")] {
+            let barcode = Barcode {
+                sequence: seq.to_vec(),
+                position: 0,
+                max_mismatches: 1,
+            };
+            tied.add(sample, barcode);
+        }
+        assert_eq!(tied.assign(b"AAAC"), None);
+        assert_eq!(tied.assign(b"AAAT"), Some(1));
+
+        let mut lowercase = BarcodeTable::new();
+        let barcode = Barcode {
+            sequence: b"acgt".to_vec(),
+            position: 0,
+            max_mismatches: 0,
+        };
+        lowercase.add("s1", barcode);
+        assert_eq!(lowercase.assign(b"ACGTAAAA"), Some(0));
+        assert_eq!(lowercase.assign(b"acgtAAAA"), Some(0));
+        assert_eq!(lowercase.assign(b"acgnAAAA"), None);
+
+        let err = BarcodeTable::from_reader(&b"s1\tACGT\ns2\n"[..]).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        assert_eq!(err.position.line, 2);
+        let err = BarcodeTable::from_reader(&b"s1\tACGT\tx\n"[..]).unwrap_err();
+        assert_eq!(err.position.line, 1);
+    }
+
+    #[test]
+    fn test_tag_umi() {
+        assert_eq!(tag_umi(b"r1", b"ACG"), b"r1:ACG");
+        assert_eq!(tag_umi(b"r1/2", b"ACG"), b"r1:ACG/2");
+        assert_eq!(tag_umi(b"r1 1:N:0", b"ACG"), b"r1:ACG 1:N:0");
+    }
+
+    #[test]
+    fn test_demux_paired_to_writers() {
+        let table = BarcodeTable::from_reader(&b"s1\tAAAA\ns2\tCCCC\n"[..]).unwrap();
+        let mut r1 = parse_fastx_reader(
+            &b"@a/1 x\nAAAATTGTACGT\n+\nIIIIIIIIIIII\n@b/1\nGGGGTTGTACGT\n+\nIIIIIIIIIIII\n"[..],
+        )
+        .unwrap();
+        let mut r2 =
+            parse_fastx_reader(&b"@a/2 y\nTTTT\n+\nIIII\n@b/2\nTTTT\n+\nIIII\n"[..]).unwrap();
+        let (mut s1_1, mut s1_2, mut s2_1, mut s2_2, mut un1, mut un2) = (
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        );
+        let options = DemuxOptions {
+            umi: Some(4..7),
+            trim: true,
+        };
+        let counts = demux_paired_to_writers(
+            &mut r1,
+            &mut r2,
+            &table,
+            &options,
+            &mut [(&mut s1_1, &mut s1_2), (&mut s2_1, &mut s2_2)],
+            (&mut un1, &mut un2),
+        )
+        .unwrap();
+        assert_eq!(
+            counts,
+            DemuxCounts {
+                samples: vec![1, 0],
+                unassigned: 1
+            }
+        );
+        assert_eq!(s1_1, b"@a:TTG/1 x\nTACGT\n+\nIIIII\n");
+        assert_eq!(s1_2, b"@a:TTG/2 y\nTTTT\n+\nIIII\n");
+        assert!(s2_1.is_empty() && s2_2.is_empty());
+        assert_eq!(un1, b"@b/1\nGGGGTTGTACGT\n+\nIIIIIIIIIIII\n");
+        assert_eq!(un2, b"@b/2\nTTTT\n+\nIIII\n");
+    }
+}
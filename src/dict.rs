@@ -0,0 +1,69 @@
+//! Sequence dictionaries (`.dict` files) as produced by Picard's
+//! `CreateSequenceDictionary` and used by GATK
+use std::io::Write;
+
+use crate::errors::ParseError;
+use crate::parser::{id_key, FastxReader};
+
+/// Write a SAM-header style sequence dictionary for all the records in `reader`.
+///
+/// The output starts with a `@HD` line followed by one `@SQ` line per record
+/// containing its name (`SN`, the id up to the first whitespace), its length
+/// (`LN`), the MD5 of its uppercased sequence (`M5`) and optionally the `UR`
+/// field if a `uri` is given.
+///
+/// ```
+/// use needletail::dict::write_sequence_dictionary;
+/// use needletail::parse_fastx_reader;
+///
+/// let mut reader = parse_fastx_reader(&b">chr1 first\nACGT\nacgt\n"[..]).unwrap();
+/// let mut out = Vec::new();
+/// write_sequence_dictionary(&mut reader, &mut out, None).unwrap();
+/// assert_eq!(
+///     out,
+///     b"@HD\tVN:1.6\tSO:unsorted\n@SQ\tSN:chr1\tLN:8\tM5:cc0af3a4fedb18378b4b57b98068e69f\n"
+/// );
+/// ```
+pub fn write_sequence_dictionary(
+    reader: &mut dyn FastxReader,
+    writer: &mut dyn Write,
+    uri: Option<&str>,
+) -> Result<(), ParseError> {
+    writer.write_all(b"@HD\tVN:1.6\tSO:unsorted\n")?;
+
+    while let Some(record) = reader.next() {
+        let record = record?;
+        let name = id_key(record.id());
+        let seq = record.seq().to_ascii_uppercase();
+        let digest = md5::compute(&seq);
+
+        writer.write_all(b"@SQ\tSN:")?;
+        writer.write_all(name)?;
+        write!(writer, "\tLN:{}\tM5:{:x}", seq.len(), digest)?;
+        if let Some(uri) = uri {
+            write!(writer, "\tUR:{uri}")?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_file;
+
+    #[test]
+    fn test_dict_from_file() {
+        let mut reader = parse_fastx_file("tests/data/test.fa").unwrap();
+        let mut out = Vec::new();
+        write_sequence_dictionary(&mut reader, &mut out, Some("file:/data/test.fa")).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "@HD\tVN:1.6\tSO:unsorted");
+        assert!(lines[1].starts_with("@SQ\tSN:test\tLN:10\tM5:"));
+        assert!(lines[1].ends_with("\tUR:file:/data/test.fa"));
+        assert!(lines[2].starts_with("@SQ\tSN:test2\tLN:4\tM5:"));
+    }
+}
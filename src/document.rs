@@ -0,0 +1,190 @@
+//! Small FASTA/FASTQ files (primer panels, spike-in references...) loaded in memory
+//! to be edited and written back
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::{create_file, id_key, parse_fastx_file, FastxReader, FastxWriter, OwnedRecord};
+
+/// The records of a file in order, indexed by id (the header up to the first
+/// whitespace), which has to be unique. Meant for files small enough to hold in memory:
+/// use the streaming readers and writers for anything else.
+///
+/// ```no_run
+/// use needletail::document::FastxDocument;
+/// use needletail::parser::OwnedRecord;
+///
+/// let mut primers = FastxDocument::read("primers.fa").unwrap();
+/// primers.remove(b"old_primer");
+/// primers
+///     .append(OwnedRecord {
+///         id: b"new_primer".to_vec(),
+///         seq: b"ACGTTGCA".to_vec(),
+///         qual: None,
+///     })
+///     .unwrap();
+/// primers.write("primers.fa").unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FastxDocument {
+    records: Vec<OwnedRecord>,
+    index: HashMap<Vec<u8>, usize>,
+}
+
+impl FastxDocument {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads all the records of the file at `path`, which can be compressed.
+    /// Returns an error of kind `InvalidRecord` if an id is found twice.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Self::from_reader(&mut *parse_fastx_file(path)?)
+    }
+
+    /// Reads all the remaining records of `reader`, see [`read`](FastxDocument::read)
+    pub fn from_reader(reader: &mut dyn FastxReader) -> Result<Self, ParseError> {
+        let mut document = Self::new();
+        while let Some(record) = reader.next() {
+            let record = record?;
+            if document.get(record.id()).is_some() {
+                return Err(duplicate_id(record.id(), record.start_line_number()));
+            }
+            document.push(record.to_owned_record());
+        }
+        Ok(document)
+    }
+
+    /// Writes the records in order to the file at `path`, replacing it. Each record is
+    /// written in its own format.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ParseError> {
+        self.write_to(create_file(path.as_ref())?)
+    }
+
+    /// Writes the records in order to `writer`
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), ParseError> {
+        let mut writer = FastxWriter::new(writer);
+        for record in &self.records {
+            writer.write_owned_record(record)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// The records, in order
+    pub fn records(&self) -> &[OwnedRecord] {
+        &self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns the record with the id `id`. Anything after a whitespace in `id` is
+    /// ignored, so a full header can be given.
+    pub fn get(&self, id: &[u8]) -> Option<&OwnedRecord> {
+        self.index.get(id_key(id)).map(|&i| &self.records[i])
+    }
+
+    /// Adds `record` at the end. Returns an error of kind `InvalidRecord` if there is
+    /// already a record with its id.
+    pub fn append(&mut self, record: OwnedRecord) -> Result<(), ParseError> {
+        if self.get(&record.id).is_some() {
+            return Err(duplicate_id(&record.id, 0));
+        }
+        self.push(record);
+        Ok(())
+    }
+
+    /// Replaces the record with the same id as `record`, keeping its place, and returns
+    /// it. `record` is added at the end if there is none.
+    pub fn replace(&mut self, record: OwnedRecord) -> Option<OwnedRecord> {
+        match self.index.get(id_key(&record.id)) {
+            Some(&i) => Some(std::mem::replace(&mut self.records[i], record)),
+            None => {
+                self.push(record);
+                None
+            }
+        }
+    }
+
+    /// Removes the record with the id `id` and returns it
+    pub fn remove(&mut self, id: &[u8]) -> Option<OwnedRecord> {
+        let i = self.index.remove(id_key(id))?;
+        let record = self.records.remove(i);
+        for position in self.index.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+        Some(record)
+    }
+
+    fn push(&mut self, record: OwnedRecord) {
+        self.index
+            .insert(id_key(&record.id).to_vec(), self.records.len());
+        self.records.push(record);
+    }
+}
+
+fn duplicate_id(id: &[u8], line: u64) -> ParseError {
+    let id = String::from_utf8_lossy(id_key(id)).into_owned();
+    ParseError::new_invalid_record(
+        format!("Duplicate id: {id}"),
+        ErrorPosition { line, id: Some(id) },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    fn record(id: &[u8], seq: &[u8]) -> OwnedRecord {
+        OwnedRecord {
+            id: id.to_vec(),
+            seq: seq.to_vec(),
+            qual: None,
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        let fasta = b">p1 forward\nACGT\n>p2\nTTTT\n>p3\nGGGG\n";
+        let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+        let mut document = FastxDocument::from_reader(&mut reader).unwrap();
+        assert_eq!(document.len(), 3);
+        assert_eq!(document.get(b"p1").unwrap().seq, b"ACGT");
+        assert_eq!(document.get(b"p1 forward").unwrap().seq, b"ACGT");
+
+        assert_eq!(document.remove(b"p2").unwrap().seq, b"TTTT");
+        assert!(document.remove(b"p2").is_none());
+        assert_eq!(document.get(b"p3").unwrap().seq, b"GGGG");
+        let err = document.append(record(b"p3 again", b"A")).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        document.append(record(b"p4", b"CCCC")).unwrap();
+        let old = document.replace(record(b"p1 reverse", b"TGCA")).unwrap();
+        assert_eq!(old.id, b"p1 forward");
+        assert!(document.replace(record(b"p5", b"AA")).is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("primers.fa");
+        document.write(&path).unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b">p1 reverse\nTGCA\n>p3\nGGGG\n>p4\nCCCC\n>p5\nAA\n"
+        );
+        assert_eq!(FastxDocument::read(&path).unwrap(), document);
+
+        let mut reader = parse_fastx_reader(&b">a\nA\n>b\nC\n>a x\nG\n"[..]).unwrap();
+        let err = FastxDocument::from_reader(&mut reader).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        assert_eq!(err.position.line, 5);
+    }
+}
@@ -23,8 +23,10 @@ impl fmt::Display for ErrorPosition {
     }
 }
 
-/// The type of error that occured during file parsing
+/// The type of error that occured during file parsing. New kinds may be added in minor
+/// releases so matches need a wildcard arm.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ParseErrorKind {
     /// An error happened during file/stream input/output
     Io,
@@ -40,6 +42,21 @@ pub enum ParseErrorKind {
     UnexpectedEnd,
     /// The file appears to be empty
     EmptyFile,
+    /// Paired files don't have the same number of records
+    UnpairedRecords,
+    /// The records of a read pair aren't mates: different names or not R1 then R2
+    MismatchedMates,
+    /// A record couldn't be read from a file of another format (e.g. a table row missing a column)
+    InvalidRecord,
+    /// Stdin is a terminal rather than a pipe or a file, so there is nothing to read
+    NoInput,
+    /// A FASTA record was given where a FASTQ one is needed (e.g. to use its quality)
+    /// or the other way around
+    WrongFormat,
+    /// An index (e.g. a `.fai`) is malformed or doesn't match the file it indexes
+    InvalidIndex,
+    /// A region was asked for a sequence missing from the index or is outside of it
+    InvalidRegion,
 }
 
 /// The only error type that needletail returns
@@ -83,6 +100,19 @@ impl ParseError {
         }
     }
 
+    pub fn new_separator_id_mismatch(separator_id: &[u8], position: ErrorPosition) -> Self {
+        let msg = format!(
+            "Separator line repeats id '{}' instead of the id of the record",
+            String::from_utf8_lossy(separator_id)
+        );
+        Self {
+            kind: ParseErrorKind::InvalidSeparator,
+            msg,
+            position,
+            format: Some(Format::Fastq),
+        }
+    }
+
     pub fn new_unknown_format(byte_found: u8) -> Self {
         let msg = format!(
             "Expected '@' or '>' at the start of the file but found '{}'.",
@@ -115,6 +145,97 @@ impl ParseError {
         }
     }
 
+    pub fn new_write_error(
+        err: io::Error,
+        position: ErrorPosition,
+        format: Option<Format>,
+    ) -> Self {
+        Self {
+            msg: format!("failed to write {position}: {err}"),
+            kind: ParseErrorKind::Io,
+            position,
+            format,
+        }
+    }
+
+    pub fn new_unpaired_records(position: ErrorPosition, format: Format) -> Self {
+        Self {
+            msg: String::from("The paired inputs don't have the same number of records"),
+            kind: ParseErrorKind::UnpairedRecords,
+            position,
+            format: Some(format),
+        }
+    }
+
+    pub fn new_mismatched_mates(
+        id1: &[u8],
+        id2: &[u8],
+        position: ErrorPosition,
+        format: Format,
+    ) -> Self {
+        Self {
+            msg: format!(
+                "'{}' and '{}' aren't the R1 and R2 of the same read pair",
+                String::from_utf8_lossy(id1),
+                String::from_utf8_lossy(id2)
+            ),
+            kind: ParseErrorKind::MismatchedMates,
+            position,
+            format: Some(format),
+        }
+    }
+
+    pub fn new_invalid_record(msg: String, position: ErrorPosition) -> Self {
+        Self {
+            msg,
+            kind: ParseErrorKind::InvalidRecord,
+            position,
+            format: None,
+        }
+    }
+
+    pub fn new_invalid_index(msg: String, position: ErrorPosition) -> Self {
+        Self {
+            msg,
+            kind: ParseErrorKind::InvalidIndex,
+            position,
+            format: None,
+        }
+    }
+
+    pub fn new_invalid_region(msg: String) -> Self {
+        Self {
+            msg,
+            kind: ParseErrorKind::InvalidRegion,
+            position: ErrorPosition::default(),
+            format: Some(Format::Fasta),
+        }
+    }
+
+    pub fn new_no_input() -> Self {
+        Self {
+            msg: String::from(
+                "No input: stdin is a terminal. Pipe or redirect a FASTA/FASTQ file into it",
+            ),
+            kind: ParseErrorKind::NoInput,
+            position: ErrorPosition::default(),
+            format: None,
+        }
+    }
+
+    pub fn new_wrong_format(expected: Format, position: ErrorPosition) -> Self {
+        let (expected_name, found, found_name) = match expected {
+            Format::Fasta => ("FASTA", Format::Fastq, "FASTQ"),
+            Format::Fastq => ("FASTQ", Format::Fasta, "FASTA"),
+        };
+        Self {
+            msg: format!("Expected a {expected_name} record but found a {found_name} one"),
+            kind: ParseErrorKind::WrongFormat,
+            position,
+            format: Some(found),
+        }
+    }
+
     pub fn new_empty_file() -> Self {
         Self {
             msg: String::from("Failed to read the first two bytes. Is the file empty?"),
@@ -129,10 +250,16 @@ impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
             ParseErrorKind::Io => write!(f, "I/O error: {}", self.msg),
+            ParseErrorKind::NoInput | ParseErrorKind::InvalidRegion => write!(f, "{}", self.msg),
             ParseErrorKind::UnequalLengths
             | ParseErrorKind::InvalidStart
             | ParseErrorKind::UnknownFormat
             | ParseErrorKind::EmptyFile
+            | ParseErrorKind::UnpairedRecords
+            | ParseErrorKind::MismatchedMates
+            | ParseErrorKind::InvalidRecord
+            | ParseErrorKind::WrongFormat
+            | ParseErrorKind::InvalidIndex
             | ParseErrorKind::InvalidSeparator => write!(f, "{} ({})", self.msg, self.position),
             ParseErrorKind::UnexpectedEnd => {
                 write!(f, "Unexpected end of input ({}).", self.position)
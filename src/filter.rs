@@ -0,0 +1,194 @@
+//! Length and quality filters for records, to use as predicates with
+//! [`crate::partition::partition`] or anywhere else records are filtered.
+//!
+//! Each filter is a function returning a predicate on a [`SequenceRecord`], and
+//! [`RecordFilter`] combines them. Records without quality information (FASTA) always
+//! pass the quality filters, use [`RecordFilter::try_passes`] to get an error instead.
+use crate::errors::ParseError;
+use crate::parser::{Format, SequenceRecord};
+use crate::quality::{expected_errors, mean_quality};
+
+/// Keeps the records of at least `len` bases
+pub fn min_len(len: usize) -> impl Fn(&SequenceRecord) -> bool {
+    move |record| record.num_bases() >= len
+}
+
+/// Keeps the records of at most `len` bases
+pub fn max_len(len: usize) -> impl Fn(&SequenceRecord) -> bool {
+    move |record| record.num_bases() <= len
+}
+
+/// Fraction of the bases of `record` that are `N` (in either case), 0 for empty records
+pub fn n_fraction(record: &SequenceRecord) -> f64 {
+    let num_bases = record.num_bases();
+    if num_bases == 0 {
+        return 0.;
+    }
+    // line endings aren't `N` so there is no need to strip them
+    let n = record
+        .raw_seq()
+        .iter()
+        .filter(|b| b.eq_ignore_ascii_case(&b'N'))
+        .count();
+    n as f64 / num_bases as f64
+}
+
+/// Keeps the records where at most `fraction` of the bases are `N`
+pub fn max_n_fraction(fraction: f64) -> impl Fn(&SequenceRecord) -> bool {
+    move |record| n_fraction(record) <= fraction
+}
+
+/// Keeps the records with a mean Phred score of at least `quality`. Records without
+/// bases don't have a mean quality and are dropped. Records without quality
+/// information at all (FASTA) are kept since they can't be checked.
+pub fn min_mean_quality(quality: f64) -> impl Fn(&SequenceRecord) -> bool {
+    move |record| match record.qual() {
+        None => true,
+        Some(qual) => mean_quality(qual).is_some_and(|mean| mean >= quality),
+    }
+}
+
+/// Keeps the records with at most `max` expected errors, see
+/// [`crate::quality::expected_errors`]. Records without quality information (FASTA)
+/// are kept since they can't be checked.
+pub fn max_expected_errors(max: f64) -> impl Fn(&SequenceRecord) -> bool {
+    move |record| {
+        record
+            .qual()
+            .is_none_or(|qual| expected_errors(qual) <= max)
+    }
+}
+
+/// All the filters at once, each one being skipped when it's `None`
+///
+/// ```
+/// use needletail::filter::RecordFilter;
+/// use needletail::parse_fastx_reader;
+/// use needletail::partition::partition;
+///
+/// let fastq = b"@r1\nACGTACGT\n+\nIIIIIIII\n@r2\nACGT\n+\nIIII\n@r3\nACGTACGN\n+\nII####II\n";
+/// let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+/// let filter = RecordFilter {
+///     min_len: Some(6),
+///     max_expected_errors: Some(1.),
+///     ..Default::default()
+/// };
+/// let (mut pass, mut fail) = (Vec::new(), Vec::new());
+/// let counts = partition(&mut reader, |r| filter.passes(r), &mut pass, &mut fail).unwrap();
+/// assert_eq!((counts.matched, counts.unmatched), (1, 2));
+/// assert_eq!(pass, b"@r1\nACGTACGT\n+\nIIIIIIII\n");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RecordFilter {
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+    pub max_n_fraction: Option<f64>,
+    pub min_mean_quality: Option<f64>,
+    pub max_expected_errors: Option<f64>,
+}
+
+impl RecordFilter {
+    /// Whether the record passes all the filters set. Records without quality
+    /// information pass the quality filters.
+    pub fn passes(&self, record: &SequenceRecord) -> bool {
+        self.min_len.is_none_or(|len| min_len(len)(record))
+            && self.max_len.is_none_or(|len| max_len(len)(record))
+            && self
+                .max_n_fraction
+                .is_none_or(|fraction| max_n_fraction(fraction)(record))
+            && self
+                .min_mean_quality
+                .is_none_or(|quality| min_mean_quality(quality)(record))
+            && self
+                .max_expected_errors
+                .is_none_or(|max| max_expected_errors(max)(record))
+    }
+
+    /// Same as `passes`, but returns an error of kind `WrongFormat` for records without
+    /// quality information when a quality filter is set, instead of letting them pass
+    ///
+    /// ```
+    /// use needletail::errors::ParseErrorKind;
+    /// use needletail::filter::RecordFilter;
+    /// use needletail::parse_fastx_reader;
+    ///
+    /// let filter = RecordFilter {
+    ///     min_mean_quality: Some(20.),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = parse_fastx_reader(&b">r1\nACGT\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// assert!(filter.passes(&record));
+    /// let err = filter.try_passes(&record).unwrap_err();
+    /// assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+    /// ```
+    pub fn try_passes(&self, record: &SequenceRecord) -> Result<bool, ParseError> {
+        if self.min_mean_quality.is_some() || self.max_expected_errors.is_some() {
+            record.require_format(Format::Fastq)?;
+        }
+        Ok(self.passes(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    fn check<F: Fn(&SequenceRecord) -> bool>(records: &[u8], filter: F) -> Vec<bool> {
+        let mut reader = parse_fastx_reader(records).unwrap();
+        let mut passed = Vec::new();
+        while let Some(record) = reader.next() {
+            passed.push(filter(&record.unwrap()));
+        }
+        passed
+    }
+
+    #[test]
+    fn test_filters() {
+        let fasta = b">a\nACGT\nNN\n>b\nAC\n>c\n\n";
+        assert_eq!(check(fasta, min_len(2)), [true, true, false]);
+        assert_eq!(check(fasta, max_len(2)), [false, true, true]);
+        assert_eq!(check(fasta, max_n_fraction(0.3)), [false, true, true]);
+        assert_eq!(check(fasta, max_n_fraction(1. / 3.)), [true, true, true]);
+        // FASTA records have no quality to filter on
+        assert_eq!(check(fasta, min_mean_quality(40.)), [true, true, true]);
+        assert_eq!(check(fasta, max_expected_errors(0.)), [true, true, true]);
+
+        let fastq = b"@a\nACGT\n+\nII55\n@b\nnnAC\n+\n++++\n@c\n\n+\n\n";
+        assert_eq!(check(fastq, min_mean_quality(30.)), [true, false, false]);
+        assert_eq!(check(fastq, max_expected_errors(0.03)), [true, false, true]);
+        assert_eq!(check(fastq, max_n_fraction(0.5)), [true, true, true]);
+        assert_eq!(check(fastq, max_n_fraction(0.4)), [true, false, true]);
+
+        let filter = RecordFilter {
+            min_len: Some(1),
+            max_n_fraction: Some(0.4),
+            ..Default::default()
+        };
+        assert_eq!(check(fastq, |r| filter.passes(r)), [true, false, false]);
+        assert_eq!(
+            check(fastq, |r| RecordFilter::default().passes(r)),
+            [true, true, true]
+        );
+        assert_eq!(
+            check(fastq, |r| filter.try_passes(r).unwrap()),
+            [true, false, false]
+        );
+
+        // FASTA records can't be checked against quality filters
+        let quality = RecordFilter {
+            max_expected_errors: Some(1.),
+            ..Default::default()
+        };
+        assert_eq!(check(fasta, |r| quality.passes(r)), [true, true, true]);
+        assert_eq!(
+            check(fasta, |r| quality.try_passes(r).is_err()),
+            [true, true, true]
+        );
+        assert_eq!(
+            check(fasta, |r| filter.try_passes(r).unwrap()),
+            [true, true, false]
+        );
+    }
+}
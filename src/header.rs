@@ -0,0 +1,211 @@
+//! Parsing the structured fields found in FASTA/FASTQ headers, e.g.
+//! `>read1 length=150 barcode=ACGT` or `>gi|12345|ref|NC_000913.3| E. coli`.
+use std::io::Write;
+
+use crate::errors::ParseError;
+
+/// How the attributes of a header are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeaderStyle {
+    /// NCBI-style `db|accession` pairs in the first word, e.g. `gi|12345|ref|NC_000913.3|`
+    NcbiPipes,
+    /// Whitespace-separated `key=value` fields after the first word
+    KeyValue,
+    /// No recognized structure: just a name and an optional description
+    Plain,
+}
+
+/// A header split into its name and attributes, see [`parse_attributes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header<'a> {
+    /// The first word of the header
+    pub name: &'a [u8],
+    pub style: HeaderStyle,
+    /// The attributes, in the order they appear in the header.
+    /// For `NcbiPipes` headers, a lone last field is stored with an empty value.
+    pub attributes: Vec<(&'a [u8], &'a [u8])>,
+    /// Whatever follows the first word if it isn't made of `key=value` fields
+    pub description: Option<&'a [u8]>,
+}
+
+impl<'a> Header<'a> {
+    /// Returns the value of the first attribute named `key`
+    pub fn get(&self, key: &[u8]) -> Option<&'a [u8]> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+    }
+
+    /// Writes the header back (without the leading `>` or `@`), using the
+    /// attributes rather than the name for `NcbiPipes` headers so changes to
+    /// them are kept. Whitespace between fields is written as a single space.
+    pub fn write(&self, writer: &mut dyn Write) -> Result<(), ParseError> {
+        match self.style {
+            HeaderStyle::NcbiPipes => {
+                for (i, (key, value)) in self.attributes.iter().enumerate() {
+                    if i > 0 {
+                        writer.write_all(b"|")?;
+                    }
+                    writer.write_all(key)?;
+                    if !value.is_empty() || i + 1 < self.attributes.len() {
+                        writer.write_all(b"|")?;
+                        writer.write_all(value)?;
+                    }
+                }
+            }
+            HeaderStyle::KeyValue => {
+                writer.write_all(self.name)?;
+                for (key, value) in &self.attributes {
+                    writer.write_all(b" ")?;
+                    writer.write_all(key)?;
+                    writer.write_all(b"=")?;
+                    writer.write_all(value)?;
+                }
+            }
+            HeaderStyle::Plain => writer.write_all(self.name)?,
+        }
+        if let Some(description) = self.description {
+            writer.write_all(b" ")?;
+            writer.write_all(description)?;
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn is_whitespace(c: &u8) -> bool {
+    c.is_ascii_whitespace()
+}
+
+/// Split a header (as returned by `SequenceRecord::id`) into its name and
+/// attributes.
+///
+/// The header is `KeyValue` if everything after the first word is made of
+/// `key=value` fields, `NcbiPipes` if the first word contains a `|` and
+/// `Plain` otherwise.
+///
+/// ```
+/// use needletail::header::{parse_attributes, HeaderStyle};
+///
+/// let header = parse_attributes(b"read1 length=150 barcode=ACGT");
+/// assert_eq!(header.style, HeaderStyle::KeyValue);
+/// assert_eq!(header.name, b"read1");
+/// assert_eq!(header.get(b"barcode"), Some(&b"ACGT"[..]));
+///
+/// let header = parse_attributes(b"gi|12345|ref|NC_000913.3| E. coli");
+/// assert_eq!(header.style, HeaderStyle::NcbiPipes);
+/// assert_eq!(header.get(b"ref"), Some(&b"NC_000913.3"[..]));
+/// assert_eq!(header.description, Some(&b"E. coli"[..]));
+/// ```
+pub fn parse_attributes(id: &[u8]) -> Header<'_> {
+    let id = id.trim_ascii();
+    let (name, rest) = match id.iter().position(is_whitespace) {
+        Some(pos) => (&id[..pos], id[pos..].trim_ascii_start()),
+        None => (id, &b""[..]),
+    };
+
+    let key_values: Option<Vec<_>> = rest
+        .split(is_whitespace)
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            let pos = field.iter().position(|c| *c == b'=')?;
+            Some((&field[..pos], &field[pos + 1..]))
+        })
+        .collect();
+    let description = if rest.is_empty() { None } else { Some(rest) };
+
+    match key_values {
+        Some(attributes) if !attributes.is_empty() => Header {
+            name,
+            style: HeaderStyle::KeyValue,
+            attributes,
+            description: None,
+        },
+        _ if name.contains(&b'|') => {
+            let fields: Vec<&[u8]> = name.split(|c| *c == b'|').collect();
+            let attributes = fields
+                .chunks(2)
+                .map(|pair| (pair[0], pair.get(1).copied().unwrap_or_default()))
+                .collect();
+            Header {
+                name,
+                style: HeaderStyle::NcbiPipes,
+                attributes,
+                description,
+            }
+        }
+        _ => Header {
+            name,
+            style: HeaderStyle::Plain,
+            attributes: Vec::new(),
+            description,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(header: &Header) -> Vec<u8> {
+        let mut out = Vec::new();
+        header.write(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_key_value() {
+        let header = parse_attributes(b"read1  length=150\tbarcode=ACGT empty=");
+        assert_eq!(header.style, HeaderStyle::KeyValue);
+        assert_eq!(
+            header.attributes,
+            vec![
+                (&b"length"[..], &b"150"[..]),
+                (b"barcode", b"ACGT"),
+                (b"empty", b""),
+            ]
+        );
+        assert_eq!(header.get(b"missing"), None);
+        assert_eq!(
+            roundtrip(&header),
+            b"read1 length=150 barcode=ACGT empty=".to_vec()
+        );
+
+        // not all the fields are key=value
+        let header = parse_attributes(b"read1 length=150 some description");
+        assert_eq!(header.style, HeaderStyle::Plain);
+        assert_eq!(
+            header.description,
+            Some(&b"length=150 some description"[..])
+        );
+    }
+
+    #[test]
+    fn test_ncbi_pipes() {
+        let mut header = parse_attributes(b"gi|12345|ref|NC_000913.3|");
+        assert_eq!(header.style, HeaderStyle::NcbiPipes);
+        assert_eq!(header.get(b"gi"), Some(&b"12345"[..]));
+        assert_eq!(roundtrip(&header), b"gi|12345|ref|NC_000913.3|".to_vec());
+
+        header.attributes[0].1 = b"6789";
+        assert_eq!(roundtrip(&header), b"gi|6789|ref|NC_000913.3|".to_vec());
+
+        let header = parse_attributes(b"sp|P69905|HBA_HUMAN Hemoglobin subunit alpha");
+        assert_eq!(header.attributes[1], (&b"HBA_HUMAN"[..], &b""[..]));
+        assert_eq!(
+            roundtrip(&header),
+            b"sp|P69905|HBA_HUMAN Hemoglobin subunit alpha".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_plain() {
+        let header = parse_attributes(b"chr1");
+        assert_eq!(header.style, HeaderStyle::Plain);
+        assert_eq!(header.name, b"chr1");
+        assert_eq!(header.description, None);
+        assert_eq!(roundtrip(&header), b"chr1".to_vec());
+        assert_eq!(parse_attributes(b"").name, b"");
+    }
+}
@@ -1,5 +1,13 @@
 //! Functions for splitting sequences into fixed-width moving windows (kmers)
 //! and utilities for dealing with these kmers.
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+use crate::bitkmer::{BitKmer, BitKmerSeq, BitNuclKmer};
+use crate::sequence::tables::NUC2BIT;
+use crate::sequence::{complement, extend_reverse_complement};
+
+pub mod counting;
 
 /// Returns true if the base is a unambiguous nucleic acid base (e.g. ACGT) and
 /// false otherwise.
@@ -129,9 +137,624 @@ impl<'a> Iterator for CanonicalKmers<'a> {
     }
 }
 
+/// Same as [`CanonicalKmers`] without needing the reverse complement of the
+/// sequence: it's computed for each kmer, and only copied into a buffer of `k`
+/// bytes owned by the iterator when it's the canonical one.
+///
+/// Because those kmers point into that buffer, this imitates the Iterator API but
+/// does not support any iterator functions.
+///
+/// ```
+/// use needletail::Sequence;
+///
+/// let mut kmers = b"ACGTNTTT".canonical_kmers_owned(3);
+/// let mut found = Vec::new();
+/// while let Some((pos, kmer, was_rc)) = kmers.next() {
+///     found.push((pos, kmer.to_vec(), was_rc));
+/// }
+/// assert_eq!(
+///     found,
+///     vec![
+///         (0, b"ACG".to_vec(), false),
+///         (1, b"ACG".to_vec(), true),
+///         (5, b"AAA".to_vec(), true),
+///     ]
+/// );
+/// ```
+pub struct OwnedCanonicalKmers<'a> {
+    k: usize,
+    start_pos: usize,
+    buffer: &'a [u8],
+    // bases before this index are known to be ACGT from `start_pos`
+    checked: usize,
+    rc: Vec<u8>,
+}
+
+impl<'a> OwnedCanonicalKmers<'a> {
+    pub fn new(buffer: &'a [u8], k: u8) -> Self {
+        OwnedCanonicalKmers {
+            k: k as usize,
+            start_pos: 0,
+            buffer,
+            checked: 0,
+            rc: Vec::with_capacity(k as usize),
+        }
+    }
+
+    /// Returns the next kmer as a tuple containing (position, the canonical kmer,
+    /// if the kmer is the reverse complement of the original).
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(usize, &[u8], bool)> {
+        let k = self.k;
+        if k == 0 {
+            return None;
+        }
+        // skip the kmers containing anything else than ACGT
+        while self.checked < self.start_pos + k {
+            let chr = *self.buffer.get(self.checked)?;
+            if !is_good_base(chr) {
+                self.start_pos = self.checked + 1;
+            }
+            self.checked += 1;
+        }
+        let pos = self.start_pos;
+        self.start_pos += 1;
+
+        let buffer = self.buffer;
+        let kmer = &buffer[pos..pos + k];
+        let rc = kmer.iter().rev().map(|b| complement(*b));
+        match kmer.iter().copied().cmp(rc) {
+            Ordering::Less => Some((pos, kmer, false)),
+            // a palindrome is its own reverse complement
+            Ordering::Equal => Some((pos, kmer, true)),
+            Ordering::Greater => {
+                self.rc.clear();
+                extend_reverse_complement(&mut self.rc, kmer);
+                Some((pos, &self.rc, true))
+            }
+        }
+    }
+}
+
+/// Normalize a single base the same way `sequence::normalize` does for
+/// unambiguous bases; returns `None` for anything that can't be part of a kmer.
+#[inline]
+fn normalize_base(chr: u8) -> Option<u8> {
+    match chr {
+        b'a' | b'A' => Some(b'A'),
+        b'c' | b'C' => Some(b'C'),
+        b'g' | b'G' => Some(b'G'),
+        b't' | b'T' | b'u' | b'U' => Some(b'T'),
+        _ => None,
+    }
+}
+
+/// A kmer-izer that normalizes the sequence on the fly instead of requiring a
+/// normalized (and reverse complemented) copy of it first.
+///
+/// Bases are uppercased, U is converted to T, whitespace and line endings are
+/// skipped and kmers containing any other character are skipped as well.
+/// Positions are the ones in the normalized sequence, i.e. they match the ones
+/// from `seq.normalize(false).canonical_kmers(..)`.
+///
+/// Only two buffers of `k` bytes are allocated when the iterator is created.
+/// Because the kmers returned point into those buffers, this imitates the
+/// Iterator API but does not support any iterator functions.
+///
+/// ```
+/// use needletail::Sequence;
+///
+/// let mut kmers = b"acgu\nNACG".normalized_kmers(3, true);
+/// let mut found = Vec::new();
+/// while let Some((pos, kmer, was_rc)) = kmers.next() {
+///     found.push((pos, kmer.to_vec(), was_rc));
+/// }
+/// assert_eq!(
+///     found,
+///     vec![
+///         (0, b"ACG".to_vec(), false),
+///         (1, b"ACG".to_vec(), true),
+///         (5, b"ACG".to_vec(), false),
+///     ]
+/// );
+/// ```
+pub struct NormalizedKmers<'a> {
+    k: usize,
+    buffer: &'a [u8],
+    // index of the next byte to read in `buffer`
+    idx: usize,
+    // position in the normalized sequence of the next base
+    pos: usize,
+    // number of valid bases currently in the window
+    filled: usize,
+    canonical: bool,
+    fwd: Vec<u8>,
+    rc: Vec<u8>,
+}
+
+impl<'a> NormalizedKmers<'a> {
+    /// Creates a new iterator. If `canonical` is true, the lexicographically
+    /// lowest of the kmer and its reverse complement is returned.
+    pub fn new(buffer: &'a [u8], k: u8, canonical: bool) -> Self {
+        let k = k as usize;
+        NormalizedKmers {
+            k,
+            buffer,
+            idx: 0,
+            pos: 0,
+            filled: 0,
+            canonical,
+            fwd: vec![0; k],
+            rc: vec![0; k],
+        }
+    }
+
+    /// Returns the next kmer as a tuple containing (position, the kmer, if the
+    /// kmer is the reverse complement of the original).
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(usize, &[u8], bool)> {
+        let k = self.k;
+        if k == 0 {
+            return None;
+        }
+
+        while self.idx < self.buffer.len() {
+            let chr = self.buffer[self.idx];
+            self.idx += 1;
+            if matches!(chr, b' ' | b'\t' | b'\r' | b'\n') {
+                continue;
+            }
+            self.pos += 1;
+
+            let Some(base) = normalize_base(chr) else {
+                self.filled = 0;
+                continue;
+            };
+            if self.filled == k {
+                self.fwd.copy_within(1.., 0);
+                self.rc.copy_within(..k - 1, 1);
+            } else {
+                self.filled += 1;
+            }
+            self.fwd[self.filled - 1] = base;
+            self.rc[k - self.filled] = complement(base);
+
+            if self.filled == k {
+                let pos = self.pos - k;
+                if self.canonical && self.rc <= self.fwd {
+                    return Some((pos, &self.rc, true));
+                }
+                return Some((pos, &self.fwd, false));
+            }
+        }
+        None
+    }
+}
+
+/// Offsets of the `1`s of a spaced seed mask such as `b"1101011"`
+fn mask_offsets(mask: &[u8]) -> Vec<usize> {
+    assert!(
+        mask.iter().all(|c| matches!(c, b'0' | b'1')),
+        "a spaced seed mask can only contain 0s and 1s"
+    );
+    let offsets: Vec<_> = mask
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c == b'1')
+        .map(|(i, _)| i)
+        .collect();
+    assert!(
+        !offsets.is_empty(),
+        "a spaced seed mask needs at least one 1"
+    );
+    offsets
+}
+
+/// Spaced seed (gapped) kmers: windows of the length of a mask such as `b"1101011"`,
+/// keeping only the bases at the positions of the `1`s. All the windows are returned,
+/// whatever their bases, as with [`Kmers`].
+///
+/// ```
+/// use needletail::kmer::SpacedKmers;
+///
+/// let mut kmers = SpacedKmers::new(b"ACGTAC", b"1101");
+/// let mut found = Vec::new();
+/// while let Some((pos, kmer)) = kmers.next() {
+///     found.push((pos, kmer.to_vec()));
+/// }
+/// assert_eq!(found, [(0, b"ACT".to_vec()), (1, b"CGA".to_vec()), (2, b"GTC".to_vec())]);
+/// ```
+pub struct SpacedKmers<'a> {
+    buffer: &'a [u8],
+    offsets: Vec<usize>,
+    span: usize,
+    pos: usize,
+    kmer: Vec<u8>,
+}
+
+impl<'a> SpacedKmers<'a> {
+    /// # Panics
+    ///
+    /// Panics if `mask` has other characters than `0` and `1` or no `1`.
+    pub fn new(buffer: &'a [u8], mask: &[u8]) -> Self {
+        let offsets = mask_offsets(mask);
+        SpacedKmers {
+            buffer,
+            kmer: Vec::with_capacity(offsets.len()),
+            offsets,
+            span: mask.len(),
+            pos: 0,
+        }
+    }
+
+    /// Returns the next kmer along with the position of its window
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(usize, &[u8])> {
+        if self.pos + self.span > self.buffer.len() {
+            return None;
+        }
+        let window = &self.buffer[self.pos..self.pos + self.span];
+        self.kmer.clear();
+        self.kmer.extend(self.offsets.iter().map(|i| window[*i]));
+        self.pos += 1;
+        Some((self.pos - 1, &self.kmer))
+    }
+}
+
+/// Same as [`SpacedKmers`] with the kmers packed as `BitKmer`s, the windows with a
+/// non-ACGT base at the position of a `1` of the mask being skipped. The mask can have
+/// at most 32 `1`s.
+///
+/// ```
+/// use needletail::bitkmer::bitmer_to_bytes;
+/// use needletail::kmer::SpacedBitKmers;
+///
+/// // the N is skipped by the mask in the first window only
+/// let found: Vec<_> = SpacedBitKmers::new(b"ACNTAC", b"1101")
+///     .map(|(pos, kmer)| (pos, bitmer_to_bytes(kmer)))
+///     .collect();
+/// assert_eq!(found, [(0, b"ACT".to_vec())]);
+/// ```
+pub struct SpacedBitKmers<'a> {
+    buffer: &'a [u8],
+    offsets: Vec<usize>,
+    span: usize,
+    pos: usize,
+}
+
+impl<'a> SpacedBitKmers<'a> {
+    /// # Panics
+    ///
+    /// Panics if `mask` has other characters than `0` and `1`, no `1` or more than 32.
+    pub fn new(buffer: &'a [u8], mask: &[u8]) -> Self {
+        let offsets = mask_offsets(mask);
+        assert!(
+            offsets.len() <= 32,
+            "a spaced seed mask can have at most 32 1s"
+        );
+        SpacedBitKmers {
+            buffer,
+            offsets,
+            span: mask.len(),
+            pos: 0,
+        }
+    }
+}
+
+impl Iterator for SpacedBitKmers<'_> {
+    type Item = (usize, BitKmer);
+
+    fn next(&mut self) -> Option<(usize, BitKmer)> {
+        while self.pos + self.span <= self.buffer.len() {
+            let window = &self.buffer[self.pos..self.pos + self.span];
+            self.pos += 1;
+            let kmer = self.offsets.iter().try_fold(0, |kmer: BitKmerSeq, i| {
+                NUC2BIT[window[*i] as usize].map(|base| (kmer << 2) | BitKmerSeq::from(base))
+            });
+            if let Some(kmer) = kmer {
+                return Some((self.pos - 1, (kmer, self.offsets.len() as u8)));
+            }
+        }
+        None
+    }
+}
+
+/// A run of consecutive kmers sharing the same minimizer, see [`super_kmers`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuperKmer {
+    /// The canonical minimizer shared by all the kmers of the run
+    pub minimizer: BitKmer,
+    /// Position of the first base of the first kmer
+    pub start: usize,
+    /// Position after the last base of the last kmer
+    pub end: usize,
+}
+
+/// Iterator over the super-kmers of a sequence, see [`super_kmers`]
+pub struct SuperKmers<'a> {
+    // the kmers are the windows of `k - m + 1` mmers
+    mmers: Minimizers<'a>,
+    k: usize,
+    current: Option<SuperKmer>,
+}
+
+impl Iterator for SuperKmers<'_> {
+    type Item = SuperKmer;
+
+    fn next(&mut self) -> Option<SuperKmer> {
+        while let Some((kmer_start, _, minimizer)) = self.mmers.next_window() {
+            let kmer_end = kmer_start + self.k;
+            match &mut self.current {
+                Some(current) if current.minimizer == minimizer && current.end + 1 == kmer_end => {
+                    current.end = kmer_end;
+                }
+                _ => {
+                    let new = SuperKmer {
+                        minimizer,
+                        start: kmer_start,
+                        end: kmer_end,
+                    };
+                    if let Some(done) = self.current.replace(new) {
+                        return Some(done);
+                    }
+                }
+            }
+        }
+        self.current.take()
+    }
+}
+
+/// Splits `seq` in super-kmers: maximal runs of consecutive kmers of size `k` that have
+/// the same canonical minimizer of size `w`, as done before counting kmers in
+/// KMC/GATB-style counters to bucket them by minimizer.
+///
+/// Kmers containing non-ACGT bases are skipped, so super-kmers never span them.
+/// `w` needs to be at most `k` and `k` at most 32.
+///
+/// ```
+/// use needletail::kmer::super_kmers;
+///
+/// let seq = b"ACGTTGCANNTTTTT";
+/// let spans: Vec<_> = super_kmers(seq, 4, 2).map(|s| &seq[s.start..s.end]).collect();
+/// assert_eq!(spans, vec![&b"ACGT"[..], b"CGTTGC", b"TGCA", b"TTTTT"]);
+/// ```
+pub fn super_kmers(seq: &[u8], k: u8, w: u8) -> SuperKmers<'_> {
+    assert!(
+        w > 0 && w <= k,
+        "the minimizer size needs to be between 1 and k"
+    );
+    assert!(k <= 32, "k can be at most 32");
+    SuperKmers {
+        mmers: Minimizers::new(seq, w, usize::from(k - w) + 1, true),
+        k: k as usize,
+        current: None,
+    }
+}
+
+/// Iterator over the minimizers of a sequence: in every window of `w` consecutive
+/// kmers of size `k`, the kmer with the smallest value. Each minimizer is yielded once
+/// as `(position, kmer)`, even if it is the smallest kmer of several windows.
+///
+/// Kmers are compared by their 2-bit encoding, i.e. lexicographically, unless another
+/// order is given with [`Minimizers::with_hash`]. On ties the leftmost kmer wins.
+/// Kmers containing non-ACGT bases are skipped and windows never span them.
+///
+/// ```
+/// use needletail::bitkmer::bitmer_to_bytes;
+/// use needletail::kmer::Minimizers;
+///
+/// let found: Vec<_> = Minimizers::new(b"TTGCAGTT", 3, 2, false)
+///     .map(|(pos, kmer)| (pos, bitmer_to_bytes(kmer)))
+///     .collect();
+/// let expected = [(1, b"TGC"), (2, b"GCA"), (3, b"CAG"), (4, b"AGT")];
+/// assert_eq!(found, expected.map(|(pos, kmer)| (pos, kmer.to_vec())));
+/// ```
+pub struct Minimizers<'a> {
+    kmers: BitNuclKmer<'a>,
+    w: usize,
+    hash: fn(BitKmer) -> u64,
+    // candidates of the current window as (position, kmer, hash), by increasing hash
+    window: VecDeque<(usize, BitKmer, u64)>,
+    // position of the first kmer of the current stretch of ACGT bases
+    stretch_start: usize,
+    last_pos: Option<usize>,
+    last_minimizer: Option<usize>,
+}
+
+impl<'a> Minimizers<'a> {
+    /// Minimizers of windows of `w` kmers of size `k` (at most 32), canonical kmers
+    /// being compared if `canonical` is set
+    pub fn new(seq: &'a [u8], k: u8, w: usize, canonical: bool) -> Self {
+        assert!(k > 0 && k <= 32, "k needs to be between 1 and 32");
+        assert!(w > 0, "windows need to have at least one kmer");
+        Self {
+            kmers: BitNuclKmer::new(seq, k, canonical),
+            w,
+            hash: |kmer| kmer.0,
+            window: VecDeque::new(),
+            stretch_start: 0,
+            last_pos: None,
+            last_minimizer: None,
+        }
+    }
+
+    /// Compares kmers by `hash` rather than lexicographically, e.g. with
+    /// `needletail::bitkmer::bitmer_hash` to avoid favoring runs of `A`s
+    pub fn with_hash(mut self, hash: fn(BitKmer) -> u64) -> Self {
+        self.hash = hash;
+        self
+    }
+}
+
+impl Minimizers<'_> {
+    /// Slides the window by one kmer and returns its start and its minimizer as
+    /// `(window start, minimizer position, minimizer)`
+    fn next_window(&mut self) -> Option<(usize, usize, BitKmer)> {
+        for (pos, kmer, _) in self.kmers.by_ref() {
+            if self.last_pos.is_none_or(|last| pos != last + 1) {
+                // skipped a non-ACGT base: no window spans it
+                self.window.clear();
+                self.stretch_start = pos;
+            }
+            self.last_pos = Some(pos);
+            let hash = (self.hash)(kmer);
+            while self.window.back().is_some_and(|(_, _, h)| *h > hash) {
+                self.window.pop_back();
+            }
+            self.window.push_back((pos, kmer, hash));
+            if pos - self.stretch_start + 1 < self.w {
+                continue;
+            }
+
+            let window_start = pos + 1 - self.w;
+            while self
+                .window
+                .front()
+                .is_some_and(|(p, _, _)| *p < window_start)
+            {
+                self.window.pop_front();
+            }
+            let (min_pos, minimizer, _) =
+                *self.window.front().expect("the window has the last kmer");
+            return Some((window_start, min_pos, minimizer));
+        }
+        None
+    }
+}
+
+impl Iterator for Minimizers<'_> {
+    type Item = (usize, BitKmer);
+
+    fn next(&mut self) -> Option<(usize, BitKmer)> {
+        while let Some((_, min_pos, minimizer)) = self.next_window() {
+            if self.last_minimizer != Some(min_pos) {
+                self.last_minimizer = Some(min_pos);
+                return Some((min_pos, minimizer));
+            }
+        }
+        None
+    }
+}
+
+/// Counts of kmers, as returned by [`parallel_count`]
+#[cfg(feature = "parallel")]
+pub type KmerCounts = std::collections::HashMap<BitKmer, u64, ahash::RandomState>;
+
+/// Counts the canonical kmers of size `k` (up to 32) of all the records of `reader`
+/// using `n_threads` worker threads.
+///
+/// Records are read on the calling thread and sent in batches of about 4MB to the
+/// workers, which count in their own map. The maps are merged at the end.
+/// Kmers containing non-ACGT bases are skipped. Fails with an error of kind `Io`
+/// (`InvalidInput`) if `k` isn't between 1 and 32.
+///
+/// ```
+/// use needletail::bitkmer::BitNuclKmer;
+/// use needletail::kmer::parallel_count;
+/// use needletail::parse_fastx_reader;
+///
+/// let mut reader = parse_fastx_reader(&b">a\nACGTT\n>b\nAACG\n"[..]).unwrap();
+/// let counts = parallel_count(&mut reader, 3, 2).unwrap();
+/// // CGT is the reverse complement of ACG so it is counted as ACG
+/// let (_, acg, _) = BitNuclKmer::new(b"ACG", 3, true).next().unwrap();
+/// assert_eq!(counts[&acg], 3);
+/// ```
+#[cfg(feature = "parallel")]
+pub fn parallel_count(
+    reader: &mut dyn crate::parser::FastxReader,
+    k: u8,
+    n_threads: usize,
+) -> Result<KmerCounts, crate::errors::ParseError> {
+    count_kmers_parallel(reader, k, true, n_threads)
+}
+
+/// Same as `parallel_count`, counting the kmers as they are if `canonical` isn't set
+#[cfg(feature = "parallel")]
+pub(crate) fn count_kmers_parallel(
+    reader: &mut dyn crate::parser::FastxReader,
+    k: u8,
+    canonical: bool,
+    n_threads: usize,
+) -> Result<KmerCounts, crate::errors::ParseError> {
+    use std::io;
+    use std::panic;
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+
+    use crate::parser::BoundedBatcher;
+    use crate::Sequence;
+
+    const BATCH_BYTES: usize = 4 * 1024 * 1024;
+
+    if !(1..=32).contains(&k) {
+        let msg = format!("k must be between 1 and 32, got {k}");
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, msg).into());
+    }
+
+    let n_threads = n_threads.max(1);
+    // a couple of batches in flight per worker is enough to keep them busy
+    let (sender, receiver) = mpsc::sync_channel(2 * n_threads);
+    // only the workers hold the receiver: once they all stopped, e.g. because one
+    // panicked, sending fails instead of blocking forever
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..n_threads)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                scope.spawn(move || {
+                    let mut counts = KmerCounts::default();
+                    loop {
+                        // the lock is released as soon as a batch is received
+                        let batch: Vec<crate::parser::OwnedRecord> =
+                            match receiver.lock().unwrap().recv() {
+                                Ok(batch) => batch,
+                                Err(_) => return counts,
+                            };
+                        for record in &batch {
+                            for (_, kmer, _) in record.bit_kmers(k, canonical) {
+                                *counts.entry(kmer).or_insert(0) += 1;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(receiver);
+
+        let mut batcher = BoundedBatcher::new(BATCH_BYTES);
+        let read_result = loop {
+            match batcher.next_batch(reader) {
+                Ok(Some(batch)) => {
+                    if sender.send(batch).is_err() {
+                        break Ok(());
+                    }
+                }
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        // closing the channel stops the workers once they're done
+        drop(sender);
+
+        let mut maps = workers.into_iter().map(|w| match w.join() {
+            Ok(map) => map,
+            Err(payload) => panic::resume_unwind(payload),
+        });
+        let mut counts = maps.next().unwrap_or_default();
+        for map in maps {
+            for (kmer, count) in map {
+                *counts.entry(kmer).or_insert(0) += count;
+            }
+        }
+        read_result.map(|_| counts)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitkmer::bitmer_to_bytes;
     use crate::sequence::Sequence;
 
     #[test]
@@ -224,4 +847,152 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn can_canonicalize_without_reverse_complement() {
+        let seq = b"ACGTnACGTTTGCA\nGGNNACGTAACCGT";
+        let rc = seq.reverse_complement();
+        for k in [1, 2, 3, 4, 7, 30] {
+            let expected: Vec<_> = CanonicalKmers::new(seq, &rc, k)
+                .map(|(pos, kmer, was_rc)| (pos, kmer.to_vec(), was_rc))
+                .collect();
+            let mut kmers = OwnedCanonicalKmers::new(seq, k);
+            let mut found = Vec::new();
+            while let Some((pos, kmer, was_rc)) = kmers.next() {
+                found.push((pos, kmer.to_vec(), was_rc));
+            }
+            assert_eq!(found, expected);
+        }
+        assert!(OwnedCanonicalKmers::new(b"ACGT", 0).next().is_none());
+    }
+
+    #[test]
+    fn can_normalize_kmers_on_the_fly() {
+        let seq = b"ACGTnacgu\nTTGCA\r\nGGNNAcgtU-ACGT";
+        let norm = seq.normalize(false);
+        let rc = norm.reverse_complement();
+        let expected: Vec<_> = norm
+            .canonical_kmers(3, &rc)
+            .map(|(pos, kmer, was_rc)| (pos, kmer.to_vec(), was_rc))
+            .collect();
+
+        let mut kmers = NormalizedKmers::new(seq, 3, true);
+        let mut found = Vec::new();
+        while let Some((pos, kmer, was_rc)) = kmers.next() {
+            found.push((pos, kmer.to_vec(), was_rc));
+        }
+        assert_eq!(found, expected);
+
+        let mut kmers = NormalizedKmers::new(b"aCgT", 2, false);
+        assert_eq!(kmers.next(), Some((0, &b"AC"[..], false)));
+        assert_eq!(kmers.next(), Some((1, &b"CG"[..], false)));
+        assert_eq!(kmers.next(), Some((2, &b"GT"[..], false)));
+        assert_eq!(kmers.next(), None);
+    }
+
+    #[test]
+    fn can_iterate_spaced_kmers() {
+        let seq = b"ACGTNACGGTTA";
+        let mask = b"10011";
+        let mut kmers = SpacedKmers::new(seq, mask);
+        let mut found = Vec::new();
+        while let Some((pos, kmer)) = kmers.next() {
+            found.push((pos, kmer.to_vec()));
+        }
+        assert_eq!(found.len(), seq.len() - mask.len() + 1);
+        assert_eq!(found[1], (1, b"CNA".to_vec()));
+
+        // a mask of only 1s gives the usual kmers
+        let spaced: Vec<_> = SpacedBitKmers::new(seq, b"111").collect();
+        let kmers: Vec<_> = BitNuclKmer::new(seq, 3, false)
+            .map(|(pos, kmer, _)| (pos, kmer))
+            .collect();
+        assert_eq!(spaced, kmers);
+
+        let packed: Vec<_> = SpacedBitKmers::new(seq, mask)
+            .map(|(pos, kmer)| (pos, bitmer_to_bytes(kmer)))
+            .collect();
+        let expected: Vec<_> = found
+            .into_iter()
+            .filter(|(_, kmer)| !kmer.contains(&b'N'))
+            .collect();
+        assert_eq!(packed, expected);
+        assert_eq!(SpacedBitKmers::new(b"ACG", mask).count(), 0);
+    }
+
+    #[test]
+    fn can_find_minimizers() {
+        let seq = b"ACGTTGCATGNCCATGGACTTAGGNNACGTAGCTAGGA";
+        let kmers: Vec<_> = BitNuclKmer::new(seq, 4, true).collect();
+        for w in [1, 3, 5] {
+            // the leftmost smallest kmer of every window of consecutive kmers
+            let mut expected: Vec<(usize, BitKmer)> = Vec::new();
+            for window in kmers.windows(w) {
+                if window[w - 1].0 - window[0].0 != w - 1 {
+                    continue;
+                }
+                let min = window.iter().min_by_key(|(_, kmer, _)| kmer.0).unwrap();
+                if expected.last() != Some(&(min.0, min.1)) {
+                    expected.push((min.0, min.1));
+                }
+            }
+            assert_eq!(
+                Minimizers::new(seq, 4, w, true).collect::<Vec<_>>(),
+                expected
+            );
+        }
+
+        let hashed: Vec<_> = Minimizers::new(seq, 4, 3, false)
+            .with_hash(crate::bitkmer::bitmer_hash)
+            .collect();
+        assert!(!hashed.is_empty());
+        assert_eq!(Minimizers::new(b"ACGTNACG", 4, 2, false).count(), 0);
+    }
+
+    #[test]
+    fn can_split_super_kmers() {
+        let seq = b"ACGTTGCANNTTTTT";
+        let found: Vec<_> = super_kmers(seq, 4, 2).collect();
+        assert_eq!(found.len(), 4);
+        assert_eq!((found[0].start, found[0].end), (0, 4));
+        assert_eq!(found[0].minimizer, (0b0001, 2));
+        // TT is canonicalized to AA
+        assert_eq!((found[1].start, found[1].end), (1, 7));
+        assert_eq!(found[1].minimizer, (0b0000, 2));
+        assert_eq!((found[2].start, found[2].end), (4, 8));
+        assert_eq!((found[3].start, found[3].end), (10, 15));
+
+        // every kmer is in exactly one super-kmer
+        let num_kmers: usize = super_kmers(seq, 4, 2).map(|s| s.end - s.start - 3).sum();
+        assert_eq!(num_kmers, BitNuclKmer::new(seq, 4, false).count());
+
+        assert_eq!(super_kmers(b"ACG", 4, 2).count(), 0);
+        assert_eq!(super_kmers(b"ACGT", 4, 4).count(), 1);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn can_count_kmers_in_parallel() {
+        use crate::bitkmer::BitNuclKmer;
+        use crate::parse_fastx_reader;
+
+        let mut fasta = Vec::new();
+        for i in 0..1000 {
+            fasta.extend_from_slice(format!(">{i}\nACGTNAAAA\n").as_bytes());
+        }
+        let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+        let counts = parallel_count(&mut reader, 3, 4).unwrap();
+        let kmer = |seq: &[u8]| BitNuclKmer::new(seq, 3, true).next().unwrap().1;
+        assert_eq!(counts.len(), 2);
+        // ACG and CGT are the reverse complement of each other
+        assert_eq!(counts[&kmer(b"ACG")], 2000);
+        assert_eq!(counts[&kmer(b"AAA")], 2000);
+        assert_eq!(counts.values().sum::<u64>(), 4000);
+
+        for k in [0, 33] {
+            let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+            let err = parallel_count(&mut reader, k, 2).unwrap_err();
+            assert_eq!(err.kind, crate::errors::ParseErrorKind::Io);
+        }
+    }
 }
@@ -0,0 +1,322 @@
+//! Counting the kmers of sequences or whole files, exactly or approximately with a
+//! count-min sketch to keep the memory bounded.
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::bitkmer::{bitmer_hash, bitmer_to_bytes, canonical, BitKmer, BitNuclKmer};
+use crate::errors::ParseError;
+use crate::parser::FastxReader;
+use crate::Sequence;
+
+/// Approximate counts of hashes in `depth` rows of `width` counters: a count is
+/// never underestimated but can be overestimated when hashes collide in every row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u32>,
+}
+
+impl CountMinSketch {
+    /// # Panics
+    ///
+    /// Panics if `width` or `depth` is 0.
+    pub fn new(width: usize, depth: usize) -> Self {
+        assert!(
+            width > 0 && depth > 0,
+            "a count-min sketch needs at least one counter"
+        );
+        Self {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+        }
+    }
+
+    /// Index of the counter of `hash` in each row
+    fn indices(&self, hash: u64) -> impl Iterator<Item = usize> {
+        // double hashing: the rows use h1 + row * h2
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        let width = self.width;
+        (0..self.depth as u64).map(move |row| {
+            let index = h1.wrapping_add(row.wrapping_mul(h2)) % width as u64;
+            row as usize * width + index as usize
+        })
+    }
+
+    /// Adds one to the count of `hash` and returns its new estimate
+    pub fn add(&mut self, hash: u64) -> u64 {
+        // only the lowest counters need to grow ("conservative update")
+        let estimate = self
+            .indices(hash)
+            .map(|i| self.counters[i])
+            .min()
+            .unwrap_or(0);
+        for i in self.indices(hash) {
+            if self.counters[i] == estimate {
+                self.counters[i] = estimate.saturating_add(1);
+            }
+        }
+        u64::from(estimate) + 1
+    }
+
+    /// Estimated count of `hash`
+    pub fn estimate(&self, hash: u64) -> u64 {
+        self.indices(hash)
+            .map(|i| u64::from(self.counters[i]))
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// Counts the kmers of size `k` (up to 32) of sequences or readers. Kmers containing
+/// anything else than `ACGT` are skipped, the same way `Sequence::bit_kmers` does.
+///
+/// All the kmers are counted exactly in a hash map by default. With a count-min sketch
+/// ([`KmerCounter::with_count_min_sketch`]), kmers are only added to the map once
+/// their estimated count reaches the minimum count, which saves the memory of the
+/// (often many) kmers seen only a few times from sequencing errors, at the cost of
+/// counts that can be overestimated.
+///
+/// ```
+/// use needletail::bitkmer::bitmer_to_bytes;
+/// use needletail::kmer::counting::KmerCounter;
+///
+/// let mut counter = KmerCounter::new(3, true).with_min_count(2);
+/// counter.add_seq(&b"ACGTNACG"[..]);
+/// let counts: Vec<_> = counter
+///     .into_sorted()
+///     .into_iter()
+///     .map(|(kmer, count)| (bitmer_to_bytes(kmer), count))
+///     .collect();
+/// // CGT is the reverse complement of ACG
+/// assert_eq!(counts, [(b"ACG".to_vec(), 3)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct KmerCounter {
+    k: u8,
+    canonical: bool,
+    min_count: u64,
+    counts: HashMap<BitKmer, u64>,
+    sketch: Option<CountMinSketch>,
+}
+
+impl KmerCounter {
+    /// Counts the kmers of size `k`, as the lowest of the kmer and its reverse
+    /// complement if `canonical` is set
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` isn't between 1 and 32.
+    pub fn new(k: u8, canonical: bool) -> Self {
+        assert!((1..=32).contains(&k), "k must be between 1 and 32, got {k}");
+        Self {
+            k,
+            canonical,
+            min_count: 1,
+            counts: HashMap::new(),
+            sketch: None,
+        }
+    }
+
+    /// Only keeps the kmers seen at least `min_count` times in the output
+    pub fn with_min_count(mut self, min_count: u64) -> Self {
+        self.min_count = min_count.max(1);
+        self
+    }
+
+    /// Counts approximately in a count-min sketch of `depth` rows of `width` counters,
+    /// see [`KmerCounter`]. To set before adding sequences.
+    pub fn with_count_min_sketch(mut self, width: usize, depth: usize) -> Self {
+        self.sketch = Some(CountMinSketch::new(width, depth));
+        self
+    }
+
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    /// Adds a kmer, canonicalizing it first if needed
+    pub fn add_kmer(&mut self, kmer: BitKmer) {
+        let kmer = if self.canonical {
+            canonical(kmer).0
+        } else {
+            kmer
+        };
+        self.add_counted_kmer(kmer);
+    }
+
+    fn add_counted_kmer(&mut self, kmer: BitKmer) {
+        match &mut self.sketch {
+            None => *self.counts.entry(kmer).or_insert(0) += 1,
+            Some(sketch) => {
+                let estimate = sketch.add(bitmer_hash(kmer));
+                if estimate >= self.min_count {
+                    self.counts.insert(kmer, estimate);
+                }
+            }
+        }
+    }
+
+    /// Adds the kmers of a sequence
+    pub fn add_seq<'a, S: Sequence<'a> + ?Sized>(&mut self, seq: &'a S) {
+        for (_, kmer, _) in BitNuclKmer::new(&seq.strip_returns(), self.k, self.canonical) {
+            self.add_counted_kmer(kmer);
+        }
+    }
+
+    /// Adds the kmers of all the records of `reader`
+    pub fn add_reader(&mut self, reader: &mut dyn FastxReader) -> Result<(), ParseError> {
+        while let Some(record) = reader.next() {
+            self.add_seq(&record?);
+        }
+        Ok(())
+    }
+
+    /// Same as `add_reader` with the kmers counted on `n_threads` worker threads, see
+    /// [`parallel_count`](crate::kmer::parallel_count). With a count-min sketch, the
+    /// kmers are still counted on the calling thread since they all go through it.
+    #[cfg(feature = "parallel")]
+    pub fn add_reader_parallel(
+        &mut self,
+        reader: &mut dyn FastxReader,
+        n_threads: usize,
+    ) -> Result<(), ParseError> {
+        if self.sketch.is_some() {
+            return self.add_reader(reader);
+        }
+        let counts = crate::kmer::count_kmers_parallel(reader, self.k, self.canonical, n_threads)?;
+        for (kmer, count) in counts {
+            *self.counts.entry(kmer).or_insert(0) += count;
+        }
+        Ok(())
+    }
+
+    /// Number of times `kmer` was seen (canonicalized first if needed), estimated if
+    /// counting with a count-min sketch
+    pub fn count(&self, kmer: BitKmer) -> u64 {
+        let kmer = if self.canonical {
+            canonical(kmer).0
+        } else {
+            kmer
+        };
+        match (self.counts.get(&kmer), &self.sketch) {
+            (Some(count), _) => *count,
+            (None, Some(sketch)) => sketch.estimate(bitmer_hash(kmer)),
+            (None, None) => 0,
+        }
+    }
+
+    /// Number of distinct kmers seen at least the minimum count times
+    pub fn len(&self) -> usize {
+        self.counts
+            .values()
+            .filter(|count| **count >= self.min_count)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The kmers seen at least the minimum count times with their counts, sorted by
+    /// kmer
+    pub fn into_sorted(self) -> Vec<(BitKmer, u64)> {
+        let min_count = self.min_count;
+        let mut counts: Vec<_> = self
+            .counts
+            .into_iter()
+            .filter(|(_, count)| *count >= min_count)
+            .collect();
+        counts.sort_unstable();
+        counts
+    }
+
+    /// Writes the kmers seen at least the minimum count times and their counts,
+    /// separated by a tab, one per line and sorted by kmer
+    pub fn write_counts(&self, writer: &mut dyn Write) -> Result<(), ParseError> {
+        let mut counts: Vec<_> = self
+            .counts
+            .iter()
+            .filter(|(_, count)| **count >= self.min_count)
+            .collect();
+        counts.sort_unstable();
+        for (kmer, count) in counts {
+            writer.write_all(&bitmer_to_bytes(*kmer))?;
+            writeln!(writer, "\t{count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_count_min_sketch() {
+        let mut sketch = CountMinSketch::new(64, 4);
+        for _ in 0..5 {
+            sketch.add(42);
+        }
+        assert_eq!(sketch.add(7), 1);
+        assert_eq!(sketch.estimate(42), 5);
+        assert!(sketch.estimate(7) >= 1);
+    }
+
+    #[test]
+    fn test_kmer_counter() {
+        let fasta = b">a\nACGTT\nGCA\n>b\nAACGN\n";
+        let mut exact = KmerCounter::new(3, false);
+        exact
+            .add_reader(&mut *parse_fastx_reader(&fasta[..]).unwrap())
+            .unwrap();
+        let (_, acg, _) = BitNuclKmer::new(b"ACG", 3, false).next().unwrap();
+        assert_eq!(exact.count(acg), 2);
+        assert_eq!(exact.count(canonical(acg).0), 2);
+        // ACG CGT GTT TTG TGC GCA AAC ACG
+        assert_eq!(exact.len(), 7);
+
+        let mut out = Vec::new();
+        exact
+            .clone()
+            .with_min_count(2)
+            .write_counts(&mut out)
+            .unwrap();
+        assert_eq!(out, b"ACG\t2\n");
+
+        let mut canonical_counts = KmerCounter::new(3, true);
+        canonical_counts.add_seq(&b"ACGTT\nGCA"[..]);
+        // GCA is the reverse complement of TGC
+        assert_eq!(canonical_counts.count(acg), 2);
+        assert_eq!(canonical_counts.len(), 4);
+
+        let mut approximate = KmerCounter::new(3, false)
+            .with_min_count(2)
+            .with_count_min_sketch(1024, 4);
+        approximate
+            .add_reader(&mut *parse_fastx_reader(&fasta[..]).unwrap())
+            .unwrap();
+        let sorted = approximate.into_sorted();
+        assert_eq!(sorted.len(), 1);
+        assert_eq!(sorted[0], (acg, 2));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_add_reader_parallel() {
+        let fasta = b">a\nACGTT\nGCA\n>b\nAACGN\n>c\nTTGCA\n";
+        for canonical in [false, true] {
+            let mut expected = KmerCounter::new(3, canonical);
+            expected
+                .add_reader(&mut *parse_fastx_reader(&fasta[..]).unwrap())
+                .unwrap();
+            let mut counter = KmerCounter::new(3, canonical);
+            counter
+                .add_reader_parallel(&mut *parse_fastx_reader(&fasta[..]).unwrap(), 2)
+                .unwrap();
+            assert_eq!(counter.into_sorted(), expected.into_sorted());
+        }
+    }
+}
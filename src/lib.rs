@@ -20,15 +20,13 @@
 //!         // normalize to make sure all the bases are consistently capitalized and
 //!         // that we remove the newlines since this is FASTA
 //!         let norm_seq = seqrec.normalize(false);
-//!         // we make a reverse complemented copy of the sequence first for
-//!         // `canonical_kmers` to draw the complemented sequences from.
-//!         let rc = norm_seq.reverse_complement();
 //!         // now we keep track of the number of AAAAs (or TTTTs via
 //!         // canonicalization) in the file; note we also get the position (i.0;
 //!         // in the event there were `N`-containing kmers that were skipped)
 //!         // and whether the sequence was complemented (i.2) in addition to
 //!         // the canonical kmer (i.1)
-//!         for (_, kmer, _) in norm_seq.canonical_kmers(4, &rc) {
+//!         let mut kmers = norm_seq.canonical_kmers_owned(4);
+//!         while let Some((_, kmer, _)) = kmers.next() {
 //!             if kmer == b"AAAA" {
 //!                 n_valid_kmers += 1;
 //!             }
@@ -42,15 +40,56 @@
 #[cfg(any(feature = "python", feature = "python_test"))]
 extern crate pyo3;
 
+pub mod alphabet;
+#[cfg(feature = "anonymize")]
+pub mod anonymize;
 pub mod bitkmer;
+pub mod cluster;
+pub mod compare;
+pub mod consensus;
+pub mod convert;
+pub mod correct;
+pub mod dedupe;
+pub mod demux;
+#[cfg(feature = "dict")]
+pub mod dict;
+pub mod document;
+pub mod filter;
+pub mod header;
 pub mod kmer;
+pub mod lint;
+pub mod mask;
+pub mod orient;
+pub mod packed;
 pub mod parser;
+pub mod partition;
+pub mod prelude;
+pub mod quality;
+#[cfg(feature = "url")]
+pub mod remote;
+pub mod sample;
+pub mod screen;
+pub mod search;
 pub mod sequence;
+pub mod sketch;
+pub mod stats;
+pub mod trim;
+pub mod validate;
 
 pub mod errors;
 
 #[cfg(any(feature = "python", feature = "python_test"))]
 pub mod python;
 
-pub use parser::{parse_fastx_file, parse_fastx_reader, parse_fastx_stdin, FastxReader};
+#[cfg(feature = "mmap")]
+pub use parser::parse_fastx_mmap;
+#[cfg(feature = "tar")]
+pub use parser::parse_fastx_tar;
+pub use parser::{
+    parse_fasta_qual_files, parse_fastx_file, parse_fastx_files, parse_fastx_reader,
+    parse_fastx_stdin, parse_fastx_stdin_interactive, FastxReader,
+};
+pub use quality::QualitySequence;
+#[cfg(feature = "url")]
+pub use remote::{parse_fastx_url, parse_fastx_url_with};
 pub use sequence::Sequence;
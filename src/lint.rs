@@ -0,0 +1,282 @@
+//! Finding the common problems of FASTA/FASTQ files that parse fine but trip up
+//! other tools, with the records and lines to fix
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::alphabet::Alphabet;
+use crate::errors::ErrorPosition;
+use crate::parser::{id_key, FastxReader, LineEnding, SequenceRecord};
+use crate::quality::Phred;
+
+/// The kinds of problems [`check`] looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// The file couldn't be parsed any further
+    ParseError,
+    /// A record uses other line endings than the first one. Only reported once.
+    MixedLineEndings,
+    /// The sequence has a character outside of the alphabet checked against, `ACGTN`
+    /// (in any case) by default
+    InvalidBase,
+    /// The id (up to the first whitespace) was already used by an earlier record
+    DuplicateId,
+    /// A read doesn't have the length of the first one, for platforms producing reads
+    /// of a uniform length. Only reported once.
+    InconsistentLength,
+    /// A quality character is outside of the Phred+33 range (`!` to `~`), or all the
+    /// qualities of the file look like Phred+64
+    QualityEncoding,
+    /// A header or a sequence line ends with spaces or tabs
+    TrailingWhitespace,
+}
+
+/// A problem found by [`check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    pub kind: LintKind,
+    /// Number of the record in the file (starting with 1)
+    pub record: u64,
+    /// Line of the problem and id of the record
+    pub position: ErrorPosition,
+    pub message: String,
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (record {}): {}",
+            self.position, self.record, self.message
+        )
+    }
+}
+
+fn is_whitespace(c: &u8) -> bool {
+    *c == b' ' || *c == b'\t'
+}
+
+/// Collects the findings while going through the records
+#[derive(Default)]
+struct Linter {
+    alphabet: Alphabet,
+    findings: Vec<LintFinding>,
+    record: u64,
+    ids: HashMap<Vec<u8>, u64>,
+    first_line_ending: Option<LineEnding>,
+    mixed_line_endings: bool,
+    first_length: Option<usize>,
+    inconsistent_length: bool,
+    // lowest quality character seen and where the first one above `J` was seen
+    min_quality: Option<u8>,
+    high_quality: Option<(u64, ErrorPosition)>,
+}
+
+impl Linter {
+    fn report(&mut self, kind: LintKind, line: u64, id: &[u8], message: String) {
+        self.findings.push(LintFinding {
+            kind,
+            record: self.record,
+            position: ErrorPosition {
+                line,
+                id: Some(String::from_utf8_lossy(id).into()),
+            },
+            message,
+        });
+    }
+
+    fn check_record(&mut self, record: &SequenceRecord) {
+        self.record += 1;
+        let line = record.start_line_number();
+        let id = record.id();
+        let key = id_key(id);
+
+        let line_ending = record.line_ending();
+        match self.first_line_ending {
+            None => self.first_line_ending = Some(line_ending),
+            Some(first) if first != line_ending && !self.mixed_line_endings => {
+                self.mixed_line_endings = true;
+                let message =
+                    format!("{line_ending:?} line endings but the file starts with {first:?} ones");
+                self.report(LintKind::MixedLineEndings, line, id, message);
+            }
+            _ => {}
+        }
+
+        if id.last().is_some_and(is_whitespace) {
+            self.report(
+                LintKind::TrailingWhitespace,
+                line,
+                id,
+                "in the header".into(),
+            );
+        }
+        if let Some(first) = self.ids.get(key) {
+            let message = format!("id already used by record {first}");
+            self.report(LintKind::DuplicateId, line, id, message);
+        } else {
+            self.ids.insert(key.to_vec(), self.record);
+        }
+
+        for (i, seq_line) in record.raw_parts().seq_lines.enumerate() {
+            let seq_line_number = line + 1 + i as u64;
+            let trimmed_len = seq_line.len()
+                - seq_line
+                    .iter()
+                    .rev()
+                    .take_while(|c| is_whitespace(c))
+                    .count();
+            if trimmed_len < seq_line.len() {
+                let message = "in the sequence".into();
+                self.report(LintKind::TrailingWhitespace, seq_line_number, id, message);
+            }
+            if let Some(i) = self.alphabet.first_invalid(&seq_line[..trimmed_len]) {
+                let message = format!("invalid base '{}'", seq_line[i].escape_ascii());
+                self.report(LintKind::InvalidBase, seq_line_number, id, message);
+            }
+        }
+
+        let length = record.num_bases();
+        match self.first_length {
+            None => self.first_length = Some(length),
+            Some(first) if first != length && !self.inconsistent_length => {
+                self.inconsistent_length = true;
+                let message = format!("{length} bases but the first record has {first}");
+                self.report(LintKind::InconsistentLength, line, id, message);
+            }
+            _ => {}
+        }
+
+        if let Some(qual) = record.qual() {
+            let qual_line = line + 3;
+            if let Some(c) = qual.iter().find(|c| Phred::from_ascii(**c).is_none()) {
+                let message = format!("invalid quality character '{}'", c.escape_ascii());
+                self.report(LintKind::QualityEncoding, qual_line, id, message);
+            }
+            if let Some(min) = qual.iter().min() {
+                self.min_quality = Some(self.min_quality.map_or(*min, |m| m.min(*min)));
+            }
+            if self.high_quality.is_none() && qual.iter().any(|c| *c > b'J') {
+                let position = ErrorPosition {
+                    line: qual_line,
+                    id: Some(String::from_utf8_lossy(id).into()),
+                };
+                self.high_quality = Some((self.record, position));
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<LintFinding> {
+        // Phred+64 files have no quality below `;` (-5 in Solexa) and usually go above `J`
+        // (Q41 in Phred+33)
+        if let (Some(min), Some((record, position))) = (self.min_quality, self.high_quality.take())
+        {
+            if min >= b';' {
+                self.findings.push(LintFinding {
+                    kind: LintKind::QualityEncoding,
+                    record,
+                    position,
+                    message: "qualities look like Phred+64 rather than Phred+33".into(),
+                });
+            }
+        }
+        self.findings
+    }
+}
+
+/// Goes through all the records of `reader` and returns the problems found, in the
+/// order of the file. Parse errors are reported as findings of kind `ParseError`:
+/// checking stops there unless the reader can skip invalid records.
+///
+/// ```
+/// use needletail::lint::{check, LintKind};
+/// use needletail::parse_fastx_reader;
+///
+/// let fastq = b"@r1\nACGT\n+\nIIII\n@r1 \nACXT\n+\nIIII\n";
+/// let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+/// let findings = check(&mut reader);
+/// let kinds: Vec<_> = findings.iter().map(|f| f.kind).collect();
+/// assert_eq!(
+///     kinds,
+///     [LintKind::TrailingWhitespace, LintKind::DuplicateId, LintKind::InvalidBase]
+/// );
+/// assert_eq!(findings[2].to_string(), "record 'r1 ' at line 6 (record 2): invalid base 'X'");
+/// ```
+pub fn check(reader: &mut dyn FastxReader) -> Vec<LintFinding> {
+    check_with_alphabet(reader, &Alphabet::Dna)
+}
+
+/// Same as [`check`] but reports the characters outside of `alphabet` as invalid
+/// bases, e.g. to check protein files
+///
+/// ```
+/// use needletail::alphabet::Alphabet;
+/// use needletail::lint::check_with_alphabet;
+/// use needletail::parse_fastx_reader;
+///
+/// let mut reader = parse_fastx_reader(&b">p1\nMKWVTF\n"[..]).unwrap();
+/// assert!(check_with_alphabet(&mut reader, &Alphabet::Protein).is_empty());
+/// ```
+pub fn check_with_alphabet(reader: &mut dyn FastxReader, alphabet: &Alphabet) -> Vec<LintFinding> {
+    let mut linter = Linter {
+        alphabet: alphabet.clone(),
+        ..Default::default()
+    };
+    let can_skip = reader.can_skip_invalid_records();
+    while let Some(record) = reader.next() {
+        match record {
+            Ok(record) => linter.check_record(&record),
+            Err(e) => {
+                linter.record += 1;
+                linter.findings.push(LintFinding {
+                    kind: LintKind::ParseError,
+                    record: linter.record,
+                    position: e.position.clone(),
+                    message: e.to_string(),
+                });
+                if !can_skip {
+                    break;
+                }
+            }
+        }
+    }
+    linter.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    fn kinds(data: &[u8]) -> Vec<(LintKind, u64, u64)> {
+        let mut reader = parse_fastx_reader(data).unwrap();
+        check(&mut reader)
+            .into_iter()
+            .map(|f| (f.kind, f.record, f.position.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(kinds(b">r1\nACGT\nacgn\n>r2\nAAAAAAAA\n"), []);
+        assert_eq!(
+            kinds(b">r1\nACGT\n>r2\r\nAC \r\n>r3\nAAAA\n>r4\r\nAAAAA\n"),
+            [
+                (LintKind::MixedLineEndings, 2, 3),
+                (LintKind::TrailingWhitespace, 2, 4),
+                (LintKind::InconsistentLength, 2, 3),
+            ]
+        );
+        assert_eq!(
+            kinds(b"@r1\nACGT\n+\nhhhh\n@r2\nACGT\n+\n;;;h\n"),
+            [(LintKind::QualityEncoding, 1, 4)]
+        );
+        assert_eq!(
+            kinds(b"@r1\nACGT\n+\n!!!h\n@r2\nACGT\n+\nII\x7fI\n"),
+            [(LintKind::QualityEncoding, 2, 8)]
+        );
+        assert_eq!(
+            kinds(b"@r1\nACGT\n+\nIIII\n@r2\nACGT\n+\nIII\n"),
+            [(LintKind::ParseError, 2, 5)]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Hard-masking of sequences, e.g. to scrub host reads before writing them out
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use crate::bitkmer::BitKmer;
+use crate::Sequence;
+
+/// Replace every base covered by a kmer of `blacklist` with an `N`.
+///
+/// The blacklist is expected to contain canonical kmers of size `k`
+/// (see [`crate::screen::probe_kmers`]) so both strands get masked.
+/// Newlines are stripped from the sequence first so the positions of the
+/// returned sequence match the ones of `strip_returns`. If nothing needs to be
+/// masked, no allocation is made.
+///
+/// ```
+/// use needletail::mask::by_kmer_set;
+/// use needletail::screen::probe_kmers;
+///
+/// let blacklist = probe_kmers(b"GGGGG", 4);
+/// let masked = by_kmer_set(&b"ACGGGGGTA"[..], &blacklist, 4);
+/// assert_eq!(masked.as_ref(), b"ACNNNNNTA");
+/// ```
+pub fn by_kmer_set<'a, S: Sequence<'a> + ?Sized>(
+    record: &'a S,
+    blacklist: &HashSet<BitKmer>,
+    k: u8,
+) -> Cow<'a, [u8]> {
+    let mut seq = record.strip_returns();
+    if blacklist.is_empty() {
+        return seq;
+    }
+
+    let hits: Vec<usize> = seq
+        .bit_kmers(k, true)
+        .filter(|(_, kmer, _)| blacklist.contains(kmer))
+        .map(|(pos, _, _)| pos)
+        .collect();
+    if hits.is_empty() {
+        return seq;
+    }
+
+    let buf = seq.to_mut();
+    // kmers overlap so we only write the bases that weren't already masked
+    let mut masked_until = 0;
+    for pos in hits {
+        let start = pos.max(masked_until);
+        let end = pos + k as usize;
+        buf[start..end].fill(b'N');
+        masked_until = end;
+    }
+    seq
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+    use crate::screen::probe_kmers;
+
+    #[test]
+    fn test_mask_overlapping_and_reverse_kmers() {
+        let blacklist = probe_kmers(b"AAAAC", 3);
+        // GTT is the reverse complement of AAC
+        let masked = by_kmer_set(&b"CAAAAGCGTTC"[..], &blacklist, 3);
+        assert_eq!(masked.as_ref(), b"CNNNNGCNNNC");
+    }
+
+    #[test]
+    fn test_mask_nothing_borrows() {
+        let blacklist = probe_kmers(b"AAAAC", 3);
+        let masked = by_kmer_set(&b"GCGCGC"[..], &blacklist, 3);
+        assert!(matches!(masked, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_mask_multiline_record() {
+        let blacklist = probe_kmers(b"CCCC", 4);
+        let mut reader = parse_fastx_reader(&b">r\nACCC\nCCTA\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let masked = by_kmer_set(&record, &blacklist, 4);
+        assert_eq!(masked.as_ref(), b"ANNNNNTA");
+    }
+}
@@ -0,0 +1,93 @@
+//! Fixing the orientation of reads (e.g. amplicons sequenced from both ends)
+//! so they all match the strand of a reference.
+use std::collections::HashSet;
+
+use crate::bitkmer::{reverse_complement, BitKmer};
+use crate::parser::{OwnedRecord, SequenceRecord};
+use crate::Sequence;
+
+/// Build the set of the forward-strand kmers of size `k` of a reference
+/// sequence. Unlike [`crate::screen::probe_kmers`], the kmers are *not*
+/// canonicalized since we need to know which strand they come from.
+pub fn reference_kmers(seq: &[u8], k: u8) -> HashSet<BitKmer> {
+    seq.strip_returns()
+        .bit_kmers(k, false)
+        .map(|(_, kmer, _)| kmer)
+        .collect()
+}
+
+/// Count how many kmers of `seq` are found on the plus and minus strands of
+/// the reference, returned as `(plus, minus)`.
+pub fn kmer_votes(seq: &[u8], reference_kmers: &HashSet<BitKmer>, k: u8) -> (usize, usize) {
+    let mut plus = 0;
+    let mut minus = 0;
+    for (_, kmer, _) in seq.bit_kmers(k, false) {
+        if reference_kmers.contains(&kmer) {
+            plus += 1;
+        } else if reference_kmers.contains(&reverse_complement(kmer)) {
+            minus += 1;
+        }
+    }
+    (plus, minus)
+}
+
+/// Returns an owned copy of the record, reverse complemented (along with its
+/// quality) if more of its kmers match the minus strand of the reference than
+/// the plus strand.
+///
+/// `reference_kmers` should be built with [`reference_kmers`] and the same `k`.
+///
+/// ```
+/// use needletail::orient::{by_kmer_vote, reference_kmers};
+/// use needletail::parse_fastx_reader;
+///
+/// let reference = reference_kmers(b"ACGGATTACAGGCT", 5);
+/// let mut reader = parse_fastx_reader(&b"@r\nCTGTAATCC\n+\nABCDEFGHI\n"[..]).unwrap();
+/// let record = reader.next().unwrap().unwrap();
+/// let oriented = by_kmer_vote(&record, &reference, 5);
+/// assert_eq!(oriented.seq, b"GGATTACAG");
+/// assert_eq!(oriented.qual.unwrap(), b"IHGFEDCBA");
+/// ```
+pub fn by_kmer_vote(
+    record: &SequenceRecord,
+    reference_kmers: &HashSet<BitKmer>,
+    k: u8,
+) -> OwnedRecord {
+    let mut owned = record.to_owned_record();
+    let (plus, minus) = kmer_votes(&owned.seq, reference_kmers, k);
+    if minus > plus {
+        owned.seq = owned.seq.reverse_complement();
+        if let Some(qual) = owned.qual.as_mut() {
+            qual.reverse();
+        }
+    }
+    owned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_orient() {
+        let reference = reference_kmers(b"AAGGTTCCAGTCAGTTGCA", 4);
+        let mut reader =
+            parse_fastx_reader(&b">plus\nGGTTCCAG\n>minus\nCAACTGAC\n>unknown\nCCCCCCCC\n"[..])
+                .unwrap();
+
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(kmer_votes(&rec.seq(), &reference, 4), (5, 0));
+        assert_eq!(by_kmer_vote(&rec, &reference, 4).seq, b"GGTTCCAG");
+
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(kmer_votes(&rec.seq(), &reference, 4), (0, 5));
+        let oriented = by_kmer_vote(&rec, &reference, 4);
+        assert_eq!(oriented.id, b"minus");
+        assert_eq!(oriented.seq, b"GTCAGTTG");
+        assert_eq!(oriented.qual, None);
+
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(by_kmer_vote(&rec, &reference, 4).seq, b"CCCCCCCC");
+    }
+}
@@ -0,0 +1,346 @@
+//! Nucleic acid sequences packed in 2 bits per base, e.g. to hold whole genomes in
+//! memory, with the bases that aren't `ACGT` (`N` runs, ambiguity codes...) stored
+//! on the side.
+use std::ops::Range;
+
+use crate::bitkmer::{canonical, BitKmer, BitKmerSeq};
+use crate::sequence::complement;
+use crate::sequence::tables::NUC2BIT;
+
+/// Number of bases packed in each word
+const BASES_PER_WORD: usize = 32;
+
+/// Consecutive bases that aren't `ACGT`, all the same byte, see [`TwoBitSeq`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BaseRun {
+    pub range: Range<usize>,
+    pub base: u8,
+}
+
+/// A sequence stored in 2 bits per base, with the encoding of `needletail::bitkmer`
+/// (`A` is 0, `C` 1, `G` 2 and `T` 3), and the runs of other bytes (`N`, IUPAC codes,
+/// gaps...) kept in a list on the side so that they round-trip.
+///
+/// `ACGT` bases are read back in upper case, other bytes as they were.
+///
+/// ```
+/// use needletail::packed::TwoBitSeq;
+///
+/// let seq = TwoBitSeq::from_bytes(b"ACGTNNNNacgR");
+/// assert_eq!(seq.len(), 12);
+/// assert_eq!(seq.to_bytes(), b"ACGTNNNNACGR");
+/// assert_eq!(seq.get(4), Some(b'N'));
+/// assert_eq!(seq.slice(2..6).to_bytes(), b"GTNN");
+/// assert_eq!(seq.reverse_complement().to_bytes(), b"YCGTNNNNACGT");
+/// // kmers overlapping a base that isn't ACGT are skipped
+/// let kmers: Vec<_> = seq.kmers(3, false).map(|(pos, _, _)| pos).collect();
+/// assert_eq!(kmers, [0, 1, 8]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TwoBitSeq {
+    /// The first base in the highest bits of the first word, other bytes than `ACGT`
+    /// are packed as `A`
+    words: Vec<u64>,
+    len: usize,
+    /// Sorted and not overlapping
+    runs: Vec<BaseRun>,
+}
+
+impl TwoBitSeq {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Packs a sequence of ASCII bases
+    pub fn from_bytes(seq: &[u8]) -> Self {
+        let mut packed = Self {
+            words: Vec::with_capacity(seq.len().div_ceil(BASES_PER_WORD)),
+            ..Self::default()
+        };
+        packed.extend_from_bytes(seq);
+        packed
+    }
+
+    /// Appends a sequence of ASCII bases
+    pub fn extend_from_bytes(&mut self, seq: &[u8]) {
+        for base in seq {
+            match NUC2BIT[*base as usize] {
+                Some(code) => self.push_code(code),
+                None => {
+                    let pos = self.len;
+                    match self.runs.last_mut() {
+                        Some(run) if run.range.end == pos && run.base == *base => {
+                            run.range.end += 1;
+                        }
+                        _ => self.runs.push(BaseRun {
+                            range: pos..pos + 1,
+                            base: *base,
+                        }),
+                    }
+                    self.push_code(0);
+                }
+            }
+        }
+    }
+
+    fn push_code(&mut self, code: u8) {
+        let offset = self.len % BASES_PER_WORD;
+        if offset == 0 {
+            self.words.push(0);
+        }
+        let shift = 2 * (BASES_PER_WORD - 1 - offset);
+        *self.words.last_mut().unwrap() |= u64::from(code) << shift;
+        self.len += 1;
+    }
+
+    /// 2-bit code of the base at `pos`, the one of `A` for other bytes than `ACGT`
+    #[inline]
+    fn code(&self, pos: usize) -> u8 {
+        let shift = 2 * (BASES_PER_WORD - 1 - pos % BASES_PER_WORD);
+        ((self.words[pos / BASES_PER_WORD] >> shift) & 0b11) as u8
+    }
+
+    /// The run containing `pos`, if any
+    fn run_at(&self, pos: usize) -> Option<&BaseRun> {
+        let i = self.runs.partition_point(|run| run.range.end <= pos);
+        self.runs.get(i).filter(|run| run.range.contains(&pos))
+    }
+
+    /// Number of bases
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The base at `pos`, `None` if it's past the end
+    pub fn get(&self, pos: usize) -> Option<u8> {
+        if pos >= self.len {
+            return None;
+        }
+        match self.run_at(pos) {
+            Some(run) => Some(run.base),
+            None => Some(b"ACGT"[self.code(pos) as usize]),
+        }
+    }
+
+    /// The runs of bases that aren't `ACGT`, in order
+    pub fn runs(&self) -> &[BaseRun] {
+        &self.runs
+    }
+
+    /// Unpacks the bases in `range` at the end of `out`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` goes past the end of the sequence.
+    pub fn extend_bytes(&self, out: &mut Vec<u8>, range: Range<usize>) {
+        assert!(
+            range.end <= self.len,
+            "range end {} is past the end of a sequence of {} bases",
+            range.end,
+            self.len
+        );
+        let start = out.len();
+        out.extend(range.clone().map(|pos| b"ACGT"[self.code(pos) as usize]));
+        let first_run = self
+            .runs
+            .partition_point(|run| run.range.end <= range.start);
+        for run in self.runs[first_run..]
+            .iter()
+            .take_while(|run| run.range.start < range.end)
+        {
+            let from = run.range.start.max(range.start) - range.start;
+            let to = run.range.end.min(range.end) - range.start;
+            out[start + from..start + to].fill(run.base);
+        }
+    }
+
+    /// Unpacks the whole sequence
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.len);
+        self.extend_bytes(&mut out, 0..self.len);
+        out
+    }
+
+    /// Copies the bases in `range` into a new packed sequence
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` goes past the end of the sequence.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        let mut slice = Self::new();
+        // unpacking by chunks keeps the memory used bounded for long slices
+        let chunk = BASES_PER_WORD * 1024;
+        let mut buf = Vec::with_capacity(chunk);
+        for start in range.clone().step_by(chunk) {
+            buf.clear();
+            self.extend_bytes(&mut buf, start..range.end.min(start + chunk));
+            slice.extend_from_bytes(&buf);
+        }
+        slice
+    }
+
+    /// The reverse complement of the sequence, the bases that aren't `ACGT` being
+    /// complemented like `needletail::sequence::complement` does
+    pub fn reverse_complement(&self) -> Self {
+        let mut rc = Self {
+            words: Vec::with_capacity(self.words.len()),
+            ..Self::default()
+        };
+        let mut runs = self.runs.iter().rev().peekable();
+        for pos in (0..self.len).rev() {
+            // the runs are packed as `A` whatever the strand
+            while runs.next_if(|run| run.range.start > pos).is_some() {}
+            match runs.peek() {
+                Some(run) if run.range.contains(&pos) => rc.push_code(0),
+                _ => rc.push_code(3 - self.code(pos)),
+            }
+        }
+        rc.runs = self
+            .runs
+            .iter()
+            .rev()
+            .map(|run| BaseRun {
+                range: self.len - run.range.end..self.len - run.range.start,
+                base: complement(run.base),
+            })
+            .collect();
+        rc
+    }
+
+    /// Iterates over the kmers of size `k` made only of `ACGT`, read directly from
+    /// the packed bases. Yields the same items as `needletail::bitkmer::BitNuclKmer`:
+    /// the position of the kmer, the kmer (canonical if `canonical` is set) and
+    /// whether it is the reverse complement of the sequence.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` isn't between 1 and 32.
+    pub fn kmers(&self, k: u8, canonical: bool) -> TwoBitKmers<'_> {
+        assert!((1..=32).contains(&k), "k must be between 1 and 32, got {k}");
+        TwoBitKmers {
+            seq: self,
+            k,
+            canonical,
+            pos: 0,
+            kmer: 0,
+            valid: 0,
+            next_run: 0,
+        }
+    }
+
+    /// Approximate number of bytes the sequence takes on the heap
+    pub fn heap_size(&self) -> usize {
+        self.words.capacity() * std::mem::size_of::<u64>()
+            + self.runs.capacity() * std::mem::size_of::<BaseRun>()
+    }
+}
+
+impl From<&[u8]> for TwoBitSeq {
+    fn from(seq: &[u8]) -> Self {
+        Self::from_bytes(seq)
+    }
+}
+
+/// Iterator over the kmers of a [`TwoBitSeq`], see [`TwoBitSeq::kmers`]
+pub struct TwoBitKmers<'a> {
+    seq: &'a TwoBitSeq,
+    k: u8,
+    canonical: bool,
+    /// Position of the next base to add to the kmer
+    pos: usize,
+    kmer: BitKmerSeq,
+    /// Number of `ACGT` bases in a row up to `pos`
+    valid: usize,
+    /// Index of the next run of bases that aren't `ACGT`
+    next_run: usize,
+}
+
+impl Iterator for TwoBitKmers<'_> {
+    type Item = (usize, BitKmer, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mask = BitKmerSeq::MAX >> (64 - 2 * u32::from(self.k));
+        while self.pos < self.seq.len {
+            if let Some(run) = self.seq.runs.get(self.next_run) {
+                if run.range.start == self.pos {
+                    self.pos = run.range.end;
+                    self.next_run += 1;
+                    self.valid = 0;
+                    continue;
+                }
+            }
+            let code = self.seq.code(self.pos);
+            self.kmer = ((self.kmer << 2) | BitKmerSeq::from(code)) & mask;
+            self.valid += 1;
+            self.pos += 1;
+            if self.valid >= usize::from(self.k) {
+                let start = self.pos - usize::from(self.k);
+                let kmer = (self.kmer, self.k);
+                return Some(if self.canonical {
+                    let (kmer, was_rc) = canonical(kmer);
+                    (start, kmer, was_rc)
+                } else {
+                    (start, kmer, false)
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitkmer::BitNuclKmer;
+    use crate::Sequence;
+
+    fn test_seq() -> Vec<u8> {
+        let mut seq: Vec<u8> = (0..150).map(|i| b"ACGTTGCA"[(i * 3 + i / 5) % 8]).collect();
+        seq[0] = b'N';
+        seq[31..35].fill(b'N');
+        seq[64] = b'R';
+        seq[65] = b'-';
+        seq[149] = b'N';
+        seq
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = test_seq();
+        let seq = TwoBitSeq::from_bytes(&bytes);
+        assert_eq!(seq.to_bytes(), bytes);
+        assert_eq!(seq.runs().len(), 5);
+        assert_eq!(seq.runs()[1].range, 31..35);
+        for (i, base) in bytes.iter().enumerate() {
+            assert_eq!(seq.get(i), Some(*base));
+        }
+        assert_eq!(seq.get(bytes.len()), None);
+        for range in [0..0, 0..150, 30..66, 33..34, 100..150] {
+            assert_eq!(seq.slice(range.clone()).to_bytes(), bytes[range]);
+        }
+        let rc = seq.reverse_complement();
+        assert_eq!(rc.to_bytes(), bytes.reverse_complement());
+        assert_eq!(rc, TwoBitSeq::from_bytes(&bytes.reverse_complement()));
+
+        let mut appended = TwoBitSeq::from_bytes(&bytes[..40]);
+        appended.extend_from_bytes(&bytes[40..]);
+        assert_eq!(appended, seq);
+        assert!(TwoBitSeq::new().is_empty());
+    }
+
+    #[test]
+    fn test_kmers_match_bitkmers() {
+        let bytes = test_seq();
+        let seq = TwoBitSeq::from_bytes(&bytes);
+        for k in [1, 3, 21, 32] {
+            for canonical in [false, true] {
+                let expected: Vec<_> = BitNuclKmer::new(&bytes, k, canonical).collect();
+                assert_eq!(seq.kmers(k, canonical).collect::<Vec<_>>(), expected);
+            }
+        }
+    }
+}
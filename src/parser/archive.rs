@@ -0,0 +1,349 @@
+//! Reading FASTA/FASTQ files stored in tar archives
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::errors::ParseError;
+use crate::parser::parse_fastx_reader;
+use crate::parser::path::open_file;
+use crate::parser::record::SequenceRecord;
+use crate::parser::utils::FastxReader;
+
+const BLOCK_SIZE: u64 = 512;
+
+/// The archive stream and where it is in the current member
+struct ArchiveState<R> {
+    reader: R,
+    // incremented for each member so the readers of the previous ones stop
+    member: u64,
+    // bytes of the current member not read yet, then up to the next header
+    remaining: u64,
+    padding: u64,
+}
+
+impl<R: Read> ArchiveState<R> {
+    fn skip(&mut self, n: u64) -> io::Result<()> {
+        if io::copy(&mut (&mut self.reader).take(n), &mut io::sink())? < n {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
+    /// Reads a header block, returns `false` at the end of the stream
+    fn read_block(&mut self, block: &mut [u8; BLOCK_SIZE as usize]) -> io::Result<bool> {
+        let mut read = 0;
+        while read < block.len() {
+            match self.reader.read(&mut block[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Skips the rest of the current member and moves to the next regular, non-empty
+    /// file, returning its path or `None` at the end of the archive
+    fn next_member(&mut self) -> io::Result<Option<String>> {
+        self.skip(self.remaining + self.padding)?;
+        self.remaining = 0;
+        self.padding = 0;
+        self.member += 1;
+
+        // GNU and pax archives put long paths in an entry of their own before the file
+        let mut long_name = None;
+        let mut block = [0; BLOCK_SIZE as usize];
+        loop {
+            if !self.read_block(&mut block)? || block.iter().all(|b| *b == 0) {
+                return Ok(None);
+            }
+            let header = tar::Header::from_byte_slice(&block);
+            let checksum: u32 = block[..148]
+                .iter()
+                .chain(&[b' '; 8])
+                .chain(&block[156..])
+                .map(|b| u32::from(*b))
+                .sum();
+            if header.cksum()? != checksum {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid tar header checksum",
+                ));
+            }
+            let size = header.entry_size()?;
+            let padding = size.next_multiple_of(BLOCK_SIZE) - size;
+            let entry_type = header.entry_type();
+
+            if entry_type.is_gnu_longname() || entry_type.is_pax_local_extensions() {
+                let mut data = Vec::new();
+                (&mut self.reader).take(size).read_to_end(&mut data)?;
+                if (data.len() as u64) < size {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                self.skip(padding)?;
+                if entry_type.is_gnu_longname() {
+                    let name = data.split(|b| *b == 0).next().unwrap_or_default();
+                    long_name = Some(String::from_utf8_lossy(name).into_owned());
+                } else if let Some(path) = tar::PaxExtensions::new(&data)
+                    .filter_map(Result::ok)
+                    .find(|ext| ext.key_bytes() == b"path")
+                {
+                    long_name = Some(String::from_utf8_lossy(path.value_bytes()).into_owned());
+                }
+                continue;
+            }
+            if entry_type.is_file() && size > 0 {
+                let name = long_name
+                    .take()
+                    .unwrap_or_else(|| String::from_utf8_lossy(&header.path_bytes()).into_owned());
+                self.remaining = size;
+                self.padding = padding;
+                return Ok(Some(name));
+            }
+            self.skip(size + padding)?;
+            long_name = None;
+        }
+    }
+}
+
+/// A tar archive of FASTA/FASTQ files (each possibly compressed)
+pub struct TarFastxReader<R: Read> {
+    state: Mutex<ArchiveState<R>>,
+}
+
+impl<R: Read + Send> TarFastxReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            state: Mutex::new(ArchiveState {
+                reader,
+                member: 0,
+                remaining: 0,
+                padding: 0,
+            }),
+        }
+    }
+
+    /// Iterate over the members of the archive. The archive is read as a stream so
+    /// iterating again carries on after the last member returned.
+    pub fn members(&mut self) -> Result<TarMembers<'_, R>, ParseError> {
+        Ok(TarMembers {
+            state: &self.state,
+            done: false,
+        })
+    }
+}
+
+/// Iterator over the regular, non-empty files of a tar archive, see
+/// [`TarFastxReader::members`]. Directories, links and empty files are skipped.
+///
+/// Each member has its compression and format detected like in
+/// `parse_fastx_reader`. Its content is streamed from the archive: getting the next
+/// member skips what's left of the previous one, whose reader then returns an error.
+pub struct TarMembers<'a, R: 'a + Read> {
+    state: &'a Mutex<ArchiveState<R>>,
+    done: bool,
+}
+
+impl<'a, R: 'a + Read + Send> Iterator for TarMembers<'a, R> {
+    type Item = Result<TarMember<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let (name, member) = {
+            let mut state = self.state.lock().unwrap();
+            match state.next_member() {
+                Ok(Some(name)) => (name, state.member),
+                Ok(None) => {
+                    self.done = true;
+                    return None;
+                }
+                // the position in the archive is lost
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            }
+        };
+        let reader = MemberReader {
+            state: self.state,
+            member,
+        };
+        Some(parse_fastx_reader(reader).map(|reader| TarMember { name, reader }))
+    }
+}
+
+/// Reads the current member of the archive, up to its end
+struct MemberReader<'a, R> {
+    state: &'a Mutex<ArchiveState<R>>,
+    member: u64,
+}
+
+impl<R: Read> Read for MemberReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        if state.member != self.member {
+            return Err(io::Error::other(
+                "the tar archive moved on to the next member",
+            ));
+        }
+        let max = buf
+            .len()
+            .min(usize::try_from(state.remaining).unwrap_or(usize::MAX));
+        let n = state.reader.read(&mut buf[..max])?;
+        if n == 0 && max > 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        state.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// A FASTA/FASTQ file in a tar archive
+pub struct TarMember<'a> {
+    name: String,
+    reader: Box<dyn FastxReader + 'a>,
+}
+
+impl<'a> TarMember<'a> {
+    /// The path of the member in the archive
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the next record of the member along with the member name
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<(&str, SequenceRecord<'_>), ParseError>> {
+        let name = self.name.as_str();
+        self.reader.next().map(|rec| rec.map(|r| (name, r)))
+    }
+
+    /// The reader of the member, e.g. to get its line ending
+    pub fn reader(&mut self) -> &mut (dyn FastxReader + 'a) {
+        &mut *self.reader
+    }
+}
+
+/// Open a tar archive of FASTA/FASTQ files, e.g. a `reads.tar` containing
+/// gzipped FASTQ files.
+///
+/// ```no_run
+/// use needletail::parser::parse_fastx_tar;
+///
+/// let mut archive = parse_fastx_tar("reads.tar").unwrap();
+/// for member in archive.members().unwrap() {
+///     let mut member = member.unwrap();
+///     while let Some(record) = member.next() {
+///         let (name, record) = record.unwrap();
+///         println!("{}\t{}", name, String::from_utf8_lossy(record.id()));
+///     }
+/// }
+/// ```
+pub fn parse_fastx_tar<P: AsRef<Path>>(path: P) -> Result<TarFastxReader<File>, ParseError> {
+    Ok(TarFastxReader::new(open_file(path.as_ref())?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn append_file(builder: &mut tar::Builder<Vec<u8>>, path: &str, data: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_entry_type(tar::EntryType::Regular);
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+
+    #[test]
+    fn test_tar_members() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut dir = tar::Header::new_gnu();
+        dir.set_size(0);
+        dir.set_mode(0o755);
+        dir.set_entry_type(tar::EntryType::Directory);
+        builder.append_data(&mut dir, "reads/", &b""[..]).unwrap();
+        append_file(&mut builder, "reads/a.fasta", b">r1\nACGT\n>r2\nGG\n");
+        append_file(&mut builder, "reads/empty.fastq", b"");
+        append_file(&mut builder, "reads/b.fastq", b"@r3\nTT\n+\nII\n");
+        let data = builder.into_inner().unwrap();
+
+        let mut archive = TarFastxReader::new(&data[..]);
+        let mut seen = Vec::new();
+        for member in archive.members().unwrap() {
+            let mut member = member.unwrap();
+            while let Some(record) = member.next() {
+                let (name, record) = record.unwrap();
+                seen.push((name.to_string(), record.id().to_vec()));
+            }
+        }
+        assert_eq!(
+            seen,
+            vec![
+                ("reads/a.fasta".to_string(), b"r1".to_vec()),
+                ("reads/a.fasta".to_string(), b"r2".to_vec()),
+                ("reads/b.fastq".to_string(), b"r3".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tar_streaming() {
+        let long_name = format!("{}/reads.fasta", "nested".repeat(30));
+        let mut builder = tar::Builder::new(Vec::new());
+        let records: Vec<u8> = (0..100)
+            .flat_map(|i| format!(">r{i}\nACGT\n").into_bytes())
+            .collect();
+        append_file(&mut builder, &long_name, &records);
+        append_file(&mut builder, "b.fasta", b">last\nGG\n");
+        let data = builder.into_inner().unwrap();
+
+        let mut archive = TarFastxReader::new(&data[..]);
+        let mut members = archive.members().unwrap();
+        let mut first = members.next().unwrap().unwrap();
+        assert_eq!(first.name(), long_name);
+        assert_eq!(first.next().unwrap().unwrap().1.id(), b"r0");
+        // the rest of the first member is skipped
+        let mut second = members.next().unwrap().unwrap();
+        let (name, record) = second.next().unwrap().unwrap();
+        assert_eq!((name, record.id()), ("b.fasta", &b"last"[..]));
+        assert!(second.next().is_none());
+        assert!(members.next().is_none());
+
+        // a member cut short
+        let mut archive = TarFastxReader::new(&data[..4 * 512]);
+        let mut member = archive.members().unwrap().next().unwrap().unwrap();
+        let mut result = Ok(());
+        while let Some(record) = member.next() {
+            if let Err(e) = record {
+                result = Err(e);
+                break;
+            }
+        }
+        assert!(result.is_err());
+
+        // a corrupted header
+        let mut corrupted = data.clone();
+        corrupted[0] ^= 1;
+        let mut archive = TarFastxReader::new(&corrupted[..]);
+        let mut members = archive.members().unwrap();
+        assert!(members.next().unwrap().is_err());
+        assert!(members.next().is_none());
+    }
+
+    #[test]
+    fn test_tar_unknown_member() {
+        let mut builder = tar::Builder::new(Vec::new());
+        append_file(&mut builder, "README", b"not a fastx file");
+        let data = builder.into_inner().unwrap();
+
+        let mut archive = TarFastxReader::new(&data[..]);
+        let mut members = archive.members().unwrap();
+        assert!(members.next().unwrap().is_err());
+        assert!(members.next().is_none());
+    }
+}
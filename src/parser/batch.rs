@@ -0,0 +1,314 @@
+//! Grouping owned records in batches of bounded memory size
+use std::mem;
+use std::slice;
+
+use crate::errors::ParseError;
+use crate::parser::record::{OwnedRecord, SequenceRecord};
+use crate::parser::utils::FastxReader;
+
+/// Accumulates owned records until adding another one would go over a byte budget,
+/// so consumers (e.g. worker threads) get batches of roughly the same memory size
+/// whether they contain millions of short reads or a few long ones.
+///
+/// The size of a record is its `approx_heap_size` plus the size of the
+/// `OwnedRecord` struct itself. A record bigger than the budget is returned
+/// alone in its own batch.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::BoundedBatcher;
+///
+/// let mut reader = parse_fastx_reader(&b">a\nACGT\n>b\nACGT\n>c\nACGT\n"[..]).unwrap();
+/// let mut batcher = BoundedBatcher::new(200);
+/// let mut sizes = Vec::new();
+/// while let Some(batch) = batcher.next_batch(&mut reader).unwrap() {
+///     sizes.push(batch.len());
+/// }
+/// assert_eq!(sizes, vec![2, 1]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BoundedBatcher {
+    max_bytes: usize,
+    batch: Vec<OwnedRecord>,
+    bytes: usize,
+}
+
+impl BoundedBatcher {
+    /// Creates a batcher yielding batches of at most `max_bytes`
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            batch: Vec::new(),
+            bytes: 0,
+        }
+    }
+
+    fn record_size(record: &OwnedRecord) -> usize {
+        record.approx_heap_size() + mem::size_of::<OwnedRecord>()
+    }
+
+    /// Approximate size in bytes of the records accumulated so far
+    pub fn current_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Adds a record, returning the previous batch if the record didn't fit in it.
+    /// The record is then the first one of the new batch.
+    pub fn push(&mut self, record: OwnedRecord) -> Option<Vec<OwnedRecord>> {
+        let size = Self::record_size(&record);
+        let full = if !self.batch.is_empty() && self.bytes + size > self.max_bytes {
+            self.bytes = 0;
+            Some(mem::take(&mut self.batch))
+        } else {
+            None
+        };
+        self.bytes += size;
+        self.batch.push(record);
+        full
+    }
+
+    /// Returns the records accumulated so far, if any, e.g. once the input is exhausted
+    pub fn finish(&mut self) -> Option<Vec<OwnedRecord>> {
+        self.bytes = 0;
+        if self.batch.is_empty() {
+            None
+        } else {
+            Some(mem::take(&mut self.batch))
+        }
+    }
+
+    /// Reads records from `reader` until a batch is full and returns it.
+    /// Returns the last, possibly smaller, batch at the end of the input and `None` after that.
+    pub fn next_batch(
+        &mut self,
+        reader: &mut dyn FastxReader,
+    ) -> Result<Option<Vec<OwnedRecord>>, ParseError> {
+        while let Some(record) = reader.next() {
+            if let Some(batch) = self.push(record?.to_owned_record()) {
+                return Ok(Some(batch));
+            }
+        }
+        Ok(self.finish())
+    }
+}
+
+/// A reusable buffer of owned records, filled many records at a time by
+/// `FastxReader::read_record_set`.
+///
+/// The allocations of the records are kept between fills so reading a whole file
+/// through the same set only allocates until the records reach their usual size.
+/// The set can also be sent to another thread as a whole, e.g. to a worker, and sent
+/// back to be filled again.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::RecordSet;
+///
+/// let fasta = b">a\nACGT\n>b\nAC\nGT\n>c\nAAAA\n";
+/// let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+/// let mut set = RecordSet::new(2);
+/// let mut ids = Vec::new();
+/// while reader.read_record_set(&mut set).unwrap() > 0 {
+///     ids.push(set.iter().map(|r| r.id().to_vec()).collect::<Vec<_>>());
+/// }
+/// assert_eq!(ids, [vec![b"a".to_vec(), b"b".to_vec()], vec![b"c".to_vec()]]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RecordSet {
+    // only the first `len` ones are part of the set, the others are kept for their allocations
+    records: Vec<OwnedRecord>,
+    len: usize,
+    max_records: usize,
+    max_bytes: usize,
+    bytes: usize,
+}
+
+impl RecordSet {
+    /// Creates a set holding up to `max_records` (at least 1) records per fill
+    pub fn new(max_records: usize) -> Self {
+        Self {
+            // the set grows as it's filled, e.g. when only `max_bytes` bounds it
+            records: Vec::with_capacity(max_records.min(1024)),
+            len: 0,
+            max_records: max_records.max(1),
+            max_bytes: usize::MAX,
+            bytes: 0,
+        }
+    }
+
+    /// Also stops filling the set once the buffers of its records hold `max_bytes`
+    /// bytes (see `OwnedRecord::approx_heap_size`). The record going over the budget
+    /// is still added.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Maximum number of records per fill
+    pub fn max_records(&self) -> usize {
+        self.max_records
+    }
+
+    /// Number of records in the set
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the set has no records, e.g. once the input is exhausted
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the set reached its number of records or its byte budget
+    pub fn is_full(&self) -> bool {
+        self.len >= self.max_records || self.bytes >= self.max_bytes
+    }
+
+    /// Number of bytes held by the buffers of the records in the set
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Removes all the records, keeping their allocations for the next fill
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.bytes = 0;
+    }
+
+    /// The records of the set
+    pub fn records(&self) -> &[OwnedRecord] {
+        &self.records[..self.len]
+    }
+
+    pub fn get(&self, index: usize) -> Option<&OwnedRecord> {
+        self.records().get(index)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, OwnedRecord> {
+        self.records().iter()
+    }
+
+    /// Returns the records of the set, dropping the allocations kept for reuse
+    pub fn into_records(mut self) -> Vec<OwnedRecord> {
+        self.records.truncate(self.len);
+        self.records
+    }
+
+    /// Copies `record` at the end of the set, in the allocations of a previous record
+    /// if there is one
+    pub fn push(&mut self, record: &SequenceRecord) {
+        let parts = record.raw_parts();
+        if self.len == self.records.len() {
+            self.records.push(OwnedRecord {
+                id: Vec::new(),
+                seq: Vec::new(),
+                qual: None,
+            });
+        }
+        let owned = &mut self.records[self.len];
+        owned.id.clear();
+        owned.id.extend_from_slice(parts.id);
+        owned.seq.clear();
+        for line in parts.seq_lines {
+            owned.seq.extend_from_slice(line);
+        }
+        match (parts.qual, &mut owned.qual) {
+            (Some(qual), Some(owned_qual)) => {
+                owned_qual.clear();
+                owned_qual.extend_from_slice(qual);
+            }
+            (qual, owned_qual) => *owned_qual = qual.map(<[u8]>::to_vec),
+        }
+        // the reused buffers may hold more than the record
+        self.bytes += owned.approx_heap_size();
+        self.len += 1;
+    }
+
+    /// Empties the set and refills it from `reader`, see `FastxReader::read_record_set`
+    pub(crate) fn fill<R: FastxReader + ?Sized>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<usize, ParseError> {
+        self.clear();
+        while !self.is_full() {
+            match reader.next() {
+                Some(record) => self.push(&record?),
+                None => break,
+            }
+        }
+        Ok(self.len)
+    }
+}
+
+impl<'a> IntoIterator for &'a RecordSet {
+    type Item = &'a OwnedRecord;
+    type IntoIter = slice::Iter<'a, OwnedRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(len: usize) -> OwnedRecord {
+        OwnedRecord {
+            id: b"r".to_vec(),
+            seq: vec![b'A'; len],
+            qual: None,
+        }
+    }
+
+    #[test]
+    fn test_bounded_batcher() {
+        let overhead = mem::size_of::<OwnedRecord>() + 1;
+        let mut batcher = BoundedBatcher::new(2 * overhead + 100);
+        assert!(batcher.push(record(50)).is_none());
+        assert!(batcher.push(record(50)).is_none());
+        assert_eq!(batcher.current_bytes(), 2 * overhead + 100);
+        // doesn't fit anymore
+        let batch = batcher.push(record(1)).unwrap();
+        assert_eq!(batch.len(), 2);
+        // too big on its own: gets its own batch
+        let batch = batcher.push(record(1000)).unwrap();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].seq.len(), 1);
+        assert_eq!(batcher.finish().unwrap()[0].seq.len(), 1000);
+        assert!(batcher.finish().is_none());
+    }
+
+    #[test]
+    fn test_record_set() {
+        let data = b"@a\nACGT\n+\nIIII\n@b\nAC\n+\nII\n@c\nA\n+\nI\n@d\nAA\n+\nI\n";
+        let mut reader = crate::parse_fastx_reader(&data[..]).unwrap();
+        let mut set = RecordSet::new(2).with_max_bytes(1000);
+        assert_eq!(reader.read_record_set(&mut set).unwrap(), 2);
+        let heap_size: usize = set.records().iter().map(|r| r.approx_heap_size()).sum();
+        assert_eq!(set.bytes(), heap_size);
+        assert!(set.bytes() >= 9 + 5);
+        assert!(set.get(2).is_none());
+        // the byte budget stops this fill after the first record
+        let mut set = set.with_max_bytes(3);
+        assert_eq!(reader.read_record_set(&mut set).unwrap(), 1);
+        assert_eq!(set.records()[0].qual.as_deref(), Some(&b"I"[..]));
+        assert!(reader.read_record_set(&mut set).is_err());
+
+        // FASTA records reuse the buffers of the FASTQ ones
+        let mut reader = crate::parse_fastx_reader(&b">e\nAC\nGT\n"[..]).unwrap();
+        assert_eq!(reader.read_record_set(&mut set).unwrap(), 1);
+        assert_eq!(set.get(0).unwrap().seq, b"ACGT");
+        assert_eq!(set.get(0).unwrap().qual, None);
+        assert_eq!(reader.read_record_set(&mut set).unwrap(), 0);
+        assert!(set.is_empty());
+
+        // the records before an error stay in the set
+        let data = b"@a\nACGT\n+\nIIII\n@b\nA\n+\nI\n@c\nAA\n+\nI\n";
+        let mut reader = crate::parse_fastx_reader(&data[..]).unwrap();
+        let mut set = RecordSet::new(10);
+        assert!(reader.read_record_set(&mut set).is_err());
+        let ids: Vec<_> = set.iter().map(|r| r.id.as_slice()).collect();
+        assert_eq!(ids, [b"a", b"b"]);
+    }
+}
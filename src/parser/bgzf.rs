@@ -0,0 +1,649 @@
+//! Reading and writing BGZF files, the block-compressed gzip files written by `bgzip`.
+//!
+//! A BGZF file is a series of gzip members (blocks) of at most 64KB of data each, whose
+//! compressed size is stored in their header. Any gzip decoder can read them but they can
+//! also be read from any block: a position in the file is then a virtual offset, made of
+//! the offset of the block in the compressed file (upper 48 bits) and of the position in
+//! the decompressed block (lower 16 bits).
+//!
+//! A `.gzi` index ([`GziIndex`]) gives where each block starts in the compressed and
+//! decompressed file, so a BGZF file can also be read from any decompressed offset,
+//! e.g. one from a `.fai` index.
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use flate2::{Crc, Decompress, FlushDecompress, Status};
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::path::{create_file, open_file};
+
+/// Size of the gzip header fields before the extra field
+const FIXED_HEADER_SIZE: usize = 12;
+/// Size of the CRC32 and uncompressed size at the end of a block
+const FOOTER_SIZE: usize = 8;
+
+/// Largest number of bytes of data in a block
+pub const MAX_BLOCK_SIZE: usize = 65536;
+
+/// Returns whether `header` (the first bytes of a file) is the header of a BGZF block.
+/// At least 18 bytes are needed to tell, as written by `bgzip`.
+pub fn is_bgzf(header: &[u8]) -> bool {
+    header.len() >= FIXED_HEADER_SIZE
+        && header[..4] == [0x1F, 0x8B, 0x08, 0x04]
+        && block_size(&header[FIXED_HEADER_SIZE..]).is_some()
+}
+
+/// Finds the total size of the block in the `BC` subfield of the extra field of its header
+fn block_size(mut extra: &[u8]) -> Option<usize> {
+    while extra.len() >= 4 {
+        let length = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + length)?;
+        if extra[..2] == *b"BC" && length == 2 {
+            return Some(u16::from_le_bytes([data[0], data[1]]) as usize + 1);
+        }
+        extra = &extra[4 + length..];
+    }
+    None
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Fills `buf`, returning `false` if the reader was already at its end
+fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Reads the block at `offset`, leaving its deflated data and footer in `buf`.
+/// Returns the size of the block, `None` at the end of the file.
+fn read_raw_block<R: Read>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    offset: u64,
+) -> io::Result<Option<usize>> {
+    let mut header = [0; FIXED_HEADER_SIZE];
+    if !read_exact_or_eof(reader, &mut header)? {
+        return Ok(None);
+    }
+    if header[..4] != [0x1F, 0x8B, 0x08, 0x04] {
+        return Err(invalid_data(format!(
+            "No BGZF block header at offset {offset}"
+        )));
+    }
+    let extra_size = u16::from_le_bytes([header[10], header[11]]) as usize;
+    buf.resize(extra_size, 0);
+    reader.read_exact(buf)?;
+    let size = block_size(buf)
+        .filter(|size| *size >= FIXED_HEADER_SIZE + extra_size + FOOTER_SIZE)
+        .ok_or_else(|| invalid_data(format!("No BGZF block size at offset {offset}")))?;
+    buf.resize(size - FIXED_HEADER_SIZE - extra_size, 0);
+    reader.read_exact(buf)?;
+    Ok(Some(size))
+}
+
+/// Reads the CRC32 and the size of the decompressed data at the end of a block
+fn read_footer(block: &[u8]) -> (u32, usize) {
+    let footer = &block[block.len() - FOOTER_SIZE..];
+    let crc = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]);
+    let data_size = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]) as usize;
+    (crc, data_size)
+}
+
+/// Decompresses a BGZF file block by block, keeping track of the virtual offset of what
+/// is read. With a seekable input, reading can resume from any virtual offset, e.g. one
+/// kept while reading the file before or from an index.
+///
+/// The reader must be at the start of the file (or of a block, but virtual offsets are
+/// then relative to it).
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufRead;
+/// use needletail::parser::bgzf::BgzfReader;
+///
+/// let mut reader = BgzfReader::new(File::open("reads.fq.gz").unwrap());
+/// let mut line = Vec::new();
+/// reader.read_until(b'\n', &mut line).unwrap();
+/// let second_line = reader.virtual_position();
+/// // ... read the rest of the file, then go back
+/// reader.seek_virtual(second_line).unwrap();
+/// ```
+pub struct BgzfReader<R: Read> {
+    inner: R,
+    inflater: Decompress,
+    compressed: Vec<u8>,
+    data: Vec<u8>,
+    // position of the next byte to read in `data`
+    pos: usize,
+    // offset of the current block and of the next one in the compressed file
+    block_offset: u64,
+    next_block_offset: u64,
+    index: Option<GziIndex>,
+}
+
+impl<R: Read> BgzfReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            inflater: Decompress::new(false),
+            compressed: Vec::new(),
+            data: Vec::with_capacity(MAX_BLOCK_SIZE),
+            pos: 0,
+            block_offset: 0,
+            next_block_offset: 0,
+            index: None,
+        }
+    }
+
+    /// Uses the `.gzi` index of the file to seek to decompressed offsets with `Seek`
+    pub fn with_index(mut self, index: GziIndex) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Virtual offset of the next byte to read. At the end of a block, it is the offset
+    /// of the start of the next one.
+    pub fn virtual_position(&self) -> u64 {
+        if self.pos == self.data.len() {
+            self.next_block_offset << 16
+        } else {
+            (self.block_offset << 16) | self.pos as u64
+        }
+    }
+
+    /// Decompresses the block at `next_block_offset`, returns `false` at the end of the file
+    fn read_block(&mut self) -> io::Result<bool> {
+        self.block_offset = self.next_block_offset;
+        self.data.clear();
+        self.pos = 0;
+
+        let offset = self.block_offset;
+        let Some(size) = read_raw_block(&mut self.inner, &mut self.compressed, offset)? else {
+            return Ok(false);
+        };
+        let (crc, data_size) = read_footer(&self.compressed);
+        let deflated = &self.compressed[..self.compressed.len() - FOOTER_SIZE];
+        if data_size > MAX_BLOCK_SIZE {
+            return Err(invalid_data(format!(
+                "BGZF block at offset {offset} is too big"
+            )));
+        }
+
+        self.inflater.reset(false);
+        self.data.reserve(data_size);
+        let status = self
+            .inflater
+            .decompress_vec(deflated, &mut self.data, FlushDecompress::Finish)
+            .map_err(|e| invalid_data(format!("BGZF block at offset {offset}: {e}")))?;
+        let mut actual_crc = Crc::new();
+        actual_crc.update(&self.data);
+        if status != Status::StreamEnd || self.data.len() != data_size || actual_crc.sum() != crc {
+            return Err(invalid_data(format!(
+                "BGZF block at offset {offset} is corrupted"
+            )));
+        }
+        self.next_block_offset = self.block_offset + size as u64;
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    /// Moves to the virtual offset `virtual_offset`, as returned by `virtual_position`
+    pub fn seek_virtual(&mut self, virtual_offset: u64) -> io::Result<()> {
+        let block_offset = virtual_offset >> 16;
+        let pos = (virtual_offset & 0xFFFF) as usize;
+        if block_offset != self.block_offset || self.data.is_empty() {
+            self.inner.seek(SeekFrom::Start(block_offset))?;
+            self.next_block_offset = block_offset;
+            self.read_block()?;
+        }
+        if pos > self.data.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Virtual offset {virtual_offset} is after the end of its block"),
+            ));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+}
+
+/// Seeking to offsets in the decompressed file, which needs the index of the file (see
+/// `with_index`). The decompressed size of the file isn't known so seeking from the end
+/// isn't supported.
+impl<R: Read + Seek> Seek for BgzfReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let index = self.index.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Seeking in a BGZF file needs its .gzi index",
+            )
+        })?;
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => index
+                .uncompressed_offset(self.block_offset)
+                .and_then(|start| (start + self.pos as u64).checked_add_signed(delta)),
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Can't seek from the end of a BGZF file",
+                ))
+            }
+        };
+        let offset = offset.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Invalid offset in a BGZF file")
+        })?;
+        let virtual_offset = index.virtual_offset(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Offset {offset} isn't in a block of the .gzi index"),
+            )
+        })?;
+        self.seek_virtual(virtual_offset)?;
+        Ok(offset)
+    }
+}
+
+/// The `.gzi` index of a BGZF file, as written by `bgzip -i`: the compressed and
+/// decompressed offsets of the start of each block
+///
+/// ```
+/// use std::io::{Cursor, Read, Seek, SeekFrom};
+/// use needletail::parser::bgzf::{BgzfReader, GziIndex};
+///
+/// # let fasta = std::fs::read("tests/data/28S.fasta").unwrap();
+/// # let mut bgzf = Vec::new();
+/// # for block in fasta.chunks(1000) {
+/// #     let mut encoder = flate2::GzBuilder::new()
+/// #         .extra(vec![b'B', b'C', 2, 0, 0, 0])
+/// #         .write(Vec::new(), flate2::Compression::default());
+/// #     std::io::Write::write_all(&mut encoder, block).unwrap();
+/// #     let mut gz = encoder.finish().unwrap();
+/// #     let size = (gz.len() - 1) as u16;
+/// #     gz[16..18].copy_from_slice(&size.to_le_bytes());
+/// #     bgzf.extend(gz);
+/// # }
+/// // `bgzf` is the content of a bgzip-compressed file
+/// let index = GziIndex::build(&bgzf[..]).unwrap();
+/// let mut reader = BgzfReader::new(Cursor::new(bgzf)).with_index(index);
+/// reader.seek(SeekFrom::Start(2500)).unwrap();
+/// let mut bases = [0; 10];
+/// reader.read_exact(&mut bases).unwrap();
+/// assert_eq!(bases, fasta[2500..2510]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GziIndex {
+    // (compressed, decompressed) offsets, starting with the first block at (0, 0)
+    blocks: Vec<(u64, u64)>,
+}
+
+impl Default for GziIndex {
+    fn default() -> Self {
+        Self {
+            blocks: vec![(0, 0)],
+        }
+    }
+}
+
+impl GziIndex {
+    /// Reads the `.gzi` file at `path`
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Self::from_reader(BufReader::new(open_file(path.as_ref())?))
+    }
+
+    /// Reads the content of a `.gzi` file
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, ParseError> {
+        let truncated = |e: io::Error| match e.kind() {
+            io::ErrorKind::UnexpectedEof => ParseError::new_invalid_index(
+                String::from("The .gzi index is truncated"),
+                ErrorPosition::default(),
+            ),
+            _ => e.into(),
+        };
+        let mut number = [0; 8];
+        reader.read_exact(&mut number).map_err(truncated)?;
+        let count = u64::from_le_bytes(number);
+        let mut index = Self::default();
+        for _ in 0..count {
+            reader.read_exact(&mut number).map_err(truncated)?;
+            let compressed = u64::from_le_bytes(number);
+            reader.read_exact(&mut number).map_err(truncated)?;
+            let uncompressed = u64::from_le_bytes(number);
+            let &(last_compressed, last_uncompressed) = index.blocks.last().unwrap();
+            if compressed <= last_compressed || uncompressed < last_uncompressed {
+                let msg = format!("The blocks of the .gzi index aren't in order at {compressed}");
+                return Err(ParseError::new_invalid_index(msg, ErrorPosition::default()));
+            }
+            index.blocks.push((compressed, uncompressed));
+        }
+        Ok(index)
+    }
+
+    /// Indexes a BGZF file by going through the headers of its blocks, without
+    /// decompressing them
+    pub fn build<R: Read>(mut reader: R) -> Result<Self, ParseError> {
+        let mut index = Self::default();
+        let mut buf = Vec::new();
+        let (mut compressed, mut uncompressed) = (0, 0);
+        while let Some(size) = read_raw_block(&mut reader, &mut buf, compressed)? {
+            if compressed > 0 {
+                index.blocks.push((compressed, uncompressed));
+            }
+            compressed += size as u64;
+            uncompressed += read_footer(&buf).1 as u64;
+        }
+        Ok(index)
+    }
+
+    /// Writes the index to a `.gzi` file at `path`
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(create_file(path.as_ref())?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the index in the `.gzi` format (the first block isn't in it)
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&(self.blocks.len() as u64 - 1).to_le_bytes())?;
+        for (compressed, uncompressed) in &self.blocks[1..] {
+            writer.write_all(&compressed.to_le_bytes())?;
+            writer.write_all(&uncompressed.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the virtual offset of the decompressed offset `offset`, `None` if it is
+    /// too far after the start of the last block to be in it
+    pub fn virtual_offset(&self, offset: u64) -> Option<u64> {
+        // the last block starting at or before `offset`, after any empty ones
+        let i = self.blocks.partition_point(|(_, start)| *start <= offset) - 1;
+        let (block, start) = self.blocks[i];
+        let pos = offset - start;
+        (pos <= 0xFFFF).then_some((block << 16) | pos)
+    }
+
+    /// Returns the decompressed offset of the start of the block at compressed offset
+    /// `block`, `None` if no block starts there
+    pub fn uncompressed_offset(&self, block: u64) -> Option<u64> {
+        let i = self
+            .blocks
+            .binary_search_by_key(&block, |(compressed, _)| *compressed)
+            .ok()?;
+        Some(self.blocks[i].1)
+    }
+
+    /// The compressed and decompressed offsets of the blocks, including the first one
+    pub fn blocks(&self) -> &[(u64, u64)] {
+        &self.blocks
+    }
+}
+
+impl<R: Read> BufRead for BgzfReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // skips empty blocks, like the one at the end of the file
+        while self.pos == self.data.len() {
+            if !self.read_block()? {
+                break;
+            }
+        }
+        Ok(&self.data[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.data.len());
+    }
+}
+
+impl<R: Read> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// Largest number of bytes of data `BgzfWriter` puts in a block, as `bgzip` does, so
+/// that the compressed block fits in 64KB even if the data doesn't compress
+const WRITE_BLOCK_SIZE: usize = 0xFF00;
+
+/// Compresses `data` (at most `MAX_BLOCK_SIZE` bytes) in a BGZF block appended to `out`
+pub(crate) fn write_block(data: &[u8], out: &mut Vec<u8>) {
+    use flate2::write::DeflateEncoder;
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+    // writing to a `Vec` can't fail
+    encoder.write_all(data).unwrap();
+    let deflated = encoder.finish().unwrap();
+    let size = (FIXED_HEADER_SIZE + 6 + deflated.len() + FOOTER_SIZE - 1) as u16;
+    out.extend_from_slice(&[
+        0x1F, 0x8B, 0x08, 0x04, 0, 0, 0, 0, 0, 0xFF, 6, 0, b'B', b'C', 2, 0,
+    ]);
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&deflated);
+    let mut crc = Crc::new();
+    crc.update(data);
+    out.extend_from_slice(&crc.sum().to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+}
+
+/// Compresses what is written to it as a BGZF file, ending with the empty block
+/// `bgzip` (and the BAM readers checking for truncated files) expect at the end.
+///
+/// Call [`finish`](BgzfWriter::finish) when you're done writing: the last block and
+/// the end of the file are also written when the writer is dropped, but any error is
+/// ignored then.
+///
+/// ```
+/// use std::io::{Read, Write};
+/// use needletail::parser::bgzf::{is_bgzf, BgzfReader, BgzfWriter};
+///
+/// let mut writer = BgzfWriter::new(Vec::new());
+/// writer.write_all(b">id1\nACGT\n").unwrap();
+/// let compressed = writer.finish().unwrap();
+/// assert!(is_bgzf(&compressed));
+///
+/// let mut data = Vec::new();
+/// BgzfReader::new(&compressed[..]).read_to_end(&mut data).unwrap();
+/// assert_eq!(data, b">id1\nACGT\n");
+/// ```
+pub struct BgzfWriter<W: Write> {
+    // only `None` once `finish` has been called
+    inner: Option<W>,
+    data: Vec<u8>,
+    block: Vec<u8>,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            data: Vec::with_capacity(WRITE_BLOCK_SIZE),
+            block: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("writer is only taken in finish")
+    }
+
+    /// Compresses the buffered data in a block, if there is any
+    fn write_data(&mut self) -> io::Result<()> {
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        self.block.clear();
+        write_block(&self.data, &mut self.block);
+        self.data.clear();
+        let inner = self.inner.as_mut().expect("writer is only taken in finish");
+        inner.write_all(&self.block)
+    }
+
+    /// Writes the last block and the end of the file, and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_end()?;
+        Ok(self.inner.take().expect("writer is only taken in finish"))
+    }
+
+    fn write_end(&mut self) -> io::Result<()> {
+        self.write_data()?;
+        self.block.clear();
+        write_block(b"", &mut self.block);
+        let inner = self.inner.as_mut().expect("writer is only taken in finish");
+        inner.write_all(&self.block)?;
+        inner.flush()
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(WRITE_BLOCK_SIZE - self.data.len());
+        self.data.extend_from_slice(&buf[..n]);
+        if self.data.len() == WRITE_BLOCK_SIZE {
+            self.write_data()?;
+        }
+        Ok(n)
+    }
+
+    /// Compresses the buffered data in a block even if it isn't full
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_data()?;
+        self.inner
+            .as_mut()
+            .expect("writer is only taken in finish")
+            .flush()
+    }
+}
+
+impl<W: Write> Drop for BgzfWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    fn fastq(n: usize) -> Vec<u8> {
+        (0..n)
+            .flat_map(|i| format!("@r{i}\nACGTACGT\n+\nIIIIIIII\n").into_bytes())
+            .collect()
+    }
+
+    fn bgzf(data: &[u8], block: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        for chunk in data.chunks(block) {
+            write_block(chunk, &mut out);
+        }
+        // the empty block bgzip ends files with
+        write_block(b"", &mut out);
+        out
+    }
+
+    #[test]
+    fn test_read() {
+        let data = fastq(500);
+        let compressed = bgzf(&data, 1000);
+        assert!(is_bgzf(&compressed));
+        assert!(!is_bgzf(b"@r1\nACGT\n+\nIIII\n"));
+
+        let mut decompressed = Vec::new();
+        let mut reader = BgzfReader::new(&compressed[..]);
+        reader.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, data);
+
+        // any gzip decoder can read it
+        let mut records = parse_fastx_reader(&compressed[..]).unwrap();
+        let mut n = 0;
+        while let Some(record) = records.next() {
+            record.unwrap();
+            n += 1;
+        }
+        assert_eq!(n, 500);
+
+        let mut corrupted = compressed.clone();
+        corrupted[30] ^= 0xFF;
+        let mut reader = BgzfReader::new(&corrupted[..]);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_writer() {
+        let data = fastq(5000);
+        let mut writer = BgzfWriter::new(Vec::new());
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let compressed = writer.finish().unwrap();
+        let eof = [
+            0x1F, 0x8B, 0x08, 0x04, 0, 0, 0, 0, 0, 0xFF, 6, 0, b'B', b'C', 2, 0, 0x1B, 0, 3, 0, 0,
+            0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert!(compressed.ends_with(&eof));
+
+        // full blocks, the last one and the end of file block
+        let index = GziIndex::build(&compressed[..]).unwrap();
+        assert_eq!(
+            index.blocks.len(),
+            data.len().div_ceil(WRITE_BLOCK_SIZE) + 1
+        );
+        let mut decompressed = Vec::new();
+        let mut reader = BgzfReader::new(&compressed[..]);
+        reader.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, data);
+
+        // an empty file is just the end of file block
+        assert_eq!(BgzfWriter::new(Vec::new()).finish().unwrap(), eof);
+    }
+
+    #[test]
+    fn test_seek_virtual() {
+        let compressed = bgzf(&fastq(500), 1000);
+        let mut reader = BgzfReader::new(Cursor::new(compressed));
+        let mut lines = Vec::new();
+        loop {
+            let position = reader.virtual_position();
+            let mut line = Vec::new();
+            if reader.read_until(b'\n', &mut line).unwrap() == 0 {
+                break;
+            }
+            lines.push((position, line));
+        }
+        assert_eq!(lines.len(), 2000);
+        for (position, line) in lines.iter().rev().step_by(7) {
+            reader.seek_virtual(*position).unwrap();
+            let mut read = Vec::new();
+            reader.read_until(b'\n', &mut read).unwrap();
+            assert_eq!(&read, line);
+        }
+        assert!(reader.seek_virtual(2000).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::ops::Range;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::record::SequenceRecord;
+use crate::parser::synthetic::RecordBuffer;
+use crate::parser::utils::{id_key, FastxReader, LineEnding, Position};
+
+/// The region to keep of each read, keyed by read id (up to the first whitespace)
+pub type Clips = HashMap<Vec<u8>, Range<usize>>;
+
+/// Reads the regions to keep from a BED file whose first column is the read id, then
+/// the 0-based start and exclusive end of the region. Other columns, empty lines and
+/// `#`, `track` or `browser` lines are ignored. The last region wins if a read is
+/// listed several times.
+pub fn read_bed_clips<R: BufRead>(reader: R) -> Result<Clips, ParseError> {
+    let mut clips = Clips::new();
+    for (i, line) in reader.split(b'\n').enumerate() {
+        let line = line?;
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        if line.is_empty()
+            || line.starts_with(b"#")
+            || line.starts_with(b"track")
+            || line.starts_with(b"browser")
+        {
+            continue;
+        }
+        let mut columns = line.split(|c| *c == b'\t');
+        let id = columns.next().unwrap_or_default();
+        let error = |msg: &str| {
+            ParseError::new_invalid_record(
+                format!("Invalid BED line: {msg}"),
+                ErrorPosition {
+                    line: i as u64 + 1,
+                    id: Some(String::from_utf8_lossy(id).into()),
+                },
+            )
+        };
+        let mut coordinate = |name: &str| -> Result<usize, ParseError> {
+            let column = columns
+                .next()
+                .ok_or_else(|| error(&format!("missing {name} column")))?;
+            std::str::from_utf8(column)
+                .ok()
+                .and_then(|c| c.trim().parse().ok())
+                .ok_or_else(|| error(&format!("{name} is not a number")))
+        };
+        let start = coordinate("start")?;
+        let end = coordinate("end")?;
+        if end < start {
+            return Err(error("end is before start"));
+        }
+        clips.insert(id.to_vec(), start..end);
+    }
+    Ok(clips)
+}
+
+/// A reader keeping only a region of each read, e.g. to remove primers from amplicon
+/// reads at known coordinates while parsing. Reads without a region are left as they
+/// are and regions going past the end of a read are cut at its end.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::{read_bed_clips, ClippingReader, FastxReader};
+///
+/// let bed = b"r1\t2\t6\n";
+/// let clips = read_bed_clips(&bed[..]).unwrap();
+/// let fastq = b"@r1 sample=a\nAAACGTTT\n+\n01234567\n@r2\nTT\n+\nII\n";
+/// let mut reader = ClippingReader::new(parse_fastx_reader(&fastq[..]).unwrap(), clips);
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.seq().as_ref(), b"ACGT");
+/// assert_eq!(record.qual(), Some(&b"2345"[..]));
+/// assert_eq!(reader.next().unwrap().unwrap().seq().as_ref(), b"TT");
+/// ```
+pub struct ClippingReader<R: FastxReader> {
+    reader: R,
+    clips: Clips,
+    record: RecordBuffer,
+    position: Position,
+}
+
+impl<R: FastxReader> ClippingReader<R> {
+    pub fn new(reader: R, clips: Clips) -> Self {
+        Self {
+            reader,
+            clips,
+            record: RecordBuffer::new(),
+            position: Position::new(0, 0),
+        }
+    }
+}
+
+impl<R: FastxReader> FastxReader for ClippingReader<R> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        let record = match self.reader.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+        let Some(range) = self.clips.get(id_key(record.id())) else {
+            return Some(Ok(record));
+        };
+        let seq = record.seq();
+        let end = range.end.min(seq.len());
+        let start = range.start.min(end);
+        self.record.set_with_separator(
+            record.id(),
+            &seq[start..end],
+            record
+                .qual()
+                .map(|q| &q[start.min(q.len())..end.min(q.len())]),
+            record.separator().unwrap_or_default(),
+        );
+        self.position = record.position().clone();
+        let line_ending = record.line_ending();
+        Some(Ok(self.record.record(&self.position, Some(line_ending))))
+    }
+
+    fn position(&self) -> &Position {
+        self.reader.position()
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.reader.line_ending()
+    }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        self.reader.has_mixed_line_endings()
+    }
+
+    fn can_skip_invalid_records(&self) -> bool {
+        self.reader.can_skip_invalid_records()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_read_bed_clips() {
+        let bed = b"track name=primers\n# comment\nr1\t2\t6\tprimer_1\t0\t+\r\n\nr2\t0\t100\n";
+        let clips = read_bed_clips(&bed[..]).unwrap();
+        assert_eq!(clips.len(), 2);
+        assert_eq!(clips[&b"r1"[..]], 2..6);
+
+        let err = read_bed_clips(&b"r1\t2\t6\nr2\t5\n"[..]).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        assert_eq!(err.position.line, 2);
+        assert!(read_bed_clips(&b"r1\t6\t2\n"[..]).is_err());
+        assert!(read_bed_clips(&b"r1\tA\t2\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_clipping_reader() {
+        let fasta = b">r1\nAAAC\nGTTT\n>r2\nACGT\n>r3\nACGT\n";
+        let clips = Clips::from([(b"r1".to_vec(), 2..6), (b"r2".to_vec(), 2..100)]);
+        let mut reader = ClippingReader::new(parse_fastx_reader(&fasta[..]).unwrap(), clips);
+        let mut seqs = Vec::new();
+        while let Some(record) = reader.next() {
+            seqs.push(record.unwrap().seq().to_vec());
+        }
+        assert_eq!(
+            seqs,
+            vec![b"ACGT".to_vec(), b"GT".to_vec(), b"ACGT".to_vec()]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::parser::record::{OwnedRecord, SequenceRecord};
+use crate::parser::utils::id_key;
+
+/// Changes to a record, applied without copying its parts until they are modified.
+/// Built with `SequenceRecord::edit` or `OwnedRecord::edit`.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+///
+/// let mut reader = parse_fastx_reader(&b"@read1 lane=1\nAACGTT\n+\n012345\n"[..]).unwrap();
+/// let record = reader.next().unwrap().unwrap();
+/// let edited = record
+///     .edit()
+///     .set_id(b"sample_read1")
+///     .append_description(b"trimmed=1")
+///     .slice(1..5)
+///     .mask(&[2..3])
+///     .finish();
+/// assert_eq!(edited.id, b"sample_read1 lane=1 trimmed=1");
+/// assert_eq!(edited.seq, b"ACNT");
+/// assert_eq!(edited.qual.unwrap(), b"1234");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordEdit<'a> {
+    id: Cow<'a, [u8]>,
+    seq: Cow<'a, [u8]>,
+    qual: Option<Cow<'a, [u8]>>,
+}
+
+impl<'a> RecordEdit<'a> {
+    fn new(id: &'a [u8], seq: Cow<'a, [u8]>, qual: Option<&'a [u8]>) -> Self {
+        Self {
+            id: Cow::Borrowed(id),
+            seq,
+            qual: qual.map(Cow::Borrowed),
+        }
+    }
+
+    /// Replaces the id, i.e. the header up to the first whitespace. The description
+    /// after it is kept.
+    pub fn set_id(mut self, id: &[u8]) -> Self {
+        let end = id_key(&self.id).len();
+        self.id.to_mut().splice(..end, id.iter().copied());
+        self
+    }
+
+    /// Adds `description` at the end of the header, after a space
+    pub fn append_description(mut self, description: &[u8]) -> Self {
+        let id = self.id.to_mut();
+        id.push(b' ');
+        id.extend_from_slice(description);
+        self
+    }
+
+    /// Keeps only the bases (and qualities) in `range`, cut to the length of the sequence
+    pub fn slice(mut self, range: Range<usize>) -> Self {
+        let end = range.end.min(self.seq.len());
+        let start = range.start.min(end);
+        slice_cow(&mut self.seq, start, end);
+        if let Some(qual) = self.qual.as_mut() {
+            slice_cow(qual, start, end);
+        }
+        self
+    }
+
+    /// Replaces the bases in `ranges` with `N`s, keeping their qualities. Ranges are
+    /// cut to the length of the sequence.
+    pub fn mask(mut self, ranges: &[Range<usize>]) -> Self {
+        let len = self.seq.len();
+        for range in ranges {
+            let end = range.end.min(len);
+            let start = range.start.min(end);
+            if start < end {
+                self.seq.to_mut()[start..end].fill(b'N');
+            }
+        }
+        self
+    }
+
+    /// Returns the edited record
+    pub fn finish(self) -> OwnedRecord {
+        OwnedRecord {
+            id: self.id.into_owned(),
+            seq: self.seq.into_owned(),
+            qual: self.qual.map(Cow::into_owned),
+        }
+    }
+}
+
+fn slice_cow(data: &mut Cow<[u8]>, start: usize, end: usize) {
+    match data {
+        Cow::Borrowed(slice) => *slice = &slice[start..end],
+        Cow::Owned(vec) => {
+            vec.truncate(end);
+            vec.drain(..start);
+        }
+    }
+}
+
+impl SequenceRecord<'_> {
+    /// Starts editing a copy of the record, see [`RecordEdit`]
+    pub fn edit(&self) -> RecordEdit<'_> {
+        RecordEdit::new(self.id(), self.seq(), self.qual())
+    }
+}
+
+impl OwnedRecord {
+    /// Starts editing a copy of the record, see [`RecordEdit`]
+    pub fn edit(&self) -> RecordEdit<'_> {
+        RecordEdit::new(&self.id, Cow::Borrowed(&self.seq), self.qual.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit() {
+        let record = OwnedRecord {
+            id: b"r1".to_vec(),
+            seq: b"ACGTACGT".to_vec(),
+            qual: None,
+        };
+        // nothing is copied until it's changed
+        let edit = record.edit().slice(2..100);
+        assert!(matches!(edit.seq, Cow::Borrowed(b"GTACGT")));
+        assert!(matches!(edit.id, Cow::Borrowed(_)));
+
+        let edited = edit.mask(&[0..1, 4..10]).slice(1..4).set_id(b"r2").finish();
+        assert_eq!(edited.id, b"r2");
+        assert_eq!(edited.seq, b"TAC");
+        assert_eq!(edited.qual, None);
+
+        assert_eq!(record.edit().slice(10..12).finish().seq, b"");
+        assert_eq!(record.edit().finish(), record);
+    }
+}
@@ -0,0 +1,352 @@
+//! `.fai` indexes of FASTA files, as written by `samtools faidx`: where each sequence
+//! starts in the file and how its lines are laid out, to read any region of it without
+//! going through the whole file.
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::indexed::open_indexable;
+use crate::parser::path::{create_file, open_file};
+use crate::parser::synthetic::LineReader;
+use crate::parser::utils::{id_key, Format, LineEnding};
+
+/// One line of a `.fai` index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiEntry {
+    /// Id of the record up to the first whitespace
+    pub name: String,
+    /// Number of bases
+    pub length: u64,
+    /// Byte offset of the first base in the file
+    pub offset: u64,
+    /// Number of bases on each line (but maybe the last one)
+    pub line_bases: u64,
+    /// Number of bytes of each line, including the line ending
+    pub line_width: u64,
+}
+
+impl FaiEntry {
+    /// Byte offset in the file of the base at `position` (0-based). Fails with an
+    /// `InvalidIndex` error if it doesn't fit in a `u64`.
+    pub fn offset_of(&self, position: u64) -> Result<u64, ParseError> {
+        if self.line_bases == 0 {
+            return Ok(self.offset);
+        }
+        (position / self.line_bases)
+            .checked_mul(self.line_width)
+            .and_then(|line_start| line_start.checked_add(position % self.line_bases))
+            .and_then(|offset| offset.checked_add(self.offset))
+            .ok_or_else(|| {
+                let msg = format!(
+                    "Offset of base {position} of '{}' is too large for a file",
+                    self.name
+                );
+                let position = ErrorPosition {
+                    line: 0,
+                    id: Some(self.name.clone()),
+                };
+                ParseError::new_invalid_index(msg, position)
+            })
+    }
+
+    /// The line ending of the sequence lines, `None` if they don't have one (a single
+    /// line at the end of the file)
+    pub fn line_ending(&self) -> Option<LineEnding> {
+        match self.line_width.checked_sub(self.line_bases) {
+            Some(1) => Some(LineEnding::Unix),
+            Some(2) => Some(LineEnding::Windows),
+            _ => None,
+        }
+    }
+}
+
+/// The entries of a `.fai` index, in the order of the file
+///
+/// ```
+/// use needletail::parser::FaiIndex;
+///
+/// let index = FaiIndex::build(&b">chr1 first\nACGTA\nCG\n>chr2\nAAAA\n"[..]).unwrap();
+/// let mut fai = Vec::new();
+/// index.write_to(&mut fai).unwrap();
+/// assert_eq!(fai, b"chr1\t7\t12\t5\t6\nchr2\t4\t27\t4\t5\n");
+/// assert_eq!(FaiIndex::from_reader(&fai[..]).unwrap(), index);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaiIndex {
+    entries: Vec<FaiEntry>,
+    names: HashMap<String, usize>,
+}
+
+impl FaiIndex {
+    /// Adds an entry, failing if its name is already in the index
+    fn push(&mut self, entry: FaiEntry, position: ErrorPosition) -> Result<(), ParseError> {
+        if self.names.contains_key(&entry.name) {
+            let msg = format!("Sequence name '{}' is used twice", entry.name);
+            return Err(ParseError::new_invalid_index(msg, position));
+        }
+        self.names.insert(entry.name.clone(), self.entries.len());
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Reads the `.fai` file at `path`
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Self::from_reader(BufReader::new(open_file(path.as_ref())?))
+    }
+
+    /// Reads the content of a `.fai` file. Extra columns (e.g. the quality offset of the
+    /// indexes of FASTQ files) are ignored.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, ParseError> {
+        let mut index = Self::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let position = ErrorPosition {
+                line: i as u64 + 1,
+                id: None,
+            };
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            let columns: Vec<_> = line.split('\t').collect();
+            let numbers: Option<Vec<u64>> = columns
+                .get(1..5)
+                .map(|numbers| numbers.iter().map(|n| n.parse().ok()).collect())
+                .unwrap_or_default();
+            let Some(&[length, offset, line_bases, line_width]) = numbers.as_deref() else {
+                let msg = String::from("Expected a name and 4 numbers separated by tabs");
+                return Err(ParseError::new_invalid_index(msg, position));
+            };
+            let entry = FaiEntry {
+                name: columns[0].to_string(),
+                length,
+                offset,
+                line_bases,
+                line_width,
+            };
+            if (line_bases == 0 && length > 0) || line_width < line_bases {
+                let msg = format!(
+                    "Invalid line layout for '{}': {line_bases} bases in lines of {line_width} bytes",
+                    entry.name
+                );
+                return Err(ParseError::new_invalid_index(msg, position));
+            }
+            index.push(entry, position)?;
+        }
+        Ok(index)
+    }
+
+    /// Indexes the FASTA file at `path`, uncompressed or compressed with `bgzip`, like
+    /// `samtools faidx` does. The offsets are the ones in the decompressed file.
+    /// See [`build`](FaiIndex::build) for the files that can be indexed.
+    ///
+    /// ```no_run
+    /// use needletail::parser::{append_extension, FaiIndex};
+    ///
+    /// let index = FaiIndex::build_from_fasta("genome.fa").unwrap();
+    /// index.write(append_extension("genome.fa", "fai")).unwrap();
+    /// ```
+    pub fn build_from_fasta<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Self::build(open_indexable(path.as_ref())?)
+    }
+
+    /// Indexes the content of a FASTA file. All the sequence lines of a record must have the same
+    /// length but the last one, which can be shorter, otherwise the regions of the
+    /// record couldn't be found from the index.
+    pub fn build<R: Read>(reader: R) -> Result<Self, ParseError> {
+        let mut index = Self::default();
+        let mut lines = LineReader::new(reader);
+        // the record being indexed, where its header is and whether it had a short line
+        let mut current: Option<(FaiEntry, ErrorPosition, bool)> = None;
+        while let Some(line) = lines.next_line()? {
+            let width = line.content.len() as u64
+                + line.line_ending.map_or(0, |l| l.as_bytes().len() as u64);
+            let position = ErrorPosition {
+                line: line.position.line,
+                id: current.as_ref().map(|(entry, _, _)| entry.name.clone()),
+            };
+            if let Some(header) = line.content.strip_prefix(b">") {
+                if let Some((entry, position, _)) = current.take() {
+                    index.push(entry, position)?;
+                }
+                let name = id_key(header);
+                let entry = FaiEntry {
+                    name: String::from_utf8_lossy(name).into(),
+                    length: 0,
+                    offset: line.position.byte + width,
+                    line_bases: 0,
+                    line_width: 0,
+                };
+                let position = ErrorPosition {
+                    line: line.position.line,
+                    id: Some(entry.name.clone()),
+                };
+                current = Some((entry, position, false));
+                continue;
+            }
+
+            let Some((entry, _, had_short_line)) = current.as_mut() else {
+                let first = line.content.first().copied().unwrap_or(b'\n');
+                return Err(ParseError::new_invalid_start(
+                    first,
+                    position,
+                    Format::Fasta,
+                ));
+            };
+            let bases = line
+                .content
+                .iter()
+                .rposition(|c| !c.is_ascii_whitespace())
+                .map_or(0, |i| i as u64 + 1);
+            if entry.line_width == 0 {
+                entry.line_bases = bases;
+                entry.line_width = width;
+            } else if bases > 0 && *had_short_line {
+                let msg =
+                    String::from("Sequence line after a shorter one, the file can't be indexed");
+                return Err(ParseError::new_invalid_record(msg, position));
+            } else if bases > entry.line_bases
+                || (bases == entry.line_bases
+                    && width != entry.line_width
+                    && line.line_ending.is_some())
+            {
+                let msg = format!(
+                    "Sequence line of {bases} bases in {width} bytes instead of {} in {}, the file can't be indexed",
+                    entry.line_bases, entry.line_width
+                );
+                return Err(ParseError::new_invalid_record(msg, position));
+            }
+            if bases < entry.line_bases {
+                *had_short_line = true;
+            }
+            entry.length += bases;
+        }
+        if let Some((entry, position, _)) = current {
+            index.push(entry, position)?;
+        }
+        Ok(index)
+    }
+
+    /// Writes the index to a `.fai` file at `path`, usually the path of the FASTA file
+    /// with `.fai` appended (see `append_extension`)
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(create_file(path.as_ref())?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the index in the `.fai` format
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for entry in &self.entries {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                entry.name, entry.length, entry.offset, entry.line_bases, entry.line_width
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the entry of the sequence called `name`
+    pub fn get(&self, name: &str) -> Option<&FaiEntry> {
+        self.names.get(name).map(|&i| &self.entries[i])
+    }
+
+    /// Returns the position of the sequence called `name` in the index
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    pub fn entries(&self) -> &[FaiEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+
+    #[test]
+    fn test_build() {
+        let index = FaiIndex::build(&b">a\r\nACG\r\nT\r\n\r\n>b desc\n>c\nAC  \nG\n"[..]).unwrap();
+        let expected = [("a", 4, 4, 3, 5), ("b", 0, 22, 0, 0), ("c", 3, 25, 2, 5)];
+        let entries: Vec<_> = index
+            .entries()
+            .iter()
+            .map(|e| {
+                let name = e.name.as_str();
+                (name, e.length, e.offset, e.line_bases, e.line_width)
+            })
+            .collect();
+        assert_eq!(entries, expected);
+        assert_eq!(
+            index.get("a").unwrap().line_ending(),
+            Some(LineEnding::Windows)
+        );
+        assert_eq!(index.get("c").unwrap().offset_of(2).unwrap(), 30);
+        let huge = FaiIndex::from_reader(&b"a\t18446744073709551615\t3\t1\t4\n"[..]).unwrap();
+        let err = huge.get("a").unwrap().offset_of(u64::MAX - 1).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidIndex);
+
+        // lines of different lengths
+        let err = FaiIndex::build(&b">a\nACG\nT\nACG\n"[..]).unwrap_err();
+        assert_eq!(
+            (err.kind, err.position.line),
+            (ParseErrorKind::InvalidRecord, 4)
+        );
+        let err = FaiIndex::build(&b">a\nACG\nACGT\n"[..]).unwrap_err();
+        assert_eq!(
+            (err.kind, err.position.line),
+            (ParseErrorKind::InvalidRecord, 3)
+        );
+        let err = FaiIndex::build(&b">a\nACG\r\nACG\n"[..]).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        let err = FaiIndex::build(&b">a\nA\n>a\nC\n"[..]).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidIndex);
+        let err = FaiIndex::build(&b"ACGT\n"[..]).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidStart);
+    }
+
+    #[test]
+    fn test_build_from_fasta() {
+        let dir = tempfile::tempdir().unwrap();
+        let fai = dir.path().join("28S.fasta.fai");
+        let index = FaiIndex::build_from_fasta("tests/data/28S.fasta").unwrap();
+        index.write(&fai).unwrap();
+        assert_eq!(FaiIndex::read(&fai).unwrap(), index);
+        assert_eq!(index.entries()[0].line_bases, 70);
+
+        let gz = dir.path().join("28S.fasta.gz");
+        std::fs::write(&gz, [0x1F, 0x8B, 0x08, 0x00]).unwrap();
+        let err = FaiIndex::build_from_fasta(&gz).unwrap_err();
+        assert!(err.msg.contains("bgzip"), "{}", err.msg);
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let index = FaiIndex::from_reader(&b"r1\t4\t4\t4\t5\t14\n\nr2\t0\t20\t0\t0\n"[..]).unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.position("r2"), Some(1));
+        for fai in [
+            &b"r1\t4\t4\t4\n"[..],
+            b"r1\t4\tx\t4\t5\n",
+            b"r1\t4\t4\t0\t0\n",
+        ] {
+            let err = FaiIndex::from_reader(fai).unwrap_err();
+            assert_eq!(
+                (err.kind, err.position.line),
+                (ParseErrorKind::InvalidIndex, 1)
+            );
+        }
+    }
+}
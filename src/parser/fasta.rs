@@ -1,12 +1,13 @@
 //! The vast majority of the code is taken from https://github.com/markschl/seq_io/blob/master/src/fasta.rs
 
 use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::path::open_file;
 use crate::parser::record::SequenceRecord;
 use crate::parser::utils::{
-    fill_buf, find_line_ending, grow_to, trim_cr, FastxReader, Format, LineEnding, Position,
-    BUFSIZE,
+    fill_buf, find_line_ending, grow_to, invalid_record, trim_cr, FastxReader, Format, LineEnding,
+    Position, BUFSIZE,
 };
-use memchr::{memchr2, Memchr};
+use memchr::Memchr;
 use std::borrow::Cow;
 use std::fs::File;
 use std::io;
@@ -26,18 +27,18 @@ pub struct BufferPosition {
 
 impl BufferPosition {
     #[inline]
-    fn is_new(&self) -> bool {
+    pub(crate) fn is_new(&self) -> bool {
         self.seq_pos.is_empty()
     }
 
     #[inline]
-    fn reset(&mut self, start: usize) {
+    pub(crate) fn reset(&mut self, start: usize) {
         self.seq_pos.clear();
         self.start = start;
     }
 
     #[inline]
-    fn find_line_ending(&self, buffer: &[u8]) -> Option<LineEnding> {
+    pub(crate) fn find_line_ending(&self, buffer: &[u8]) -> Option<LineEnding> {
         find_line_ending(self.all(buffer))
     }
 
@@ -64,36 +65,16 @@ impl BufferPosition {
 
     #[inline]
     pub(crate) fn seq<'a>(&self, buffer: &'a [u8]) -> Cow<'a, [u8]> {
-        // TODO: make that DRY
-        let seq = if self.seq_pos.len() > 1 {
-            let start = *self.seq_pos.first().unwrap() + 1;
-            let end = *self.seq_pos.last().unwrap();
-            trim_cr(&buffer[start..end])
-        } else {
-            b""
-        };
-
-        // first part is a fast check to see if we need to do any allocations
-        let mut i;
-        match memchr2(b'\r', b'\n', seq) {
-            Some(break_loc) => i = break_loc,
-            None => return seq.into(),
+        // a single sequence line (or none at all) doesn't need any copy
+        if self.seq_pos.len() <= 2 {
+            return self.raw_seq(buffer).into();
         }
-        // we found a newline; create a new buffer and stripping out newlines
-        // and writing into it
-        let mut new_buf = Vec::with_capacity(seq.len() - 1);
-        new_buf.extend_from_slice(&seq[..i]);
-        while i < seq.len() {
-            match memchr2(b'\r', b'\n', &seq[i..]) {
-                None => {
-                    new_buf.extend_from_slice(&seq[i..]);
-                    break;
-                }
-                Some(match_pos) => {
-                    new_buf.extend_from_slice(&seq[i..i + match_pos]);
-                    i += match_pos + 1;
-                }
-            }
+
+        // We already know where every line ends so we can copy the lines as whole
+        // blocks instead of scanning the sequence again for line endings
+        let mut new_buf = Vec::with_capacity(*self.seq_pos.last().unwrap() - self.seq_pos[0]);
+        for bounds in self.seq_pos.windows(2) {
+            new_buf.extend_from_slice(trim_cr(&buffer[bounds[0] + 1..bounds[1]]));
         }
         new_buf.into()
     }
@@ -118,6 +99,7 @@ pub struct Reader<R: io::Read> {
     position: Position,
     finished: bool,
     line_ending: Option<LineEnding>,
+    mixed_line_endings: bool,
 }
 
 impl<R> Reader<R>
@@ -156,6 +138,7 @@ where
             search_pos: 0,
             finished: false,
             line_ending: None,
+            mixed_line_endings: false,
         }
     }
 }
@@ -174,7 +157,7 @@ impl Reader<File> {
     /// ```
     #[inline]
     pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        File::open(path).map(Self::new)
+        open_file(path.as_ref()).map(Self::new)
     }
 }
 
@@ -271,6 +254,7 @@ where
         let cap = self.buf_reader.capacity();
         let new_size = grow_to(cap);
         let additional = new_size - cap;
+        trace_event!(debug, from = cap, to = new_size, "growing the FASTA buffer");
         self.buf_reader.reserve(additional);
     }
 
@@ -279,6 +263,11 @@ where
         let consumed = self.buf_pos.start;
         self.buf_reader.consume(consumed);
         self.buf_reader.make_room();
+        trace_event!(
+            trace,
+            consumed,
+            "moved the incomplete record to the buffer start"
+        );
         self.buf_pos.start = 0;
         self.search_pos -= consumed;
         for s in &mut self.buf_pos.seq_pos {
@@ -288,7 +277,7 @@ where
 }
 
 impl<R: io::Read + Send> FastxReader for Reader<R> {
-    fn next(&mut self) -> Option<Result<SequenceRecord, ParseError>> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
         if self.finished {
             return None;
         }
@@ -313,14 +302,14 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
                 self.buf_pos.start = 0;
                 self.search_pos = 1;
             } else {
-                return Some(Err(ParseError::new_invalid_start(
+                return Some(Err(invalid_record(ParseError::new_invalid_start(
                     self.get_buf()[0],
                     ErrorPosition {
                         line: self.position.line,
                         id: None,
                     },
                     Format::Fasta,
-                )));
+                ))));
             }
         }
 
@@ -346,23 +335,26 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
         }
 
         if self.buf_pos.seq_pos.is_empty() {
-            return Some(Err(ParseError::new_unexpected_end(
+            return Some(Err(invalid_record(ParseError::new_unexpected_end(
                 ErrorPosition {
                     line: self.position.line,
                     id: None,
                 },
                 Format::Fasta,
-            )));
+            ))));
         }
 
+        let record_line_ending = self.buf_pos.find_line_ending(self.get_buf());
         if self.line_ending.is_none() {
-            self.line_ending = self.buf_pos.find_line_ending(self.get_buf());
+            self.line_ending = record_line_ending;
+        } else if record_line_ending.is_some() && record_line_ending != self.line_ending {
+            self.mixed_line_endings = true;
         }
         Some(Ok(SequenceRecord::new_fasta(
             self.get_buf(),
             &self.buf_pos,
             &self.position,
-            self.line_ending,
+            record_line_ending.or(self.line_ending),
         )))
     }
 
@@ -373,6 +365,10 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
     fn line_ending(&self) -> Option<LineEnding> {
         self.line_ending
     }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
 }
 
 #[cfg(test)]
@@ -381,6 +377,7 @@ mod tests {
 
     use super::*;
     use crate::errors::ParseErrorKind;
+    use crate::Sequence;
 
     fn seq(s: &[u8]) -> Cursor<&[u8]> {
         Cursor::new(s)
@@ -413,6 +410,7 @@ mod tests {
         let r = rec.unwrap();
         assert_eq!(r.id(), b"test");
         assert_eq!(r.raw_seq(), b"ACGT\nACGT");
+        assert_eq!(r.seq().as_ref(), b"ACGTACGT");
         assert_eq!(r.num_bases(), 8);
         assert_eq!(reader.line_ending().unwrap(), LineEnding::Unix);
         let rec = reader.next().unwrap();
@@ -440,11 +438,37 @@ mod tests {
         let r = rec.unwrap();
         assert_eq!(r.id(), b"test2");
         assert_eq!(r.raw_seq(), b"TGCA\r\nTG");
+        assert_eq!(r.seq().as_ref(), b"TGCATG");
         assert_eq!(r.num_bases(), 6);
         assert_eq!(r.start_line_number(), 4);
         assert!(reader.next().is_none());
     }
 
+    #[test]
+    fn test_seq_strips_line_endings() {
+        let mut reader = Reader::new(seq(b">a\nAC\r\n\r\nGT\nT\n\n>b\nACGT\n>c\n>d\nA\nC"));
+        let r = reader.next().unwrap().unwrap();
+        assert_eq!(r.seq().as_ref(), b"ACGTT");
+        assert_eq!(r.strip_returns().as_ref(), b"ACGTT");
+        let r = reader.next().unwrap().unwrap();
+        assert!(matches!(r.seq(), Cow::Borrowed(b"ACGT")));
+        let r = reader.next().unwrap().unwrap();
+        assert_eq!(r.seq().as_ref(), b"");
+        let r = reader.next().unwrap().unwrap();
+        assert_eq!(r.seq().as_ref(), b"AC");
+    }
+
+    #[test]
+    fn test_mixed_line_endings() {
+        let mut reader = Reader::new(seq(b">a\r\nAC\r\nGT\r\n>b\nAC\nGT\n"));
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.line_ending(), LineEnding::Windows);
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.line_ending(), LineEnding::Unix);
+        assert_eq!(reader.line_ending(), Some(LineEnding::Windows));
+        assert!(reader.has_mixed_line_endings());
+    }
+
     #[test]
     fn test_premature_ending() {
         let mut reader = Reader::new(seq(b">test\nAGCT\n>test2"));
@@ -0,0 +1,240 @@
+//! Reading a FASTA file along with its `.qual` file, which holds the qualities of the
+//! bases as numbers (e.g. from 454 or older Sanger pipelines), as FASTQ records.
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::parse_fastx_file;
+use crate::parser::record::SequenceRecord;
+use crate::parser::synthetic::RecordBuffer;
+use crate::parser::utils::{invalid_record, FastxReader, Format, LineEnding, Position};
+
+/// Highest Phred score that can be written as a Phred+33 character
+const MAX_SCORE: u8 = 93;
+
+/// Parser combining a FASTA file and its QUAL file into FASTQ records. The QUAL file
+/// looks like a FASTA file with the same records in the same order but with the Phred
+/// scores of the bases, separated by whitespace, instead of the bases. The scores are
+/// converted to Phred+33 characters.
+///
+/// Returns an error of kind `UnpairedRecords` if one file has more records than the
+/// other, `InvalidRecord` if the ids of the records differ or a score isn't a number
+/// between 0 and 93 and `UnequalLengths` if a record doesn't have a score per base.
+///
+/// ```
+/// use needletail::parser::{FastaQualReader, FastxReader};
+/// use needletail::parse_fastx_reader;
+///
+/// let fasta = parse_fastx_reader(&b">r1 desc\nACGT\nAC\n"[..]).unwrap();
+/// let qual = parse_fastx_reader(&b">r1 desc\n40 40 30\n20 0 93\n"[..]).unwrap();
+/// let mut reader = FastaQualReader::new(fasta, qual);
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.id(), b"r1 desc");
+/// assert_eq!(record.seq().as_ref(), b"ACGTAC");
+/// assert_eq!(record.qual(), Some(&b"II?5!~"[..]));
+/// assert!(reader.next().is_none());
+/// ```
+pub struct FastaQualReader {
+    fasta: Box<dyn FastxReader>,
+    qual: Box<dyn FastxReader>,
+    record: RecordBuffer,
+    scores: Vec<u8>,
+    position: Position,
+    line_ending: Option<LineEnding>,
+    finished: bool,
+}
+
+impl FastaQualReader {
+    pub fn new(fasta: Box<dyn FastxReader>, qual: Box<dyn FastxReader>) -> Self {
+        Self {
+            fasta,
+            qual,
+            record: RecordBuffer::new(),
+            scores: Vec::new(),
+            position: Position::new(0, 0),
+            line_ending: None,
+            finished: false,
+        }
+    }
+
+    /// Reads the next pair of records into the record buffer
+    fn read_record(&mut self) -> Result<bool, ParseError> {
+        let (fasta, qual) = match (self.fasta.next(), self.qual.next()) {
+            (None, None) => return Ok(false),
+            (Some(fasta), Some(qual)) => (fasta?, qual?),
+            (Some(r), None) | (None, Some(r)) => {
+                let record = r?;
+                return Err(ParseError::new_unpaired_records(
+                    record_position(&record),
+                    Format::Fasta,
+                ));
+            }
+        };
+        if fasta.id() != qual.id() {
+            let msg = format!(
+                "The QUAL record '{}' doesn't match the FASTA record '{}'",
+                String::from_utf8_lossy(qual.id()),
+                String::from_utf8_lossy(fasta.id())
+            );
+            return Err(ParseError::new_invalid_record(msg, record_position(&qual)));
+        }
+
+        self.scores.clear();
+        // the line endings are kept since they separate scores too
+        for score in qual
+            .raw_seq()
+            .split(|c| c.is_ascii_whitespace())
+            .filter(|s| !s.is_empty())
+        {
+            match std::str::from_utf8(score)
+                .ok()
+                .and_then(|s| s.parse::<u8>().ok())
+            {
+                Some(score) if score <= MAX_SCORE => self.scores.push(score + 33),
+                _ => {
+                    let msg = format!(
+                        "Invalid quality score '{}': expected a number between 0 and {MAX_SCORE}",
+                        String::from_utf8_lossy(score)
+                    );
+                    return Err(ParseError::new_invalid_record(msg, record_position(&qual)));
+                }
+            }
+        }
+        let seq = fasta.seq();
+        if seq.len() != self.scores.len() {
+            return Err(ParseError::new_unequal_length(
+                seq.len(),
+                self.scores.len(),
+                record_position(&fasta),
+            ));
+        }
+
+        self.record.set(fasta.id(), &seq, Some(&self.scores));
+        self.position = fasta.position().clone();
+        self.line_ending = self.fasta.line_ending();
+        Ok(true)
+    }
+}
+
+fn record_position(record: &SequenceRecord) -> ErrorPosition {
+    ErrorPosition {
+        line: record.start_line_number(),
+        id: Some(String::from_utf8_lossy(record.id()).into()),
+    }
+}
+
+impl FastxReader for FastaQualReader {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        if self.finished {
+            return None;
+        }
+        match self.read_record() {
+            Ok(true) => Some(Ok(self.record.record(&self.position, self.line_ending))),
+            Ok(false) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                // the FASTA/FASTQ parsers can't carry on after an error
+                self.finished = true;
+                Some(Err(invalid_record(e)))
+            }
+        }
+    }
+
+    fn position(&self) -> &Position {
+        &self.position
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.line_ending
+    }
+}
+
+/// Reads the FASTA file at `fasta` along with its QUAL file at `qual` as FASTQ
+/// records, see [`FastaQualReader`]. Both files can be compressed.
+///
+/// ```no_run
+/// use needletail::parser::parse_fasta_qual_files;
+///
+/// let mut reader = parse_fasta_qual_files("reads.fna", "reads.qual").unwrap();
+/// while let Some(record) = reader.next() {
+///     let record = record.unwrap();
+///     assert_eq!(record.qual().unwrap().len(), record.num_bases());
+/// }
+/// ```
+pub fn parse_fasta_qual_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    fasta: P,
+    qual: Q,
+) -> Result<Box<dyn FastxReader>, ParseError> {
+    let fasta = parse_fastx_file(fasta)?;
+    let qual = parse_fastx_file(qual)?;
+    Ok(Box::new(FastaQualReader::new(fasta, qual)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    fn read_all(fasta: &'static [u8], qual: &'static [u8]) -> Vec<Result<Vec<u8>, ParseErrorKind>> {
+        let mut reader = FastaQualReader::new(
+            parse_fastx_reader(fasta).unwrap(),
+            parse_fastx_reader(qual).unwrap(),
+        );
+        let mut records = Vec::new();
+        while let Some(record) = reader.next() {
+            records.push(
+                record
+                    .map(|r| r.qual().unwrap().to_vec())
+                    .map_err(|e| e.kind),
+            );
+        }
+        records
+    }
+
+    #[test]
+    fn test_fasta_qual() {
+        assert_eq!(
+            read_all(b">a\nAC\n>b\nG\r\nT\r\n", b">a\n10\n20\n>b\n  30  40 \r\n"),
+            [Ok(b"+5".to_vec()), Ok(b"?I".to_vec())]
+        );
+        assert_eq!(
+            read_all(b">a\nAC\n>b\nGT\n", b">a\n10 20\n"),
+            [Ok(b"+5".to_vec()), Err(ParseErrorKind::UnpairedRecords)]
+        );
+        assert_eq!(
+            read_all(b">a\nAC\n", b">b\n10 20\n"),
+            [Err(ParseErrorKind::InvalidRecord)]
+        );
+        assert_eq!(
+            read_all(b">a\nAC\n", b">a\n10 94\n"),
+            [Err(ParseErrorKind::InvalidRecord)]
+        );
+        assert_eq!(
+            read_all(b">a\nAC\n", b">a\n10 -1\n"),
+            [Err(ParseErrorKind::InvalidRecord)]
+        );
+        assert_eq!(
+            read_all(b">a\nACG\n", b">a\n10 20\n"),
+            [Err(ParseErrorKind::UnequalLengths)]
+        );
+    }
+
+    #[test]
+    fn test_parse_fasta_qual_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (fasta, qual) = (dir.path().join("reads.fna"), dir.path().join("reads.qual"));
+        std::fs::write(&fasta, b">r1\nACGT\n").unwrap();
+        std::fs::write(&qual, b">r1\n40 30 20 10\n").unwrap();
+        let mut reader = parse_fasta_qual_files(&fasta, &qual).unwrap();
+        let mut out = Vec::new();
+        reader
+            .next()
+            .unwrap()
+            .unwrap()
+            .write(&mut out, None)
+            .unwrap();
+        assert_eq!(out, b"@r1\nACGT\n+\nI?5+\n");
+    }
+}
@@ -5,10 +5,11 @@ use std::io::{self, BufRead};
 use std::path::Path;
 
 use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::path::open_file;
 use crate::parser::record::SequenceRecord;
 use crate::parser::utils::{
-    fill_buf, find_line_ending, grow_to, trim_cr, FastxReader, Format, LineEnding, Position,
-    BUFSIZE,
+    fill_buf, find_line_ending, grow_to, id_key, invalid_record, trim_cr, FastxReader, Format,
+    LineEnding, Position, BUFSIZE,
 };
 use memchr::memchr;
 
@@ -43,6 +44,11 @@ impl BufferPosition {
         trim_cr(&buffer[self.seq..self.sep - 1])
     }
 
+    #[inline]
+    pub(crate) fn separator<'a>(&'a self, buffer: &'a [u8]) -> &'a [u8] {
+        trim_cr(&buffer[self.sep + 1..self.qual - 1])
+    }
+
     #[inline]
     pub(crate) fn qual<'a>(&'a self, buffer: &'a [u8]) -> &'a [u8] {
         trim_cr(&buffer[self.qual..self.end])
@@ -72,6 +78,25 @@ enum SearchPosition {
     Quality,
 }
 
+/// What `FastqReader` does with a separator line repeating an id other than the
+/// one of the record (`@read1` then `+read2`), which can be a sign of swapped lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SeparatorIdCheck {
+    /// Don't look at the separator line
+    #[default]
+    Ignore,
+    /// Count the mismatches, see `FastqReader::separator_id_mismatches`
+    Warn,
+    /// Return an error of kind `InvalidSeparator`
+    Error,
+}
+
+/// Whether the separator line is empty or repeats the id, either the whole header or
+/// only up to the first whitespace
+fn separator_matches_id(sep: &[u8], id: &[u8]) -> bool {
+    sep.is_empty() || sep == id || id_key(id) == sep
+}
+
 /// Parser for FASTQ files.
 /// Only use this directly if you know your file is FASTQ and that it is not compressed as
 /// it does not handle decompression.
@@ -83,6 +108,9 @@ pub struct Reader<R: io::Read> {
     position: Position,
     finished: bool,
     line_ending: Option<LineEnding>,
+    mixed_line_endings: bool,
+    separator_id_check: SeparatorIdCheck,
+    separator_id_mismatches: u64,
 }
 
 impl<R> Reader<R>
@@ -116,8 +144,42 @@ where
             position: Position::new(1, 0),
             finished: false,
             line_ending: None,
+            mixed_line_endings: false,
+            separator_id_check: SeparatorIdCheck::Ignore,
+            separator_id_mismatches: 0,
         }
     }
+
+    /// Sets what to do with separator lines repeating another id than the one of their
+    /// record. They are not checked by default.
+    ///
+    /// ```
+    /// use needletail::errors::ParseErrorKind;
+    /// use needletail::parser::{FastqReader, FastxReader, SeparatorIdCheck};
+    ///
+    /// let fastq = b"@r1\nACGT\n+r1\nIIII\n@r2\nACGT\n+r3\nIIII\n";
+    /// let mut reader = FastqReader::new(&fastq[..]).with_separator_id_check(SeparatorIdCheck::Warn);
+    /// while let Some(record) = reader.next() {
+    ///     record.unwrap();
+    /// }
+    /// assert_eq!(reader.separator_id_mismatches(), 1);
+    ///
+    /// let mut reader = FastqReader::new(&fastq[..]).with_separator_id_check(SeparatorIdCheck::Error);
+    /// assert!(reader.next().unwrap().is_ok());
+    /// let err = reader.next().unwrap().unwrap_err();
+    /// assert_eq!(err.kind, ParseErrorKind::InvalidSeparator);
+    /// assert_eq!(err.position.line, 7);
+    /// ```
+    pub fn with_separator_id_check(mut self, check: SeparatorIdCheck) -> Self {
+        self.separator_id_check = check;
+        self
+    }
+
+    /// Number of records seen so far whose separator line repeats another id, when
+    /// checking them with `SeparatorIdCheck::Warn`
+    pub fn separator_id_mismatches(&self) -> u64 {
+        self.separator_id_mismatches
+    }
 }
 
 impl Reader<File> {
@@ -133,7 +195,7 @@ impl Reader<File> {
     /// // (... do something with the reader)
     /// ```
     pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        File::open(path).map(Self::new)
+        open_file(path.as_ref()).map(Self::new)
     }
 }
 
@@ -241,20 +303,39 @@ where
         let start_byte = self.get_buf()[self.buf_pos.start];
         if start_byte != b'@' {
             self.finished = true;
-            return Err(ParseError::new_invalid_start(
+            return Err(invalid_record(ParseError::new_invalid_start(
                 start_byte,
                 self.get_error_pos(0, false),
                 Format::Fastq,
-            ));
+            )));
         }
 
         let sep_byte = self.get_buf()[self.buf_pos.sep];
         if sep_byte != b'+' {
             self.finished = true;
-            return Err(ParseError::new_invalid_separator(
+            return Err(invalid_record(ParseError::new_invalid_separator(
                 sep_byte,
                 self.get_error_pos(2, true),
-            ));
+            )));
+        }
+
+        if self.separator_id_check != SeparatorIdCheck::Ignore {
+            let buf = self.get_buf();
+            let sep = self.buf_pos.separator(buf);
+            if !separator_matches_id(sep, self.buf_pos.id(buf)) {
+                if self.separator_id_check == SeparatorIdCheck::Error {
+                    let error =
+                        ParseError::new_separator_id_mismatch(sep, self.get_error_pos(2, true));
+                    self.finished = true;
+                    return Err(invalid_record(error));
+                }
+                trace_event!(
+                    warn,
+                    line = self.position.line + 2,
+                    "separator line repeats another id than its record"
+                );
+                self.separator_id_mismatches += 1;
+            }
         }
 
         let buf = self.get_buf();
@@ -275,11 +356,11 @@ where
 
         if seq_len != qual_len {
             self.finished = true;
-            return Err(ParseError::new_unequal_length(
+            return Err(invalid_record(ParseError::new_unequal_length(
                 seq_len,
                 qual_len,
                 self.get_error_pos(0, true),
-            ));
+            )));
         }
         Ok(())
     }
@@ -349,10 +430,10 @@ where
             return Ok(false);
         }
 
-        Err(ParseError::new_unexpected_end(
+        Err(invalid_record(ParseError::new_unexpected_end(
             self.get_error_pos(self.search_pos as u64, self.search_pos > SearchPosition::Id),
             Format::Fastq,
-        ))
+        )))
     }
 
     // Grow the internal buffer. Used if the original buffer is not big
@@ -361,6 +442,7 @@ where
         let cap = self.buf_reader.capacity();
         let new_size = grow_to(cap);
         let additional = new_size - cap;
+        trace_event!(debug, from = cap, to = new_size, "growing the FASTQ buffer");
         self.buf_reader.reserve(additional);
     }
 
@@ -369,6 +451,11 @@ where
         let consumed = self.buf_pos.start;
         self.buf_reader.consume(consumed);
         self.buf_reader.make_room();
+        trace_event!(
+            trace,
+            consumed,
+            "moved the incomplete record to the buffer start"
+        );
 
         self.buf_pos.start = 0;
 
@@ -385,7 +472,7 @@ where
 }
 
 impl<R: io::Read + Send> FastxReader for Reader<R> {
-    fn next(&mut self) -> Option<Result<SequenceRecord, ParseError>> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
         // No more records to read
         if self.finished {
             return None;
@@ -436,15 +523,18 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
                 return None;
             }
         }
+        let record_line_ending = self.buf_pos.find_line_ending(self.get_buf());
         if self.line_ending.is_none() {
-            self.line_ending = self.buf_pos.find_line_ending(self.get_buf());
+            self.line_ending = record_line_ending;
+        } else if record_line_ending.is_some() && record_line_ending != self.line_ending {
+            self.mixed_line_endings = true;
         }
         // We got one!
         Some(Ok(SequenceRecord::new_fastq(
             self.get_buf(),
             &self.buf_pos,
             &self.position,
-            self.line_ending,
+            record_line_ending.or(self.line_ending),
         )))
     }
 
@@ -455,13 +545,17 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
     fn line_ending(&self) -> Option<LineEnding> {
         self.line_ending
     }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::io::Cursor;
 
-    use super::Reader;
+    use super::{separator_matches_id, Reader};
     use crate::errors::ParseErrorKind;
     use crate::parser::utils::LineEnding;
     use crate::FastxReader;
@@ -510,6 +604,22 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_mixed_line_endings() {
+        let mut reader = Reader::new(seq(b"@a\nA\n+\nI\n@b\r\nA\r\n+\r\nI\r\n@c\nA\n+\nI"));
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.line_ending(), LineEnding::Unix);
+        assert!(!reader.has_mixed_line_endings());
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.line_ending(), LineEnding::Windows);
+        assert_eq!(rec.qual().unwrap(), b"I");
+        assert!(reader.has_mixed_line_endings());
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.line_ending(), LineEnding::Unix);
+        assert_eq!(reader.line_ending(), Some(LineEnding::Unix));
+        assert!(reader.has_mixed_line_endings());
+    }
+
     #[test]
     fn test_eof_in_qual() {
         let mut reader = Reader::new(seq(b"@test\nACGT\n+\nIII"));
@@ -626,4 +736,24 @@ mod test {
         // It errors when it tries to validate the separator line that needs to start with `+`
         assert_eq!(e.kind, ParseErrorKind::InvalidSeparator);
     }
+
+    #[test]
+    fn test_raw_parts() {
+        let mut reader = Reader::new(&b"@r1 desc\r\nACGT\r\n+r1\r\nIIII"[..]);
+        let record = reader.next().unwrap().unwrap();
+        let parts = record.raw_parts();
+        assert_eq!(parts.id, b"r1 desc");
+        assert_eq!(parts.seq_lines.collect::<Vec<_>>(), [b"ACGT"]);
+        assert_eq!(parts.sep, Some(&b"r1"[..]));
+        assert_eq!(parts.qual, Some(&b"IIII"[..]));
+    }
+
+    #[test]
+    fn test_separator_matches_id() {
+        assert!(separator_matches_id(b"", b"r1 desc"));
+        assert!(separator_matches_id(b"r1 desc", b"r1 desc"));
+        assert!(separator_matches_id(b"r1", b"r1 desc"));
+        assert!(!separator_matches_id(b"r2", b"r1 desc"));
+        assert!(!separator_matches_id(b"r1 other", b"r1 desc"));
+    }
 }
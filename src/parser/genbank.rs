@@ -0,0 +1,245 @@
+//! Extracting the sequences of GenBank and EMBL flatfiles, ignoring their
+//! annotations and features.
+use std::io;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::record::SequenceRecord;
+use crate::parser::synthetic::{LineReader, RecordBuffer};
+use crate::parser::utils::{FastxReader, LineEnding, Position};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Looking for a `LOCUS` or `ID` line
+    Header,
+    /// Looking for the `ORIGIN` or `SQ` line
+    Annotations,
+    /// Reading sequence lines until `//`
+    Sequence,
+}
+
+/// Returns the id of a record if `line` starts one, e.g. `LOCUS       NC_001422  5386 bp`
+/// in GenBank or `ID   X56734; SV 1; linear;` in EMBL.
+fn parse_header(line: &[u8]) -> Option<&[u8]> {
+    let rest = line
+        .strip_prefix(b"LOCUS ")
+        .or_else(|| line.strip_prefix(b"ID "))?;
+    let id = rest.trim_ascii_start();
+    let end = id
+        .iter()
+        .position(|c| c.is_ascii_whitespace() || *c == b';')
+        .unwrap_or(id.len());
+    Some(&id[..end])
+}
+
+/// Parser yielding the sequences of GenBank and EMBL flatfiles (e.g. references
+/// downloaded from NCBI) as FASTA records. The id of a record is the name of the
+/// `LOCUS` (GenBank) or `ID` (EMBL) line and its sequence the content of the
+/// `ORIGIN` or `SQ` block, without the numbers and spaces. Everything else is ignored.
+///
+/// Files of both formats are handled, they are not detected by `parse_fastx_reader`.
+///
+/// ```
+/// use needletail::parser::{FastxReader, GenbankReader};
+///
+/// let genbank = b"LOCUS       SCU49845     12 bp    DNA             PLN       21-JUN-1999
+/// DEFINITION  Saccharomyces cerevisiae TCP1-beta gene.
+/// FEATURES             Location/Qualifiers
+///      source          1..12
+/// ORIGIN
+///         1 gatcctccat atac
+/// //
+/// ";
+/// let mut reader = GenbankReader::new(&genbank[..]);
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.id(), b"SCU49845");
+/// assert_eq!(record.seq().as_ref(), b"gatcctccatatac");
+/// assert!(reader.next().is_none());
+/// ```
+pub struct GenbankReader<R: io::Read> {
+    lines: LineReader<R>,
+    id: Vec<u8>,
+    seq: Vec<u8>,
+    record: RecordBuffer,
+    position: Position,
+    // of the first record
+    line_ending: Option<LineEnding>,
+    record_line_ending: Option<LineEnding>,
+    mixed_line_endings: bool,
+}
+
+impl<R: io::Read> GenbankReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: LineReader::new(reader),
+            id: Vec::new(),
+            seq: Vec::new(),
+            record: RecordBuffer::new(),
+            position: Position::new(0, 0),
+            line_ending: None,
+            record_line_ending: None,
+            mixed_line_endings: false,
+        }
+    }
+
+    /// Reads the next record into `self.id` and `self.seq`, returns whether there was one
+    fn read_record(&mut self) -> Result<bool, ParseError> {
+        let mut state = State::Header;
+        while let Some(line) = self.lines.next_line()? {
+            match state {
+                State::Header => {
+                    if line.content.trim_ascii().is_empty() {
+                        continue;
+                    }
+                    let id = parse_header(line.content).ok_or_else(|| {
+                        ParseError::new_invalid_record(
+                            String::from("Expected a LOCUS or ID line"),
+                            ErrorPosition {
+                                line: line.position.line,
+                                id: None,
+                            },
+                        )
+                    })?;
+                    self.id.clear();
+                    self.id.extend_from_slice(id);
+                    self.seq.clear();
+                    self.position = line.position.clone();
+                    self.record_line_ending = line.line_ending;
+                    state = State::Annotations;
+                }
+                State::Annotations => {
+                    if line.content.starts_with(b"ORIGIN") || line.content.starts_with(b"SQ ") {
+                        state = State::Sequence;
+                    } else if line.content.starts_with(b"//") {
+                        // a record without any sequence
+                        state = State::Header;
+                        break;
+                    }
+                }
+                State::Sequence => {
+                    if line.content.starts_with(b"//") {
+                        state = State::Header;
+                        break;
+                    }
+                    self.seq.extend(
+                        line.content
+                            .iter()
+                            .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_digit()),
+                    );
+                }
+            }
+        }
+
+        match state {
+            State::Header if self.position.line == 0 => Ok(false),
+            State::Header => {
+                if self.line_ending.is_none() {
+                    self.line_ending = self.record_line_ending;
+                } else if self.record_line_ending.is_some()
+                    && self.record_line_ending != self.line_ending
+                {
+                    self.mixed_line_endings = true;
+                }
+                Ok(true)
+            }
+            _ => Err(ParseError::new_invalid_record(
+                String::from("Missing '//' at the end of the record"),
+                ErrorPosition {
+                    line: self.position.line,
+                    id: Some(String::from_utf8_lossy(&self.id).into()),
+                },
+            )),
+        }
+    }
+}
+
+impl<R: io::Read + Send> FastxReader for GenbankReader<R> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        // reset so the end of the stream can be told apart from a record
+        self.position = Position::new(0, 0);
+        match self.read_record() {
+            Ok(true) => {
+                self.record.set(&self.id, &self.seq, None);
+                let line_ending = self.record_line_ending.or(self.line_ending);
+                Some(Ok(self.record.record(&self.position, line_ending)))
+            }
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn position(&self) -> &Position {
+        &self.position
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.line_ending
+    }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+
+    fn can_skip_invalid_records(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+
+    #[test]
+    fn test_embl() {
+        let embl = b"ID   X56734; SV 1; linear; mRNA; STD; PLN; 1859 BP.\r
+XX\r
+DE   Trifolium repens mRNA for non-cyanogenic beta-glucosidase\r
+SQ   Sequence 20 BP; 6 A; 4 C; 5 G; 5 T; 0 other;\r
+     aaacaaacca aatatggatt        20\r
+//\r
+ID   X56735; SV 1; linear; mRNA; STD; PLN; 4 BP.\r
+SQ   Sequence 4 BP;\r
+     acgt                          4\r
+//\r
+";
+        let mut reader = GenbankReader::new(&embl[..]);
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.id(), b"X56734");
+        assert_eq!(record.seq().as_ref(), b"aaacaaaccaaatatggatt");
+        assert_eq!(record.line_ending(), LineEnding::Windows);
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.id(), b"X56735");
+        assert_eq!(record.start_line_number(), 7);
+        assert_eq!(record.seq().as_ref(), b"acgt");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_mixed_line_endings() {
+        let embl = b"ID   A; SV 1; 4 BP.\r\nSQ   Sequence 4 BP;\r\n     acgt 4\r\n//\r\nID   B; SV 1; 4 BP.\nSQ   Sequence 4 BP;\n     acgt 4\n//\n";
+        let mut reader = GenbankReader::new(&embl[..]);
+        assert_eq!(
+            reader.next().unwrap().unwrap().line_ending(),
+            LineEnding::Windows
+        );
+        assert_eq!(
+            reader.next().unwrap().unwrap().line_ending(),
+            LineEnding::Unix
+        );
+        assert!(reader.has_mixed_line_endings());
+        assert_eq!(reader.line_ending(), Some(LineEnding::Windows));
+    }
+
+    #[test]
+    fn test_genbank_errors() {
+        let mut reader = GenbankReader::new(&b"LOCUS       A  4 bp\nORIGIN\n 1 acgt\n"[..]);
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        assert_eq!(err.position.id.as_deref(), Some("A"));
+
+        let mut reader = GenbankReader::new(&b">id\nACGT\n"[..]);
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        assert_eq!(err.position.line, 1);
+    }
+}
@@ -0,0 +1,360 @@
+//! Random access to the sequences of a FASTA file through its `.fai` index
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+#[cfg(feature = "flate2")]
+use crate::parser::bgzf::{is_bgzf, BgzfReader, GziIndex};
+use crate::parser::fai::FaiIndex;
+use crate::parser::path::{append_extension, open_file};
+use crate::parser::record::{OwnedRecord, SequenceRecord};
+use crate::parser::synthetic::RecordBuffer;
+use crate::parser::utils::{LineEnding, Position};
+
+/// A FASTA file opened by `IndexedFastaReader::from_path`
+pub enum IndexedFile {
+    Plain(BufReader<File>),
+    /// Compressed with `bgzip`, read with its `.gzi` index
+    #[cfg(feature = "flate2")]
+    Bgzf(BgzfReader<BufReader<File>>),
+}
+
+impl Read for IndexedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(reader) => reader.read(buf),
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for IndexedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Plain(reader) => reader.seek(pos),
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(reader) => reader.seek(pos),
+        }
+    }
+}
+
+/// Opens a FASTA file to index or read through its index. Compressed files can't be
+/// read at random positions, unless they are compressed with `bgzip`.
+pub(crate) fn open_indexable(path: &Path) -> Result<IndexedFile, ParseError> {
+    let mut file = BufReader::new(open_file(path)?);
+    let header = file.fill_buf()?;
+    #[cfg(feature = "flate2")]
+    if is_bgzf(header) {
+        return Ok(IndexedFile::Bgzf(BgzfReader::new(file)));
+    }
+    if header.starts_with(&[0x1F, 0x8B]) {
+        let msg = format!(
+            "{}: only FASTA files compressed with bgzip can be indexed",
+            path.display()
+        );
+        return Err(io::Error::new(io::ErrorKind::Unsupported, msg).into());
+    }
+    Ok(IndexedFile::Plain(file))
+}
+
+/// Reads regions of the sequences of a FASTA file, using a `.fai` index to go straight
+/// to them.
+///
+/// The regions are handed out as `SequenceRecord`s named after the sequence, which
+/// only live until the next fetch like the records of the other readers. Their
+/// position is the byte offset of the first base of the region, the line isn't known.
+///
+/// ```
+/// use std::io::Cursor;
+/// use needletail::parser::{FaiIndex, IndexedFastaReader};
+///
+/// let fasta = b">chr1\nACGTA\nCGGT\n>chr2\nTTTT\n";
+/// let index = FaiIndex::build(&fasta[..]).unwrap();
+/// let mut reader = IndexedFastaReader::new(Cursor::new(fasta), index);
+/// let record = reader.fetch("chr1", 3, 7).unwrap();
+/// assert_eq!((record.id(), &record.seq()[..]), (&b"chr1"[..], &b"TACG"[..]));
+/// assert!(reader.fetch("chr2", 2, 5).is_err());
+/// ```
+pub struct IndexedFastaReader<R: Read + Seek> {
+    reader: R,
+    index: FaiIndex,
+    // the bytes of the region, with line endings, and the record handed out
+    raw: Vec<u8>,
+    seq: Vec<u8>,
+    record: RecordBuffer,
+    position: Position,
+}
+
+impl IndexedFastaReader<IndexedFile> {
+    /// Opens the FASTA file at `path` with its `path.fai` index, indexing the file if
+    /// there is none (the index isn't written, see `FaiIndex::write`).
+    /// Files compressed with `bgzip` are read with their `path.gzi` index, which is
+    /// also built if missing (see `GziIndex::write`).
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let path = path.as_ref();
+        let mut file = match open_indexable(path)? {
+            #[cfg(feature = "flate2")]
+            IndexedFile::Bgzf(reader) => {
+                let gzi_path = append_extension(path, "gzi");
+                let index = if gzi_path.exists() {
+                    GziIndex::read(&gzi_path)?
+                } else {
+                    GziIndex::build(BufReader::new(open_file(path)?))?
+                };
+                IndexedFile::Bgzf(reader.with_index(index))
+            }
+            file => file,
+        };
+        let fai_path = append_extension(path, "fai");
+        let index = if fai_path.exists() {
+            FaiIndex::read(&fai_path)?
+        } else {
+            let index = FaiIndex::build(&mut file)?;
+            file.rewind()?;
+            index
+        };
+        Ok(Self::new(file, index))
+    }
+}
+
+impl<R: Read + Seek> IndexedFastaReader<R> {
+    /// Reads the FASTA file `reader` with its `index`
+    pub fn new(reader: R, index: FaiIndex) -> Self {
+        Self {
+            reader,
+            index,
+            raw: Vec::new(),
+            seq: Vec::new(),
+            record: RecordBuffer::new(),
+            position: Position::new(0, 0),
+        }
+    }
+
+    pub fn index(&self) -> &FaiIndex {
+        &self.index
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns the bases `start..end` (0-based, end excluded) of the sequence `name`.
+    /// Fails with an `InvalidRegion` error if there is no such sequence or region and
+    /// an `InvalidIndex` one if the file doesn't match the index.
+    pub fn fetch(
+        &mut self,
+        name: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<SequenceRecord<'_>, ParseError> {
+        let entry = self.index.get(name).ok_or_else(|| {
+            ParseError::new_invalid_region(format!("No sequence '{name}' in the index"))
+        })?;
+        if start > end || end > entry.length {
+            let msg = format!(
+                "Region {start}-{end} is outside of '{name}' ({} bases)",
+                entry.length
+            );
+            return Err(ParseError::new_invalid_region(msg));
+        }
+
+        let from = entry.offset_of(start)?;
+        self.seq.clear();
+        if start < end {
+            let to = entry.offset_of(end - 1)?.saturating_add(1);
+            // don't read past the end of the file for an index that doesn't match it,
+            // when its size is known (it isn't for BGZF files)
+            let file_size = match self.reader.seek(SeekFrom::End(0)) {
+                Ok(size) => Some(size),
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => None,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(file_size) = file_size.filter(|size| to > *size) {
+                let msg = format!(
+                    "The file doesn't match its index: bases {start}-{end} would end at byte {to} of a {file_size} bytes file"
+                );
+                let position = ErrorPosition {
+                    line: 0,
+                    id: Some(name.to_string()),
+                };
+                return Err(ParseError::new_invalid_index(msg, position));
+            }
+            self.reader.seek(SeekFrom::Start(from))?;
+            self.raw.clear();
+            // grows with what is actually read rather than with what the index claims
+            (&mut self.reader)
+                .take(to - from)
+                .read_to_end(&mut self.raw)?;
+            let complete = self.raw.len() as u64 == to - from;
+            // with line lengths that don't match the index, line endings (or the next
+            // record) show up where bases are expected or the other way around
+            let offset = from - entry.offset;
+            let matches_index = complete
+                && self.raw.iter().enumerate().all(|(i, c)| {
+                    let column = (offset + i as u64) % entry.line_width;
+                    (column < entry.line_bases) != c.is_ascii_whitespace() && *c != b'>'
+                });
+            if !matches_index {
+                let msg = format!(
+                    "The file doesn't match its index: bytes {from}-{to} aren't bases {start}-{end}"
+                );
+                let position = ErrorPosition {
+                    line: 0,
+                    id: Some(name.to_string()),
+                };
+                return Err(ParseError::new_invalid_index(msg, position));
+            }
+            self.seq.extend(
+                self.raw
+                    .iter()
+                    .filter(|c| !c.is_ascii_whitespace())
+                    .copied(),
+            );
+        }
+
+        let line_ending = entry.line_ending();
+        self.record.set(name.as_bytes(), &self.seq, None);
+        self.position = Position::new(0, from);
+        Ok(self.record.record(
+            &self.position,
+            Some(line_ending.unwrap_or(LineEnding::Unix)),
+        ))
+    }
+
+    /// Returns the whole sequence `name`
+    pub fn fetch_all(&mut self, name: &str) -> Result<SequenceRecord<'_>, ParseError> {
+        let length = self.index.get(name).map_or(0, |entry| entry.length);
+        self.fetch(name, 0, length)
+    }
+
+    fn fetch_owned(&mut self, i: usize) -> Result<OwnedRecord, ParseError> {
+        let name = self.index.entries()[i].name.clone();
+        Ok(self.fetch_all(&name)?.to_owned_record())
+    }
+
+    /// Returns the records from the last one of the file to the first, e.g. to read the
+    /// latest records appended to a file
+    pub fn iter_reverse(&mut self) -> impl Iterator<Item = Result<OwnedRecord, ParseError>> + '_ {
+        (0..self.index.len()).rev().map(|i| self.fetch_owned(i))
+    }
+
+    /// Returns the records of the file starting from the sequence `name`, e.g. to resume
+    /// reading a file
+    pub fn iter_from(
+        &mut self,
+        name: &str,
+    ) -> Result<impl Iterator<Item = Result<OwnedRecord, ParseError>> + '_, ParseError> {
+        let first = self.index.position(name).ok_or_else(|| {
+            ParseError::new_invalid_region(format!("No sequence '{name}' in the index"))
+        })?;
+        Ok((first..self.index.len()).map(|i| self.fetch_owned(i)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_file;
+
+    #[test]
+    fn test_fetch() {
+        let mut reader = IndexedFastaReader::from_path("tests/data/28S.fasta").unwrap();
+        let mut records = parse_fastx_file("tests/data/28S.fasta").unwrap();
+        let first = records.next().unwrap().unwrap().to_owned_record();
+        let name = std::str::from_utf8(first.id.split(|c| *c == b' ').next().unwrap()).unwrap();
+        let all = reader.fetch_all(name).unwrap();
+        assert_eq!(&all.seq()[..], first.seq);
+        let length = first.seq.len() as u64;
+        for (start, end) in [(0, 1), (59, 61), (100, 300), (length - 5, length), (7, 7)] {
+            let region = reader.fetch(name, start, end).unwrap();
+            assert_eq!(&region.seq()[..], &first.seq[start as usize..end as usize]);
+        }
+        let err = reader.fetch(name, 10, length + 1).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRegion);
+        let err = reader.fetch("missing", 0, 1).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRegion);
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_bgzf() {
+        let fasta = std::fs::read("tests/data/28S.fasta").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("28S.fasta.gz");
+        let mut bgzf = Vec::new();
+        for block in fasta.chunks(1000) {
+            crate::parser::bgzf::write_block(block, &mut bgzf);
+        }
+        std::fs::write(&path, &bgzf).unwrap();
+
+        let mut plain = IndexedFastaReader::from_path("tests/data/28S.fasta").unwrap();
+        let mut compressed = IndexedFastaReader::from_path(&path).unwrap();
+        assert_eq!(plain.index(), compressed.index());
+        // the sequences span several blocks
+        for entry in plain.index().entries().to_vec().iter().rev() {
+            let expected = plain.fetch_all(&entry.name).unwrap().seq().into_owned();
+            let record = compressed.fetch_all(&entry.name).unwrap();
+            assert_eq!(record.seq(), expected);
+            let end = entry.length.min(200);
+            let expected = plain
+                .fetch(&entry.name, 50, end)
+                .unwrap()
+                .seq()
+                .into_owned();
+            assert_eq!(
+                compressed.fetch(&entry.name, 50, end).unwrap().seq(),
+                expected
+            );
+        }
+        let name = plain.index().entries()[0].name.clone();
+
+        let gzi_path = append_extension(&path, "gzi");
+        let gzi = GziIndex::build(&bgzf[..]).unwrap();
+        gzi.write(&gzi_path).unwrap();
+        assert_eq!(GziIndex::read(&gzi_path).unwrap(), gzi);
+        assert_eq!(gzi.blocks().len(), fasta.len().div_ceil(1000));
+        let mut compressed = IndexedFastaReader::from_path(&path).unwrap();
+        let expected = plain.fetch(&name, 10, 20).unwrap().seq().into_owned();
+        assert_eq!(compressed.fetch(&name, 10, 20).unwrap().seq(), expected);
+    }
+
+    #[test]
+    fn test_mismatched_index() {
+        let fasta = b">a\nACGT\nAC\n>b\nAAA\n";
+        // built when the lines of `a` were 3 bases long
+        let index = FaiIndex::from_reader(&b"a\t6\t3\t3\t4\nb\t3\t15\t3\t4\n"[..]).unwrap();
+        let mut reader = IndexedFastaReader::new(Cursor::new(fasta), index);
+        assert_eq!(&reader.fetch("a", 0, 3).unwrap().seq()[..], b"ACG");
+        let err = reader.fetch("a", 0, 6).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidIndex);
+        let err = reader.fetch("b", 2, 3).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidIndex);
+
+        // an index way larger than the file
+        let index = FaiIndex::from_reader(&b"a\t4000000000000\t3\t60\t61\n"[..]).unwrap();
+        let mut reader = IndexedFastaReader::new(Cursor::new(fasta), index);
+        let err = reader.fetch_all("a").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidIndex);
+    }
+
+    #[test]
+    fn test_iter() {
+        let fasta = b">a\nAC\n>b\nGG\nG\n>c\n>d\nTT\n";
+        let index = FaiIndex::build(&fasta[..]).unwrap();
+        let mut reader = IndexedFastaReader::new(Cursor::new(fasta), index);
+        let ids: Vec<_> = reader.iter_reverse().map(|r| r.unwrap().id).collect();
+        assert_eq!(ids, [&b"d"[..], b"c", b"b", b"a"]);
+        let seqs: Vec<_> = reader
+            .iter_from("b")
+            .unwrap()
+            .map(|r| r.unwrap().seq)
+            .collect();
+        assert_eq!(seqs, [&b"GGG"[..], b"", b"TT"]);
+        assert!(reader.iter_from("e").is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! Interleaved paired-end reads, where each R1 is directly followed by its R2 in a
+//! single file: reading them as pairs and converting them from and to a pair of files.
+use std::io::Write;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::record::{OwnedRecord, SequenceRecord};
+use crate::parser::utils::{id_key, FastxReader, Format};
+
+/// Part of an id shared by both mates of a pair: up to the first whitespace and
+/// without a `/1` or `/2` suffix
+pub(crate) fn mate_key(id: &[u8]) -> &[u8] {
+    let id = id_key(id);
+    id.strip_suffix(b"/1")
+        .or_else(|| id.strip_suffix(b"/2"))
+        .unwrap_or(id)
+}
+
+/// Which mate of a pair the id says the read is, from a `/1` or `/2` suffix or an
+/// Illumina `1:N:0:...` comment
+fn mate_number(id: &[u8]) -> Option<u8> {
+    let mut words = id.split(|c| c.is_ascii_whitespace());
+    let name = words.next().unwrap_or_default();
+    match name {
+        [.., b'/', n @ (b'1' | b'2')] => Some(n - b'0'),
+        _ => match words.find(|w| !w.is_empty()) {
+            Some([n @ (b'1' | b'2'), b':', ..]) => Some(n - b'0'),
+            _ => None,
+        },
+    }
+}
+
+/// Whether the reads with those ids are the R1 and the R2 of the same pair: their
+/// names must match, and if the ids tell which mate they are, the first one must be
+/// R1 and the second one R2.
+///
+/// ```
+/// use needletail::parser::are_mates;
+///
+/// assert!(are_mates(b"read1/1", b"read1/2"));
+/// assert!(are_mates(b"read1 1:N:0:ACGT", b"read1 2:N:0:ACGT"));
+/// assert!(are_mates(b"read1", b"read1"));
+/// assert!(!are_mates(b"read1/2", b"read1/1"));
+/// assert!(!are_mates(b"read1/1", b"read2/2"));
+/// ```
+pub fn are_mates(id1: &[u8], id2: &[u8]) -> bool {
+    mate_key(id1) == mate_key(id2)
+        && match (mate_number(id1), mate_number(id2)) {
+            (None, None) => true,
+            (n1, n2) => n1 == Some(1) && n2 == Some(2),
+        }
+}
+
+fn check_mates(id1: &[u8], record2: &SequenceRecord) -> Result<(), ParseError> {
+    if are_mates(id1, record2.id()) {
+        return Ok(());
+    }
+    Err(ParseError::new_mismatched_mates(
+        id1,
+        record2.id(),
+        position_of(record2),
+        record2.format(),
+    ))
+}
+
+fn position_of(record: &SequenceRecord) -> ErrorPosition {
+    ErrorPosition {
+        line: record.start_line_number(),
+        id: Some(String::from_utf8_lossy(record.id()).into()),
+    }
+}
+
+/// Error for an R1 without its R2 at the end of an interleaved file
+fn missing_r2_error(reader: &dyn FastxReader, id1: &[u8], format: Format) -> ParseError {
+    ParseError::new_unpaired_records(
+        ErrorPosition {
+            line: reader.position().line(),
+            id: Some(String::from_utf8_lossy(id1).into()),
+        },
+        format,
+    )
+}
+
+/// Iterator over the `(R1, R2)` pairs of an interleaved file, checking with
+/// [`are_mates`] that the records of each pair go together.
+///
+/// Returns an error of kind `MismatchedMates` for a pair whose ids don't match and of
+/// kind `UnpairedRecords` if the file has an odd number of records. The iteration
+/// stops after an error.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::InterleavedReader;
+///
+/// let interleaved = b"@a/1\nACGT\n+\nIIII\n@a/2\nTTTT\n+\nIIII\n@b/1\nA\n+\nI\n@c/2\nA\n+\nI\n";
+/// let mut reader = InterleavedReader::new(parse_fastx_reader(&interleaved[..]).unwrap());
+/// let (r1, r2) = reader.next().unwrap().unwrap();
+/// assert_eq!((r1.seq, r2.seq), (b"ACGT".to_vec(), b"TTTT".to_vec()));
+/// assert!(reader.next().unwrap().is_err());
+/// assert!(reader.next().is_none());
+/// ```
+pub struct InterleavedReader {
+    reader: Box<dyn FastxReader>,
+    finished: bool,
+}
+
+impl InterleavedReader {
+    pub fn new(reader: Box<dyn FastxReader>) -> Self {
+        Self {
+            reader,
+            finished: false,
+        }
+    }
+
+    pub fn into_inner(self) -> Box<dyn FastxReader> {
+        self.reader
+    }
+
+    fn read_pair(&mut self) -> Option<Result<(OwnedRecord, OwnedRecord), ParseError>> {
+        let r1 = match self.reader.next()? {
+            Ok(r) => r.to_owned_record(),
+            Err(e) => return Some(Err(e)),
+        };
+        let pair = match self.reader.next() {
+            Some(r2) => r2.and_then(|r2| {
+                check_mates(&r1.id, &r2)?;
+                Ok(r2.to_owned_record())
+            }),
+            None => Err(missing_r2_error(&*self.reader, &r1.id, r1.format())),
+        };
+        Some(pair.map(|r2| (r1, r2)))
+    }
+}
+
+impl Iterator for InterleavedReader {
+    type Item = Result<(OwnedRecord, OwnedRecord), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let pair = self.read_pair();
+        // the FASTA/FASTQ parsers can't carry on after an error
+        self.finished = !matches!(pair, Some(Ok(_)));
+        pair
+    }
+}
+
+/// Writes the R1 of each pair of the interleaved `reader` to the first writer and its
+/// R2 to the second one, checking that they are mates like [`InterleavedReader`].
+/// Returns the number of pairs written.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::deinterleave;
+///
+/// let mut reader = parse_fastx_reader(&b">a/1\nAC\n>a/2\nGT\n"[..]).unwrap();
+/// let (mut r1, mut r2) = (Vec::new(), Vec::new());
+/// assert_eq!(deinterleave(&mut reader, (&mut r1, &mut r2)).unwrap(), 1);
+/// assert_eq!((&r1[..], &r2[..]), (&b">a/1\nAC\n"[..], &b">a/2\nGT\n"[..]));
+/// ```
+pub fn deinterleave(
+    reader: &mut dyn FastxReader,
+    writers: (&mut dyn Write, &mut dyn Write),
+) -> Result<usize, ParseError> {
+    let mut pairs = 0;
+    // R1 is written right away, only its id is kept to check R2
+    let mut id1 = Vec::new();
+    while let Some(record1) = reader.next() {
+        let record1 = record1?;
+        record1.write(writers.0, None)?;
+        id1.clear();
+        id1.extend_from_slice(record1.id());
+        let format = record1.format();
+        let record2 = match reader.next() {
+            Some(record2) => record2?,
+            None => return Err(missing_r2_error(reader, &id1, format)),
+        };
+        check_mates(&id1, &record2)?;
+        record2.write(writers.1, None)?;
+        pairs += 1;
+    }
+    Ok(pairs)
+}
+
+/// Writes each record of `reader1` followed by the record of `reader2` at the same
+/// position to `writer`, checking that they are mates like [`InterleavedReader`].
+/// Returns the number of pairs written.
+///
+/// Returns an error of kind `UnpairedRecords` if one of the readers runs out of
+/// records before the other.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::interleave;
+///
+/// let mut reader1 = parse_fastx_reader(&b">a/1\nAC\n"[..]).unwrap();
+/// let mut reader2 = parse_fastx_reader(&b">a/2\nGT\n"[..]).unwrap();
+/// let mut out = Vec::new();
+/// assert_eq!(interleave(&mut reader1, &mut reader2, &mut out).unwrap(), 1);
+/// assert_eq!(out, b">a/1\nAC\n>a/2\nGT\n");
+/// ```
+pub fn interleave(
+    reader1: &mut dyn FastxReader,
+    reader2: &mut dyn FastxReader,
+    writer: &mut dyn Write,
+) -> Result<usize, ParseError> {
+    let mut pairs = 0;
+    loop {
+        let (record1, record2) = match (reader1.next(), reader2.next()) {
+            (None, None) => break,
+            (Some(r1), Some(r2)) => (r1?, r2?),
+            (Some(r), None) | (None, Some(r)) => {
+                let r = r?;
+                return Err(ParseError::new_unpaired_records(
+                    position_of(&r),
+                    r.format(),
+                ));
+            }
+        };
+        check_mates(record1.id(), &record2)?;
+        record1.write(writer, None)?;
+        record2.write(writer, None)?;
+        pairs += 1;
+    }
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_are_mates() {
+        assert_eq!(mate_key(b"read1/1 extra"), b"read1");
+        assert_eq!(mate_key(b"read1/2"), b"read1");
+        assert_eq!(mate_key(b"read1"), b"read1");
+
+        assert_eq!(mate_number(b"r/1"), Some(1));
+        assert_eq!(mate_number(b"r  2:N:0:1"), Some(2));
+        assert_eq!(mate_number(b"r/3"), None);
+        assert_eq!(mate_number(b"r 12:N"), None);
+
+        assert!(are_mates(b"r/1 x", b"r/2 y"));
+        assert!(!are_mates(b"r/1", b"r/1"));
+        assert!(!are_mates(b"r/1", b"r"));
+        assert!(!are_mates(b"r", b"s"));
+    }
+
+    #[test]
+    fn test_interleaved_reader_errors() {
+        let fastq = b"@a/1\nA\n+\nI\n@a/2\nC\n+\nI\n@b/1\nG\n+\nI\n";
+        let mut reader = InterleavedReader::new(parse_fastx_reader(&fastq[..]).unwrap());
+        assert!(reader.next().unwrap().is_ok());
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnpairedRecords);
+        assert_eq!(err.position.id.as_deref(), Some("b/1"));
+        assert!(reader.next().is_none());
+
+        let fasta = b">a/1\nA\n>b/2\nC\n";
+        let mut reader = InterleavedReader::new(parse_fastx_reader(&fasta[..]).unwrap());
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MismatchedMates);
+        assert_eq!(err.position.line, 3);
+    }
+
+    #[test]
+    fn test_interleave_round_trip() {
+        let r1 = b"@a/1\nACGT\n+\nIIII\n@b/1\nAA\n+\nII\n";
+        let r2 = b"@a/2\nTTTT\n+\n####\n@b/2\nCC\n+\n##\n";
+        let mut interleaved = Vec::new();
+        let pairs = interleave(
+            &mut parse_fastx_reader(&r1[..]).unwrap(),
+            &mut parse_fastx_reader(&r2[..]).unwrap(),
+            &mut interleaved,
+        )
+        .unwrap();
+        assert_eq!(pairs, 2);
+
+        let (mut out1, mut out2) = (Vec::new(), Vec::new());
+        let mut reader = parse_fastx_reader(&interleaved[..]).unwrap();
+        assert_eq!(
+            deinterleave(&mut reader, (&mut out1, &mut out2)).unwrap(),
+            2
+        );
+        assert_eq!((&out1[..], &out2[..]), (&r1[..], &r2[..]));
+
+        let err = interleave(
+            &mut parse_fastx_reader(&r1[..]).unwrap(),
+            &mut parse_fastx_reader(&b"@a/2\nT\n+\nI\n@c/2\nC\n+\nI\n"[..]).unwrap(),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MismatchedMates);
+    }
+}
@@ -0,0 +1,190 @@
+//! Reading single-end, paired-end and interleaved inputs the same way, so tools
+//! only need to handle a stream of reads or read pairs.
+use std::path::PathBuf;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::parse_fastx_file;
+use crate::parser::record::{OwnedRecord, SequenceRecord};
+use crate::parser::utils::FastxReader;
+
+/// How the reads of an input are laid out in files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputLayout {
+    /// Single-end reads in one file
+    Single(PathBuf),
+    /// Paired-end reads with R1 and R2 in two files, in the same order
+    Paired(PathBuf, PathBuf),
+    /// Paired-end reads in one file, each R1 followed by its R2
+    Interleaved(PathBuf),
+}
+
+/// A single-end read or a read pair, as yielded by [`PairedOrSingleReader`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadUnit {
+    Single(OwnedRecord),
+    Pair(OwnedRecord, OwnedRecord),
+}
+
+enum Readers {
+    Single(Box<dyn FastxReader>),
+    Paired(Box<dyn FastxReader>, Box<dyn FastxReader>),
+    Interleaved(Box<dyn FastxReader>),
+}
+
+fn unpaired_error(record: &SequenceRecord) -> ParseError {
+    ParseError::new_unpaired_records(
+        ErrorPosition {
+            line: record.start_line_number(),
+            id: Some(String::from_utf8_lossy(record.id()).into()),
+        },
+        record.format(),
+    )
+}
+
+/// Iterator over the reads of an input whatever its [`InputLayout`]: read pairs are
+/// yielded as `ReadUnit::Pair` and single-end reads as `ReadUnit::Single`.
+///
+/// Returns an error of kind `UnpairedRecords` if R1 and R2 files don't have the
+/// same number of records or an interleaved file has an odd number of records.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::{PairedOrSingleReader, ReadUnit};
+///
+/// let interleaved = b"@a/1\nACGT\n+\nIIII\n@a/2\nTTTT\n+\nIIII\n";
+/// let reader = PairedOrSingleReader::interleaved(parse_fastx_reader(&interleaved[..]).unwrap());
+/// for unit in reader {
+///     match unit.unwrap() {
+///         ReadUnit::Pair(r1, r2) => assert_eq!((r1.id, r2.id), (b"a/1".to_vec(), b"a/2".to_vec())),
+///         ReadUnit::Single(_) => unreachable!(),
+///     }
+/// }
+/// ```
+pub struct PairedOrSingleReader {
+    readers: Readers,
+    finished: bool,
+}
+
+impl PairedOrSingleReader {
+    fn new(readers: Readers) -> Self {
+        Self {
+            readers,
+            finished: false,
+        }
+    }
+
+    pub fn single(reader: Box<dyn FastxReader>) -> Self {
+        Self::new(Readers::Single(reader))
+    }
+
+    pub fn paired(reader1: Box<dyn FastxReader>, reader2: Box<dyn FastxReader>) -> Self {
+        Self::new(Readers::Paired(reader1, reader2))
+    }
+
+    pub fn interleaved(reader: Box<dyn FastxReader>) -> Self {
+        Self::new(Readers::Interleaved(reader))
+    }
+
+    /// Whether this yields read pairs
+    pub fn is_paired(&self) -> bool {
+        !matches!(self.readers, Readers::Single(_))
+    }
+
+    fn read_unit(&mut self) -> Option<Result<ReadUnit, ParseError>> {
+        match &mut self.readers {
+            Readers::Single(reader) => {
+                let record = reader.next()?;
+                Some(record.map(|r| ReadUnit::Single(r.to_owned_record())))
+            }
+            Readers::Paired(reader1, reader2) => match (reader1.next(), reader2.next()) {
+                (None, None) => None,
+                (Some(r1), Some(r2)) => Some(r1.and_then(|r1| {
+                    Ok(ReadUnit::Pair(r1.to_owned_record(), r2?.to_owned_record()))
+                })),
+                (Some(r), None) | (None, Some(r)) => Some(r.and_then(|r| Err(unpaired_error(&r)))),
+            },
+            Readers::Interleaved(reader) => {
+                let r1 = match reader.next()? {
+                    Ok(r) => r.to_owned_record(),
+                    Err(e) => return Some(Err(e)),
+                };
+                let r2 = match reader.next() {
+                    Some(r) => r.map(|r| r.to_owned_record()),
+                    None => Err(ParseError::new_unpaired_records(
+                        ErrorPosition {
+                            line: reader.position().line(),
+                            id: Some(String::from_utf8_lossy(&r1.id).into()),
+                        },
+                        r1.format(),
+                    )),
+                };
+                Some(r2.map(|r2| ReadUnit::Pair(r1, r2)))
+            }
+        }
+    }
+}
+
+impl Iterator for PairedOrSingleReader {
+    type Item = Result<ReadUnit, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let unit = self.read_unit();
+        // the FASTA/FASTQ parsers can't carry on after an error
+        self.finished = !matches!(unit, Some(Ok(_)));
+        unit
+    }
+}
+
+/// Opens the file(s) of `layout`, see [`PairedOrSingleReader`]
+pub fn open_input(layout: &InputLayout) -> Result<PairedOrSingleReader, ParseError> {
+    Ok(match layout {
+        InputLayout::Single(path) => PairedOrSingleReader::single(parse_fastx_file(path)?),
+        InputLayout::Paired(path1, path2) => {
+            PairedOrSingleReader::paired(parse_fastx_file(path1)?, parse_fastx_file(path2)?)
+        }
+        InputLayout::Interleaved(path) => {
+            PairedOrSingleReader::interleaved(parse_fastx_file(path)?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_paired_or_single_reader() {
+        let single = open_input(&InputLayout::Single("tests/data/test.fa".into())).unwrap();
+        assert!(!single.is_paired());
+        assert!(single
+            .map(|u| u.unwrap())
+            .all(|u| matches!(u, ReadUnit::Single(_))));
+
+        let layout = InputLayout::Paired("tests/data/test.fa".into(), "tests/data/test.fa".into());
+        let units: Vec<_> = open_input(&layout).unwrap().map(|u| u.unwrap()).collect();
+        assert!(units
+            .iter()
+            .all(|u| matches!(u, ReadUnit::Pair(r1, r2) if r1 == r2)));
+
+        let reader1 = parse_fastx_reader(&b">a\nA\n>b\nC\n"[..]).unwrap();
+        let reader2 = parse_fastx_reader(&b">a\nA\n"[..]).unwrap();
+        let mut paired = PairedOrSingleReader::paired(reader1, reader2);
+        assert!(paired.next().unwrap().is_ok());
+        let err = paired.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnpairedRecords);
+        assert_eq!(err.position.id.as_deref(), Some("b"));
+        assert!(paired.next().is_none());
+
+        let reader = parse_fastx_reader(&b">a\nA\n>b\nC\n>c\nG\n"[..]).unwrap();
+        let mut interleaved = PairedOrSingleReader::interleaved(reader);
+        assert!(interleaved.next().unwrap().is_ok());
+        let err = interleaved.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnpairedRecords);
+        assert_eq!(err.position.id.as_deref(), Some("c"));
+    }
+}
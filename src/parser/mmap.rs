@@ -0,0 +1,264 @@
+//! Reading uncompressed FASTA files through a memory mapping, the records pointing
+//! directly into the mapped file instead of being copied into a buffer.
+use std::fs::File;
+use std::path::Path;
+
+use memchr::Memchr;
+use memmap2::Mmap;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::fasta::BufferPosition;
+use crate::parser::parse_fastx_file;
+use crate::parser::path::open_file;
+use crate::parser::record::SequenceRecord;
+use crate::parser::utils::{invalid_record, FastxReader, Format, LineEnding, Position};
+
+/// FASTA parser over a memory-mapped file: there is no buffer to refill or grow,
+/// records of any size are read in place and reading the file again with
+/// [`MmapFastaReader::rewind`] is cheap.
+///
+/// The file must not be modified while it is being read, which would change the
+/// records under the parser's feet (or crash the program if it's truncated).
+pub struct MmapFastaReader {
+    mmap: Mmap,
+    buf_pos: BufferPosition,
+    search_pos: usize,
+    position: Position,
+    finished: bool,
+    line_ending: Option<LineEnding>,
+    mixed_line_endings: bool,
+}
+
+impl MmapFastaReader {
+    /// Maps the (uncompressed) FASTA file at `path`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Self::new(&open_file(path.as_ref())?)
+    }
+
+    /// Maps an (uncompressed) FASTA file
+    pub fn new(file: &File) -> Result<Self, ParseError> {
+        // SAFETY: the mapping is only read, and the documentation of the reader warns
+        // that the file must not be modified while it's being read
+        let mmap = unsafe { Mmap::map(file)? };
+        Ok(Self {
+            mmap,
+            buf_pos: BufferPosition {
+                start: 0,
+                seq_pos: Vec::with_capacity(1),
+            },
+            search_pos: 0,
+            position: Position::new(0, 0),
+            finished: false,
+            line_ending: None,
+            mixed_line_endings: false,
+        })
+    }
+
+    /// Goes back to the first record of the file
+    pub fn rewind(&mut self) {
+        self.buf_pos.reset(0);
+        self.search_pos = 0;
+        self.position = Position::new(0, 0);
+        self.finished = false;
+        self.line_ending = None;
+        self.mixed_line_endings = false;
+    }
+
+    #[inline]
+    fn next_pos(&mut self) {
+        self.position.line += self.buf_pos.seq_pos.len() as u64;
+        self.position.byte += (self.search_pos - self.buf_pos.start) as u64;
+        self.buf_pos.reset(self.search_pos);
+    }
+
+    /// Finds the end of the current record, which is either the start of the next one
+    /// or the end of the file
+    #[inline]
+    fn find(&mut self) {
+        let buffer = &self.mmap[..];
+        let start = self.search_pos;
+        // end of the last record: a final line ending isn't part of it, like for
+        // `FastaReader`
+        let mut end = buffer.len();
+        for pos in Memchr::new(b'\n', &buffer[start..]) {
+            let pos = start + pos;
+            if pos + 1 == buffer.len() {
+                end = pos;
+                break;
+            }
+            self.buf_pos.seq_pos.push(pos);
+            if buffer[pos + 1] == b'>' {
+                self.search_pos = pos + 1;
+                return;
+            }
+        }
+        self.search_pos = end;
+        self.finished = true;
+        if !self.buf_pos.seq_pos.is_empty() {
+            self.buf_pos.seq_pos.push(end);
+        }
+    }
+}
+
+impl FastxReader for MmapFastaReader {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        if self.finished {
+            return None;
+        }
+
+        if self.position.line == 0 {
+            match self.mmap.first() {
+                None => {
+                    self.finished = true;
+                    return None;
+                }
+                Some(b'>') => {
+                    self.position.line = 1;
+                    self.search_pos = 1;
+                }
+                Some(c) => {
+                    self.finished = true;
+                    return Some(Err(invalid_record(ParseError::new_invalid_start(
+                        *c,
+                        ErrorPosition::default(),
+                        Format::Fasta,
+                    ))));
+                }
+            }
+        }
+
+        if !self.buf_pos.is_new() {
+            self.next_pos();
+        }
+        self.find();
+
+        if self.buf_pos.seq_pos.is_empty() {
+            return Some(Err(invalid_record(ParseError::new_unexpected_end(
+                ErrorPosition {
+                    line: self.position.line,
+                    id: None,
+                },
+                Format::Fasta,
+            ))));
+        }
+
+        let record_line_ending = self.buf_pos.find_line_ending(&self.mmap);
+        if self.line_ending.is_none() {
+            self.line_ending = record_line_ending;
+        } else if record_line_ending.is_some() && record_line_ending != self.line_ending {
+            self.mixed_line_endings = true;
+        }
+        Some(Ok(SequenceRecord::new_fasta(
+            &self.mmap,
+            &self.buf_pos,
+            &self.position,
+            record_line_ending.or(self.line_ending),
+        )))
+    }
+
+    fn position(&self) -> &Position {
+        &self.position
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.line_ending
+    }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+}
+
+/// Same as `parse_fastx_file` but memory-maps uncompressed FASTA files and parses
+/// them in place with a [`MmapFastaReader`], which avoids copying the file into the
+/// parser's buffer. Compressed and FASTQ files are read like `parse_fastx_file` does.
+///
+/// The file must not be modified while it is being read.
+///
+/// ```no_run
+/// use needletail::parse_fastx_mmap;
+///
+/// let mut reader = parse_fastx_mmap("genome.fasta").unwrap();
+/// while let Some(record) = reader.next() {
+///     let record = record.unwrap();
+///     println!("{}\t{}", String::from_utf8_lossy(record.id()), record.num_bases());
+/// }
+/// ```
+pub fn parse_fastx_mmap<P: AsRef<Path>>(path: P) -> Result<Box<dyn FastxReader>, ParseError> {
+    let path = path.as_ref();
+    let reader = MmapFastaReader::from_path(path)?;
+    // a compressed file never starts with `>`
+    if reader.mmap.len() >= 2 && reader.mmap[0] == b'>' {
+        return Ok(Box::new(reader));
+    }
+    parse_fastx_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::errors::ParseErrorKind;
+
+    type Item = Result<(Vec<u8>, Vec<u8>, u64), ParseErrorKind>;
+
+    fn records(reader: &mut dyn FastxReader) -> Vec<Item> {
+        let mut records = Vec::new();
+        while let Some(record) = reader.next() {
+            records.push(
+                record
+                    .map(|r| (r.id().to_vec(), r.seq().to_vec(), r.start_line_number()))
+                    .map_err(|e| e.kind),
+            );
+            if records.last().unwrap().is_err() {
+                break;
+            }
+        }
+        records
+    }
+
+    #[test]
+    fn test_same_records_as_fasta_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seqs.fasta");
+        for content in [
+            &b">a\nACGT\nAC\n>b desc\n\n>c\r\nGG\r\nTT"[..],
+            b">a\nACGT\n>b\nT\n",
+            b">a\n",
+            b">a\n\n\n",
+        ] {
+            fs::write(&path, content).unwrap();
+            let expected = records(&mut *parse_fastx_file(&path).unwrap());
+            let mut reader = MmapFastaReader::from_path(&path).unwrap();
+            assert_eq!(records(&mut reader), expected, "{content:?}");
+            reader.rewind();
+            assert_eq!(records(&mut reader), expected, "{content:?}");
+        }
+
+        fs::write(&path, b">a").unwrap();
+        let err = MmapFastaReader::from_path(&path)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd);
+    }
+
+    #[test]
+    fn test_parse_fastx_mmap_falls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reads.fastq");
+        fs::write(&path, b"@r1\nACGT\n+\nIIII\n").unwrap();
+        let mut reader = parse_fastx_mmap(&path).unwrap();
+        assert_eq!(
+            records(&mut reader),
+            [Ok((b"r1".to_vec(), b"ACGT".to_vec(), 1))]
+        );
+
+        let empty = dir.path().join("empty.fasta");
+        fs::write(&empty, b"").unwrap();
+        let err = parse_fastx_mmap(&empty).err().unwrap();
+        assert_eq!(err.kind, ParseErrorKind::EmptyFile);
+    }
+}
@@ -1,6 +1,5 @@
 //! Handles all the FASTA/FASTQ parsing
-use std::fs::File;
-use std::io::{stdin, Cursor, Read};
+use std::io::{stdin, Cursor, IsTerminal, Read};
 use std::path::Path;
 
 #[cfg(feature = "bzip2")]
@@ -15,12 +14,52 @@ use zstd::stream::read::Decoder as ZstdDecoder;
 use crate::errors::ParseError;
 pub use crate::parser::fasta::Reader as FastaReader;
 pub use crate::parser::fastq::Reader as FastqReader;
+pub use crate::parser::fastq::SeparatorIdCheck;
 
+/// Emits a `tracing` event if the `tracing` feature is enabled, compiles to nothing otherwise
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+#[cfg(feature = "tar")]
+mod archive;
+mod batch;
+#[cfg(feature = "flate2")]
+pub mod bgzf;
+mod clip;
+mod edit;
 mod record;
 mod utils;
 
+mod fai;
 mod fasta;
+mod fastaqual;
 mod fastq;
+mod genbank;
+mod indexed;
+mod interleave;
+mod layout;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod multi;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+mod path;
+mod peek;
+mod raw;
+mod recover;
+#[cfg(feature = "sam")]
+mod sam;
+#[cfg(feature = "bytes")]
+mod shared;
+mod synthetic;
+#[cfg(feature = "table")]
+mod table;
+mod twobit;
+mod writer;
 
 pub use crate::parser::utils::FastxReader;
 
@@ -83,8 +122,17 @@ fn get_fastx_reader<'a, R: 'a + io::Read + Send>(
 /// [zstd]: https://facebook.github.io/zstd/
 ///
 pub fn parse_fastx_reader<'a, R: 'a + io::Read + Send>(
-    mut reader: R,
+    reader: R,
 ) -> Result<Box<dyn FastxReader + 'a>, ParseError> {
+    let (reader, first_byte) = decompress_reader(reader)?;
+    get_fastx_reader(reader, first_byte)
+}
+
+/// Wraps `reader` in the decoder matching its compression format, if any, and
+/// returns it along with the first byte of the decompressed stream.
+pub(crate) fn decompress_reader<'a, R: 'a + io::Read + Send>(
+    mut reader: R,
+) -> Result<(Box<dyn io::Read + Send + 'a>, u8), ParseError> {
     let mut first_two_bytes = [0; 2];
     reader
         .read_exact(&mut first_two_bytes)
@@ -98,53 +146,97 @@ pub fn parse_fastx_reader<'a, R: 'a + io::Read + Send>(
             let mut gz_reader = MultiGzDecoder::new(new_reader);
             let mut first = [0; 1];
             gz_reader.read_exact(&mut first)?;
-            let r = Cursor::new(first).chain(gz_reader);
-            get_fastx_reader(r, first[0])
+            Ok((Box::new(Cursor::new(first).chain(gz_reader)), first[0]))
         }
         #[cfg(feature = "bzip2")]
         BZ_MAGIC => {
             let mut bz_reader = BzDecoder::new(new_reader);
             let mut first = [0; 1];
             bz_reader.read_exact(&mut first)?;
-            let r = Cursor::new(first).chain(bz_reader);
-            get_fastx_reader(r, first[0])
+            Ok((Box::new(Cursor::new(first).chain(bz_reader)), first[0]))
         }
         #[cfg(feature = "xz2")]
         XZ_MAGIC => {
             let mut xz_reader = XzDecoder::new(new_reader);
             let mut first = [0; 1];
             xz_reader.read_exact(&mut first)?;
-            let r = Cursor::new(first).chain(xz_reader);
-            get_fastx_reader(r, first[0])
+            Ok((Box::new(Cursor::new(first).chain(xz_reader)), first[0]))
         }
         #[cfg(feature = "zstd")]
         ZST_MAGIC => {
             let mut zst_reader = ZstdDecoder::new(new_reader)?;
             let mut first = [0; 1];
             zst_reader.read_exact(&mut first)?;
-            let r = Cursor::new(first).chain(zst_reader);
-            get_fastx_reader(r, first[0])
+            Ok((Box::new(Cursor::new(first).chain(zst_reader)), first[0]))
         }
-        _ => get_fastx_reader(new_reader, first_two_bytes[0]),
+        _ => Ok((Box::new(new_reader), first_two_bytes[0])),
     }
 }
 
 /// The main entry point of needletail if you're reading from stdin.
 /// Shortcut to calling `parse_fastx_reader` with `stdin()`
+///
+/// Returns an error of kind `NoInput` instead of waiting for input if stdin is a
+/// terminal, e.g. when a program is run without piping a file to it.
+/// Use `parse_fastx_stdin_interactive` to read from a terminal on purpose.
 pub fn parse_fastx_stdin() -> Result<Box<dyn FastxReader>, ParseError> {
     let stdin = stdin();
+    if stdin.is_terminal() {
+        return Err(ParseError::new_no_input());
+    }
     parse_fastx_reader(stdin)
 }
 
+/// Same as `parse_fastx_stdin` but also reads from stdin when it is a terminal,
+/// for records typed or pasted interactively
+pub fn parse_fastx_stdin_interactive() -> Result<Box<dyn FastxReader>, ParseError> {
+    parse_fastx_reader(stdin())
+}
+
 /// The main entry point of needletail if you're reading from a file.
 /// Shortcut to calling `parse_fastx_reader` with a file
 pub fn parse_fastx_file<P: AsRef<Path>>(path: P) -> Result<Box<dyn FastxReader>, ParseError> {
-    parse_fastx_reader(File::open(&path)?)
+    parse_fastx_reader(open_file(path.as_ref())?)
 }
 
-pub use record::{mask_header_tabs, mask_header_utf8, write_fasta, write_fastq, SequenceRecord};
+pub use batch::{BoundedBatcher, RecordSet};
+pub use clip::{read_bed_clips, ClippingReader, Clips};
+pub use edit::RecordEdit;
+pub use fai::{FaiEntry, FaiIndex};
+pub use fastaqual::{parse_fasta_qual_files, FastaQualReader};
+pub use genbank::GenbankReader;
+pub use indexed::{IndexedFastaReader, IndexedFile};
+pub(crate) use interleave::mate_key;
+pub use interleave::{are_mates, deinterleave, interleave, InterleavedReader};
+pub use layout::{open_input, InputLayout, PairedOrSingleReader, ReadUnit};
+#[cfg(feature = "mmap")]
+pub use mmap::{parse_fastx_mmap, MmapFastaReader};
+pub use multi::parse_fastx_files;
+pub use path::append_extension;
+pub(crate) use path::{create_file, open_file};
+pub use peek::PeekableFastxReader;
+pub use raw::RawLinesReader;
+pub use record::{
+    mask_header_tabs, mask_header_utf8, write_fasta, write_fastq, write_fastq_with_options,
+    FastqWriteOptions, OwnedRecord, RawRecordParts, SeqLines, SequenceRecord,
+};
+pub use recover::{collect_with_errors, RecordOrError, RecordsWithErrors};
 use std::io;
-pub use utils::{Format, LineEnding};
+pub use twobit::TwoBitReader;
+pub(crate) use utils::id_key;
+pub use utils::{
+    find_line_ending, Compression, Format, LineEnding, LineEndingCounts, OwnedRecords,
+};
+pub use writer::{CompressedWriter, FastxWriter};
+
+#[cfg(feature = "tar")]
+pub use archive::{parse_fastx_tar, TarFastxReader, TarMember, TarMembers};
+#[cfg(feature = "sam")]
+pub use sam::{SamOptions, SamReader, UnalignedOptions, UnalignedWriter};
+#[cfg(feature = "bytes")]
+pub use shared::{read_bytes_batch, BytesRecord};
+#[cfg(feature = "table")]
+pub use table::{TableOptions, TableReader, TableWriter};
 
 #[cfg(test)]
 mod test {
@@ -172,4 +264,16 @@ mod test {
         let expected_err = ParseErrorKind::EmptyFile;
         assert_eq!(actual_err, expected_err);
     }
+
+    #[test]
+    fn test_into_records_iter_stops_after_error() {
+        use crate::FastxReader;
+
+        let reader =
+            parse_fastx_reader(&b"@a\nACGT\n+\nIIII\n@b\nAC\n+\nI\n@c\nA\n+\nI\n"[..]).unwrap();
+        let records: Vec<_> = reader.into_records_iter().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].as_ref().unwrap().id, b"a");
+        assert!(records[1].is_err());
+    }
 }
@@ -0,0 +1,168 @@
+//! Reading several FASTA/FASTQ files one after the other as if they were a single one,
+//! e.g. the files of the different lanes of a sequencing run.
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::path::open_file;
+use crate::parser::utils::{FastxReader, Format};
+use crate::parser::{decompress_reader, get_fastx_reader};
+
+/// Decompressed content of the files concatenated, each file being opened once the
+/// previous one is read entirely.
+struct ChainedFiles {
+    paths: VecDeque<PathBuf>,
+    current: Box<dyn Read + Send>,
+    last_byte: Option<u8>,
+}
+
+impl ChainedFiles {
+    /// Opens the next file, returning false if there are none left
+    fn open_next(&mut self) -> io::Result<bool> {
+        let Some(path) = self.paths.pop_front() else {
+            return Ok(false);
+        };
+        let (reader, _) = decompress_reader(open_file(&path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        })?;
+        self.current = reader;
+        Ok(true)
+    }
+}
+
+impl Read for ChainedFiles {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 {
+                self.last_byte = Some(buf[n - 1]);
+                return Ok(n);
+            }
+            if !self.open_next()? {
+                return Ok(0);
+            }
+            // a file without a final newline would otherwise glue its last line to
+            // the first one of the next file
+            if !matches!(self.last_byte, None | Some(b'\n')) {
+                self.last_byte = Some(b'\n');
+                buf[0] = b'\n';
+                return Ok(1);
+            }
+        }
+    }
+}
+
+/// Returns the first byte of the decompressed content of the file at `path`
+fn first_byte(path: &Path) -> Result<u8, ParseError> {
+    let (_, first_byte) = decompress_reader(open_file(path)?)?;
+    Ok(first_byte)
+}
+
+/// Reads the (possibly compressed) files at `paths` one after the other as a single
+/// FASTA/FASTQ file: line numbers and positions of the records continue from one file
+/// to the next. The files can use different compressions but must all be in the same
+/// format, which is checked on every file before returning the reader.
+///
+/// Each file is only opened once all the records of the previous one were read.
+///
+/// # Errors
+///
+/// Returns an error of kind `NoInput` if `paths` is empty, of kind `WrongFormat` if a
+/// file isn't in the format of the first one and the errors of `parse_fastx_file` for
+/// a file that can't be opened or is empty.
+///
+/// ```no_run
+/// use needletail::parse_fastx_files;
+///
+/// let paths = ["reads_L001.fastq.gz", "reads_L002.fastq.gz"];
+/// let mut reader = parse_fastx_files(&paths).unwrap();
+/// while let Some(record) = reader.next() {
+///     let record = record.unwrap();
+///     println!("{}", String::from_utf8_lossy(record.id()));
+/// }
+/// ```
+pub fn parse_fastx_files<P: AsRef<Path>>(paths: &[P]) -> Result<Box<dyn FastxReader>, ParseError> {
+    let paths: VecDeque<PathBuf> = paths.iter().map(|p| p.as_ref().to_path_buf()).collect();
+    let Some(first_path) = paths.front() else {
+        let mut err = ParseError::new_no_input();
+        err.msg = String::from("No input: no paths were given");
+        return Err(err);
+    };
+    let expected = first_byte(first_path)?;
+    for path in paths.iter().skip(1) {
+        let found = first_byte(path)?;
+        if found != expected {
+            let mut err = match expected {
+                b'>' => ParseError::new_wrong_format(Format::Fasta, ErrorPosition::default()),
+                b'@' => ParseError::new_wrong_format(Format::Fastq, ErrorPosition::default()),
+                _ => ParseError::new_unknown_format(expected),
+            };
+            err.msg = format!("{}: {}", path.display(), err.msg);
+            return Err(err);
+        }
+    }
+
+    let mut files = ChainedFiles {
+        paths,
+        current: Box::new(io::empty()),
+        last_byte: None,
+    };
+    files.open_next()?;
+    get_fastx_reader(files, expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::errors::ParseErrorKind;
+
+    #[test]
+    fn test_parse_fastx_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("L001.fastq");
+        let second = dir.path().join("L002.fastq");
+        fs::write(&first, "@r1\nACGT\n+\nIIII\n@r2\nGG\n+\nII").unwrap();
+        fs::write(&second, "@r3\nTTTT\n+\nIIII\n").unwrap();
+
+        let mut reader = parse_fastx_files(&[&first, &second]).unwrap();
+        let mut records = Vec::new();
+        while let Some(record) = reader.next() {
+            let record = record.unwrap();
+            records.push((record.id().to_vec(), record.start_line_number()));
+        }
+        assert_eq!(
+            records,
+            [
+                (b"r1".to_vec(), 1),
+                (b"r2".to_vec(), 5),
+                (b"r3".to_vec(), 9)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fastx_files_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let fastq = dir.path().join("reads.fastq");
+        let fasta = dir.path().join("reads.fasta");
+        fs::write(&fastq, "@r1\nACGT\n+\nIIII\n").unwrap();
+        fs::write(&fasta, ">r2\nACGT\n").unwrap();
+
+        let err = parse_fastx_files(&[&fastq, &fasta]).err().unwrap();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+        assert!(err.msg.starts_with(&*fasta.to_string_lossy()));
+
+        let no_paths: &[PathBuf] = &[];
+        let err = parse_fastx_files(no_paths).err().unwrap();
+        assert_eq!(err.kind, ParseErrorKind::NoInput);
+    }
+}
@@ -0,0 +1,356 @@
+//! Processing records on several threads: the calling thread reads and parses the
+//! input into record sets, worker threads process the batches and the
+//! results are handed back to the calling thread, in the order of the input if asked.
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::panic;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use crate::errors::ParseError;
+use crate::parser::batch::RecordSet;
+use crate::parser::parse_fastx_file;
+use crate::parser::utils::FastxReader;
+
+/// How [`process_parallel`] splits the work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallelOptions {
+    /// Number of worker threads, the number of cores by default
+    pub n_threads: usize,
+    /// Approximate size in bytes of the batches sent to the workers, 4MB by default.
+    /// See `RecordSet::with_max_bytes`.
+    pub batch_bytes: usize,
+    /// Hand over the results in the order of the batches in the input, whatever order
+    /// the workers finish them in. Off by default.
+    pub ordered: bool,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self {
+            n_threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            batch_bytes: 4 * 1024 * 1024,
+            ordered: false,
+        }
+    }
+}
+
+/// Results of the workers waiting to be handed over
+struct Results<T> {
+    ordered: bool,
+    next: usize,
+    pending: BTreeMap<usize, T>,
+}
+
+impl<T> Results<T> {
+    fn add<O>(&mut self, (index, result): (usize, T), output: &mut O) -> Result<(), ParseError>
+    where
+        O: FnMut(T) -> Result<(), ParseError>,
+    {
+        if !self.ordered {
+            return output(result);
+        }
+        self.pending.insert(index, result);
+        while let Some(result) = self.pending.remove(&self.next) {
+            self.next += 1;
+            output(result)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads all the records of `reader` in batches on the calling thread, calls `work` on
+/// each batch on worker threads (the `RecordSet`s are then reused for the next batches) and calls `output` with the results on the calling
+/// thread as they come, e.g. to write them. With `options.ordered`, the results are
+/// handed over in the order of the batches, so writing records keeps the order of
+/// the input (and read pairs in sync).
+///
+/// Stops at the first error of the reader or of `output` and returns it, after
+/// waiting for the workers to finish the batches they were given.
+///
+/// # Panics
+///
+/// If `work` panics, reading stops and the panic is raised again on the calling thread
+/// once the other workers are done.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::parallel::{process_parallel, ParallelOptions};
+/// use needletail::Sequence;
+///
+/// let fasta = b">r1\nAACC\n>r2\nGGTT\n>r3\nACGT\n";
+/// let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+/// let options = ParallelOptions {
+///     n_threads: 2,
+///     batch_bytes: 1,
+///     ordered: true,
+/// };
+/// let mut reverse_complements = Vec::new();
+/// process_parallel(
+///     &mut reader,
+///     &options,
+///     |batch| batch.iter().map(|r| r.seq.reverse_complement()).collect::<Vec<_>>(),
+///     |rcs| {
+///         reverse_complements.extend(rcs);
+///         Ok(())
+///     },
+/// )
+/// .unwrap();
+/// assert_eq!(reverse_complements, [b"GGTT", b"AACC", b"ACGT"]);
+/// ```
+pub fn process_parallel<T, W, O>(
+    reader: &mut dyn FastxReader,
+    options: &ParallelOptions,
+    work: W,
+    mut output: O,
+) -> Result<(), ParseError>
+where
+    T: Send,
+    W: Fn(&RecordSet) -> T + Sync,
+    O: FnMut(T) -> Result<(), ParseError>,
+{
+    let n_threads = options.n_threads.max(1);
+    // a couple of batches in flight per worker is enough to keep them busy
+    let (batch_sender, batch_receiver) = mpsc::sync_channel::<(usize, RecordSet)>(2 * n_threads);
+    // only the workers hold the receiver: once they all stopped, e.g. because `work`
+    // panicked, sending fails instead of blocking forever
+    let batch_receiver = Arc::new(Mutex::new(batch_receiver));
+    // the workers give the sets back to be filled again
+    let (spare_sender, spare_receiver) = mpsc::channel();
+    // results are taken out as batches are sent so this one doesn't need a bound
+    let (result_sender, result_receiver) = mpsc::channel();
+    // in ordered mode, how many results can wait for a slow batch before reading stops
+    let max_pending = 4 * n_threads;
+    let mut results = Results {
+        ordered: options.ordered,
+        next: 0,
+        pending: BTreeMap::new(),
+    };
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..n_threads)
+            .map(|_| {
+                let result_sender = result_sender.clone();
+                let spare_sender = spare_sender.clone();
+                let batch_receiver = Arc::clone(&batch_receiver);
+                let work = &work;
+                scope.spawn(move || loop {
+                    // the lock is released as soon as a batch is received
+                    let (index, batch) = match batch_receiver.lock().unwrap().recv() {
+                        Ok(batch) => batch,
+                        Err(_) => return,
+                    };
+                    let result = work(&batch);
+                    let _ = spare_sender.send(batch);
+                    if result_sender.send((index, result)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+        drop((result_sender, spare_sender, batch_receiver));
+
+        let mut index = 0;
+        let mut read_result = Ok(());
+        let mut output_result = Ok(());
+        while output_result.is_ok() {
+            let mut batch = spare_receiver
+                .try_recv()
+                .unwrap_or_else(|_| RecordSet::new(usize::MAX).with_max_bytes(options.batch_bytes));
+            let read = reader.read_record_set(&mut batch);
+            // the records read before an error still get processed
+            if !batch.is_empty() {
+                if batch_sender.send((index, batch)).is_err() {
+                    break;
+                }
+                index += 1;
+            }
+            match read {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    read_result = Err(e);
+                    break;
+                }
+            }
+            for result in result_receiver.try_iter() {
+                output_result = output_result.and_then(|_| results.add(result, &mut output));
+            }
+            while output_result.is_ok() && results.pending.len() >= max_pending {
+                match result_receiver.recv() {
+                    Ok(result) => output_result = results.add(result, &mut output),
+                    Err(_) => break,
+                }
+            }
+        }
+        // closing the channel stops the workers once they're done, the results of the
+        // batches read before an error are still handed over
+        drop(batch_sender);
+        for result in result_receiver.iter() {
+            output_result = output_result.and_then(|_| results.add(result, &mut output));
+        }
+        for worker in workers {
+            if let Err(payload) = worker.join() {
+                panic::resume_unwind(payload);
+            }
+        }
+        read_result.and(output_result)
+    })
+}
+
+/// Reads the (possibly compressed) file at `path` and calls `work` on batches of its
+/// records on `n_threads` worker threads, see [`process_parallel`] to get results back.
+///
+/// ```no_run
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use needletail::parser::parallel::parse_fastx_file_parallel;
+///
+/// let bases = AtomicUsize::new(0);
+/// parse_fastx_file_parallel("reads.fastq.gz", 8, |batch| {
+///     let n: usize = batch.iter().map(|r| r.num_bases()).sum();
+///     bases.fetch_add(n, Ordering::Relaxed);
+/// })
+/// .unwrap();
+/// ```
+pub fn parse_fastx_file_parallel<P, W>(path: P, n_threads: usize, work: W) -> Result<(), ParseError>
+where
+    P: AsRef<Path>,
+    W: Fn(&RecordSet) + Sync,
+{
+    let mut reader = parse_fastx_file(path)?;
+    let options = ParallelOptions {
+        n_threads,
+        ..Default::default()
+    };
+    process_parallel(&mut reader, &options, work, |_| Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    fn fasta(n: usize) -> Vec<u8> {
+        (0..n)
+            .flat_map(|i| format!(">r{i}\nACGT\n").into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_ordered() {
+        let data = fasta(200);
+        let mut reader = parse_fastx_reader(&data[..]).unwrap();
+        let options = ParallelOptions {
+            n_threads: 4,
+            batch_bytes: 1,
+            ordered: true,
+        };
+        let mut ids = Vec::new();
+        process_parallel(
+            &mut reader,
+            &options,
+            |batch| {
+                // make later batches finish first now and then
+                let i: u64 = std::str::from_utf8(&batch.records()[0].id[1..])
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                thread::sleep(Duration::from_micros((200 - i) % 7 * 100));
+                batch.iter().map(|r| r.id.clone()).collect::<Vec<_>>()
+            },
+            |batch_ids| {
+                ids.extend(batch_ids);
+                Ok(())
+            },
+        )
+        .unwrap();
+        let expected: Vec<_> = (0..200).map(|i| format!("r{i}").into_bytes()).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_parse_fastx_file_parallel() {
+        let bases = Mutex::new(0);
+        parse_fastx_file_parallel("tests/data/28S.fasta", 3, |batch| {
+            *bases.lock().unwrap() += batch.iter().map(|r| r.num_bases()).sum::<usize>();
+        })
+        .unwrap();
+        let mut reader = parse_fastx_file("tests/data/28S.fasta").unwrap();
+        let mut expected = 0;
+        while let Some(record) = reader.next() {
+            expected += record.unwrap().num_bases();
+        }
+        assert_eq!(*bases.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut data: Vec<u8> = (0..50)
+            .flat_map(|i| format!("@r{i}\nACGT\n+\nIIII\n").into_bytes())
+            .collect();
+        data.extend_from_slice(b"@bad\nACGT\n+\nII\n");
+        let mut reader = parse_fastx_reader(&data[..]).unwrap();
+        let options = ParallelOptions {
+            n_threads: 2,
+            batch_bytes: 1,
+            ordered: true,
+        };
+        let mut count = 0;
+        let err = process_parallel(
+            &mut reader,
+            &options,
+            |batch| batch.len(),
+            |n| {
+                count += n;
+                Ok(())
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnequalLengths);
+        // the batches before the error were all handed over
+        assert_eq!(count, 50);
+
+        let data = fasta(50);
+        let mut reader = parse_fastx_reader(&data[..]).unwrap();
+        let mut calls = 0;
+        let err = process_parallel(
+            &mut reader,
+            &options,
+            |batch| batch.len(),
+            |_| {
+                calls += 1;
+                Err(ParseError::new_no_input())
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::NoInput);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_worker_panics() {
+        // more batches than the channel holds, so the reading thread would block on a
+        // full channel if the panics went unnoticed
+        let data = fasta(100);
+        let mut reader = parse_fastx_reader(&data[..]).unwrap();
+        let options = ParallelOptions {
+            n_threads: 2,
+            batch_bytes: 1,
+            ordered: false,
+        };
+        let payload = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            process_parallel(
+                &mut reader,
+                &options,
+                |_| -> usize { panic!("worker failed") },
+                |_| Ok(()),
+            )
+        }))
+        .unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"worker failed"));
+    }
+}
@@ -0,0 +1,124 @@
+//! Opening files from user-supplied paths. Paths are only handled as `OsStr` so
+//! non-UTF8 file names work, and long absolute paths get the extended-length
+//! prefix on Windows.
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Returns `path` with `.extension` appended to its file name, e.g. the `.fai` index
+/// of `reads.fa` is `reads.fa.fai`, unlike `Path::with_extension` which would replace `.fa`.
+/// Works on file names that aren't valid UTF-8.
+///
+/// ```
+/// use std::path::Path;
+/// use needletail::parser::append_extension;
+///
+/// assert_eq!(append_extension("data/reads.fa", "fai"), Path::new("data/reads.fa.fai"));
+/// ```
+pub fn append_extension<P: AsRef<Path>>(path: P, extension: &str) -> PathBuf {
+    let mut path = OsString::from(path.as_ref());
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Opens a file for reading. The path is added to the message of the error if it
+/// can't be opened.
+pub(crate) fn open_file(path: &Path) -> io::Result<File> {
+    File::open(extended_length(path))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+}
+
+/// Creates (or truncates) a file for writing, adding the path to the message of the
+/// error like `open_file`.
+pub(crate) fn create_file(path: &Path) -> io::Result<File> {
+    File::create(extended_length(path))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+}
+
+/// Paths longer than `MAX_PATH` can only be opened on Windows with the `\\?\` prefix,
+/// which turns off the normalisation of the path: only absolute paths without
+/// `.` or `..` components get it.
+#[cfg(windows)]
+fn extended_length(path: &Path) -> Cow<'_, Path> {
+    use std::path::{Component, Prefix};
+
+    const MAX_PATH: usize = 260;
+    if path.as_os_str().len() < MAX_PATH {
+        return Cow::Borrowed(path);
+    }
+    let mut components = path.components();
+    let mut extended = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => {
+                // `C:reads.fa` is relative to the current directory of the drive
+                if components.next() != Some(Component::RootDir) {
+                    return Cow::Borrowed(path);
+                }
+                let mut extended = OsString::from(r"\\?\");
+                extended.push(prefix.as_os_str());
+                extended
+            }
+            Prefix::UNC(server, share) => {
+                let mut extended = OsString::from(r"\\?\UNC\");
+                extended.push(server);
+                extended.push(r"\");
+                extended.push(share);
+                extended
+            }
+            // already extended-length or a device
+            _ => return Cow::Borrowed(path),
+        },
+        _ => return Cow::Borrowed(path),
+    };
+    for component in components {
+        match component {
+            Component::RootDir => {}
+            Component::Normal(name) => {
+                extended.push(r"\");
+                extended.push(name);
+            }
+            _ => return Cow::Borrowed(path),
+        }
+    }
+    Cow::Owned(PathBuf::from(extended))
+}
+
+#[cfg(not(windows))]
+fn extended_length(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_file;
+    use crate::parser::{Compression, Format};
+
+    #[test]
+    fn test_odd_file_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut names = vec![OsString::from("reads with spaces.fq"), "réads.fq".into()];
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            names.push(OsString::from_vec(b"r\xffads.fq".to_vec()));
+        }
+        for name in names {
+            let path = dir.path().join(&name);
+            std::fs::write(&path, b"@r1\nACGT\n+\nIIII\n").unwrap();
+            let mut reader = parse_fastx_file(&path).unwrap();
+            assert_eq!(reader.next().unwrap().unwrap().id(), b"r1");
+            assert_eq!(Format::from_path_extension(&path), Some(Format::Fastq));
+            let gz = append_extension(&path, "gz");
+            assert_eq!(gz.file_stem(), Some(name.as_os_str()));
+            assert_eq!(Compression::from_path_extension(&gz), Compression::Gzip);
+        }
+
+        let missing = dir.path().join("missing.fq");
+        let err = parse_fastx_file(&missing).err().unwrap();
+        assert!(err.msg.contains("missing.fq"), "{}", err.msg);
+    }
+}
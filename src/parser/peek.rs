@@ -0,0 +1,138 @@
+use crate::errors::ParseError;
+use crate::parser::record::SequenceRecord;
+use crate::parser::synthetic::RecordBuffer;
+use crate::parser::utils::{FastxReader, LineEnding, Position};
+
+/// What the last call to `peek` read from the inner reader
+enum Peeked {
+    Nothing,
+    Record(LineEnding),
+    Error(ParseError),
+    End,
+}
+
+/// A reader that can look at the next record without consuming it, e.g. to check the
+/// read length or the quality encoding of a file before processing it.
+///
+/// The peeked record is copied, so a multi-line FASTA sequence peeked at is returned
+/// on a single line. `position` is the one of the inner reader: after `peek`, it is
+/// already the position of the peeked record.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::{FastxReader, PeekableFastxReader};
+///
+/// let reader = parse_fastx_reader(&b"@r1\nACGT\n+\nIIII\n@r2\nTT\n+\nII\n"[..]).unwrap();
+/// let mut reader = PeekableFastxReader::new(reader);
+/// let read_length = reader.peek().unwrap().unwrap().num_bases();
+/// assert_eq!(read_length, 4);
+/// assert_eq!(reader.next().unwrap().unwrap().id(), b"r1");
+/// assert_eq!(reader.next().unwrap().unwrap().id(), b"r2");
+/// assert!(reader.next().is_none());
+/// ```
+pub struct PeekableFastxReader<R: FastxReader> {
+    reader: R,
+    peeked: Peeked,
+    record: RecordBuffer,
+    // position of the peeked record
+    position: Position,
+}
+
+impl<R: FastxReader> PeekableFastxReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            peeked: Peeked::Nothing,
+            record: RecordBuffer::new(),
+            position: Position::new(0, 0),
+        }
+    }
+
+    /// Returns the record the next call to `next` will return, without consuming it
+    pub fn peek(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        if let Peeked::Nothing = self.peeked {
+            self.peeked = match self.reader.next() {
+                None => Peeked::End,
+                Some(Err(e)) => Peeked::Error(e),
+                Some(Ok(record)) => {
+                    self.record.set_record(&record);
+                    self.position = record.position().clone();
+                    Peeked::Record(record.line_ending())
+                }
+            };
+        }
+        match &self.peeked {
+            Peeked::Nothing | Peeked::End => None,
+            Peeked::Error(e) => Some(Err(e.clone())),
+            Peeked::Record(line_ending) => {
+                Some(Ok(self.record.record(&self.position, Some(*line_ending))))
+            }
+        }
+    }
+
+    /// Returns the inner reader. A peeked record is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: FastxReader> FastxReader for PeekableFastxReader<R> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        match std::mem::replace(&mut self.peeked, Peeked::Nothing) {
+            Peeked::Nothing => self.reader.next(),
+            Peeked::End => None,
+            Peeked::Error(e) => Some(Err(e)),
+            Peeked::Record(line_ending) => {
+                Some(Ok(self.record.record(&self.position, Some(line_ending))))
+            }
+        }
+    }
+
+    fn position(&self) -> &Position {
+        self.reader.position()
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.reader.line_ending()
+    }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        self.reader.has_mixed_line_endings()
+    }
+
+    fn can_skip_invalid_records(&self) -> bool {
+        self.reader.can_skip_invalid_records()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_peek() {
+        let fastq = b"@r1\r\nACGT\r\n+r1\r\nIIII\r\n@r2\r\nTT\r\n+\r\nII";
+        let mut reader = PeekableFastxReader::new(parse_fastx_reader(&fastq[..]).unwrap());
+        // peeking several times doesn't consume the record
+        assert_eq!(reader.peek().unwrap().unwrap().id(), b"r1");
+        let record = reader.peek().unwrap().unwrap();
+        assert_eq!(record.separator(), Some(&b"r1"[..]));
+        assert_eq!(record.line_ending(), LineEnding::Windows);
+        assert_eq!(record.start_line_number(), 1);
+
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(
+            (record.id(), record.qual()),
+            (&b"r1"[..], Some(&b"IIII"[..]))
+        );
+        // without peeking, records come from the inner reader
+        assert_eq!(reader.next().unwrap().unwrap().id(), b"r2");
+        assert!(reader.peek().is_none());
+        assert!(reader.next().is_none());
+
+        let mut reader = PeekableFastxReader::new(parse_fastx_reader(&b"@r1\nACGT\n"[..]).unwrap());
+        assert!(reader.peek().unwrap().is_err());
+        assert!(reader.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Reading raw sequences, one per line and without any header
+use std::io;
+use std::io::Write;
+
+use crate::errors::ParseError;
+use crate::parser::record::SequenceRecord;
+use crate::parser::synthetic::{LineReader, RecordBuffer};
+use crate::parser::utils::{FastxReader, LineEnding, Position};
+
+/// Parser for files containing only sequences, one per line, as dumped by some
+/// instruments. Each non-blank line becomes a FASTA record with a generated id:
+/// `seq_1`, `seq_2`, etc.
+///
+/// ```
+/// use needletail::parser::{FastxReader, RawLinesReader};
+///
+/// let mut reader = RawLinesReader::new(&b"ACGT\n\nGGCC\n"[..]);
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.id(), b"seq_1");
+/// assert_eq!(record.seq().as_ref(), b"ACGT");
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.id(), b"seq_2");
+/// assert_eq!(record.start_line_number(), 3);
+/// assert!(reader.next().is_none());
+/// ```
+pub struct RawLinesReader<R: io::Read> {
+    lines: LineReader<R>,
+    id: Vec<u8>,
+    record: RecordBuffer,
+    position: Position,
+    num_records: u64,
+    line_ending: Option<LineEnding>,
+    mixed_line_endings: bool,
+}
+
+impl<R: io::Read> RawLinesReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: LineReader::new(reader),
+            id: Vec::new(),
+            record: RecordBuffer::new(),
+            position: Position::new(0, 0),
+            num_records: 0,
+            line_ending: None,
+            mixed_line_endings: false,
+        }
+    }
+}
+
+impl<R: io::Read + Send> FastxReader for RawLinesReader<R> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        let line = loop {
+            match self.lines.next_line() {
+                Ok(Some(line)) if !line.content.trim_ascii().is_empty() => break line,
+                Ok(Some(_)) => continue,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e.into())),
+            }
+        };
+        self.position = line.position.clone();
+        let record_line_ending = line.line_ending;
+
+        self.num_records += 1;
+        self.id.clear();
+        // writing to a Vec can't fail
+        write!(&mut self.id, "seq_{}", self.num_records).unwrap();
+        self.record.set(&self.id, line.content, None);
+
+        if self.line_ending.is_none() {
+            self.line_ending = record_line_ending;
+        } else if record_line_ending.is_some() && record_line_ending != self.line_ending {
+            self.mixed_line_endings = true;
+        }
+        Some(Ok(self.record.record(
+            &self.position,
+            record_line_ending.or(self.line_ending),
+        )))
+    }
+
+    fn position(&self) -> &Position {
+        &self.position
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.line_ending
+    }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Format;
+
+    #[test]
+    fn test_raw_lines() {
+        let mut reader = RawLinesReader::new(&b"ACGT\r\n  \r\nGG\nTT"[..]);
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.format(), Format::Fasta);
+        assert_eq!(record.id(), b"seq_1");
+        assert_eq!(record.raw_seq(), b"ACGT");
+        assert_eq!(record.num_bases(), 4);
+        assert_eq!(record.line_ending(), LineEnding::Windows);
+        let mut out = Vec::new();
+        record.write(&mut out, None).unwrap();
+        assert_eq!(out, b">seq_1\r\nACGT\r\n");
+
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.id(), b"seq_2");
+        assert_eq!(record.position(), &Position::new(3, 10));
+        assert_eq!(record.line_ending(), LineEnding::Unix);
+        assert!(reader.has_mixed_line_endings());
+
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.id(), b"seq_3");
+        assert_eq!(record.seq().as_ref(), b"TT");
+        // no line ending on the last line, use the first one found
+        assert_eq!(record.line_ending(), LineEnding::Windows);
+        assert!(reader.next().is_none());
+    }
+}
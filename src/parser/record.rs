@@ -3,10 +3,11 @@ use std::io::Write;
 
 use memchr::memchr;
 
-use crate::errors::ParseError;
+use crate::errors::{ErrorPosition, ParseError};
 use crate::parser::fasta::BufferPosition as FastaBufferPosition;
 use crate::parser::fastq::BufferPosition as FastqBufferPosition;
-use crate::parser::utils::{Format, LineEnding, Position};
+use crate::parser::utils::{trim_cr, Format, LineEnding, Position};
+use crate::quality::error_probabilities;
 use crate::Sequence;
 
 #[derive(Debug, Clone)]
@@ -83,7 +84,7 @@ impl<'a> SequenceRecord<'a> {
 
     /// Returns the cleaned up sequence of the record. For FASTQ it is the same as `raw_seq` but
     /// for FASTA it is `raw_seq` minus all the `\r\n`
-    pub fn seq(&self) -> Cow<[u8]> {
+    pub fn seq(&self) -> Cow<'_, [u8]> {
         match self.buf_pos {
             BufferPositionKind::Fasta(bp) => bp.seq(self.buffer),
             BufferPositionKind::Fastq(bp) => bp.seq(self.buffer).into(),
@@ -100,6 +101,72 @@ impl<'a> SequenceRecord<'a> {
         }
     }
 
+    /// Returns what follows the `+` on the separator line of a FASTQ record (often
+    /// nothing, sometimes a copy of the id). Always `None` for FASTA.
+    #[inline]
+    pub fn separator(&self) -> Option<&[u8]> {
+        match self.buf_pos {
+            BufferPositionKind::Fasta(_) => None,
+            BufferPositionKind::Fastq(bp) => Some(bp.separator(self.buffer)),
+        }
+    }
+
+    /// Returns the probability of each base being wrong according to its Phred+33
+    /// quality, e.g. 0.001 for `I` (Q40). They are decoded with a lookup table on each
+    /// call: see `QualitySequence::error_probabilities_into` to decode them in a
+    /// buffer reused across records. Returns an error of kind `WrongFormat` for FASTA
+    /// records.
+    ///
+    /// ```
+    /// use needletail::parse_fastx_reader;
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@read1\nACG\n+\n!+5\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// assert_eq!(record.error_probabilities().unwrap(), [1.0, 0.1, 0.01]);
+    /// ```
+    pub fn error_probabilities(&self) -> Result<Vec<f32>, ParseError> {
+        self.require_format(Format::Fastq)?;
+        Ok(error_probabilities(self.qual().unwrap_or_default()))
+    }
+
+    /// Returns the parts of the record as they are in the buffer of the parser, without
+    /// copying or scanning anything, to build custom views of records.
+    ///
+    /// ```
+    /// use needletail::parse_fastx_reader;
+    ///
+    /// let mut reader = parse_fastx_reader(&b">read1 desc\r\nACGT\r\nTT\r\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// let parts = record.raw_parts();
+    /// assert_eq!(parts.id, b"read1 desc");
+    /// assert_eq!(parts.seq_lines.collect::<Vec<_>>(), [&b"ACGT"[..], &b"TT"[..]]);
+    /// assert_eq!((parts.sep, parts.qual), (None, None));
+    /// ```
+    pub fn raw_parts(&self) -> RawRecordParts<'_> {
+        match self.buf_pos {
+            BufferPositionKind::Fasta(bp) => RawRecordParts {
+                id: bp.id(self.buffer),
+                seq_lines: SeqLines {
+                    buffer: self.buffer,
+                    line_ends: &bp.seq_pos,
+                    single: None,
+                },
+                sep: None,
+                qual: None,
+            },
+            BufferPositionKind::Fastq(bp) => RawRecordParts {
+                id: bp.id(self.buffer),
+                seq_lines: SeqLines {
+                    buffer: self.buffer,
+                    line_ends: &[],
+                    single: Some(bp.seq(self.buffer)),
+                },
+                sep: Some(bp.separator(self.buffer)),
+                qual: Some(bp.qual(self.buffer)),
+            },
+        }
+    }
+
     /// Returns the full sequence, including line endings. This doesn't include a trailing newline.
     #[inline]
     pub fn all(&self) -> &[u8] {
@@ -118,6 +185,34 @@ impl<'a> SequenceRecord<'a> {
         }
     }
 
+    /// Returns an error of kind `WrongFormat` if the record isn't in `format`, e.g. before
+    /// using its quality
+    ///
+    /// ```
+    /// use needletail::errors::ParseErrorKind;
+    /// use needletail::parse_fastx_reader;
+    /// use needletail::parser::Format;
+    ///
+    /// let mut reader = parse_fastx_reader(&b">r1\nACGT\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// let err = record.require_format(Format::Fastq).unwrap_err();
+    /// assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+    /// assert_eq!(err.position.id.as_deref(), Some("r1"));
+    /// assert!(record.require_format(Format::Fasta).is_ok());
+    /// ```
+    pub fn require_format(&self, format: Format) -> Result<(), ParseError> {
+        if self.format() == format {
+            return Ok(());
+        }
+        Err(ParseError::new_wrong_format(
+            format,
+            ErrorPosition {
+                line: self.start_line_number(),
+                id: Some(String::from_utf8_lossy(self.id()).into()),
+            },
+        ))
+    }
+
     /// Return the line number in the file of the start of the sequence
     pub fn start_line_number(&self) -> u64 {
         self.position.line
@@ -128,6 +223,11 @@ impl<'a> SequenceRecord<'a> {
         self.position
     }
 
+    /// Copy the record into an `OwnedRecord`, stripping the line endings from the sequence
+    pub fn to_owned_record(&self) -> OwnedRecord {
+        OwnedRecord::from(self)
+    }
+
     /// Which line ending is this record using?
     pub fn line_ending(&self) -> LineEnding {
         self.line_ending
@@ -162,6 +262,188 @@ impl<'a> Sequence<'a> for SequenceRecord<'a> {
     fn sequence(&'a self) -> &'a [u8] {
         self.raw_seq()
     }
+
+    /// The parser already knows where the lines are so this is the same as `seq`
+    /// and doesn't need to scan the sequence for line endings.
+    fn strip_returns(&'a self) -> Cow<'a, [u8]> {
+        self.seq()
+    }
+}
+
+/// The parts of a record in the buffer of the parser, see `SequenceRecord::raw_parts`.
+/// None of them include line endings.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RawRecordParts<'a> {
+    /// The header line, without the `>` or `@`
+    pub id: &'a [u8],
+    /// The lines of the sequence: FASTQ sequences always have a single one
+    pub seq_lines: SeqLines<'a>,
+    /// What follows the `+` of the separator line, `None` for FASTA
+    pub sep: Option<&'a [u8]>,
+    /// The quality line, `None` for FASTA
+    pub qual: Option<&'a [u8]>,
+}
+
+/// Iterator over the lines of the sequence of a record, without line endings
+#[derive(Debug, Clone)]
+pub struct SeqLines<'a> {
+    buffer: &'a [u8],
+    // positions of the line endings of FASTA records, starting with the one of the header
+    line_ends: &'a [usize],
+    single: Option<&'a [u8]>,
+}
+
+impl<'a> Iterator for SeqLines<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(line) = self.single.take() {
+            return Some(line);
+        }
+        match self.line_ends {
+            [start, end, ..] => {
+                let line = trim_cr(&self.buffer[start + 1..*end]);
+                self.line_ends = &self.line_ends[1..];
+                Some(line)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A FASTA or FASTQ record that owns its data, so it can be kept around after
+/// the reader moves on to the next record or sent to another thread.
+/// It has the same accessors as `SequenceRecord` and is created with
+/// `SequenceRecord::to_owned_record`.
+///
+/// The sequence never contains line endings.
+///
+/// ```
+/// use std::thread;
+/// use needletail::parse_fastx_reader;
+///
+/// let mut reader = parse_fastx_reader(&b">r1\nAC\nGT\n>r2\nTT\n"[..]).unwrap();
+/// let mut records = Vec::new();
+/// while let Some(record) = reader.next() {
+///     records.push(record.unwrap().to_owned_record());
+/// }
+/// let lengths = thread::scope(|scope| {
+///     let handles: Vec<_> = records
+///         .iter()
+///         .map(|record| scope.spawn(move || record.num_bases()))
+///         .collect();
+///     handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+/// });
+/// assert_eq!(lengths, [4, 2]);
+///
+/// let mut out = Vec::new();
+/// records[0].write(&mut out, None).unwrap();
+/// assert_eq!(out, b">r1\nACGT\n");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OwnedRecord {
+    pub id: Vec<u8>,
+    pub seq: Vec<u8>,
+    /// Always `None` for FASTA and `Some` for FASTQ
+    pub qual: Option<Vec<u8>>,
+}
+
+impl OwnedRecord {
+    /// Returns the format of the record
+    #[inline]
+    pub fn format(&self) -> Format {
+        if self.qual.is_some() {
+            Format::Fastq
+        } else {
+            Format::Fasta
+        }
+    }
+
+    /// Returns the id of the record, same as the `id` field
+    #[inline]
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// Returns the sequence of the record. Never copies it: it's a `Cow` to have the
+    /// same signature as `SequenceRecord::seq`, so code can work on both.
+    #[inline]
+    pub fn seq(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.seq)
+    }
+
+    /// Returns the quality line if there is one, same as the `qual` field
+    #[inline]
+    pub fn qual(&self) -> Option<&[u8]> {
+        self.qual.as_deref()
+    }
+
+    /// Returns the number of bases in the sequence
+    #[inline]
+    pub fn num_bases(&self) -> usize {
+        self.seq.len()
+    }
+
+    /// Write the record to a `Write` instance in its own format, with `\n` line endings
+    /// unless another line ending is given
+    pub fn write(
+        &self,
+        writer: &mut dyn Write,
+        line_ending: Option<LineEnding>,
+    ) -> Result<(), ParseError> {
+        let line_ending = line_ending.unwrap_or(LineEnding::Unix);
+        match &self.qual {
+            Some(qual) => write_fastq(&self.id, &self.seq, Some(qual), writer, line_ending),
+            None => write_fasta(&self.id, &self.seq, writer, line_ending),
+        }
+    }
+
+    /// Returns an error of kind `WrongFormat` if the record isn't in `format`, see
+    /// `SequenceRecord::require_format`. The line of the error is 0 since it isn't known.
+    pub fn require_format(&self, format: Format) -> Result<(), ParseError> {
+        if self.format() == format {
+            return Ok(());
+        }
+        Err(ParseError::new_wrong_format(
+            format,
+            ErrorPosition {
+                line: 0,
+                id: Some(String::from_utf8_lossy(&self.id).into()),
+            },
+        ))
+    }
+
+    /// Returns the probability of each base being wrong according to its Phred+33
+    /// quality, see `SequenceRecord::error_probabilities`
+    pub fn error_probabilities(&self) -> Result<Vec<f32>, ParseError> {
+        self.require_format(Format::Fastq)?;
+        Ok(error_probabilities(
+            self.qual.as_deref().unwrap_or_default(),
+        ))
+    }
+
+    /// Approximate number of bytes the record takes on the heap, i.e. the capacity
+    /// of its buffers. Add `std::mem::size_of::<OwnedRecord>()` to get its total size.
+    pub fn approx_heap_size(&self) -> usize {
+        self.id.capacity() + self.seq.capacity() + self.qual.as_ref().map_or(0, |q| q.capacity())
+    }
+}
+
+impl From<&SequenceRecord<'_>> for OwnedRecord {
+    fn from(record: &SequenceRecord<'_>) -> Self {
+        Self {
+            id: record.id().to_vec(),
+            seq: record.seq().into_owned(),
+            qual: record.qual().map(|q| q.to_vec()),
+        }
+    }
+}
+
+impl<'a> Sequence<'a> for OwnedRecord {
+    fn sequence(&'a self) -> &'a [u8] {
+        &self.seq
+    }
 }
 
 /// Mask tabs in header lines to `|`s
@@ -199,6 +481,8 @@ pub fn write_fasta(
     Ok(())
 }
 
+/// Write a FASTQ record. Sequences without quality get `I` (Q40) qualities,
+/// see `write_fastq_with_options` to make that an error instead.
 pub fn write_fastq(
     id: &[u8],
     seq: &[u8],
@@ -206,6 +490,45 @@ pub fn write_fastq(
     writer: &mut dyn Write,
     line_ending: LineEnding,
 ) -> Result<(), ParseError> {
+    write_fastq_with_options(
+        id,
+        seq,
+        qual,
+        writer,
+        line_ending,
+        &FastqWriteOptions::default(),
+    )
+}
+
+/// How `write_fastq_with_options` writes FASTQ records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FastqWriteOptions<'a> {
+    /// Return an error of kind `InvalidRecord` for a sequence without quality
+    /// instead of making up `I` qualities
+    pub strict: bool,
+    /// What to write after the `+` of the separator line, e.g. the one of the original
+    /// record from `SequenceRecord::separator`. Nothing by default.
+    pub separator: &'a [u8],
+}
+
+/// Write a FASTQ record, see `FastqWriteOptions`
+pub fn write_fastq_with_options(
+    id: &[u8],
+    seq: &[u8],
+    qual: Option<&[u8]>,
+    writer: &mut dyn Write,
+    line_ending: LineEnding,
+    options: &FastqWriteOptions,
+) -> Result<(), ParseError> {
+    if qual.is_none() && options.strict {
+        return Err(ParseError::new_invalid_record(
+            String::from("Can't write a sequence without quality as FASTQ"),
+            ErrorPosition {
+                line: 0,
+                id: Some(String::from_utf8_lossy(id).into()),
+            },
+        ));
+    }
     let ending = line_ending.to_bytes();
     writer.write_all(b"@")?;
     writer.write_all(id)?;
@@ -213,6 +536,7 @@ pub fn write_fastq(
     writer.write_all(seq)?;
     writer.write_all(&ending)?;
     writer.write_all(b"+")?;
+    writer.write_all(options.separator)?;
     writer.write_all(&ending)?;
     // this is kind of a hack, but we want to allow writing out sequences
     // that don't have qualitys so this will mask to "good" if the quality
@@ -263,4 +587,35 @@ mod test {
         let rec = reader.next().unwrap().unwrap();
         assert_eq!(rec.position().byte(), 40);
     }
+
+    #[test]
+    fn test_write_fastq_with_options() {
+        use super::*;
+        use crate::errors::ParseErrorKind;
+
+        let fastq = b"@r1\r\nACGT\r\n+r1\r\nIIII\r\n";
+        let mut reader = parse_fastx_reader(seq(fastq)).unwrap();
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.separator(), Some(&b"r1"[..]));
+        let options = FastqWriteOptions {
+            strict: true,
+            separator: rec.separator().unwrap(),
+        };
+        let mut out = Vec::new();
+        write_fastq_with_options(
+            rec.id(),
+            &rec.seq(),
+            rec.qual(),
+            &mut out,
+            rec.line_ending(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(out, fastq);
+
+        let err =
+            write_fastq_with_options(b"r2", b"AC", None, &mut out, LineEnding::Unix, &options)
+                .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+    }
 }
@@ -0,0 +1,107 @@
+//! Reading as many records as possible from a stream, reporting the errors
+//! instead of stopping at the first one, e.g. for QC reports.
+use crate::errors::{ParseError, ParseErrorKind};
+use crate::parser::record::OwnedRecord;
+use crate::parser::utils::FastxReader;
+
+/// An item of [`RecordsWithErrors`]
+#[derive(Debug)]
+pub enum RecordOrError {
+    Record(OwnedRecord),
+    Error(ParseError),
+}
+
+/// Iterator over the records of a reader where errors are yielded inline instead of
+/// ending the iteration.
+///
+/// After an error, reading carries on with the next record if the reader supports
+/// it (see `FastxReader::can_skip_invalid_records`) and the error isn't an I/O one.
+/// Otherwise the error is the last item.
+pub struct RecordsWithErrors<'a> {
+    reader: &'a mut dyn FastxReader,
+    finished: bool,
+}
+
+impl<'a> RecordsWithErrors<'a> {
+    pub fn new(reader: &'a mut dyn FastxReader) -> Self {
+        Self {
+            reader,
+            finished: false,
+        }
+    }
+}
+
+impl Iterator for RecordsWithErrors<'_> {
+    type Item = RecordOrError;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.reader.next() {
+            Some(Ok(record)) => Some(RecordOrError::Record(record.to_owned_record())),
+            Some(Err(e)) => {
+                self.finished =
+                    e.kind == ParseErrorKind::Io || !self.reader.can_skip_invalid_records();
+                Some(RecordOrError::Error(e))
+            }
+            None => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+}
+
+/// Reads all the records it can from `reader`, returning them along with the
+/// errors encountered. See [`RecordsWithErrors`] for when reading stops.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::collect_with_errors;
+///
+/// let mut reader = parse_fastx_reader(&b"@a\nACGT\n+\nIIII\n@b\nAC\n+\nI\n"[..]).unwrap();
+/// let (records, errors) = collect_with_errors(&mut reader);
+/// assert_eq!(records.len(), 1);
+/// assert_eq!(errors.len(), 1);
+/// ```
+pub fn collect_with_errors(reader: &mut dyn FastxReader) -> (Vec<OwnedRecord>, Vec<ParseError>) {
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+    for item in RecordsWithErrors::new(reader) {
+        match item {
+            RecordOrError::Record(r) => records.push(r),
+            RecordOrError::Error(e) => errors.push(e),
+        }
+    }
+    (records, errors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::GenbankReader;
+
+    #[test]
+    fn test_collect_with_errors_skips_invalid_records() {
+        let genbank = b"LOCUS       A  4 bp
+ORIGIN
+        1 acgt
+//
+garbage
+LOCUS       B  2 bp
+ORIGIN
+        1 gg
+//
+LOCUS       C  2 bp
+ORIGIN
+";
+        let mut reader = GenbankReader::new(&genbank[..]);
+        let (records, errors) = collect_with_errors(&mut reader);
+        let ids: Vec<_> = records.iter().map(|r| r.id.as_slice()).collect();
+        assert_eq!(ids, vec![&b"A"[..], b"B"]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].position.line, 5);
+        assert_eq!(errors[1].position.id.as_deref(), Some("C"));
+    }
+}
@@ -0,0 +1,648 @@
+//! Extracting the reads of SAM files, like `samtools fastq` does, so that re-analysis
+//! can start from alignments without converting them first, and writing reads as
+//! unaligned SAM/BAM, like `samtools import` does.
+use std::io::{self, Write};
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::bgzf::BgzfWriter;
+use crate::parser::record::SequenceRecord;
+use crate::parser::synthetic::{LineReader, RecordBuffer};
+use crate::parser::utils::{id_key, FastxReader, LineEnding, Position};
+use crate::parser::{are_mates, mate_key};
+use crate::Sequence;
+
+/// The read is paired
+const FLAG_PAIRED: u16 = 0x1;
+/// The read is unmapped
+const FLAG_UNMAPPED: u16 = 0x4;
+/// The mate of the read is unmapped
+const FLAG_MATE_UNMAPPED: u16 = 0x8;
+/// The read is mapped to the reverse strand
+const FLAG_REVERSE: u16 = 0x10;
+/// The read is the first of a pair
+const FLAG_READ1: u16 = 0x40;
+/// The read is the second of a pair
+const FLAG_READ2: u16 = 0x80;
+/// Secondary alignment of a read, which is also in the file as a primary one
+const FLAG_SECONDARY: u16 = 0x100;
+/// Supplementary alignment of a read, e.g. the other part of a chimeric read
+const FLAG_SUPPLEMENTARY: u16 = 0x800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamOptions {
+    /// Skip the secondary and supplementary alignments so that each read is only
+    /// returned once
+    pub skip_non_primary: bool,
+    /// Add `/1` or `/2` to the names of paired reads
+    pub mate_suffix: bool,
+}
+
+impl Default for SamOptions {
+    fn default() -> Self {
+        Self {
+            skip_non_primary: true,
+            mate_suffix: false,
+        }
+    }
+}
+
+/// Parser yielding the reads of a SAM file: their name, sequence and quality. Reads
+/// mapped to the reverse strand are reverse complemented (and their qualities
+/// reversed) back to the sequence that was sequenced. Reads without qualities (`*`)
+/// are returned as FASTA records.
+///
+/// The header lines are skipped, as well as the alignments without sequence (`*`),
+/// which are usually secondary ones.
+///
+/// ```
+/// use needletail::parser::{FastxReader, SamOptions, SamReader};
+///
+/// let sam = b"@HD\tVN:1.6\n\
+/// r1\t0\tchr1\t100\t60\t4M\t*\t0\t0\tACGG\tIII#\n\
+/// r2\t16\tchr1\t200\t60\t4M\t*\t0\t0\tAACC\tABCD\n\
+/// r2\t256\tchr2\t300\t0\t4M\t*\t0\t0\tAACC\tABCD\n";
+/// let mut reader = SamReader::new(&sam[..], SamOptions::default());
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!((record.id(), record.qual()), (&b"r1"[..], Some(&b"III#"[..])));
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.seq().as_ref(), b"GGTT");
+/// assert_eq!(record.qual(), Some(&b"DCBA"[..]));
+/// // the secondary alignment is skipped
+/// assert!(reader.next().is_none());
+/// ```
+pub struct SamReader<R: io::Read> {
+    lines: LineReader<R>,
+    options: SamOptions,
+    id: Vec<u8>,
+    seq: Vec<u8>,
+    qual: Vec<u8>,
+    has_qual: bool,
+    record: RecordBuffer,
+    position: Position,
+    line_ending: Option<LineEnding>,
+    mixed_line_endings: bool,
+}
+
+impl<R: io::Read> SamReader<R> {
+    pub fn new(reader: R, options: SamOptions) -> Self {
+        Self {
+            lines: LineReader::new(reader),
+            options,
+            id: Vec::new(),
+            seq: Vec::new(),
+            qual: Vec::new(),
+            has_qual: false,
+            record: RecordBuffer::new(),
+            position: Position::new(0, 0),
+            line_ending: None,
+            mixed_line_endings: false,
+        }
+    }
+
+    /// Reads the next read into `self.id`, `self.seq` and `self.qual`, returns whether
+    /// there was one
+    fn read_record(&mut self) -> Result<bool, ParseError> {
+        while let Some(line) = self.lines.next_line()? {
+            if line.content.is_empty() || line.content.starts_with(b"@") {
+                continue;
+            }
+            let error = |msg: &str, id: &[u8]| {
+                ParseError::new_invalid_record(
+                    msg.to_string(),
+                    ErrorPosition {
+                        line: line.position.line,
+                        id: Some(String::from_utf8_lossy(id).into()),
+                    },
+                )
+            };
+            let fields: Vec<_> = line.content.splitn(12, |c| *c == b'\t').collect();
+            if fields.len() < 11 {
+                return Err(error(
+                    "Expected at least 11 tab-separated fields",
+                    fields[0],
+                ));
+            }
+            let (name, seq, qual) = (fields[0], fields[9], fields[10]);
+            let flag: u16 = std::str::from_utf8(fields[1])
+                .ok()
+                .and_then(|f| f.parse().ok())
+                .ok_or_else(|| error("Invalid FLAG", name))?;
+            let non_primary = flag & (FLAG_SECONDARY | FLAG_SUPPLEMENTARY) != 0;
+            if seq == b"*" || (non_primary && self.options.skip_non_primary) {
+                continue;
+            }
+            if qual != b"*" && qual.len() != seq.len() {
+                return Err(ParseError::new_unequal_length(
+                    seq.len(),
+                    qual.len(),
+                    ErrorPosition {
+                        line: line.position.line,
+                        id: Some(String::from_utf8_lossy(name).into()),
+                    },
+                ));
+            }
+
+            self.id.clear();
+            self.id.extend_from_slice(name);
+            if self.options.mate_suffix {
+                if flag & FLAG_READ1 != 0 {
+                    self.id.extend_from_slice(b"/1");
+                } else if flag & FLAG_READ2 != 0 {
+                    self.id.extend_from_slice(b"/2");
+                }
+            }
+            self.has_qual = qual != b"*";
+            self.seq.clear();
+            self.qual.clear();
+            if flag & FLAG_REVERSE != 0 {
+                self.seq.extend(seq.reverse_complement());
+                if self.has_qual {
+                    self.qual.extend(qual.iter().rev());
+                }
+            } else {
+                self.seq.extend_from_slice(seq);
+                if self.has_qual {
+                    self.qual.extend_from_slice(qual);
+                }
+            }
+
+            self.position = line.position.clone();
+            if self.line_ending.is_none() {
+                self.line_ending = line.line_ending;
+            } else if line.line_ending.is_some() && line.line_ending != self.line_ending {
+                self.mixed_line_endings = true;
+            }
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+impl<R: io::Read + Send> FastxReader for SamReader<R> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        match self.read_record() {
+            Ok(true) => {
+                let qual = self.has_qual.then_some(&self.qual[..]);
+                self.record.set(&self.id, &self.seq, qual);
+                Some(Ok(self.record.record(&self.position, self.line_ending)))
+            }
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn position(&self) -> &Position {
+        &self.position
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.line_ending
+    }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+
+    fn can_skip_invalid_records(&self) -> bool {
+        true
+    }
+}
+
+/// Longest read name allowed by the SAM specification
+const MAX_NAME_LEN: usize = 254;
+/// Bin of the reads without position in BAM records
+const UNMAPPED_BIN: u16 = 4680;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnalignedOptions {
+    /// Read group of all the reads: it is declared in the header and set in the `RG`
+    /// tag of each read
+    pub read_group: Option<String>,
+    /// Sample of the read group (`SM` in the header), only used along `read_group`
+    pub sample: Option<String>,
+}
+
+enum Output<W: Write> {
+    Sam(W),
+    Bam(BgzfWriter<W>),
+}
+
+/// Writes reads as unaligned SAM or BAM (uBAM), the format some pipelines archive raw
+/// reads in: each read has its name, sequence and qualities, optionally with a read
+/// group (`RG` tag) and a UMI (`RX` tag), and all the alignment fields are empty.
+///
+/// Reads are named after the first word of their id, and mates after the part of
+/// their id they share (without `/1` or `/2`). FASTA records have no qualities (`*`).
+///
+/// SAM is written to the underlying writer record by record, so it should be buffered.
+/// Call [`finish`](UnalignedWriter::finish) when you're done writing: for BAM, it
+/// writes the last block and the end of the file.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::{UnalignedOptions, UnalignedWriter};
+///
+/// let options = UnalignedOptions {
+///     read_group: Some("run1".to_string()),
+///     ..Default::default()
+/// };
+/// let mut writer = UnalignedWriter::sam(Vec::new(), options).unwrap();
+/// let mut reader = parse_fastx_reader(&b"@r1 desc\nACGT\n+\nIII#\n"[..]).unwrap();
+/// let record = reader.next().unwrap().unwrap();
+/// writer.write_record(&record, Some(b"AACC")).unwrap();
+/// let sam = writer.finish().unwrap();
+/// assert_eq!(
+///     String::from_utf8(sam).unwrap(),
+///     "@HD\tVN:1.6\tSO:unsorted\n\
+///      @RG\tID:run1\n\
+///      r1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tIII#\tRG:Z:run1\tRX:Z:AACC\n"
+/// );
+/// ```
+pub struct UnalignedWriter<W: Write> {
+    output: Output<W>,
+    read_group: Option<String>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> UnalignedWriter<W> {
+    /// Creates a writer of SAM text and writes the header
+    pub fn sam(mut writer: W, options: UnalignedOptions) -> Result<Self, ParseError> {
+        writer.write_all(&header(&options))?;
+        Ok(Self {
+            output: Output::Sam(writer),
+            read_group: options.read_group,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Creates a writer of BAM, compressed with BGZF, and writes the header
+    pub fn bam(writer: W, options: UnalignedOptions) -> Result<Self, ParseError> {
+        let mut writer = BgzfWriter::new(writer);
+        let text = header(&options);
+        writer.write_all(b"BAM\x01")?;
+        writer.write_all(&(text.len() as u32).to_le_bytes())?;
+        writer.write_all(&text)?;
+        // no reference sequences
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            output: Output::Bam(writer),
+            read_group: options.read_group,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Writes a single-end read, with `umi` in its `RX` tag if there is one
+    pub fn write_record(
+        &mut self,
+        record: &SequenceRecord,
+        umi: Option<&[u8]>,
+    ) -> Result<(), ParseError> {
+        self.write_read(record, id_key(record.id()), FLAG_UNMAPPED, umi)
+    }
+
+    /// Writes the two mates of a pair, with `umi` in their `RX` tag if there is one.
+    /// Returns an error of kind `MismatchedMates` if they aren't the R1 and R2 of the
+    /// same pair, see [`are_mates`](crate::parser::are_mates).
+    pub fn write_pair(
+        &mut self,
+        record1: &SequenceRecord,
+        record2: &SequenceRecord,
+        umi: Option<&[u8]>,
+    ) -> Result<(), ParseError> {
+        if !are_mates(record1.id(), record2.id()) {
+            return Err(ParseError::new_mismatched_mates(
+                record1.id(),
+                record2.id(),
+                record_position(record2),
+                record2.format(),
+            ));
+        }
+        let name = mate_key(record1.id());
+        let flag = FLAG_PAIRED | FLAG_UNMAPPED | FLAG_MATE_UNMAPPED;
+        self.write_read(record1, name, flag | FLAG_READ1, umi)?;
+        self.write_read(record2, name, flag | FLAG_READ2, umi)
+    }
+
+    /// Flushes everything and returns the underlying writer
+    pub fn finish(self) -> Result<W, ParseError> {
+        match self.output {
+            Output::Sam(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+            Output::Bam(writer) => Ok(writer.finish()?),
+        }
+    }
+
+    fn write_read(
+        &mut self,
+        record: &SequenceRecord,
+        name: &[u8],
+        flag: u16,
+        umi: Option<&[u8]>,
+    ) -> Result<(), ParseError> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(ParseError::new_invalid_record(
+                format!("Read names must have between 1 and {MAX_NAME_LEN} characters"),
+                record_position(record),
+            ));
+        }
+        let read_group = self.read_group.as_deref().map(str::as_bytes);
+        let tags = [(b"RG", read_group), (b"RX", umi)];
+        let tags = tags
+            .iter()
+            .filter_map(|(tag, value)| value.map(|value| (*tag, value)));
+        let seq = record.seq();
+        self.buffer.clear();
+        match &mut self.output {
+            Output::Sam(writer) => {
+                sam_line(&mut self.buffer, name, flag, &seq, record.qual(), tags);
+                writer.write_all(&self.buffer)?;
+            }
+            Output::Bam(writer) => {
+                bam_record(&mut self.buffer, name, flag, &seq, record.qual(), tags);
+                writer.write_all(&self.buffer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn record_position(record: &SequenceRecord) -> ErrorPosition {
+    ErrorPosition {
+        line: record.start_line_number(),
+        id: Some(String::from_utf8_lossy(record.id()).into()),
+    }
+}
+
+/// SAM header of unaligned reads, declaring the read group if there is one
+fn header(options: &UnalignedOptions) -> Vec<u8> {
+    let mut header = String::from("@HD\tVN:1.6\tSO:unsorted\n");
+    if let Some(read_group) = &options.read_group {
+        header.push_str("@RG\tID:");
+        header.push_str(read_group);
+        if let Some(sample) = &options.sample {
+            header.push_str("\tSM:");
+            header.push_str(sample);
+        }
+        header.push('\n');
+    }
+    header.into_bytes()
+}
+
+/// Appends the SAM line of an unaligned read to `out`
+fn sam_line<'a>(
+    out: &mut Vec<u8>,
+    name: &[u8],
+    flag: u16,
+    seq: &[u8],
+    qual: Option<&[u8]>,
+    tags: impl Iterator<Item = (&'a [u8; 2], &'a [u8])>,
+) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(format!("\t{flag}\t*\t0\t0\t*\t*\t0\t0\t").as_bytes());
+    out.extend_from_slice(if seq.is_empty() { b"*" } else { seq });
+    out.push(b'\t');
+    match qual {
+        Some(qual) if !qual.is_empty() => out.extend_from_slice(qual),
+        _ => out.push(b'*'),
+    }
+    for (tag, value) in tags {
+        out.push(b'\t');
+        out.extend_from_slice(tag);
+        out.extend_from_slice(b":Z:");
+        out.extend_from_slice(value);
+    }
+    out.push(b'\n');
+}
+
+/// 4-bit code of a base in BAM records, `N` for anything that isn't an IUPAC code
+fn base_code(base: u8) -> u8 {
+    match base.to_ascii_uppercase() {
+        b'=' => 0,
+        b'A' => 1,
+        b'C' => 2,
+        b'M' => 3,
+        b'G' => 4,
+        b'R' => 5,
+        b'S' => 6,
+        b'V' => 7,
+        b'T' => 8,
+        b'W' => 9,
+        b'Y' => 10,
+        b'H' => 11,
+        b'K' => 12,
+        b'D' => 13,
+        b'B' => 14,
+        _ => 15,
+    }
+}
+
+/// Appends the BAM record of an unaligned read to `out`
+fn bam_record<'a>(
+    out: &mut Vec<u8>,
+    name: &[u8],
+    flag: u16,
+    seq: &[u8],
+    qual: Option<&[u8]>,
+    tags: impl Iterator<Item = (&'a [u8; 2], &'a [u8])>,
+) {
+    // size of the record, set once it's written
+    out.extend_from_slice(&[0; 4]);
+    // reference and position
+    out.extend_from_slice(&(-1i32).to_le_bytes());
+    out.extend_from_slice(&(-1i32).to_le_bytes());
+    out.push(name.len() as u8 + 1);
+    // mapping quality
+    out.push(0);
+    out.extend_from_slice(&UNMAPPED_BIN.to_le_bytes());
+    // number of CIGAR operations
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&flag.to_le_bytes());
+    out.extend_from_slice(&(seq.len() as u32).to_le_bytes());
+    // reference and position of the mate, template length
+    out.extend_from_slice(&(-1i32).to_le_bytes());
+    out.extend_from_slice(&(-1i32).to_le_bytes());
+    out.extend_from_slice(&0i32.to_le_bytes());
+    out.extend_from_slice(name);
+    out.push(0);
+    for bases in seq.chunks(2) {
+        let second = bases.get(1).map_or(0, |b| base_code(*b));
+        out.push(base_code(bases[0]) << 4 | second);
+    }
+    match qual {
+        Some(qual) => out.extend(qual.iter().map(|q| q.saturating_sub(33))),
+        None => out.extend(std::iter::repeat_n(0xFF, seq.len())),
+    }
+    for (tag, value) in tags {
+        out.extend_from_slice(tag);
+        out.push(b'Z');
+        out.extend_from_slice(value);
+        out.push(0);
+    }
+    let size = (out.len() - 4) as u32;
+    out[..4].copy_from_slice(&size.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+
+    #[test]
+    fn test_sam_reader() {
+        let sam = b"@SQ\tSN:chr1\tLN:1000\n\
+            p1\t99\tchr1\t1\t60\t3M\t=\t10\t12\tACG\tIII\tNM:i:0\n\
+            p1\t147\tchr1\t10\t60\t3M\t=\t1\t-12\tAAC\tABC\n\
+            p2\t2048\tchr1\t1\t60\t3M\t*\t0\t0\tTTT\t*\n\
+            p3\t4\t*\t0\t0\t*\t*\t0\t0\tGGG\tIII\n\
+            bad\tx\tchr1\t1\t60\t3M\t*\t0\t0\tTTT\tIII\n\
+            short\t0\tchr1\n\
+            p4\t0\tchr1\t1\t60\t3M\t*\t0\t0\tTTT\tII\n";
+        let read_all = |options| {
+            let mut reader = SamReader::new(&sam[..], options);
+            let mut records = Vec::new();
+            while let Some(record) = reader.next() {
+                records.push(
+                    record
+                        .map(|r| {
+                            (
+                                r.id().to_vec(),
+                                r.seq().to_vec(),
+                                r.qual().map(<[u8]>::to_vec),
+                            )
+                        })
+                        .map_err(|e| (e.kind, e.position.line)),
+                );
+            }
+            records
+        };
+        let rec = |id: &[u8], seq: &[u8], qual: Option<&[u8]>| {
+            Ok((id.to_vec(), seq.to_vec(), qual.map(<[u8]>::to_vec)))
+        };
+
+        assert_eq!(
+            read_all(SamOptions::default()),
+            [
+                rec(b"p1", b"ACG", Some(b"III")),
+                rec(b"p1", b"GTT", Some(b"CBA")),
+                rec(b"p3", b"GGG", Some(b"III")),
+                Err((ParseErrorKind::InvalidRecord, 6)),
+                Err((ParseErrorKind::InvalidRecord, 7)),
+                Err((ParseErrorKind::UnequalLengths, 8)),
+            ]
+        );
+
+        let records = read_all(SamOptions {
+            skip_non_primary: false,
+            mate_suffix: true,
+        });
+        assert_eq!(records[0], rec(b"p1/1", b"ACG", Some(b"III")));
+        assert_eq!(records[1], rec(b"p1/2", b"GTT", Some(b"CBA")));
+        assert_eq!(records[2], rec(b"p2", b"TTT", None));
+    }
+
+    fn write_reads(writer: &mut UnalignedWriter<Vec<u8>>) {
+        let mut reader = crate::parse_fastx_reader(&b">single\nACGTN\n"[..]).unwrap();
+        writer
+            .write_record(&reader.next().unwrap().unwrap(), None)
+            .unwrap();
+        let mut reader1 =
+            crate::parse_fastx_reader(&b"@p1/1\nACG\n+\nIII\n@p2/1\nA\n+\nI\n"[..]).unwrap();
+        let mut reader2 =
+            crate::parse_fastx_reader(&b"@p1/2\nTTAC\n+\n#II5\n@p3/2\nA\n+\nI\n"[..]).unwrap();
+        let (record1, record2) = (reader1.next().unwrap(), reader2.next().unwrap());
+        writer
+            .write_pair(&record1.unwrap(), &record2.unwrap(), Some(b"GGAA"))
+            .unwrap();
+        let (record1, record2) = (reader1.next().unwrap(), reader2.next().unwrap());
+        let err = writer
+            .write_pair(&record1.unwrap(), &record2.unwrap(), None)
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MismatchedMates);
+    }
+
+    #[test]
+    fn test_write_unaligned_sam() {
+        let options = UnalignedOptions {
+            read_group: Some("rg1".to_string()),
+            sample: Some("s1".to_string()),
+        };
+        let mut writer = UnalignedWriter::sam(Vec::new(), options).unwrap();
+        write_reads(&mut writer);
+        let sam = writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(sam.clone()).unwrap(),
+            "@HD\tVN:1.6\tSO:unsorted\n\
+             @RG\tID:rg1\tSM:s1\n\
+             single\t4\t*\t0\t0\t*\t*\t0\t0\tACGTN\t*\tRG:Z:rg1\n\
+             p1\t77\t*\t0\t0\t*\t*\t0\t0\tACG\tIII\tRG:Z:rg1\tRX:Z:GGAA\n\
+             p1\t141\t*\t0\t0\t*\t*\t0\t0\tTTAC\t#II5\tRG:Z:rg1\tRX:Z:GGAA\n"
+        );
+
+        // and back
+        let options = SamOptions {
+            mate_suffix: true,
+            ..Default::default()
+        };
+        let mut reader = SamReader::new(&sam[..], options);
+        let mut ids = Vec::new();
+        while let Some(record) = reader.next() {
+            ids.push(record.unwrap().id().to_vec());
+        }
+        assert_eq!(ids, [&b"single"[..], b"p1/1", b"p1/2"]);
+    }
+
+    #[test]
+    fn test_write_unaligned_bam() {
+        use std::io::Read;
+
+        let mut writer = UnalignedWriter::bam(Vec::new(), UnalignedOptions::default()).unwrap();
+        write_reads(&mut writer);
+        let bam = writer.finish().unwrap();
+        let mut data = Vec::new();
+        crate::parser::bgzf::BgzfReader::new(&bam[..])
+            .read_to_end(&mut data)
+            .unwrap();
+
+        let text = b"@HD\tVN:1.6\tSO:unsorted\n";
+        assert_eq!(&data[..4], b"BAM\x01");
+        assert_eq!(data[4..8], (text.len() as u32).to_le_bytes());
+        assert_eq!(&data[8..8 + text.len()], text);
+        assert_eq!(data[8 + text.len()..12 + text.len()], [0; 4]);
+
+        let mut records = Vec::new();
+        let mut rest = &data[12 + text.len()..];
+        while !rest.is_empty() {
+            let size = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+            records.push(&rest[4..4 + size]);
+            rest = &rest[4 + size..];
+        }
+        assert_eq!(records.len(), 3);
+        let int =
+            |record: &[u8], at: usize| i32::from_le_bytes(record[at..at + 4].try_into().unwrap());
+        for record in &records {
+            assert_eq!((int(record, 0), int(record, 4)), (-1, -1));
+            assert_eq!(u16::from_le_bytes([record[10], record[11]]), UNMAPPED_BIN);
+        }
+
+        // single: no qualities
+        let single = records[0];
+        assert_eq!(single[8], 7);
+        assert_eq!(u16::from_le_bytes([single[14], single[15]]), FLAG_UNMAPPED);
+        assert_eq!(int(single, 16), 5);
+        assert_eq!(&single[32..39], b"single\0");
+        assert_eq!(
+            &single[39..],
+            [0x12, 0x48, 0xF0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+
+        // R2, with its UMI
+        let read2 = records[2];
+        assert_eq!(u16::from_le_bytes([read2[14], read2[15]]), 141);
+        assert_eq!(&read2[32..35], b"p1\0");
+        assert_eq!(&read2[35..37], [0x88, 0x12]);
+        assert_eq!(&read2[37..41], [2, 40, 40, 20]);
+        assert_eq!(&read2[41..], b"RXZGGAA\0");
+    }
+}
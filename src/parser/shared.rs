@@ -0,0 +1,156 @@
+//! Records backed by `bytes::Bytes` that can be cheaply cloned and sent to
+//! other threads/tasks.
+//!
+//! All the records of a batch point into a single reference-counted buffer so
+//! cloning a record (or a whole batch) never copies the sequence data.
+use std::io::Write;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::errors::ParseError;
+use crate::parser::record::{write_fasta, write_fastq};
+use crate::parser::utils::{FastxReader, Format, LineEnding};
+use crate::Sequence;
+
+/// A FASTA or FASTQ record whose id, sequence and quality are slices of a
+/// shared buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytesRecord {
+    id: Bytes,
+    seq: Bytes,
+    qual: Option<Bytes>,
+}
+
+impl BytesRecord {
+    /// Returns the format of the record
+    #[inline]
+    pub fn format(&self) -> Format {
+        if self.qual.is_some() {
+            Format::Fastq
+        } else {
+            Format::Fasta
+        }
+    }
+
+    /// Returns the id of the record
+    #[inline]
+    pub fn id(&self) -> &Bytes {
+        &self.id
+    }
+
+    /// Returns the sequence of the record, without any line endings
+    #[inline]
+    pub fn seq(&self) -> &Bytes {
+        &self.seq
+    }
+
+    /// Returns the quality line if there is one.
+    /// Always `None` for FASTA and `Some` for FASTQ, even if the quality line is empty.
+    #[inline]
+    pub fn qual(&self) -> Option<&Bytes> {
+        self.qual.as_ref()
+    }
+
+    /// Write record to a `Write` instance, using the given line ending
+    pub fn write(&self, writer: &mut dyn Write, line_ending: LineEnding) -> Result<(), ParseError> {
+        match self.format() {
+            Format::Fasta => write_fasta(&self.id, &self.seq, writer, line_ending),
+            Format::Fastq => write_fastq(
+                &self.id,
+                &self.seq,
+                self.qual.as_deref(),
+                writer,
+                line_ending,
+            ),
+        }
+    }
+}
+
+impl<'a> Sequence<'a> for BytesRecord {
+    fn sequence(&'a self) -> &'a [u8] {
+        &self.seq
+    }
+}
+
+/// Read up to `max_records` records from `reader` into a single shared buffer.
+///
+/// Returns an empty `Vec` once the reader is exhausted. The first parsing error
+/// encountered is returned and the records read so far in that batch are dropped.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::read_bytes_batch;
+///
+/// let mut reader = parse_fastx_reader(&b">a\nACGT\n>b\nTT\nGG\n>c\nC\n"[..]).unwrap();
+/// let batch = read_bytes_batch(&mut reader, 2).unwrap();
+/// assert_eq!(batch.len(), 2);
+/// assert_eq!(&batch[1].seq()[..], b"TTGG");
+/// let batch = read_bytes_batch(&mut reader, 2).unwrap();
+/// assert_eq!(batch.len(), 1);
+/// assert!(read_bytes_batch(&mut reader, 2).unwrap().is_empty());
+/// ```
+pub fn read_bytes_batch(
+    reader: &mut dyn FastxReader,
+    max_records: usize,
+) -> Result<Vec<BytesRecord>, ParseError> {
+    // (id, seq, qual) offsets into `buffer`
+    let mut offsets = Vec::with_capacity(max_records);
+    let mut buffer = BytesMut::new();
+
+    while offsets.len() < max_records {
+        let record = match reader.next() {
+            Some(r) => r?,
+            None => break,
+        };
+        let id_start = buffer.len();
+        buffer.extend_from_slice(record.id());
+        let seq_start = buffer.len();
+        buffer.extend_from_slice(&record.seq());
+        let qual_start = buffer.len();
+        let qual_end = record.qual().map(|q| {
+            buffer.extend_from_slice(q);
+            buffer.len()
+        });
+        offsets.push((id_start, seq_start, qual_start, qual_end));
+    }
+
+    let buffer = buffer.freeze();
+    Ok(offsets
+        .into_iter()
+        .map(|(id_start, seq_start, qual_start, qual_end)| BytesRecord {
+            id: buffer.slice(id_start..seq_start),
+            seq: buffer.slice(seq_start..qual_start),
+            qual: qual_end.map(|end| buffer.slice(qual_start..end)),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_bytes_batch_fastq() {
+        let mut reader = parse_fastx_reader(&b"@r1\nACGT\n+\nIIII\n@r2\nGG\n+\n#I\n"[..]).unwrap();
+        let batch = read_bytes_batch(&mut reader, 10).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[0].format(), Format::Fastq);
+        assert_eq!(&batch[1].id()[..], b"r2");
+        assert_eq!(batch[1].qual().map(|q| &q[..]), Some(&b"#I"[..]));
+
+        // clones share the same buffer
+        let cloned = batch[0].clone();
+        assert_eq!(cloned.seq().as_ptr(), batch[0].seq().as_ptr());
+
+        let mut out = Vec::new();
+        batch[1].write(&mut out, LineEnding::Unix).unwrap();
+        assert_eq!(out, b"@r2\nGG\n+\n#I\n");
+    }
+
+    #[test]
+    fn test_bytes_batch_error() {
+        let mut reader = parse_fastx_reader(&b"@r1\nACGT\n+\nIII\n"[..]).unwrap();
+        assert!(read_bytes_batch(&mut reader, 10).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Helpers for the readers of line-based formats other than FASTA/FASTQ: their
+//! records are rewritten in FASTA/FASTQ form so they can hand out `SequenceRecord`s
+//! like the FASTA and FASTQ parsers do.
+use std::io;
+use std::io::BufRead;
+
+use crate::parser::fasta::BufferPosition as FastaBufferPosition;
+use crate::parser::fastq::BufferPosition as FastqBufferPosition;
+use crate::parser::record::SequenceRecord;
+use crate::parser::utils::{Format, LineEnding, Position, BUFSIZE};
+
+/// Reads a stream line by line, keeping track of the position of each line
+pub(crate) struct LineReader<R: io::Read> {
+    reader: io::BufReader<R>,
+    line: Vec<u8>,
+    // position of the current line
+    position: Position,
+    // position of the next line to read
+    next_position: Position,
+}
+
+impl<R: io::Read> LineReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader: io::BufReader::with_capacity(BUFSIZE, reader),
+            line: Vec::new(),
+            position: Position::new(0, 0),
+            next_position: Position::new(1, 0),
+        }
+    }
+
+    /// Returns the next line, `None` at the end of the stream
+    pub(crate) fn next_line(&mut self) -> io::Result<Option<Line<'_>>> {
+        self.line.clear();
+        let n = self.reader.read_until(b'\n', &mut self.line)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        self.position = self.next_position.clone();
+        self.next_position.line += 1;
+        self.next_position.byte += n as u64;
+
+        let (content, line_ending) = match self.line.strip_suffix(b"\n") {
+            Some(l) => match l.strip_suffix(b"\r") {
+                Some(l) => (l, Some(LineEnding::Windows)),
+                None => (l, Some(LineEnding::Unix)),
+            },
+            None => (&self.line[..], None),
+        };
+        Ok(Some(Line {
+            content,
+            line_ending,
+            position: &self.position,
+        }))
+    }
+}
+
+pub(crate) struct Line<'a> {
+    /// The line without its line ending
+    pub(crate) content: &'a [u8],
+    /// `None` for a last line without any line ending
+    pub(crate) line_ending: Option<LineEnding>,
+    pub(crate) position: &'a Position,
+}
+
+/// A single record, kept as `>id\nSEQ` or `@id\nSEQ\n+\nQUAL`
+pub(crate) struct RecordBuffer {
+    data: Vec<u8>,
+    fasta: FastaBufferPosition,
+    fastq: FastqBufferPosition,
+    format: Format,
+}
+
+impl RecordBuffer {
+    pub(crate) fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            fasta: FastaBufferPosition {
+                start: 0,
+                seq_pos: Vec::with_capacity(2),
+            },
+            fastq: FastqBufferPosition {
+                start: 0,
+                end: 0,
+                seq: 0,
+                sep: 0,
+                qual: 0,
+            },
+            format: Format::Fasta,
+        }
+    }
+
+    /// Replace the current record. It is a FASTQ record if `qual` is set.
+    pub(crate) fn set(&mut self, id: &[u8], seq: &[u8], qual: Option<&[u8]>) {
+        self.set_with_separator(id, seq, qual, b"");
+    }
+
+    /// Replace the current record with a copy of `record`, including the content of its
+    /// FASTQ separator line
+    pub(crate) fn set_record(&mut self, record: &SequenceRecord) {
+        self.set_with_separator(
+            record.id(),
+            &record.seq(),
+            record.qual(),
+            record.separator().unwrap_or_default(),
+        );
+    }
+
+    pub(crate) fn set_with_separator(
+        &mut self,
+        id: &[u8],
+        seq: &[u8],
+        qual: Option<&[u8]>,
+        sep: &[u8],
+    ) {
+        self.data.clear();
+        match qual {
+            None => {
+                self.format = Format::Fasta;
+                self.data.push(b'>');
+                self.data.extend_from_slice(id);
+                let id_end = self.data.len();
+                self.data.push(b'\n');
+                self.data.extend_from_slice(seq);
+                self.fasta.seq_pos.clear();
+                self.fasta.seq_pos.extend([id_end, self.data.len()]);
+            }
+            Some(qual) => {
+                self.format = Format::Fastq;
+                self.data.push(b'@');
+                self.data.extend_from_slice(id);
+                self.data.push(b'\n');
+                self.fastq.seq = self.data.len();
+                self.data.extend_from_slice(seq);
+                self.data.push(b'\n');
+                self.fastq.sep = self.data.len();
+                self.data.push(b'+');
+                self.data.extend_from_slice(sep);
+                self.data.push(b'\n');
+                self.fastq.qual = self.data.len();
+                self.data.extend_from_slice(qual);
+                self.fastq.end = self.data.len();
+            }
+        }
+    }
+
+    pub(crate) fn record<'a>(
+        &'a self,
+        position: &'a Position,
+        line_ending: Option<LineEnding>,
+    ) -> SequenceRecord<'a> {
+        match self.format {
+            Format::Fasta => {
+                SequenceRecord::new_fasta(&self.data, &self.fasta, position, line_ending)
+            }
+            Format::Fastq => {
+                SequenceRecord::new_fastq(&self.data, &self.fastq, position, line_ending)
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Reading and writing sequences stored in CSV/TSV tables, one record per row,
+//! as exchanged by amplicon and synthesis workflows.
+use std::io;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::record::SequenceRecord;
+use crate::parser::synthetic::RecordBuffer;
+use crate::parser::utils::{FastxReader, LineEnding, Position};
+
+/// Where to find the id, sequence and quality of the records in a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableOptions {
+    /// Field delimiter, `,` by default
+    pub delimiter: u8,
+    /// Whether the first row is a header, `true` by default
+    pub has_header: bool,
+    /// Index of the id column, 0 by default
+    pub id_column: usize,
+    /// Index of the sequence column, 1 by default
+    pub seq_column: usize,
+    /// Index of the quality column, if any. Rows are read as FASTQ records if set.
+    pub qual_column: Option<usize>,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_header: true,
+            id_column: 0,
+            seq_column: 1,
+            qual_column: None,
+        }
+    }
+}
+
+impl TableOptions {
+    /// The default options with tabs as delimiter
+    pub fn tsv() -> Self {
+        Self {
+            delimiter: b'\t',
+            ..Default::default()
+        }
+    }
+
+    fn num_columns(&self) -> usize {
+        self.id_column
+            .max(self.seq_column)
+            .max(self.qual_column.unwrap_or(0))
+            + 1
+    }
+}
+
+fn csv_error(err: csv::Error) -> ParseError {
+    if err.is_io_error() {
+        if let csv::ErrorKind::Io(e) = err.into_kind() {
+            return e.into();
+        }
+        unreachable!("checked with is_io_error");
+    }
+    let line = err.position().map(|p| p.line()).unwrap_or_default();
+    ParseError::new_invalid_record(err.to_string(), ErrorPosition { line, id: None })
+}
+
+fn column<'a>(
+    row: &'a csv::ByteRecord,
+    index: usize,
+    name: &str,
+    line: u64,
+) -> Result<&'a [u8], ParseError> {
+    row.get(index).ok_or_else(|| {
+        ParseError::new_invalid_record(
+            format!("Missing {name} column (column {})", index + 1),
+            ErrorPosition { line, id: None },
+        )
+    })
+}
+
+/// Parser for sequences stored in a CSV/TSV table, e.g. exported from a spreadsheet.
+///
+/// Quoted fields are supported and rows can have more columns than the ones used.
+///
+/// ```
+/// use needletail::parser::{FastxReader, TableOptions, TableReader};
+///
+/// let table = b"name,sequence\nprimer1,ACGT\nprimer2,\"GGCC\"\n";
+/// let mut reader = TableReader::new(&table[..], TableOptions::default());
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.id(), b"primer1");
+/// assert_eq!(record.seq().as_ref(), b"ACGT");
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.seq().as_ref(), b"GGCC");
+/// assert!(reader.next().is_none());
+/// ```
+pub struct TableReader<R: io::Read> {
+    reader: csv::Reader<R>,
+    options: TableOptions,
+    row: csv::ByteRecord,
+    record: RecordBuffer,
+    position: Position,
+    line_ending: Option<LineEnding>,
+}
+
+impl<R: io::Read> TableReader<R> {
+    pub fn new(reader: R, options: TableOptions) -> Self {
+        Self {
+            reader: csv::ReaderBuilder::new()
+                .delimiter(options.delimiter)
+                .has_headers(options.has_header)
+                .flexible(true)
+                .from_reader(reader),
+            options,
+            row: csv::ByteRecord::new(),
+            record: RecordBuffer::new(),
+            position: Position::new(0, 0),
+            line_ending: None,
+        }
+    }
+
+    fn read_row(&mut self) -> Result<bool, ParseError> {
+        if !self
+            .reader
+            .read_byte_record(&mut self.row)
+            .map_err(csv_error)?
+        {
+            return Ok(false);
+        }
+        if let Some(pos) = self.row.position() {
+            self.position = Position::new(pos.line(), pos.byte());
+        }
+        let line = self.position.line;
+        let id = column(&self.row, self.options.id_column, "id", line)?;
+        let seq = column(&self.row, self.options.seq_column, "sequence", line)?;
+        let qual = match self.options.qual_column {
+            Some(index) => {
+                let qual = column(&self.row, index, "quality", line)?;
+                if qual.len() != seq.len() {
+                    return Err(ParseError::new_unequal_length(
+                        seq.len(),
+                        qual.len(),
+                        ErrorPosition {
+                            line,
+                            id: Some(String::from_utf8_lossy(id).into()),
+                        },
+                    ));
+                }
+                Some(qual)
+            }
+            None => None,
+        };
+        self.record.set(id, seq, qual);
+        Ok(true)
+    }
+}
+
+impl<R: io::Read + Send> FastxReader for TableReader<R> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        match self.read_row() {
+            Ok(true) => {
+                // line endings are handled by the CSV parser and not kept
+                let line_ending = *self.line_ending.get_or_insert(LineEnding::Unix);
+                Some(Ok(self.record.record(&self.position, Some(line_ending))))
+            }
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn position(&self) -> &Position {
+        &self.position
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.line_ending
+    }
+
+    fn can_skip_invalid_records(&self) -> bool {
+        true
+    }
+}
+
+/// Writes records as rows of a CSV/TSV table, using the same `TableOptions` as
+/// `TableReader`. Unused columns are left empty and the header, if any, is written
+/// before the first record.
+///
+/// ```
+/// use needletail::parser::{TableOptions, TableWriter};
+///
+/// let mut writer = TableWriter::new(Vec::new(), TableOptions::tsv());
+/// writer.write(b"primer1", b"ACGT", None).unwrap();
+/// assert_eq!(writer.into_inner().unwrap(), b"id\tsequence\nprimer1\tACGT\n");
+/// ```
+pub struct TableWriter<W: io::Write> {
+    writer: csv::Writer<W>,
+    options: TableOptions,
+    header_written: bool,
+}
+
+impl<W: io::Write> TableWriter<W> {
+    pub fn new(writer: W, options: TableOptions) -> Self {
+        Self {
+            writer: csv::WriterBuilder::new()
+                .delimiter(options.delimiter)
+                .from_writer(writer),
+            header_written: !options.has_header,
+            options,
+        }
+    }
+
+    fn write_row(&mut self, id: &[u8], seq: &[u8], qual: Option<&[u8]>) -> Result<(), ParseError> {
+        let mut row: Vec<&[u8]> = vec![b""; self.options.num_columns()];
+        row[self.options.id_column] = id;
+        row[self.options.seq_column] = seq;
+        if let (Some(index), Some(qual)) = (self.options.qual_column, qual) {
+            row[index] = qual;
+        }
+        self.writer.write_record(&row).map_err(csv_error)
+    }
+
+    /// Writes a row. The quality is ignored if there is no quality column.
+    pub fn write(&mut self, id: &[u8], seq: &[u8], qual: Option<&[u8]>) -> Result<(), ParseError> {
+        if !self.header_written {
+            self.header_written = true;
+            self.write_row(b"id", b"sequence", Some(b"quality"))?;
+        }
+        self.write_row(id, seq, qual)
+    }
+
+    /// Writes a record, without the line endings of its sequence
+    pub fn write_record(&mut self, record: &SequenceRecord) -> Result<(), ParseError> {
+        self.write(record.id(), &record.seq(), record.qual())
+    }
+
+    pub fn flush(&mut self) -> Result<(), ParseError> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// Flushes everything and returns the underlying writer
+    pub fn into_inner(self) -> Result<W, ParseError> {
+        self.writer
+            .into_inner()
+            .map_err(|e| ParseError::from(io::Error::new(e.error().kind(), e.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parser::Format;
+
+    #[test]
+    fn test_table_roundtrip_with_quality() {
+        let options = TableOptions {
+            has_header: false,
+            id_column: 2,
+            seq_column: 0,
+            qual_column: Some(1),
+            ..TableOptions::tsv()
+        };
+        let mut reader =
+            TableReader::new(&b"ACGT\tII#I\tr1\textra\nGG\t##\tr2\n"[..], options.clone());
+        let mut writer = TableWriter::new(Vec::new(), options);
+        while let Some(record) = reader.next() {
+            let record = record.unwrap();
+            assert_eq!(record.format(), Format::Fastq);
+            writer.write_record(&record).unwrap();
+        }
+        assert_eq!(reader.position().line(), 2);
+        assert_eq!(
+            writer.into_inner().unwrap(),
+            b"ACGT\tII#I\tr1\nGG\t##\tr2\n"
+        );
+    }
+
+    #[test]
+    fn test_table_errors() {
+        let mut reader = TableReader::new(&b"id,seq\nr1\n"[..], TableOptions::default());
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        assert_eq!(err.position.line, 2);
+
+        let options = TableOptions {
+            qual_column: Some(2),
+            ..Default::default()
+        };
+        let mut reader = TableReader::new(&b"id,seq,qual\nr1,ACGT,II\n"[..], options);
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnequalLengths);
+        assert_eq!(err.position.id.as_deref(), Some("r1"));
+    }
+}
@@ -0,0 +1,563 @@
+//! Reading UCSC `.2bit` files, which store genomes with 2 bits per base along with the
+//! regions of `N`s and the soft-masked (lowercase) regions of each sequence.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::path::open_file;
+use crate::parser::record::SequenceRecord;
+use crate::parser::synthetic::RecordBuffer;
+use crate::parser::utils::{FastxReader, LineEnding, Position};
+
+/// First 4 bytes of a 2bit file, in the byte order of the rest of the file
+const SIGNATURE: u32 = 0x1A41_2743;
+/// Bases in the order of their 2-bit code
+const BASES: [u8; 4] = *b"TCAG";
+
+/// Where a sequence is in the file and, once it has been read, its layout
+struct Entry {
+    name: String,
+    offset: u64,
+    layout: Option<Layout>,
+}
+
+/// What comes before the bases of a sequence
+struct Layout {
+    length: u64,
+    // offset of the packed bases in the file
+    dna_offset: u64,
+    // sorted (start, end) of the regions of `N`s and of the soft-masked regions
+    n_blocks: Vec<(u64, u64)>,
+    mask_blocks: Vec<(u64, u64)>,
+}
+
+/// Ranges of `start..end` covered by `blocks`, relative to `start`
+fn overlapping(
+    blocks: &[(u64, u64)],
+    start: u64,
+    end: u64,
+) -> impl Iterator<Item = Range<usize>> + '_ {
+    let first = blocks.partition_point(|(_, block_end)| *block_end <= start);
+    blocks[first..]
+        .iter()
+        .take_while(move |(block_start, _)| *block_start < end)
+        .map(move |(block_start, block_end)| {
+            (*block_start.max(&start) - start) as usize..(*block_end.min(&end) - start) as usize
+        })
+}
+
+fn invalid_file(msg: String, name: Option<&str>) -> ParseError {
+    ParseError::new_invalid_record(
+        msg,
+        ErrorPosition {
+            line: 0,
+            id: name.map(String::from),
+        },
+    )
+}
+
+/// Reads the sequences of a `.2bit` file, as records or regions of them.
+///
+/// `N`s are restored from the `N` blocks of the file and soft-masked regions are in
+/// lowercase, unless turned off with [`with_soft_mask`](TwoBitReader::with_soft_mask).
+/// Both byte orders and both versions of the format (32 and 64-bit offsets) are read.
+///
+/// The sequences are also read in order with `FastxReader`, as FASTA records. Their
+/// position is the offset of their first base in the file, there are no lines.
+///
+/// ```no_run
+/// use needletail::parser::{FastxReader, TwoBitReader};
+///
+/// let mut reader = TwoBitReader::from_path("hg38.2bit").unwrap();
+/// let region = reader.fetch("chr1", 10_000, 10_100).unwrap();
+/// assert_eq!(region.num_bases(), 100);
+///
+/// // or all the sequences, one after the other
+/// while let Some(record) = reader.next() {
+///     println!("{}", String::from_utf8_lossy(record.unwrap().id()));
+/// }
+/// ```
+pub struct TwoBitReader<R: Read + Seek> {
+    reader: R,
+    big_endian: bool,
+    entries: Vec<Entry>,
+    names: HashMap<String, usize>,
+    soft_mask: bool,
+    packed: Vec<u8>,
+    seq: Vec<u8>,
+    record: RecordBuffer,
+    position: Position,
+    // sequence returned by the next call to `next`
+    next: usize,
+}
+
+impl TwoBitReader<BufReader<File>> {
+    /// Opens the 2bit file at `path`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Self::new(BufReader::new(open_file(path.as_ref())?))
+    }
+}
+
+impl<R: Read + Seek> TwoBitReader<R> {
+    /// Reads the header and the list of sequences of the 2bit file `reader`. It is read
+    /// in small pieces, so it should be buffered.
+    pub fn new(mut reader: R) -> Result<Self, ParseError> {
+        reader.seek(SeekFrom::Start(0))?;
+        let mut header = [0; 16];
+        reader.read_exact(&mut header)?;
+        let signature = [header[0], header[1], header[2], header[3]];
+        let big_endian = if u32::from_le_bytes(signature) == SIGNATURE {
+            false
+        } else if u32::from_be_bytes(signature) == SIGNATURE {
+            true
+        } else {
+            return Err(invalid_file("Not a 2bit file".to_string(), None));
+        };
+
+        let mut this = Self {
+            reader,
+            big_endian,
+            entries: Vec::new(),
+            names: HashMap::new(),
+            soft_mask: true,
+            packed: Vec::new(),
+            seq: Vec::new(),
+            record: RecordBuffer::new(),
+            position: Position::new(0, 0),
+            next: 0,
+        };
+        let number = |i: usize| {
+            let bytes = [header[i], header[i + 1], header[i + 2], header[i + 3]];
+            this.u32_from(bytes)
+        };
+        let (version, count) = (number(4), number(8));
+        if version > 1 {
+            let msg = format!("Unsupported 2bit version {version}");
+            return Err(invalid_file(msg, None));
+        }
+
+        for _ in 0..count {
+            let mut name_len = [0];
+            this.reader.read_exact(&mut name_len)?;
+            let mut name = vec![0; name_len[0] as usize];
+            this.reader.read_exact(&mut name)?;
+            let name = String::from_utf8(name)
+                .map_err(|_| invalid_file("Sequence name isn't UTF-8".to_string(), None))?;
+            // version 1 has 64-bit offsets, for files over 4GB
+            let offset = if version == 1 {
+                let mut bytes = [0; 8];
+                this.reader.read_exact(&mut bytes)?;
+                if big_endian {
+                    u64::from_be_bytes(bytes)
+                } else {
+                    u64::from_le_bytes(bytes)
+                }
+            } else {
+                this.read_u32()? as u64
+            };
+            if this
+                .names
+                .insert(name.clone(), this.entries.len())
+                .is_some()
+            {
+                let msg = format!("Sequence '{name}' is in the file twice");
+                return Err(invalid_file(msg, Some(&name)));
+            }
+            this.entries.push(Entry {
+                name,
+                offset,
+                layout: None,
+            });
+        }
+        Ok(this)
+    }
+
+    /// Whether the soft-masked regions are in lowercase (the default) or in uppercase
+    /// like the rest of the sequence
+    pub fn with_soft_mask(mut self, soft_mask: bool) -> Self {
+        self.soft_mask = soft_mask;
+        self
+    }
+
+    /// Names of the sequences, in the order of the file
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Number of bases of the sequence `name`
+    pub fn sequence_length(&mut self, name: &str) -> Result<u64, ParseError> {
+        let i = self.index_of(name)?;
+        Ok(self.layout(i)?.length)
+    }
+
+    /// Returns the bases `start..end` (0-based, end excluded) of the sequence `name`.
+    /// Fails with an `InvalidRegion` error if there is no such sequence or region.
+    pub fn fetch(
+        &mut self,
+        name: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<SequenceRecord<'_>, ParseError> {
+        let i = self.index_of(name)?;
+        self.fetch_index(i, start, Some(end))
+    }
+
+    /// Returns the whole sequence `name`
+    pub fn fetch_all(&mut self, name: &str) -> Result<SequenceRecord<'_>, ParseError> {
+        let i = self.index_of(name)?;
+        self.fetch_index(i, 0, None)
+    }
+
+    fn index_of(&self, name: &str) -> Result<usize, ParseError> {
+        self.names.get(name).copied().ok_or_else(|| {
+            ParseError::new_invalid_region(format!("No sequence '{name}' in the file"))
+        })
+    }
+
+    /// Fetches `start..end` of the `i`-th sequence, up to its end if `end` is `None`
+    fn fetch_index(
+        &mut self,
+        i: usize,
+        start: u64,
+        end: Option<u64>,
+    ) -> Result<SequenceRecord<'_>, ParseError> {
+        self.layout(i)?;
+        let (entry, reader) = (&self.entries[i], &mut self.reader);
+        let layout = entry.layout.as_ref().expect("the layout was just read");
+        let end = end.unwrap_or(layout.length);
+        if start > end || end > layout.length {
+            let msg = format!(
+                "Region {start}-{end} is outside of '{}' ({} bases)",
+                entry.name, layout.length
+            );
+            return Err(ParseError::new_invalid_region(msg));
+        }
+
+        // 4 bases per byte, the first one in the highest bits
+        let first_byte = start / 4;
+        self.seq.clear();
+        if start < end {
+            self.packed
+                .resize((end.div_ceil(4) - first_byte) as usize, 0);
+            reader.seek(SeekFrom::Start(layout.dna_offset + first_byte))?;
+            reader.read_exact(&mut self.packed)?;
+            self.seq.extend((start..end).map(|pos| {
+                let byte = self.packed[(pos / 4 - first_byte) as usize];
+                BASES[(byte >> (6 - 2 * (pos % 4)) & 3) as usize]
+            }));
+            for range in overlapping(&layout.n_blocks, start, end) {
+                self.seq[range].fill(b'N');
+            }
+            if self.soft_mask {
+                for range in overlapping(&layout.mask_blocks, start, end) {
+                    self.seq[range].make_ascii_lowercase();
+                }
+            }
+        }
+
+        self.record.set(entry.name.as_bytes(), &self.seq, None);
+        self.position = Position::new(0, layout.dna_offset + first_byte);
+        Ok(self.record.record(&self.position, Some(LineEnding::Unix)))
+    }
+
+    /// Reads the length and blocks of the `i`-th sequence if they haven't been yet
+    fn layout(&mut self, i: usize) -> Result<&Layout, ParseError> {
+        if self.entries[i].layout.is_none() {
+            let layout = self.read_layout(self.entries[i].offset).map_err(|e| {
+                let msg = format!("Invalid 2bit sequence record: {e}");
+                invalid_file(msg, Some(&self.entries[i].name))
+            })?;
+            self.entries[i].layout = Some(layout);
+        }
+        Ok(self.entries[i]
+            .layout
+            .as_ref()
+            .expect("the layout was just set"))
+    }
+
+    fn read_layout(&mut self, offset: u64) -> io::Result<Layout> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let length = self.read_u32()? as u64;
+        let n_blocks = self.read_blocks(length)?;
+        let mask_blocks = self.read_blocks(length)?;
+        // reserved
+        self.read_u32()?;
+        let dna_offset = self.reader.stream_position()?;
+        // checked once here so fetches never allocate for bases that aren't in the file
+        let file_size = self.reader.seek(SeekFrom::End(0))?;
+        if dna_offset + length.div_ceil(4) > file_size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{length} bases don't fit in the file"),
+            ));
+        }
+        Ok(Layout {
+            length,
+            dna_offset,
+            n_blocks,
+            mask_blocks,
+        })
+    }
+
+    /// Reads a list of blocks: their number, their starts then their sizes.
+    /// They have to be sorted, not overlap and end within the `length` of the sequence.
+    fn read_blocks(&mut self, length: u64) -> io::Result<Vec<(u64, u64)>> {
+        let count = self.read_u32()? as usize;
+        // the count isn't trusted to allocate, a corrupted file could ask for GBs
+        let mut starts = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            starts.push(self.read_u32()? as u64);
+        }
+        let mut blocks = Vec::with_capacity(starts.len());
+        let mut previous_end = 0;
+        for start in starts {
+            let end = start + self.read_u32()? as u64;
+            if start < previous_end || end > length {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("block {start}-{end} is unsorted, overlapping or past the end"),
+                ));
+            }
+            previous_end = end;
+            blocks.push((start, end));
+        }
+        Ok(blocks)
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(self.u32_from(bytes))
+    }
+
+    fn u32_from(&self, bytes: [u8; 4]) -> u32 {
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+}
+
+impl<R: Read + Seek + Send> FastxReader for TwoBitReader<R> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        if self.next == self.entries.len() {
+            return None;
+        }
+        self.next += 1;
+        Some(self.fetch_index(self.next - 1, 0, None))
+    }
+
+    fn position(&self) -> &Position {
+        &self.position
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        None
+    }
+
+    fn can_skip_invalid_records(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::errors::ParseErrorKind;
+
+    /// Runs of bytes matching `f` in `seq`, as (start, size)
+    fn runs(seq: &[u8], f: impl Fn(u8) -> bool) -> Vec<(u32, u32)> {
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for (i, b) in seq.iter().enumerate() {
+            if !f(*b) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((start, size)) if (*start + *size) as usize == i => *size += 1,
+                _ => runs.push((i as u32, 1)),
+            }
+        }
+        runs
+    }
+
+    /// Encodes the sequences in a 2bit file, like `faToTwoBit`
+    fn two_bit(sequences: &[(&str, &[u8])], big_endian: bool, version: u32) -> Vec<u8> {
+        let word = |n: u32| {
+            if big_endian {
+                n.to_be_bytes()
+            } else {
+                n.to_le_bytes()
+            }
+        };
+        let mut records = Vec::new();
+        let mut offsets = Vec::new();
+        let index_size: usize = sequences
+            .iter()
+            .map(|(name, _)| 1 + name.len() + if version == 1 { 8 } else { 4 })
+            .sum();
+        for (_, seq) in sequences {
+            offsets.push((16 + index_size + records.len()) as u64);
+            records.extend(word(seq.len() as u32));
+            for blocks in [
+                runs(seq, |b| b.eq_ignore_ascii_case(&b'N')),
+                runs(seq, |b| b.is_ascii_lowercase()),
+            ] {
+                records.extend(word(blocks.len() as u32));
+                records.extend(blocks.iter().flat_map(|(start, _)| word(*start)));
+                records.extend(blocks.iter().flat_map(|(_, size)| word(*size)));
+            }
+            records.extend(word(0));
+            for bases in seq.chunks(4) {
+                let mut byte = 0;
+                for (i, base) in bases.iter().enumerate() {
+                    let code = BASES
+                        .iter()
+                        .position(|b| b.eq_ignore_ascii_case(base))
+                        .unwrap_or(0);
+                    byte |= (code as u8) << (6 - 2 * i);
+                }
+                records.push(byte);
+            }
+        }
+
+        let mut file = Vec::new();
+        file.extend(word(SIGNATURE));
+        file.extend(word(version));
+        file.extend(word(sequences.len() as u32));
+        file.extend(word(0));
+        for ((name, _), offset) in sequences.iter().zip(offsets) {
+            file.push(name.len() as u8);
+            file.extend(name.as_bytes());
+            if version == 1 {
+                file.extend(if big_endian {
+                    offset.to_be_bytes()
+                } else {
+                    offset.to_le_bytes()
+                });
+            } else {
+                file.extend(word(offset as u32));
+            }
+        }
+        file.extend(records);
+        file
+    }
+
+    const SEQUENCES: [(&str, &[u8]); 3] = [
+        ("chr1", b"ACGTNNNNacgtaCGTTTGAnn"),
+        ("chr2", b""),
+        ("chrM", b"GATTACA"),
+    ];
+
+    #[test]
+    fn test_fetch() {
+        for (big_endian, version) in [(false, 0), (true, 0), (false, 1)] {
+            let file = two_bit(&SEQUENCES, big_endian, version);
+            let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+            assert_eq!(reader.names().collect::<Vec<_>>(), ["chr1", "chr2", "chrM"]);
+            assert_eq!(reader.sequence_length("chr1").unwrap(), 22);
+
+            let chr1 = SEQUENCES[0].1;
+            for start in 0..chr1.len() {
+                for end in start..=chr1.len() {
+                    let region = reader.fetch("chr1", start as u64, end as u64).unwrap();
+                    assert_eq!(&region.seq()[..], &chr1[start..end]);
+                }
+            }
+            let record = reader.fetch_all("chrM").unwrap();
+            assert_eq!(
+                (record.id(), &record.seq()[..]),
+                (&b"chrM"[..], &b"GATTACA"[..])
+            );
+
+            let err = reader.fetch("chr1", 10, 23).unwrap_err();
+            assert_eq!(err.kind, ParseErrorKind::InvalidRegion);
+            let err = reader.fetch("chr3", 0, 1).unwrap_err();
+            assert_eq!(err.kind, ParseErrorKind::InvalidRegion);
+        }
+
+        let file = two_bit(&SEQUENCES, false, 0);
+        let mut reader = TwoBitReader::new(Cursor::new(file))
+            .unwrap()
+            .with_soft_mask(false);
+        let region = reader.fetch("chr1", 6, 14).unwrap();
+        assert_eq!(&region.seq()[..], b"NNACGTAC");
+    }
+
+    #[test]
+    fn test_read_records() {
+        let file = two_bit(&SEQUENCES, false, 0);
+        let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+        let mut records = Vec::new();
+        while let Some(record) = reader.next() {
+            let record = record.unwrap();
+            records.push((record.id().to_vec(), record.seq().to_vec()));
+        }
+        let expected: Vec<_> = SEQUENCES
+            .iter()
+            .map(|(name, seq)| (name.as_bytes().to_vec(), seq.to_vec()))
+            .collect();
+        assert_eq!(records, expected);
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        let err = TwoBitReader::new(Cursor::new(b">chr1\nACGT\nACGT\nACGT\n"))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+
+        // the last sequence is cut
+        let mut file = two_bit(&SEQUENCES, false, 0);
+        file.truncate(file.len() - 20);
+        let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+        assert!(reader.fetch_all("chr1").is_ok());
+        let err = reader.sequence_length("chrM").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        assert_eq!(err.position.id.as_deref(), Some("chrM"));
+    }
+
+    #[test]
+    fn test_corrupt_file() {
+        let file = two_bit(&SEQUENCES[..1], false, 0);
+        // the layout of chr1: its length, then the starts and sizes of its N blocks (4..8
+        // and 20..22) and of its masked blocks (8..13 and 20..22)
+        let layout = 16 + 1 + 4 + 4;
+        let corrupt = |at: usize, value: u32| {
+            let mut file = file.clone();
+            file[layout + at..layout + at + 4].copy_from_slice(&value.to_le_bytes());
+            let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+            reader.fetch("chr1", 0, 1).unwrap_err()
+        };
+        // a length past the end of the file
+        let err = corrupt(0, 1 << 30);
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        // the second N block starts before the first one
+        let err = corrupt(12, 0);
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        // the first N block overlaps the second one
+        let err = corrupt(16, 17);
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        // the masked block ends past the end of the sequence
+        let err = corrupt(40, 15);
+        assert_eq!(err.kind, ParseErrorKind::InvalidRecord);
+        assert_eq!(err.position.id.as_deref(), Some("chr1"));
+    }
+}
@@ -1,9 +1,11 @@
 use std::io;
+use std::path::Path;
 
 use memchr::memchr;
 
-use crate::errors::ParseError;
-use crate::parser::record::SequenceRecord;
+use crate::errors::{ParseError, ParseErrorKind};
+use crate::parser::batch::RecordSet;
+use crate::parser::record::{OwnedRecord, SequenceRecord};
 
 pub(crate) const BUFSIZE: usize = 64 * 1024;
 
@@ -17,6 +19,15 @@ pub(crate) fn trim_cr(line: &[u8]) -> &[u8] {
     }
 }
 
+/// The id of a record without its description, i.e. its header up to the first whitespace
+#[inline]
+pub(crate) fn id_key(header: &[u8]) -> &[u8] {
+    header
+        .split(|c| c.is_ascii_whitespace())
+        .next()
+        .unwrap_or_default()
+}
+
 /// Standard buffer policy: buffer size
 /// doubles until it reaches 8 MiB. Above, it will
 /// increase in steps of 8 MiB. Buffer size is not limited,
@@ -45,9 +56,27 @@ where
             Err(e) => return Err(e),
         }
     }
+    trace_event!(
+        trace,
+        read = num_read,
+        buffered = reader.buffer().len(),
+        "filled buffer"
+    );
     Ok(num_read)
 }
 
+/// Reports a record failing validation if the `tracing` feature is enabled
+#[inline]
+pub(crate) fn invalid_record(error: ParseError) -> ParseError {
+    trace_event!(
+        debug,
+        kind = ?error.kind,
+        line = error.position.line,
+        "invalid record: {error}"
+    );
+    error
+}
+
 /// Holds line number and byte offset of our current state in a parser
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
@@ -85,6 +114,96 @@ impl Format {
             Self::Fastq => '@',
         }
     }
+
+    /// The inverse of `start_char`: the format of a file starting with that byte
+    pub fn from_start_char(byte: u8) -> Option<Self> {
+        match byte {
+            b'>' => Some(Self::Fasta),
+            b'@' => Some(Self::Fastq),
+            _ => None,
+        }
+    }
+
+    /// The usual file extension for the format, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Fasta => "fasta",
+            Self::Fastq => "fastq",
+        }
+    }
+
+    /// Guess the format from a file name, ignoring any compression extension,
+    /// e.g. `out.fa.zst` is FASTA. Returns `None` if the extension isn't known.
+    ///
+    /// ```
+    /// use needletail::parser::Format;
+    ///
+    /// assert_eq!(Format::from_path_extension("out.fasta.zst"), Some(Format::Fasta));
+    /// assert_eq!(Format::from_path_extension("reads.FQ"), Some(Format::Fastq));
+    /// assert_eq!(Format::from_path_extension("reads.txt"), None);
+    /// ```
+    pub fn from_path_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        let path = if Compression::from_path_extension(path) == Compression::Uncompressed {
+            path
+        } else {
+            Path::new(path.file_stem()?)
+        };
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "fasta" | "fa" | "fna" | "ffn" | "faa" | "frn" | "fas" | "mfa" => Some(Self::Fasta),
+            "fastq" | "fq" => Some(Self::Fastq),
+            _ => None,
+        }
+    }
+}
+
+/// The compression formats `parse_fastx_reader` can detect (if the `compression`
+/// feature is enabled)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Compression {
+    #[default]
+    Uncompressed,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// The usual file extension for the compression, without the dot
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::Uncompressed => None,
+            Self::Gzip => Some("gz"),
+            Self::Bzip2 => Some("bz2"),
+            Self::Xz => Some("xz"),
+            Self::Zstd => Some("zst"),
+        }
+    }
+
+    /// Guess the compression from the extension of a file name, defaulting
+    /// to `Uncompressed`.
+    ///
+    /// ```
+    /// use needletail::parser::Compression;
+    ///
+    /// assert_eq!(Compression::from_path_extension("out.fasta.zst"), Compression::Zstd);
+    /// assert_eq!(Compression::from_path_extension("out.fastq"), Compression::Uncompressed);
+    /// ```
+    pub fn from_path_extension<P: AsRef<Path>>(path: P) -> Self {
+        let extension = match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(e) => e.to_ascii_lowercase(),
+            None => return Self::Uncompressed,
+        };
+        match extension.as_str() {
+            "gz" | "gzip" | "bgz" => Self::Gzip,
+            "bz2" | "bzip2" => Self::Bzip2,
+            "xz" => Self::Xz,
+            "zst" | "zstd" => Self::Zstd,
+            _ => Self::Uncompressed,
+        }
+    }
 }
 
 /// Whether it uses \r\n or only \n
@@ -96,13 +215,93 @@ pub enum LineEnding {
 
 impl LineEnding {
     pub fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    /// Same as `to_bytes` without allocating
+    pub fn as_bytes(&self) -> &'static [u8] {
         match self {
-            Self::Windows => vec![b'\r', b'\n'],
-            Self::Unix => vec![b'\n'],
+            Self::Windows => b"\r\n",
+            Self::Unix => b"\n",
+        }
+    }
+
+    /// The inverse of `as_bytes`: returns `None` for anything else than `\n` or `\r\n`
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            b"\r\n" => Some(Self::Windows),
+            b"\n" => Some(Self::Unix),
+            _ => None,
+        }
+    }
+}
+
+/// How many lines end with `\n` and `\r\n` in some data
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct LineEndingCounts {
+    pub unix: u64,
+    pub windows: u64,
+}
+
+impl LineEndingCounts {
+    /// Count the line endings of a whole stream, e.g. a file. Note that this does not
+    /// decompress the data.
+    ///
+    /// ```
+    /// use needletail::parser::{LineEnding, LineEndingCounts};
+    ///
+    /// let counts = LineEndingCounts::from_reader(&b">a\r\nACGT\n>b\nTT\n"[..]).unwrap();
+    /// assert_eq!(counts, LineEndingCounts { unix: 3, windows: 1 });
+    /// assert!(counts.is_mixed());
+    /// assert_eq!(counts.most_common(), Some(LineEnding::Unix));
+    /// ```
+    pub fn from_reader<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let mut counts = Self::default();
+        let mut buf = vec![0; BUFSIZE];
+        // whether the last byte of the previous chunk was a `\r`
+        let mut previous_cr = false;
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let chunk = &buf[..n];
+            for idx in memchr::memchr_iter(b'\n', chunk) {
+                let is_windows = if idx == 0 {
+                    previous_cr
+                } else {
+                    chunk[idx - 1] == b'\r'
+                };
+                if is_windows {
+                    counts.windows += 1;
+                } else {
+                    counts.unix += 1;
+                }
+            }
+            previous_cr = chunk[n - 1] == b'\r';
+        }
+        Ok(counts)
+    }
+
+    /// Whether both kinds of line endings were found
+    pub fn is_mixed(&self) -> bool {
+        self.unix > 0 && self.windows > 0
+    }
+
+    /// The most frequent line ending, if there was any line ending at all.
+    /// Ties are resolved in favour of Unix line endings.
+    pub fn most_common(&self) -> Option<LineEnding> {
+        match (self.unix, self.windows) {
+            (0, 0) => None,
+            (u, w) if w > u => Some(LineEnding::Windows),
+            _ => Some(LineEnding::Unix),
         }
     }
 }
 
+/// Returns the line ending of the first line of `bytes`, if there is a line ending
 pub fn find_line_ending(bytes: &[u8]) -> Option<LineEnding> {
     if !bytes.is_empty() {
         if let Some(idx) = memchr(b'\n', bytes) {
@@ -120,11 +319,123 @@ pub trait FastxReader: Send {
     /// Gets the next record in the stream.
     /// This imitates the Iterator API but does not support any iterator functions.
     /// This returns None once we reached the EOF.
-    fn next(&mut self) -> Option<Result<SequenceRecord, ParseError>>;
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>>;
     /// Returns the current line/byte in the stream we are reading from
     fn position(&self) -> &Position;
     /// Returns whether the current stream uses Windows or Unix style line endings
     /// It is `None` only before calling `next`, once `next` has been called it will always
     /// return a line ending.
     fn line_ending(&self) -> Option<LineEnding>;
+    /// Returns whether a record using a different line ending than the first one was
+    /// seen so far, e.g. for files concatenated from different sources.
+    /// The line ending of each record is available with `SequenceRecord::line_ending`.
+    fn has_mixed_line_endings(&self) -> bool {
+        false
+    }
+    /// Returns whether `next` can be called again after it returned an error (other than
+    /// an I/O one) to skip the invalid record and carry on with the next ones.
+    /// The FASTA and FASTQ parsers can't resynchronise so they stop at the first error.
+    fn can_skip_invalid_records(&self) -> bool {
+        false
+    }
+    /// Empties `set` and fills it with the next records, up to its limits, so they can be
+    /// processed (or sent to another thread) together. Returns the number of records read,
+    /// 0 once the input is exhausted.
+    /// On an error, the records read before it are left in the set.
+    fn read_record_set(&mut self, set: &mut RecordSet) -> Result<usize, ParseError> {
+        set.fill(self)
+    }
+    /// Turns the reader into an `Iterator` over copies of its records, to use iterator
+    /// adapters such as `zip` at the cost of copying each record. See [`OwnedRecords`].
+    fn into_records_iter(self) -> OwnedRecords<Self>
+    where
+        Self: Sized,
+    {
+        OwnedRecords::new(self)
+    }
+}
+
+/// Iterator over copies of the records of a reader, see
+/// `FastxReader::into_records_iter`.
+///
+/// After an error, reading carries on with the next record if the reader supports
+/// it (see `FastxReader::can_skip_invalid_records`) and the error isn't an I/O one.
+/// Otherwise the error is the last item.
+///
+/// ```
+/// use needletail::{parse_fastx_reader, FastxReader};
+///
+/// let r1 = parse_fastx_reader(&b">a/1\nAC\n>b/1\nGG\n"[..]).unwrap();
+/// let r2 = parse_fastx_reader(&b">a/2\nTT\n>b/2\nCC\n"[..]).unwrap();
+/// let pairs: Vec<_> = r1
+///     .into_records_iter()
+///     .zip(r2.into_records_iter())
+///     .map(|(r1, r2)| (r1.unwrap().seq, r2.unwrap().seq))
+///     .collect();
+/// assert_eq!(pairs[1], (b"GG".to_vec(), b"CC".to_vec()));
+/// ```
+pub struct OwnedRecords<R> {
+    reader: R,
+    finished: bool,
+}
+
+impl<R: FastxReader> OwnedRecords<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            finished: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: FastxReader> Iterator for OwnedRecords<R> {
+    type Item = Result<OwnedRecord, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.reader.next() {
+            Some(Ok(record)) => Some(Ok(record.to_owned_record())),
+            Some(Err(e)) => {
+                self.finished =
+                    e.kind == ParseErrorKind::Io || !self.reader.can_skip_invalid_records();
+                Some(Err(e))
+            }
+            None => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+}
+
+impl<T: FastxReader + ?Sized> FastxReader for Box<T> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        (**self).next()
+    }
+
+    fn position(&self) -> &Position {
+        (**self).position()
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        (**self).line_ending()
+    }
+
+    fn has_mixed_line_endings(&self) -> bool {
+        (**self).has_mixed_line_endings()
+    }
+
+    fn can_skip_invalid_records(&self) -> bool {
+        (**self).can_skip_invalid_records()
+    }
+
+    fn read_record_set(&mut self, set: &mut RecordSet) -> Result<usize, ParseError> {
+        (**self).read_record_set(set)
+    }
 }
@@ -0,0 +1,469 @@
+//! Buffered FASTA/FASTQ writing
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::path::create_file;
+use crate::parser::record::{write_fasta, write_fastq, OwnedRecord, SequenceRecord};
+use crate::parser::utils::{Compression, Format, LineEnding, BUFSIZE};
+
+/// Writes FASTA/FASTQ records to an underlying `Write`.
+///
+/// Records are serialized into an internal buffer and only handed over to the
+/// underlying writer once the buffer is full, so there is no need to wrap it in a
+/// `BufWriter`. Errors of the underlying writer are reported with the id of the
+/// record being written and the line it starts on in the output.
+///
+/// Call [`finish`](FastxWriter::finish) when you're done writing: the buffer is
+/// also flushed when the writer is dropped, but any error is ignored then.
+///
+/// ```
+/// use needletail::parser::FastxWriter;
+///
+/// let mut writer = FastxWriter::new(Vec::new());
+/// writer.write_fasta(b"id1", b"ACGT").unwrap();
+/// writer.write_fastq(b"id2", b"ACGT", b"IIII").unwrap();
+/// let out = writer.finish().unwrap();
+/// assert_eq!(out, b">id1\nACGT\n@id2\nACGT\n+\nIIII\n");
+/// ```
+pub struct FastxWriter<W: Write> {
+    // only `None` once `finish` has been called
+    inner: Option<W>,
+    buffer: Vec<u8>,
+    capacity: usize,
+    line_ending: LineEnding,
+    // 0 if FASTA sequences are written on a single line
+    line_width: usize,
+    // output line on which the next record starts (starting with 1)
+    line: u64,
+    // (id, line) of the first record currently in the buffer
+    first_buffered: Option<(Vec<u8>, u64)>,
+}
+
+impl<W: Write> FastxWriter<W> {
+    /// Creates a new writer with the default buffer size of 64 KiB
+    pub fn new(writer: W) -> Self {
+        Self::with_capacity(writer, BUFSIZE)
+    }
+
+    /// Creates a new writer that hands over its data to `writer` once at least
+    /// `capacity` bytes have been buffered.
+    pub fn with_capacity(writer: W, capacity: usize) -> Self {
+        Self {
+            inner: Some(writer),
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+            line_ending: LineEnding::Unix,
+            line_width: 0,
+            line: 1,
+            first_buffered: None,
+        }
+    }
+
+    /// Uses `line_ending` at the end of the lines instead of `\n`
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Wraps FASTA sequences every `width` bases (commonly 60, 70 or 80) instead of
+    /// writing them on a single line. A width of 0 turns wrapping off. FASTQ records
+    /// are never wrapped.
+    ///
+    /// ```
+    /// use needletail::parser::{FastxWriter, LineEnding};
+    ///
+    /// let mut writer = FastxWriter::new(Vec::new())
+    ///     .with_line_width(4)
+    ///     .with_line_ending(LineEnding::Windows);
+    /// writer.write_fasta(b"id1", b"ACGTACGTAC").unwrap();
+    /// assert_eq!(writer.finish().unwrap(), b">id1\r\nACGT\r\nACGT\r\nAC\r\n");
+    /// ```
+    pub fn with_line_width(mut self, width: usize) -> Self {
+        self.line_width = width;
+        self
+    }
+
+    /// Writes a record in its own format (FASTA or FASTQ)
+    pub fn write_record(&mut self, record: &SequenceRecord) -> Result<(), ParseError> {
+        match record.qual() {
+            Some(qual) => self.write_fastq(record.id(), &record.seq(), qual),
+            None => self.write_fasta(record.id(), &record.seq()),
+        }
+    }
+
+    /// Writes an owned record in its own format (FASTA or FASTQ)
+    pub fn write_owned_record(&mut self, record: &OwnedRecord) -> Result<(), ParseError> {
+        match &record.qual {
+            Some(qual) => self.write_fastq(&record.id, &record.seq, qual),
+            None => self.write_fasta(&record.id, &record.seq),
+        }
+    }
+
+    /// Writes a FASTA record
+    pub fn write_fasta(&mut self, id: &[u8], seq: &[u8]) -> Result<(), ParseError> {
+        self.start_record(id);
+        if self.line_width == 0 || seq.len() <= self.line_width {
+            write_fasta(id, seq, &mut self.buffer, self.line_ending)?;
+            self.line += 2;
+        } else {
+            let ending = self.line_ending.as_bytes();
+            self.buffer.push(b'>');
+            self.buffer.extend_from_slice(id);
+            self.buffer.extend_from_slice(ending);
+            for line in seq.chunks(self.line_width) {
+                self.buffer.extend_from_slice(line);
+                self.buffer.extend_from_slice(ending);
+            }
+            self.line += 1 + seq.len().div_ceil(self.line_width) as u64;
+        }
+        self.maybe_flush_buffer(Format::Fasta)
+    }
+
+    /// Writes a FASTQ record
+    pub fn write_fastq(&mut self, id: &[u8], seq: &[u8], qual: &[u8]) -> Result<(), ParseError> {
+        self.start_record(id);
+        write_fastq(id, seq, Some(qual), &mut self.buffer, self.line_ending)?;
+        self.line += 4;
+        self.maybe_flush_buffer(Format::Fastq)
+    }
+
+    /// Hands over all the buffered records to the underlying writer and flushes it
+    pub fn flush(&mut self) -> Result<(), ParseError> {
+        self.flush_buffer(None)?;
+        if let Some(inner) = self.inner.as_mut() {
+            inner.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flushes everything and returns the underlying writer
+    pub fn finish(mut self) -> Result<W, ParseError> {
+        self.flush()?;
+        Ok(self.inner.take().expect("writer is only taken in finish"))
+    }
+
+    /// Returns a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("writer is only taken in finish")
+    }
+
+    #[inline]
+    fn start_record(&mut self, id: &[u8]) {
+        if self.first_buffered.is_none() {
+            self.first_buffered = Some((id.to_vec(), self.line));
+        }
+    }
+
+    #[inline]
+    fn maybe_flush_buffer(&mut self, format: Format) -> Result<(), ParseError> {
+        if self.buffer.len() >= self.capacity {
+            self.flush_buffer(Some(format))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn flush_buffer(&mut self, format: Option<Format>) -> Result<(), ParseError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let inner = self.inner.as_mut().expect("writer is only taken in finish");
+        let res = inner.write_all(&self.buffer);
+        self.buffer.clear();
+        let first_buffered = self.first_buffered.take();
+        res.map_err(|e| {
+            let (id, line) = first_buffered.unwrap_or_default();
+            ParseError::new_write_error(
+                e,
+                ErrorPosition {
+                    line,
+                    id: Some(String::from_utf8_lossy(&id).into()),
+                },
+                format,
+            )
+        })
+    }
+}
+
+impl FastxWriter<CompressedWriter> {
+    /// Creates (or replaces) the file at `path`, compressed with the default level of
+    /// the compression matching its extension (e.g. gzip for `.fastq.gz`).
+    ///
+    /// Call `finish` on the writer and then on the `CompressedWriter` it returns to
+    /// get the errors happening while finishing the compressed stream.
+    ///
+    /// ```no_run
+    /// use needletail::parser::FastxWriter;
+    ///
+    /// let mut writer = FastxWriter::to_path("filtered.fastq.gz").unwrap();
+    /// writer.write_fastq(b"read1", b"ACGT", b"IIII").unwrap();
+    /// writer.finish().unwrap().finish().unwrap();
+    /// ```
+    pub fn to_path<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let compression = Compression::from_path_extension(path.as_ref());
+        Self::to_compressed_path(path, compression, None)
+    }
+
+    /// Creates (or replaces) the file at `path`, compressed with `compression` at
+    /// `level` or the default level of the compression if it's `None`. Levels go from
+    /// 0 to 9 for gzip and xz, 1 to 9 for bzip2 and 1 to 22 for zstd.
+    /// Returns an error if the compression isn't enabled.
+    pub fn to_compressed_path<P: AsRef<Path>>(
+        path: P,
+        compression: Compression,
+        level: Option<u32>,
+    ) -> Result<Self, ParseError> {
+        let file = create_file(path.as_ref())?;
+        Ok(Self::new(CompressedWriter::new(file, compression, level)?))
+    }
+
+    /// Creates (or replaces) the gzip compressed file at `path`, see `to_compressed_path`
+    #[cfg(feature = "flate2")]
+    pub fn to_gz_path<P: AsRef<Path>>(path: P, level: u32) -> Result<Self, ParseError> {
+        Self::to_compressed_path(path, Compression::Gzip, Some(level))
+    }
+
+    /// Creates (or replaces) the zstd compressed file at `path`, see `to_compressed_path`
+    #[cfg(feature = "zstd")]
+    pub fn to_zstd_path<P: AsRef<Path>>(path: P, level: u32) -> Result<Self, ParseError> {
+        Self::to_compressed_path(path, Compression::Zstd, Some(level))
+    }
+}
+
+enum Encoder {
+    Uncompressed(File),
+    #[cfg(feature = "flate2")]
+    Gzip(flate2::write::GzEncoder<File>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(bzip2::write::BzEncoder<File>),
+    #[cfg(feature = "xz2")]
+    Xz(liblzma::write::XzEncoder<File>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+}
+
+/// A file compressed while it's written, see `FastxWriter::to_path`. The compressed
+/// stream is finished when it's dropped but errors are ignored then: call
+/// [`finish`](CompressedWriter::finish) to get them.
+pub struct CompressedWriter {
+    // only `None` once `finish` has been called
+    encoder: Option<Encoder>,
+}
+
+impl CompressedWriter {
+    /// Compresses what's written to `file` with `compression` at `level`, see
+    /// `FastxWriter::to_compressed_path`
+    #[allow(unused_variables)]
+    pub fn new(file: File, compression: Compression, level: Option<u32>) -> io::Result<Self> {
+        let encoder = match compression {
+            Compression::Uncompressed => Encoder::Uncompressed(file),
+            #[cfg(feature = "flate2")]
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                file,
+                level.map_or_else(flate2::Compression::default, flate2::Compression::new),
+            )),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Encoder::Bzip2(bzip2::write::BzEncoder::new(
+                file,
+                level.map_or_else(bzip2::Compression::default, bzip2::Compression::new),
+            )),
+            #[cfg(feature = "xz2")]
+            Compression::Xz => {
+                Encoder::Xz(liblzma::write::XzEncoder::new(file, level.unwrap_or(6)))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(
+                file,
+                // 0 is zstd's default level
+                level.unwrap_or(0) as i32,
+            )?),
+            #[allow(unreachable_patterns)]
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{compression:?} compression isn't enabled"),
+                ))
+            }
+        };
+        Ok(Self {
+            encoder: Some(encoder),
+        })
+    }
+
+    fn encoder(&mut self) -> &mut dyn Write {
+        match self
+            .encoder
+            .as_mut()
+            .expect("encoder is only taken in finish")
+        {
+            Encoder::Uncompressed(file) => file,
+            #[cfg(feature = "flate2")]
+            Encoder::Gzip(encoder) => encoder,
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(encoder) => encoder,
+            #[cfg(feature = "xz2")]
+            Encoder::Xz(encoder) => encoder,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder,
+        }
+    }
+
+    /// Writes the end of the compressed stream and returns the file
+    #[allow(clippy::infallible_destructuring_match)]
+    pub fn finish(mut self) -> io::Result<File> {
+        let encoder = self
+            .encoder
+            .take()
+            .expect("encoder is only taken in finish");
+        let mut file = match encoder {
+            Encoder::Uncompressed(file) => file,
+            #[cfg(feature = "flate2")]
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(encoder) => encoder.finish()?,
+            #[cfg(feature = "xz2")]
+            Encoder::Xz(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        Ok(file)
+    }
+}
+
+impl Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.encoder().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder().flush()
+    }
+}
+
+impl Drop for CompressedWriter {
+    fn drop(&mut self) {
+        if self.encoder.is_some() {
+            let _ = CompressedWriter {
+                encoder: self.encoder.take(),
+            }
+            .finish();
+        }
+    }
+}
+
+impl<W: Write> Drop for FastxWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_records() {
+        let input = b">r1\nAC\nGT\n>r2\nTTTT\n";
+        let mut reader = parse_fastx_reader(&input[..]).unwrap();
+        let mut writer = FastxWriter::with_capacity(Vec::new(), 4);
+        while let Some(rec) = reader.next() {
+            writer.write_record(&rec.unwrap()).unwrap();
+        }
+        // the capacity is tiny so everything was already written
+        assert_eq!(writer.get_ref(), b">r1\nACGT\n>r2\nTTTT\n");
+        assert_eq!(writer.finish().unwrap(), b">r1\nACGT\n>r2\nTTTT\n");
+    }
+
+    #[test]
+    fn test_line_width() {
+        let input = b">r1\nACGTA\nCGTAC\nGT\n>r2\nAC\n";
+        let mut reader = parse_fastx_reader(&input[..]).unwrap();
+        let mut writer = FastxWriter::new(Vec::new()).with_line_width(5);
+        while let Some(rec) = reader.next() {
+            writer.write_record(&rec.unwrap()).unwrap();
+        }
+        writer.write_fastq(b"r3", b"ACGTACGT", b"IIIIIIII").unwrap();
+        writer.write_fasta(b"r4", b"").unwrap();
+        assert_eq!(writer.line, 13);
+        assert_eq!(
+            writer.finish().unwrap(),
+            b">r1\nACGTA\nCGTAC\nGT\n>r2\nAC\n@r3\nACGTACGT\n+\nIIIIIIII\n>r4\n\n"
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "out.fq",
+            "out.fq.gz",
+            "out.fq.bz2",
+            "out.fq.xz",
+            "out.fq.zst",
+        ] {
+            let path = dir.path().join(name);
+            let mut writer = FastxWriter::to_path(&path).unwrap();
+            writer.write_fastq(b"r1", b"ACGT", b"IIII").unwrap();
+            writer.finish().unwrap().finish().unwrap();
+            let mut reader = crate::parse_fastx_file(&path).unwrap();
+            assert_eq!(reader.next().unwrap().unwrap().id(), b"r1", "{name}");
+        }
+
+        // dropping the writers finishes the compressed streams too
+        let gz = dir.path().join("dropped.fa.gz");
+        let zst = dir.path().join("dropped.fa.zst");
+        let mut writer = FastxWriter::to_gz_path(&gz, 1).unwrap();
+        writer.write_fasta(b"r1", b"ACGT").unwrap();
+        drop(writer);
+        let mut writer = FastxWriter::to_zstd_path(&zst, 19).unwrap();
+        writer.write_fasta(b"r1", b"ACGT").unwrap();
+        drop(writer);
+        for path in [gz, zst] {
+            let mut reader = crate::parse_fastx_file(&path).unwrap();
+            assert_eq!(reader.next().unwrap().unwrap().seq().as_ref(), b"ACGT");
+        }
+    }
+
+    #[test]
+    fn test_error_has_record_context() {
+        let mut writer = FastxWriter::with_capacity(FailingWriter, 20);
+        writer.write_fastq(b"r1", b"ACGT", b"IIII").unwrap();
+        let err = writer.write_fastq(b"r2", b"ACGT", b"IIII").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::Io);
+        assert_eq!(err.position.id.as_deref(), Some("r1"));
+        assert_eq!(err.position.line, 1);
+
+        let err = writer
+            .write_fasta(b"r3", b"ACGT")
+            .and(writer.flush())
+            .unwrap_err();
+        assert_eq!(err.position.id.as_deref(), Some("r3"));
+        assert_eq!(err.position.line, 9);
+    }
+}
@@ -0,0 +1,145 @@
+//! Splitting a stream of records in two depending on a predicate, e.g. to
+//! separate host from microbial reads or reads passing/failing QC.
+use std::io::Write;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::{FastxReader, SequenceRecord};
+
+/// How many records (or pairs of records) went to each writer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PartitionCounts {
+    /// Records for which the predicate returned `true`
+    pub matched: usize,
+    /// Records for which the predicate returned `false`
+    pub unmatched: usize,
+}
+
+/// Write every record of `reader` to `writer_true` if `predicate` returns `true`
+/// for it and to `writer_false` otherwise. Records are written as they were read.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::partition::partition;
+///
+/// let mut reader = parse_fastx_reader(&b">a\nACGT\n>b\nAC\n>c\nGGGG\n"[..]).unwrap();
+/// let (mut long, mut short) = (Vec::new(), Vec::new());
+/// let counts = partition(&mut reader, |rec| rec.num_bases() >= 4, &mut long, &mut short).unwrap();
+/// assert_eq!((counts.matched, counts.unmatched), (2, 1));
+/// assert_eq!(short, b">b\nAC\n");
+/// ```
+pub fn partition<F>(
+    reader: &mut dyn FastxReader,
+    mut predicate: F,
+    writer_true: &mut dyn Write,
+    writer_false: &mut dyn Write,
+) -> Result<PartitionCounts, ParseError>
+where
+    F: FnMut(&SequenceRecord) -> bool,
+{
+    let mut counts = PartitionCounts::default();
+    while let Some(record) = reader.next() {
+        let record = record?;
+        if predicate(&record) {
+            record.write(writer_true, None)?;
+            counts.matched += 1;
+        } else {
+            record.write(writer_false, None)?;
+            counts.unmatched += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Paired-end version of [`partition`]: the predicate is called with both mates
+/// and the pair is written to `writers_true` or `writers_false`, R1 going to the
+/// first writer of the tuple and R2 to the second one.
+///
+/// Returns an error of kind `UnpairedRecords` if one of the readers runs out of
+/// records before the other.
+pub fn partition_paired<F>(
+    reader1: &mut dyn FastxReader,
+    reader2: &mut dyn FastxReader,
+    mut predicate: F,
+    writers_true: (&mut dyn Write, &mut dyn Write),
+    writers_false: (&mut dyn Write, &mut dyn Write),
+) -> Result<PartitionCounts, ParseError>
+where
+    F: FnMut(&SequenceRecord, &SequenceRecord) -> bool,
+{
+    let mut counts = PartitionCounts::default();
+    loop {
+        let (record1, record2) = match (reader1.next(), reader2.next()) {
+            (None, None) => break,
+            (Some(r1), Some(r2)) => (r1?, r2?),
+            (Some(r), None) | (None, Some(r)) => {
+                let record = r?;
+                return Err(ParseError::new_unpaired_records(
+                    ErrorPosition {
+                        line: record.start_line_number(),
+                        id: Some(String::from_utf8_lossy(record.id()).into()),
+                    },
+                    record.format(),
+                ));
+            }
+        };
+        if predicate(&record1, &record2) {
+            record1.write(writers_true.0, None)?;
+            record2.write(writers_true.1, None)?;
+            counts.matched += 1;
+        } else {
+            record1.write(writers_false.0, None)?;
+            record2.write(writers_false.1, None)?;
+            counts.unmatched += 1;
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_partition_paired() {
+        let mut r1 =
+            parse_fastx_reader(&b"@a/1\nACGT\n+\nIIII\n@b/1\nNNNN\n+\nIIII\n"[..]).unwrap();
+        let mut r2 =
+            parse_fastx_reader(&b"@a/2\nTTTT\n+\nIIII\n@b/2\nGGGG\n+\n####\n"[..]).unwrap();
+        let (mut pass1, mut pass2, mut fail1, mut fail2) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let counts = partition_paired(
+            &mut r1,
+            &mut r2,
+            |a, b| !a.seq().contains(&b'N') && !b.seq().contains(&b'N'),
+            (&mut pass1, &mut pass2),
+            (&mut fail1, &mut fail2),
+        )
+        .unwrap();
+        assert_eq!(counts.matched, 1);
+        assert_eq!(counts.unmatched, 1);
+        assert_eq!(pass1, b"@a/1\nACGT\n+\nIIII\n");
+        assert_eq!(pass2, b"@a/2\nTTTT\n+\nIIII\n");
+        assert_eq!(fail1, b"@b/1\nNNNN\n+\nIIII\n");
+        assert_eq!(fail2, b"@b/2\nGGGG\n+\n####\n");
+    }
+
+    #[test]
+    fn test_partition_paired_unequal() {
+        let mut r1 = parse_fastx_reader(&b">a/1\nACGT\n>b/1\nAC\n"[..]).unwrap();
+        let mut r2 = parse_fastx_reader(&b">a/2\nACGT\n"[..]).unwrap();
+        let (mut w1, mut w2, mut w3, mut w4) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let err = partition_paired(
+            &mut r1,
+            &mut r2,
+            |_, _| true,
+            (&mut w1, &mut w2),
+            (&mut w3, &mut w4),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnpairedRecords);
+        assert_eq!(err.position.id.as_deref(), Some("b/1"));
+        assert_eq!(err.position.line, 3);
+    }
+}
@@ -0,0 +1,25 @@
+//! The traits and entry points most programs need, in a single import:
+//!
+//! ```
+//! use needletail::prelude::*;
+//!
+//! let mut reader = parse_fastx_reader(&b">read1\nACGT\n"[..]).unwrap();
+//! let mut writer = FastxWriter::new(Vec::new());
+//! while let Some(record) = reader.next() {
+//!     let record = record.unwrap();
+//!     let rc = record.reverse_complement();
+//!     writer.write_fasta(record.id(), &rc).unwrap();
+//! }
+//! assert_eq!(writer.finish().unwrap(), b">read1\nACGT\n");
+//! ```
+//!
+//! Items are only added to the prelude when they are used by most programs reading
+//! or writing sequences and are not going away, so glob importing it is safe across
+//! releases. Everything else is imported from its module.
+pub use crate::errors::{ParseError, ParseErrorKind};
+pub use crate::parser::{
+    FastxReader, FastxWriter, Format, LineEnding, OwnedRecord, SequenceRecord,
+};
+pub use crate::quality::QualitySequence;
+pub use crate::sequence::Sequence;
+pub use crate::{parse_fastx_file, parse_fastx_reader, parse_fastx_stdin};
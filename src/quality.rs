@@ -0,0 +1,1049 @@
+//! Working with the quality information of FASTQ records
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::{FastxReader, Format, OwnedRecord, SequenceRecord};
+use crate::stats::SummaryStats;
+use crate::Sequence;
+
+/// A trait to wrap over sequence data that has associated quality information.
+///
+/// It is implemented for `SequenceRecord` and `OwnedRecord`: records parsed from
+/// FASTA files have no quality, in which case `quality` returns an empty slice and the
+/// methods computing something from the quality return an error of kind `WrongFormat`.
+pub trait QualitySequence<'a>: Sequence<'a> {
+    fn quality(&'a self) -> &'a [u8];
+
+    /// Returns the quality, or an error of kind `WrongFormat` if the sequence has no
+    /// quality information (e.g. a FASTA record)
+    ///
+    /// ```
+    /// use needletail::errors::ParseErrorKind;
+    /// use needletail::{parse_fastx_reader, QualitySequence};
+    ///
+    /// let mut reader = parse_fastx_reader(&b">id\nACGT\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// let err = record.require_quality().unwrap_err();
+    /// assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+    /// ```
+    fn require_quality(&'a self) -> Result<&'a [u8], ParseError> {
+        let qual = self.quality();
+        if qual.len() != self.sequence().len() {
+            return Err(ParseError::new_wrong_format(
+                Format::Fastq,
+                ErrorPosition::default(),
+            ));
+        }
+        Ok(qual)
+    }
+
+    /// Given a `SeqRecord` and a quality cutoff, mask out low-quality bases with
+    /// `N` characters.
+    ///
+    /// Sequences without quality information (e.g. FASTA records) are returned
+    /// unchanged, use [`try_quality_mask`](QualitySequence::try_quality_mask) to get
+    /// an error instead.
+    ///
+    /// ```
+    /// use needletail::{parse_fastx_reader, QualitySequence};
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@id\nACGT\n+\nII#I\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// assert_eq!(record.quality_mask(b'5').as_ref(), b"ACNT");
+    /// ```
+    fn quality_mask(&'a self, score: u8) -> Cow<'a, [u8]> {
+        let qual = self.quality();
+        if qual.is_empty() {
+            return self.sequence().into();
+        }
+        // could maybe speed this up by doing a copy of base and then
+        // iterating though qual and masking?
+        let seq: Vec<u8> = self
+            .sequence()
+            .iter()
+            .zip(qual.iter())
+            .map(|(base, qual)| if *qual < score { b'N' } else { *base })
+            .collect();
+        seq.into()
+    }
+
+    /// Same as `quality_mask` but returns an error of kind `WrongFormat` for sequences
+    /// without quality information
+    fn try_quality_mask(&'a self, score: u8) -> Result<Cow<'a, [u8]>, ParseError> {
+        self.require_quality()?;
+        Ok(self.quality_mask(score))
+    }
+
+    /// Same as `try_quality_mask` but writes the masked sequence into `buf` (after
+    /// clearing it) to reuse its allocation across records.
+    fn quality_mask_into<'b>(
+        &'a self,
+        buf: &'b mut Vec<u8>,
+        score: u8,
+    ) -> Result<&'b [u8], ParseError> {
+        buf.clear();
+        let qual = self.require_quality()?;
+        buf.extend(self.sequence().iter().zip(qual.iter()).map(|(base, qual)| {
+            if *qual < score {
+                b'N'
+            } else {
+                *base
+            }
+        }));
+        Ok(buf)
+    }
+
+    /// Writes the probability of each base being wrong (see [`error_probability`])
+    /// into `buf`, after clearing it, so that several computations on the qualities
+    /// of a record can share the decoded values and the allocation is reused across
+    /// records. Returns an error of kind `WrongFormat` for sequences without quality.
+    ///
+    /// ```
+    /// use needletail::{parse_fastx_reader, QualitySequence};
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@read1\nACG\n+\n!+5\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// let mut probabilities = Vec::new();
+    /// let decoded = record.error_probabilities_into(&mut probabilities).unwrap();
+    /// assert_eq!(decoded, [1.0, 0.1, 0.01]);
+    /// ```
+    fn error_probabilities_into<'b>(
+        &'a self,
+        buf: &'b mut Vec<f32>,
+    ) -> Result<&'b [f32], ParseError> {
+        buf.clear();
+        let qual = self.require_quality()?;
+        buf.extend(qual.iter().map(|q| error_probability(*q)));
+        Ok(buf)
+    }
+
+    /// Iterates over windows of `width` bases of the sequence and of its quality,
+    /// along with the position of their start, moving by `step` bases each time.
+    /// Trailing bases that don't fill a whole window are not returned.
+    ///
+    /// Returns an error of kind `WrongFormat` for sequences without quality
+    /// information (e.g. FASTA records). Panics if `width` or `step` is 0.
+    ///
+    /// ```
+    /// use needletail::{parse_fastx_reader, QualitySequence};
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@id\nACGTACGT\n+\nIIII##II\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// // first window of 4 bases with a mean Phred score below 25
+    /// let low = record.quality_windows(4, 2).unwrap().find(|(_, _, qual)| {
+    ///     qual.iter().map(|q| (q - 33) as usize).sum::<usize>() / qual.len() < 25
+    /// });
+    /// assert_eq!(low, Some((2, &b"GTAC"[..], &b"II##"[..])));
+    /// ```
+    fn quality_windows(
+        &'a self,
+        width: usize,
+        step: usize,
+    ) -> Result<QualityWindows<'a>, ParseError> {
+        let qual = self.require_quality()?;
+        Ok(QualityWindows::new(self.sequence(), qual, width, step))
+    }
+
+    /// Returns the mean Phred score of the bases, `None` for sequences without bases
+    /// (see [`mean_quality`]) and an error of kind `WrongFormat` for sequences without
+    /// quality information (e.g. FASTA records).
+    fn mean_quality(&'a self) -> Result<Option<f64>, ParseError> {
+        Ok(mean_quality(self.require_quality()?))
+    }
+
+    /// Returns the number of errors expected in the sequence, i.e. the sum of the error
+    /// probabilities of its bases, or an error of kind `WrongFormat` for sequences
+    /// without quality information. Reads are usually filtered on it rather than on
+    /// their mean quality, which hides a few very bad bases.
+    ///
+    /// ```
+    /// use needletail::{parse_fastx_reader, QualitySequence};
+    ///
+    /// let fastq = b"@r1\nACGTACGT\n+\nIIIIIIII\n@r2\nACGTACGT\n+\nIII++III\n";
+    /// let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+    /// let mut kept = Vec::new();
+    /// while let Some(record) = reader.next() {
+    ///     let record = record.unwrap();
+    ///     // at most 0.1 expected error, like `--fastq_maxee 0.1` of VSEARCH
+    ///     if record.expected_errors().unwrap() <= 0.1 {
+    ///         kept.push(record.id().to_vec());
+    ///     }
+    /// }
+    /// assert_eq!(kept, [b"r1"]);
+    /// ```
+    fn expected_errors(&'a self) -> Result<f64, ParseError> {
+        Ok(expected_errors(self.require_quality()?))
+    }
+
+    /// Returns the fraction of the bases with a quality of at least `min_quality`,
+    /// `None` for sequences without bases and an error of kind `WrongFormat` for
+    /// sequences without quality information
+    ///
+    /// ```
+    /// use needletail::quality::Phred;
+    /// use needletail::{parse_fastx_reader, QualitySequence};
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@r1\nACGT\n+\nII5#\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// let fraction = record.fraction_bases_above(Phred::new(30).unwrap()).unwrap();
+    /// assert_eq!(fraction, Some(0.5));
+    /// ```
+    fn fraction_bases_above(&'a self, min_quality: Phred) -> Result<Option<f64>, ParseError> {
+        let qual = self.require_quality()?;
+        if qual.is_empty() {
+            return Ok(None);
+        }
+        let min = min_quality.to_ascii();
+        let above = qual.iter().filter(|q| **q >= min).count();
+        Ok(Some(above as f64 / qual.len() as f64))
+    }
+
+    /// Sliding window trimming of the 3' end: returns the range of the bases to keep,
+    /// which ends at the first window of `window_size` bases whose mean quality is
+    /// below `min_quality`, keeping the bases of that window before its first one
+    /// below `min_quality`. Reads shorter than the window are one window.
+    ///
+    /// Returns an error of kind `WrongFormat` for sequences without quality information
+    /// (e.g. FASTA records). Panics if `window_size` is 0.
+    ///
+    /// ```
+    /// use needletail::quality::Phred;
+    /// use needletail::{parse_fastx_reader, QualitySequence};
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@id\nACGTACGTAC\n+\nIIIII5#I##\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// let keep = record.quality_trim(4, Phred::new(20).unwrap()).unwrap();
+    /// assert_eq!(keep, 0..6);
+    /// assert_eq!(&record.seq()[keep], b"ACGTAC");
+    /// ```
+    fn quality_trim(
+        &'a self,
+        window_size: usize,
+        min_quality: Phred,
+    ) -> Result<Range<usize>, ParseError> {
+        assert!(window_size > 0, "window_size needs to be at least 1");
+        let qual = self.require_quality()?;
+        let len = qual.len();
+        let window_size = window_size.min(len);
+        let min = u32::from(min_quality.score());
+        let score = |q: &u8| u32::from(q.saturating_sub(Phred::OFFSET));
+        let mut sum: u32 = qual[..window_size].iter().map(score).sum();
+        for start in 0..=len - window_size {
+            if start > 0 {
+                sum = sum - score(&qual[start - 1]) + score(&qual[start + window_size - 1]);
+            }
+            if sum < min * window_size as u32 {
+                let kept = qual[start..start + window_size]
+                    .iter()
+                    .take_while(|q| score(q) >= min)
+                    .count();
+                return Ok(0..start + kept);
+            }
+        }
+        Ok(0..len)
+    }
+
+    /// Trims the bases with a quality below `min_quality` from both ends: returns the
+    /// range from the first base at or above `min_quality` to the last one, which is
+    /// empty if there is none.
+    ///
+    /// Returns an error of kind `WrongFormat` for sequences without quality information
+    /// (e.g. FASTA records).
+    ///
+    /// ```
+    /// use needletail::quality::Phred;
+    /// use needletail::{parse_fastx_reader, QualitySequence};
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@id\nACGTACGT\n+\n#5II#I5#\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// assert_eq!(record.trim_ends(Phred::new(20).unwrap()), Ok(1..7));
+    /// assert_eq!(record.trim_ends(Phred::new(30).unwrap()), Ok(2..6));
+    /// assert_eq!(record.trim_ends(Phred::MAX), Ok(0..0));
+    /// ```
+    fn trim_ends(&'a self, min_quality: Phred) -> Result<Range<usize>, ParseError> {
+        let qual = self.require_quality()?;
+        let min = min_quality.to_ascii();
+        Ok(match qual.iter().position(|q| *q >= min) {
+            Some(start) => start..qual.iter().rposition(|q| *q >= min).unwrap() + 1,
+            None => 0..0,
+        })
+    }
+}
+
+/// An iterator over windows of a sequence and its quality, see
+/// [`QualitySequence::quality_windows`]
+#[derive(Debug, Clone)]
+pub struct QualityWindows<'a> {
+    seq: &'a [u8],
+    qual: &'a [u8],
+    width: usize,
+    step: usize,
+    pos: usize,
+}
+
+impl<'a> QualityWindows<'a> {
+    pub fn new(seq: &'a [u8], qual: &'a [u8], width: usize, step: usize) -> Self {
+        assert!(width > 0, "width needs to be at least 1");
+        assert!(step > 0, "step needs to be at least 1");
+        // a sequence without quality has no windows
+        let len = if qual.len() == seq.len() {
+            seq.len()
+        } else {
+            0
+        };
+        Self {
+            seq: &seq[..len],
+            qual: &qual[..len],
+            width,
+            step,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for QualityWindows<'a> {
+    type Item = (usize, &'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.pos.checked_add(self.width)?;
+        if end > self.seq.len() {
+            return None;
+        }
+        let pos = self.pos;
+        self.pos = self.pos.saturating_add(self.step);
+        Some((pos, &self.seq[pos..end], &self.qual[pos..end]))
+    }
+}
+
+/// Rewrites in place the quality of `record` through `f`, called with the quality
+/// byte (as stored, e.g. Phred+33), its position and the base at that position.
+/// Useful for simple recalibration or binning schemes while converting files.
+///
+/// Returns an error of kind `WrongFormat` for records without quality (FASTA).
+///
+/// ```
+/// use needletail::parser::OwnedRecord;
+/// use needletail::quality::map_quality;
+///
+/// let mut record = OwnedRecord {
+///     id: b"r".to_vec(),
+///     seq: b"ACGN".to_vec(),
+///     qual: Some(b"I5+I".to_vec()),
+/// };
+/// // bin qualities Illumina-style, with N bases getting the lowest score
+/// map_quality(&mut record, |q, _pos, base| match (base, q) {
+///     (b'N', _) => b'#',
+///     (_, q) if q >= b'5' => b'F',
+///     _ => b',',
+/// })
+/// .unwrap();
+/// assert_eq!(record.qual.unwrap(), b"FF,#");
+/// ```
+pub fn map_quality<F>(record: &mut OwnedRecord, mut f: F) -> Result<(), ParseError>
+where
+    F: FnMut(u8, usize, u8) -> u8,
+{
+    record.require_format(Format::Fastq)?;
+    if let Some(qual) = record.qual.as_mut() {
+        for (pos, (q, base)) in qual.iter_mut().zip(record.seq.iter()).enumerate() {
+            *q = f(*q, pos, *base);
+        }
+    }
+    Ok(())
+}
+
+/// A Phred quality score: the number, not the character encoding it in a file.
+/// Scores go from 0 to 93, the highest one that can be written in Phred+33.
+///
+/// ```
+/// use needletail::quality::Phred;
+///
+/// let q = Phred::from_ascii(b'5').unwrap();
+/// assert_eq!(q.score(), 20);
+/// assert_eq!(q.error_probability(), 0.01);
+/// assert_eq!(Phred::from_error_probability(0.001), Phred::new(30).unwrap());
+/// assert_eq!(Phred::new(30).unwrap().to_ascii(), b'?');
+/// assert_eq!(Phred::from_ascii(b' '), None);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Phred(u8);
+
+impl Phred {
+    pub const MIN: Phred = Phred(0);
+    pub const MAX: Phred = Phred(93);
+    /// Offset of the quality characters in Phred+33, i.e. `!`
+    pub const OFFSET: u8 = b'!';
+
+    /// Returns `None` if `score` is above 93
+    pub const fn new(score: u8) -> Option<Self> {
+        if score <= Self::MAX.0 {
+            Some(Self(score))
+        } else {
+            None
+        }
+    }
+
+    /// Decodes a Phred+33 quality character. Returns `None` for characters outside of
+    /// `!` to `~`.
+    pub const fn from_ascii(c: u8) -> Option<Self> {
+        match c.checked_sub(Self::OFFSET) {
+            Some(score) => Self::new(score),
+            None => None,
+        }
+    }
+
+    /// The closest score to the probability `p` of the base being wrong
+    pub fn from_error_probability(p: f32) -> Self {
+        let score = (-10.0 * f64::from(p).log10()).round();
+        Self(score.clamp(0.0, f64::from(Self::MAX.0)) as u8)
+    }
+
+    pub const fn score(self) -> u8 {
+        self.0
+    }
+
+    /// Encodes the score as a Phred+33 character
+    pub const fn to_ascii(self) -> u8 {
+        self.0 + Self::OFFSET
+    }
+
+    /// Returns the probability of the base being wrong, i.e. `10^(-score / 10)`
+    #[inline]
+    pub fn error_probability(self) -> f32 {
+        error_probability(self.to_ascii())
+    }
+}
+
+impl From<Phred> for u8 {
+    fn from(phred: Phred) -> u8 {
+        phred.0
+    }
+}
+
+/// Returns the probability of a base being wrong given its Phred+33 quality character.
+/// Characters below `!` (Q0) have a probability of 1.
+///
+/// ```
+/// use needletail::quality::error_probability;
+///
+/// assert_eq!(error_probability(b'+'), 0.1);
+/// assert_eq!(error_probability(b'I'), 0.0001);
+/// ```
+#[inline]
+pub fn error_probability(quality: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [1.0; 256];
+        for (q, p) in table.iter_mut().enumerate().skip(33) {
+            *p = 10f64.powf(-((q - 33) as f64) / 10.0) as f32;
+        }
+        table
+    });
+    table[quality as usize]
+}
+
+/// Returns the error probability of each quality character of `qual`, see
+/// [`error_probability`]
+pub fn error_probabilities(qual: &[u8]) -> Vec<f32> {
+    qual.iter().map(|q| error_probability(*q)).collect()
+}
+
+/// Sums the error probabilities of the bases given their Phred+33 quality characters,
+/// i.e. the number of errors expected in the read (the `maxEE` of USEARCH/VSEARCH)
+///
+/// ```
+/// use needletail::quality::expected_errors;
+///
+/// assert!((expected_errors(b"+++5") - 0.31).abs() < 1e-6);
+/// ```
+pub fn expected_errors(qual: &[u8]) -> f64 {
+    qual.iter().map(|q| f64::from(error_probability(*q))).sum()
+}
+
+/// Returns the mean Phred score of Phred+33 quality characters, `None` if there are none
+///
+/// ```
+/// use needletail::quality::mean_quality;
+///
+/// assert_eq!(mean_quality(b"II55"), Some(30.0));
+/// assert_eq!(mean_quality(b""), None);
+/// ```
+pub fn mean_quality(qual: &[u8]) -> Option<f64> {
+    if qual.is_empty() {
+        return None;
+    }
+    let sum: u64 = qual
+        .iter()
+        .map(|q| u64::from(q.saturating_sub(Phred::OFFSET)))
+        .sum();
+    Some(sum as f64 / qual.len() as f64)
+}
+
+/// How quality scores are written as characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PhredEncoding {
+    /// Score + 33, from `!`: Sanger and Illumina 1.8+
+    Phred33,
+    /// Score + 64, from `@`: Illumina 1.3 to 1.7 (and Solexa, which goes down to `;`)
+    Phred64,
+}
+
+impl PhredEncoding {
+    /// The character of a score of 0
+    pub fn offset(self) -> u8 {
+        match self {
+            Self::Phred33 => 33,
+            Self::Phred64 => 64,
+        }
+    }
+
+    /// Tells the encoding from the lowest and highest quality characters of a file.
+    /// Phred+64 has nothing below `;` (-5 in Solexa) and usually goes above `J`
+    /// (Q41 in Phred+33), `None` if both are possible.
+    fn from_range(min: u8, max: u8) -> Option<Self> {
+        if min < b';' {
+            Some(Self::Phred33)
+        } else if max > b'J' {
+            Some(Self::Phred64)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decodes quality characters into Phred scores. Characters below the offset of the
+/// encoding (e.g. Solexa scores in Phred+64) decode to 0.
+///
+/// ```
+/// use needletail::quality::{decode_phred, PhredEncoding};
+///
+/// assert_eq!(decode_phred(b"!5I", PhredEncoding::Phred33), [0, 20, 40]);
+/// assert_eq!(decode_phred(b"@Th", PhredEncoding::Phred64), [0, 20, 40]);
+/// ```
+pub fn decode_phred(qual: &[u8], encoding: PhredEncoding) -> Vec<u8> {
+    let offset = encoding.offset();
+    qual.iter().map(|q| q.saturating_sub(offset)).collect()
+}
+
+/// Encodes Phred scores as quality characters. Scores too high to be written in the
+/// encoding are written as the highest one, `~`.
+///
+/// ```
+/// use needletail::quality::{decode_phred, encode_phred, PhredEncoding};
+///
+/// assert_eq!(encode_phred(&[0, 20, 40], PhredEncoding::Phred33), b"!5I");
+/// // converting Phred+64 qualities to Phred+33
+/// let scores = decode_phred(b"@Th", PhredEncoding::Phred64);
+/// assert_eq!(encode_phred(&scores, PhredEncoding::Phred33), b"!5I");
+/// ```
+pub fn encode_phred(scores: &[u8], encoding: PhredEncoding) -> Vec<u8> {
+    let offset = encoding.offset();
+    let max = b'~' - offset;
+    scores.iter().map(|s| (*s).min(max) + offset).collect()
+}
+
+/// Guesses the quality encoding of a file from its first `max_records` records.
+/// Returns `None` if they have no quality (FASTA) or if they could be in both
+/// encodings, i.e. all their characters are between `;` and `J`.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::quality::{guess_encoding, PhredEncoding};
+///
+/// let mut reader = parse_fastx_reader(&b"@r1\nACGT\n+\nhhhh\n@r2\nACGT\n+\nhhPh\n"[..]).unwrap();
+/// assert_eq!(guess_encoding(&mut reader, 1000).unwrap(), Some(PhredEncoding::Phred64));
+/// ```
+pub fn guess_encoding(
+    reader: &mut dyn FastxReader,
+    max_records: usize,
+) -> Result<Option<PhredEncoding>, ParseError> {
+    let (mut min, mut max) = (u8::MAX, u8::MIN);
+    for _ in 0..max_records {
+        let Some(record) = reader.next() else {
+            break;
+        };
+        if let Some(qual) = record?.qual() {
+            min = qual.iter().fold(min, |m, q| m.min(*q));
+            max = qual.iter().fold(max, |m, q| m.max(*q));
+        }
+    }
+    if min > max {
+        return Ok(None);
+    }
+    Ok(PhredEncoding::from_range(min, max))
+}
+
+/// Number of each base at a position of the reads, see [`QualityProfile`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BaseCounts {
+    pub a: u64,
+    pub c: u64,
+    pub g: u64,
+    pub t: u64,
+    /// `N` and any other character
+    pub n: u64,
+}
+
+impl BaseCounts {
+    fn add(&mut self, base: u8) {
+        match base {
+            b'A' | b'a' => self.a += 1,
+            b'C' | b'c' => self.c += 1,
+            b'G' | b'g' => self.g += 1,
+            b'T' | b't' => self.t += 1,
+            _ => self.n += 1,
+        }
+    }
+
+    fn merge(&mut self, other: &BaseCounts) {
+        self.a += other.a;
+        self.c += other.c;
+        self.g += other.g;
+        self.t += other.t;
+        self.n += other.n;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.a + self.c + self.g + self.t + self.n
+    }
+}
+
+/// Distribution of the quality scores of the bases at a position of the reads, as
+/// drawn in the box plots of FastQC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CycleQuality {
+    pub mean: f64,
+    pub p10: Phred,
+    pub lower_quartile: Phred,
+    pub median: Phred,
+    pub upper_quartile: Phred,
+    pub p90: Phred,
+}
+
+/// Number of bases of each score, indexed by score
+type ScoreCounts = [u64; Phred::MAX.0 as usize + 1];
+
+/// Accumulates FastQC-like statistics over records, one record at a time: the
+/// distribution of the quality scores and the base composition at each position
+/// (cycle) of the reads, on top of the [`SummaryStats`] of the records (e.g. the
+/// distribution of their lengths).
+///
+/// Quality characters are read as Phred+33, the ones outside of `!` to `~` being
+/// counted as the closest score.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::quality::{Phred, QualityProfile};
+///
+/// let fastq = b"@r1\nACGT\n+\nII5+\n@r2\nAGG\n+\n5I5\n@r3\nATG\n+\nI5+\n";
+/// let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+/// let mut profile = QualityProfile::new();
+/// while let Some(record) = reader.next() {
+///     profile.update(&record.unwrap());
+/// }
+/// assert_eq!(profile.cycles(), 4);
+/// let first = profile.cycle_quality(0).unwrap();
+/// assert_eq!(first.median, Phred::new(40).unwrap());
+/// assert_eq!(first.p10, Phred::new(20).unwrap());
+/// assert_eq!(profile.base_composition()[0].a, 3);
+/// assert_eq!(profile.length_counts().collect::<Vec<_>>(), [(3, 2), (4, 1)]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QualityProfile {
+    summary: SummaryStats,
+    scores: Vec<ScoreCounts>,
+    bases: Vec<BaseCounts>,
+}
+
+impl QualityProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a record to the profile
+    pub fn update(&mut self, record: &SequenceRecord) {
+        self.add(&record.seq(), record.qual());
+    }
+
+    /// Adds a sequence and its Phred+33 quality, if any, to the profile
+    pub fn add(&mut self, seq: &[u8], qual: Option<&[u8]>) {
+        self.summary.add(seq, qual);
+        if seq.len() > self.bases.len() {
+            self.bases.resize(seq.len(), BaseCounts::default());
+        }
+        for (counts, base) in self.bases.iter_mut().zip(seq) {
+            counts.add(*base);
+        }
+        if let Some(qual) = qual {
+            if qual.len() > self.scores.len() {
+                self.scores
+                    .resize(qual.len(), [0; Phred::MAX.0 as usize + 1]);
+            }
+            for (counts, q) in self.scores.iter_mut().zip(qual) {
+                let score = q.saturating_sub(Phred::OFFSET).min(Phred::MAX.0);
+                counts[score as usize] += 1;
+            }
+        }
+    }
+
+    /// Adds the records of `other` to the profile, e.g. profiled on another thread
+    pub fn merge(&mut self, other: &QualityProfile) {
+        self.summary.merge(&other.summary);
+        if other.bases.len() > self.bases.len() {
+            self.bases.resize(other.bases.len(), BaseCounts::default());
+        }
+        for (counts, other) in self.bases.iter_mut().zip(&other.bases) {
+            counts.merge(other);
+        }
+        if other.scores.len() > self.scores.len() {
+            self.scores
+                .resize(other.scores.len(), [0; Phred::MAX.0 as usize + 1]);
+        }
+        for (counts, other) in self.scores.iter_mut().zip(&other.scores) {
+            for (count, other) in counts.iter_mut().zip(other) {
+                *count += other;
+            }
+        }
+    }
+
+    /// Number of records added
+    pub fn records(&self) -> u64 {
+        self.summary.totals.records
+    }
+
+    /// Statistics of the records added: length distribution, GC content...
+    pub fn summary(&self) -> &SummaryStats {
+        &self.summary
+    }
+
+    /// Number of positions in the reads, i.e. the length of the longest one
+    pub fn cycles(&self) -> usize {
+        self.bases.len()
+    }
+
+    /// Distribution of the quality scores of the bases at position `cycle` (from 0) of
+    /// the reads, `None` if no read with a quality is that long
+    pub fn cycle_quality(&self, cycle: usize) -> Option<CycleQuality> {
+        let counts = self.scores.get(cycle)?;
+        let total: u64 = counts.iter().sum();
+        let sum: u64 = counts
+            .iter()
+            .enumerate()
+            .map(|(score, count)| score as u64 * count)
+            .sum();
+        // the lowest score reached by `percent`% of the bases
+        let percentile = |percent: u64| {
+            let target = (total * percent).div_ceil(100).max(1);
+            let mut seen = 0;
+            let score = counts
+                .iter()
+                .position(|count| {
+                    seen += count;
+                    seen >= target
+                })
+                .unwrap_or_default();
+            Phred(score as u8)
+        };
+        Some(CycleQuality {
+            mean: sum as f64 / total as f64,
+            p10: percentile(10),
+            lower_quartile: percentile(25),
+            median: percentile(50),
+            upper_quartile: percentile(75),
+            p90: percentile(90),
+        })
+    }
+
+    /// [`QualityProfile::cycle_quality`] of every position with a quality
+    pub fn quality_by_cycle(&self) -> Vec<CycleQuality> {
+        (0..self.scores.len())
+            .filter_map(|cycle| self.cycle_quality(cycle))
+            .collect()
+    }
+
+    /// Number of each base at each position of the reads
+    pub fn base_composition(&self) -> &[BaseCounts] {
+        &self.bases
+    }
+
+    /// Number of records of each length, from the shortest to the longest
+    pub fn length_counts(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.summary.length_counts()
+    }
+}
+
+impl<'a> Sequence<'a> for (&'a [u8], &'a [u8]) {
+    fn sequence(&'a self) -> &'a [u8] {
+        self.0
+    }
+}
+
+impl<'a> QualitySequence<'a> for (&'a [u8], &'a [u8]) {
+    fn quality(&'a self) -> &'a [u8] {
+        self.1
+    }
+}
+
+impl<'a> QualitySequence<'a> for SequenceRecord<'a> {
+    fn quality(&'a self) -> &'a [u8] {
+        self.qual().unwrap_or_default()
+    }
+
+    fn require_quality(&'a self) -> Result<&'a [u8], ParseError> {
+        self.require_format(Format::Fastq)?;
+        Ok(self.quality())
+    }
+}
+
+impl<'a> QualitySequence<'a> for OwnedRecord {
+    fn quality(&'a self) -> &'a [u8] {
+        self.qual.as_deref().unwrap_or_default()
+    }
+
+    fn require_quality(&'a self) -> Result<&'a [u8], ParseError> {
+        self.require_format(Format::Fastq)?;
+        Ok(self.quality())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_quality_profile() {
+        let mut profile = QualityProfile::new();
+        assert_eq!(profile.cycle_quality(0), None);
+        profile.add(b"ACGN", None);
+        assert_eq!(profile.cycles(), 4);
+        assert!(profile.quality_by_cycle().is_empty());
+
+        let mut other = QualityProfile::new();
+        // 10 reads with scores 0 to 9 at the first position, and a character below `!`
+        for score in 0..10 {
+            other.add(b"TX", Some(&[b'!' + score, b' ']));
+        }
+        profile.merge(&other);
+        assert_eq!(profile.records(), 11);
+        assert_eq!(profile.cycles(), 4);
+        let first = profile.cycle_quality(0).unwrap();
+        assert_eq!(first.mean, 4.5);
+        let scores = [
+            first.p10,
+            first.lower_quartile,
+            first.median,
+            first.upper_quartile,
+            first.p90,
+        ];
+        assert_eq!(scores.map(u8::from), [0, 2, 4, 7, 8]);
+        assert_eq!(profile.cycle_quality(1).unwrap().p90, Phred::MIN);
+        assert_eq!(profile.quality_by_cycle().len(), 2);
+
+        let second = profile.base_composition()[1];
+        assert_eq!((second.c, second.n, second.total()), (1, 10, 11));
+        assert_eq!(
+            profile.length_counts().collect::<Vec<_>>(),
+            [(2, 10), (4, 1)]
+        );
+    }
+
+    #[test]
+    fn test_guess_encoding() {
+        let guess = |data: &[u8]| {
+            let mut reader = parse_fastx_reader(data).unwrap();
+            guess_encoding(&mut reader, 2).unwrap()
+        };
+        assert_eq!(guess(b"@r1\nAC\n+\n#I\n"), Some(PhredEncoding::Phred33));
+        assert_eq!(guess(b"@r1\nAC\n+\n;I\n"), None);
+        assert_eq!(guess(b">r1\nAC\n"), None);
+        // only the first records are looked at
+        let data = b"@r1\nA\n+\nI\n@r2\nA\n+\nI\n@r3\nA\n+\n!\n";
+        assert_eq!(guess(data), None);
+        assert_eq!(encode_phred(&[93, 70], PhredEncoding::Phred64), b"~~");
+    }
+
+    #[test]
+    fn test_record_statistics() {
+        let mut reader = parse_fastx_reader(&b">r1\nACGT\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let err = record.mean_quality().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+        assert_eq!(err.position.id.as_deref(), Some("r1"));
+        let err = record.expected_errors().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+        let err = record.fraction_bases_above(Phred::MIN).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+
+        let empty = (&b""[..], &b""[..]);
+        assert_eq!(empty.mean_quality(), Ok(None));
+        assert_eq!(empty.fraction_bases_above(Phred::MIN), Ok(None));
+
+        let record = OwnedRecord {
+            id: b"r1".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: Some(b"5+5+".to_vec()),
+        };
+        assert_eq!(record.mean_quality(), Ok(Some(15.0)));
+        assert!((record.expected_errors().unwrap() - 0.22).abs() < 1e-6);
+        assert_eq!(
+            record.fraction_bases_above(Phred::new(11).unwrap()),
+            Ok(Some(0.5))
+        );
+    }
+
+    #[test]
+    fn test_quality_trim() {
+        let q20 = Phred::new(20).unwrap();
+        let trim = |seq: &[u8], qual: &[u8], window| {
+            (seq, qual).quality_trim(window, q20).map_err(|e| e.kind)
+        };
+        assert_eq!(trim(b"ACGT", b"IIII", 2), Ok(0..4));
+        // a good window after a bad one doesn't matter
+        assert_eq!(trim(b"ACGTACGT", b"II##IIII", 2), Ok(0..2));
+        assert_eq!(trim(b"ACGTACGT", b"I#I#IIII", 2), Ok(0..8));
+        assert_eq!(trim(b"ACGTACGT", b"II5#IIII", 2), Ok(0..3));
+        assert_eq!(trim(b"ACGT", b"####", 10), Ok(0..0));
+        assert_eq!(trim(b"ACGT", b"5I#I", 10), Ok(0..4));
+        assert_eq!((&b"AC"[..], &b"5#"[..]).trim_ends(q20), Ok(0..1));
+        // no quality
+        assert_eq!(trim(b"ACGT", b"", 2), Err(ParseErrorKind::WrongFormat));
+        let err = (&b"ACGT"[..], &b""[..]).trim_ends(q20).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+
+        let mut reader = parse_fastx_reader(&b">r1\nACGT\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let err = record.quality_trim(2, q20).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+        assert_eq!(err.position.id.as_deref(), Some("r1"));
+        let err = record.trim_ends(q20).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+    }
+
+    #[test]
+    fn test_quality_mask() {
+        let seq_rec = (&b"AGCT"[..], &b"AAA0"[..]);
+        let filtered_rec = seq_rec.quality_mask(b'5');
+        assert_eq!(&filtered_rec[..], &b"AGCN"[..]);
+
+        let mut buf = Vec::new();
+        assert_eq!(seq_rec.quality_mask_into(&mut buf, b'5').unwrap(), b"AGCN");
+        let fasta = (&b"AGCT"[..], &b""[..]);
+        let err = fasta.quality_mask_into(&mut buf, b'5').unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+    }
+
+    #[test]
+    fn test_quality_mask_records() {
+        let mut reader = parse_fastx_reader(&b">id\nACGT\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.quality(), b"");
+        assert_eq!(record.quality_mask(b'5').as_ref(), b"ACGT");
+        let err = record.try_quality_mask(b'5').unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+
+        let owned = OwnedRecord {
+            id: b"id".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: Some(b"#III".to_vec()),
+        };
+        assert_eq!(owned.quality_mask(b'5').as_ref(), b"NCGT");
+        assert_eq!(owned.try_quality_mask(b'5').unwrap().as_ref(), b"NCGT");
+    }
+
+    #[test]
+    fn test_map_quality() {
+        let mut owned = OwnedRecord {
+            id: b"id".to_vec(),
+            seq: b"ACGT".to_vec(),
+            qual: Some(b"IIII".to_vec()),
+        };
+        map_quality(&mut owned, |q, pos, _| q - pos as u8).unwrap();
+        assert_eq!(owned.qual.as_deref(), Some(&b"IHGF"[..]));
+
+        owned.qual = None;
+        let err = map_quality(&mut owned, |_, _, _| b'#').unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+        assert_eq!(err.format, Some(Format::Fasta));
+        assert_eq!(owned.qual, None);
+    }
+
+    #[test]
+    fn test_quality_windows() {
+        let rec = (&b"ACGTA"[..], &b"ABCDE"[..]);
+        let windows: Vec<_> = rec.quality_windows(2, 2).unwrap().collect();
+        assert_eq!(
+            windows,
+            vec![(0, &b"AC"[..], &b"AB"[..]), (2, &b"GT"[..], &b"CD"[..])]
+        );
+        assert_eq!(rec.quality_windows(1, 1).unwrap().count(), 5);
+        assert_eq!(rec.quality_windows(6, 1).unwrap().count(), 0);
+
+        let fasta = (&b"ACGT"[..], &b""[..]);
+        let err = fasta.quality_windows(2, 1).err().unwrap();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+    }
+
+    #[test]
+    fn test_phred() {
+        assert_eq!(Phred::from_ascii(b'!'), Some(Phred::MIN));
+        assert_eq!(Phred::from_ascii(b'~'), Some(Phred::MAX));
+        assert_eq!(Phred::from_ascii(b'\x7f'), None);
+        assert_eq!(Phred::new(94), None);
+        for c in b'!'..=b'~' {
+            let q = Phred::from_ascii(c).unwrap();
+            assert_eq!(q.to_ascii(), c);
+            assert_eq!(Phred::from_error_probability(q.error_probability()), q);
+        }
+        assert_eq!(Phred::from_error_probability(0.0), Phred::MAX);
+        assert_eq!(Phred::from_error_probability(2.0), Phred::MIN);
+    }
+
+    #[test]
+    fn test_error_probabilities() {
+        assert_eq!(error_probability(b' '), 1.0);
+        assert_eq!(error_probability(b'!'), 1.0);
+        assert_eq!(error_probability(b'5'), 0.01);
+
+        let mut reader = parse_fastx_reader(&b"@id\nACG\n+\n+5?\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let probabilities = record.error_probabilities().unwrap();
+        assert_eq!(probabilities, [0.1, 0.01, 0.001]);
+        assert_eq!(
+            record.to_owned_record().error_probabilities().unwrap(),
+            probabilities
+        );
+
+        let mut reader = parse_fastx_reader(&b">id\nACGT\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let err = record.error_probabilities().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+        let err = record.to_owned_record().error_probabilities().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+
+        // the buffer is reused across records
+        let mut buf = Vec::with_capacity(8);
+        let ptr = buf.as_ptr();
+        let owned = OwnedRecord {
+            id: b"id".to_vec(),
+            seq: b"ACG".to_vec(),
+            qual: Some(b"+5?".to_vec()),
+        };
+        assert_eq!(
+            owned.error_probabilities_into(&mut buf).unwrap(),
+            probabilities
+        );
+        assert_eq!(
+            (&b"AC"[..], &b"!?"[..])
+                .error_probabilities_into(&mut buf)
+                .unwrap(),
+            [1.0, 0.001]
+        );
+        assert_eq!(buf.as_ptr(), ptr);
+        let err = record.error_probabilities_into(&mut buf).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::WrongFormat);
+    }
+}
@@ -0,0 +1,439 @@
+//! Reading FASTA/FASTQ files straight from remote URLs, with the `url` feature.
+//!
+//! Files are fetched through a [`Transport`]: [`HttpTransport`] handles HTTP(S) URLs and
+//! public S3 objects (`s3://bucket/key`), other stores (e.g. private S3 buckets) can be
+//! read by implementing the trait. Broken connections are resumed where they stopped
+//! with range requests, after waiting a bit longer each time.
+use std::borrow::Cow;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::thread::sleep;
+use std::time::Duration;
+
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_LENGTH, RANGE};
+use reqwest::StatusCode;
+
+use crate::errors::ParseError;
+use crate::parser::{parse_fastx_reader, FastxReader};
+
+/// How remote files are fetched
+///
+/// Failures worth retrying are reported with the kinds [`RetryPolicy`] retries: broken
+/// connections, timeouts and server errors. Any other error is returned right away.
+pub trait Transport: Send + Sync {
+    /// Opens a stream of the bytes of the file at `url`, starting at byte `start`
+    fn get(&self, url: &str, start: u64) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Returns the size in bytes of the file at `url`
+    fn size(&self, url: &str) -> io::Result<u64>;
+}
+
+/// How many times and how long to wait before trying again when a remote operation fails.
+/// Only errors of kind `TimedOut`, `ConnectionRefused`, `ConnectionReset`,
+/// `ConnectionAborted`, `NotConnected`, `BrokenPipe`, `UnexpectedEof` and `Interrupted`
+/// are retried, which `HttpTransport` also uses for server errors (5xx and 429).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of retries of a single operation, 5 by default
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each next one. 500ms by default.
+    pub initial_backoff: Duration,
+    /// Longest wait between retries, 30s by default
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn run<T>(&self, mut operation: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let mut backoff = self.initial_backoff;
+        let mut retries = 0;
+        loop {
+            match operation() {
+                Err(e) if retries < self.max_retries && is_transient(&e) => {
+                    sleep(backoff);
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::Interrupted
+    )
+}
+
+/// A remote file read through a [`Transport`]. A failed read is retried from where the
+/// previous one stopped. It can seek (e.g. for indexed readers): the next read then
+/// starts a new request at that position.
+pub struct RemoteReader<T: Transport> {
+    transport: T,
+    url: String,
+    retry: RetryPolicy,
+    offset: u64,
+    stream: Option<Box<dyn Read + Send>>,
+}
+
+impl<T: Transport> RemoteReader<T> {
+    pub fn new(transport: T, url: &str) -> Self {
+        Self {
+            transport,
+            url: url.to_string(),
+            retry: RetryPolicy::default(),
+            offset: 0,
+            stream: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+impl<T: Transport> Read for RemoteReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let Self {
+            transport,
+            url,
+            retry,
+            offset,
+            stream,
+        } = self;
+        let n = retry.run(|| {
+            let reader = match stream {
+                Some(reader) => reader,
+                None => stream.insert(transport.get(url, *offset)?),
+            };
+            reader.read(buf).inspect_err(|_| {
+                // start again from the current offset on the next attempt
+                *stream = None;
+            })
+        })?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: Transport> Seek for RemoteReader<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => {
+                let size = self.retry.run(|| self.transport.size(&self.url))?;
+                size.checked_add_signed(delta)
+            }
+        };
+        let offset = offset.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seeking before the start of the file",
+            )
+        })?;
+        if offset != self.offset {
+            self.offset = offset;
+            self.stream = None;
+        }
+        Ok(offset)
+    }
+}
+
+/// Fetches HTTP(S) URLs and public S3 objects (`s3://bucket/key`, read from
+/// `https://bucket.s3.amazonaws.com/key`)
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl HttpTransport {
+    /// A transport with a client that doesn't time out while streaming a large file,
+    /// only when connecting
+    pub fn new() -> io::Result<Self> {
+        let client = Client::builder()
+            .timeout(None)
+            .connect_timeout(Duration::from_secs(30))
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(Self { client })
+    }
+
+    /// A transport using `client`, e.g. to set headers or a proxy
+    pub fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+fn resolve_url(url: &str) -> Cow<'_, str> {
+    match url
+        .strip_prefix("s3://")
+        .and_then(|path| path.split_once('/'))
+    {
+        Some((bucket, key)) => format!("https://{bucket}.s3.amazonaws.com/{key}").into(),
+        None => url.into(),
+    }
+}
+
+fn check_status(response: Response) -> io::Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let kind = match status {
+        StatusCode::NOT_FOUND => io::ErrorKind::NotFound,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => io::ErrorKind::PermissionDenied,
+        // retried
+        StatusCode::TOO_MANY_REQUESTS => io::ErrorKind::ConnectionAborted,
+        s if s.is_server_error() => io::ErrorKind::ConnectionAborted,
+        s if s.is_client_error() => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::Other,
+    };
+    Err(io::Error::new(
+        kind,
+        format!("{} returned {status}", response.url()),
+    ))
+}
+
+/// Gives the errors of the connection a kind that is retried, other errors (e.g. an
+/// invalid URL) keep the `Other` kind
+fn request_error(error: reqwest::Error) -> io::Error {
+    let kind = if error.is_timeout() {
+        io::ErrorKind::TimedOut
+    } else if error.is_connect() {
+        io::ErrorKind::ConnectionRefused
+    } else if error.is_request() || error.is_body() {
+        io::ErrorKind::ConnectionReset
+    } else {
+        io::ErrorKind::Other
+    };
+    io::Error::new(kind, error)
+}
+
+/// The body of a response, whose read errors come from the connection
+struct Body(Response);
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::Other => io::Error::new(io::ErrorKind::ConnectionReset, e),
+            _ => e,
+        })
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get(&self, url: &str, start: u64) -> io::Result<Box<dyn Read + Send>> {
+        let mut request = self.client.get(resolve_url(url).as_ref());
+        if start > 0 {
+            request = request.header(RANGE, format!("bytes={start}-"));
+        }
+        let response = request.send().map_err(request_error)?;
+        if start > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // reading at (or past) the end of the file
+            return Ok(Box::new(io::empty()));
+        }
+        let mut body = Body(check_status(response)?);
+        if start > 0 && body.0.status() != StatusCode::PARTIAL_CONTENT {
+            // the server sent the whole file
+            if io::copy(&mut (&mut body).take(start), &mut io::sink())? < start {
+                return Ok(Box::new(io::empty()));
+            }
+        }
+        Ok(Box::new(body))
+    }
+
+    fn size(&self, url: &str) -> io::Result<u64> {
+        let request = self.client.head(resolve_url(url).as_ref());
+        let response = check_status(request.send().map_err(request_error)?)?;
+        response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("no size given for {url}"),
+                )
+            })
+    }
+}
+
+/// Reads a FASTA/FASTQ file (possibly compressed) from an HTTP(S) or public S3 URL,
+/// like `parse_fastx_reader` does for local streams
+///
+/// ```no_run
+/// use needletail::parse_fastx_url;
+///
+/// let mut reader = parse_fastx_url("https://example.com/reads.fastq.gz").unwrap();
+/// while let Some(record) = reader.next() {
+///     let record = record.unwrap();
+///     // ...
+/// }
+/// ```
+pub fn parse_fastx_url(url: &str) -> Result<Box<dyn FastxReader>, ParseError> {
+    parse_fastx_url_with(url, HttpTransport::new()?, RetryPolicy::default())
+}
+
+/// Same as [`parse_fastx_url`] with another transport or retry policy
+pub fn parse_fastx_url_with<T: Transport + 'static>(
+    url: &str,
+    transport: T,
+    retry: RetryPolicy,
+) -> Result<Box<dyn FastxReader>, ParseError> {
+    parse_fastx_reader(RemoteReader::new(transport, url).with_retry(retry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Serves `data` but each connection breaks after `chunk` bytes
+    struct FlakyTransport {
+        data: Vec<u8>,
+        chunk: usize,
+        requests: AtomicUsize,
+    }
+
+    impl Transport for FlakyTransport {
+        fn get(&self, _url: &str, start: u64) -> io::Result<Box<dyn Read + Send>> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            let start = start as usize;
+            let end = (start + self.chunk).min(self.data.len());
+            let chunk = io::Cursor::new(self.data[start..end].to_vec());
+            if end == self.data.len() {
+                return Ok(Box::new(chunk));
+            }
+            Ok(Box::new(chunk.chain(FailingReader)))
+        }
+
+        fn size(&self, _url: &str) -> io::Result<u64> {
+            Ok(self.data.len() as u64)
+        }
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "broken"))
+        }
+    }
+
+    fn no_wait(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_resumes_broken_connections() {
+        let mut fastq = Vec::new();
+        for i in 0..100 {
+            fastq.extend_from_slice(format!("@r{i}\nACGT\n+\nIIII\n").as_bytes());
+        }
+        let transport = FlakyTransport {
+            data: fastq.clone(),
+            chunk: 100,
+            requests: AtomicUsize::new(0),
+        };
+        let mut reader =
+            parse_fastx_url_with("s3://bucket/reads.fq", transport, no_wait(1)).unwrap();
+        let mut count = 0;
+        while let Some(record) = reader.next() {
+            record.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 100);
+    }
+
+    #[test]
+    fn test_seek_and_retries() {
+        let transport = FlakyTransport {
+            data: b"0123456789".to_vec(),
+            chunk: 4,
+            requests: AtomicUsize::new(0),
+        };
+        let mut reader = RemoteReader::new(transport, "https://example.com").with_retry(no_wait(1));
+        reader.seek(SeekFrom::End(-3)).unwrap();
+        let mut end = String::new();
+        reader.read_to_string(&mut end).unwrap();
+        assert_eq!(end, "789");
+        reader.seek(SeekFrom::Start(1)).unwrap();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, b"123456789");
+        assert!(reader.seek(SeekFrom::Current(-20)).is_err());
+
+        // without retries, the first broken connection is an error
+        let transport = FlakyTransport {
+            data: b"0123456789".to_vec(),
+            chunk: 4,
+            requests: AtomicUsize::new(0),
+        };
+        let mut reader = RemoteReader::new(transport, "https://example.com").with_retry(no_wait(0));
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert_eq!(reader.transport.requests.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_only_transient_errors_are_retried() {
+        for kind in [
+            io::ErrorKind::TimedOut,
+            io::ErrorKind::ConnectionReset,
+            io::ErrorKind::ConnectionAborted,
+        ] {
+            assert!(is_transient(&io::Error::new(kind, "")));
+        }
+        for kind in [
+            io::ErrorKind::NotFound,
+            io::ErrorKind::InvalidInput,
+            io::ErrorKind::InvalidData,
+            io::ErrorKind::Other,
+        ] {
+            assert!(!is_transient(&io::Error::new(kind, "")));
+        }
+
+        let attempts = AtomicUsize::new(0);
+        let result: io::Result<()> = no_wait(3).run(|| {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(io::Error::other("invalid URL"))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        assert_eq!(
+            resolve_url("s3://bucket/dir/reads.fq"),
+            "https://bucket.s3.amazonaws.com/dir/reads.fq"
+        );
+        assert_eq!(resolve_url("https://a.com/r.fq"), "https://a.com/r.fq");
+    }
+}
@@ -0,0 +1,556 @@
+//! Random subsampling of reads, e.g. to downsample a run to a target coverage or to
+//! a fixed number of reads.
+//!
+//! Whether a read is kept only depends on its id and the seed: subsampling is
+//! deterministic, done in a single pass and mates of a pair are always kept or
+//! dropped together.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io::Write;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::{mate_key, FastxReader, OwnedRecord, SequenceRecord};
+
+/// How many reads to keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubsampleMode {
+    /// Keep this fraction of the reads (or read pairs), between 0 and 1
+    Fraction(f64),
+    /// Keep enough reads to reach `coverage` (e.g. 30.0 for 30×) of a genome of
+    /// `genome_size` bases, out of an input of `total_bases` bases (counting both
+    /// mates for paired reads). `total_bases` can be obtained quickly with
+    /// [`crate::validate::fastq_quick`].
+    Coverage {
+        genome_size: u64,
+        coverage: f64,
+        total_bases: u64,
+    },
+    /// Keep exactly this many reads (or read pairs), all of them if there are fewer.
+    /// The reads kept so far are held in memory until the end of the input, see
+    /// [`Reservoir`].
+    Count(usize),
+}
+
+impl SubsampleMode {
+    /// The fraction of the reads to keep, `None` for [`SubsampleMode::Count`] since it
+    /// depends on the number of reads
+    pub fn fraction(&self) -> Option<f64> {
+        match *self {
+            SubsampleMode::Fraction(fraction) => Some(fraction.clamp(0., 1.)),
+            SubsampleMode::Coverage {
+                genome_size,
+                coverage,
+                total_bases,
+            } => Some(fraction_for_coverage(genome_size, coverage, total_bases)),
+            SubsampleMode::Count(_) => None,
+        }
+    }
+}
+
+/// Fraction of an input of `total_bases` bases needed to reach `coverage` of a genome
+/// of `genome_size` bases, 1 if there aren't enough bases
+pub fn fraction_for_coverage(genome_size: u64, coverage: f64, total_bases: u64) -> f64 {
+    if total_bases == 0 {
+        return 1.;
+    }
+    (genome_size as f64 * coverage / total_bases as f64).clamp(0., 1.)
+}
+
+/// Decides which reads to keep from their id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sampler {
+    // reads whose hash is below this are kept
+    threshold: u64,
+    seed: u64,
+    keep_all: bool,
+}
+
+impl Sampler {
+    pub fn new(fraction: f64, seed: u64) -> Self {
+        let fraction = fraction.clamp(0., 1.);
+        Self {
+            threshold: (fraction * u64::MAX as f64) as u64,
+            seed,
+            keep_all: fraction >= 1.,
+        }
+    }
+
+    /// Whether to keep the read with that id. Mates get the same answer.
+    pub fn keep(&self, id: &[u8]) -> bool {
+        self.keep_all || hash_id(id, self.seed) < self.threshold
+    }
+}
+
+/// Hash of the id of a read, the same for both mates
+fn hash_id(id: &[u8], seed: u64) -> u64 {
+    // FNV-1a, mixed with the finalizer of MurmurHash3 for better low bits
+    let mut h = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for b in mate_key(id) {
+        h ^= u64::from(*b);
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// An item in a [`Reservoir`], the largest being the one with the highest hash
+#[derive(Debug)]
+struct Slot<T> {
+    hash: u64,
+    index: usize,
+    item: T,
+}
+
+impl<T> PartialEq for Slot<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Slot<T> {}
+
+impl<T> PartialOrd for Slot<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Slot<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.hash, self.index).cmp(&(other.hash, other.index))
+    }
+}
+
+/// Reservoir sampling of a fixed number of reads out of an input of unknown size.
+///
+/// Each read gets a random priority, the hash of its id, and the reservoir holds the
+/// reads with the lowest ones seen so far: at any point, it's a uniform sample of the
+/// reads seen. Using the hash of the id rather than a random number generator keeps
+/// the sample deterministic for a seed and the same for both mates of a pair.
+///
+/// ```
+/// use needletail::sample::Reservoir;
+///
+/// let mut reservoir = Reservoir::new(10, 42);
+/// for i in 0..1000 {
+///     let id = format!("read{i}");
+///     reservoir.offer(id.as_bytes(), || i);
+/// }
+/// assert_eq!(reservoir.seen(), 1000);
+/// let kept = reservoir.into_items();
+/// assert_eq!(kept.len(), 10);
+/// // in input order
+/// assert!(kept.windows(2).all(|w| w[0] < w[1]));
+/// ```
+#[derive(Debug)]
+pub struct Reservoir<T> {
+    capacity: usize,
+    seed: u64,
+    seen: usize,
+    // a max-heap, so the read to evict is at the top
+    slots: BinaryHeap<Slot<T>>,
+}
+
+impl<T> Reservoir<T> {
+    /// Keeps at most `capacity` reads
+    pub fn new(capacity: usize, seed: u64) -> Self {
+        Self {
+            capacity,
+            seed,
+            seen: 0,
+            slots: BinaryHeap::new(),
+        }
+    }
+
+    /// Offers the next read, with the item to keep for it only being built if the
+    /// read enters the reservoir. Returns whether it did.
+    pub fn offer(&mut self, id: &[u8], item: impl FnOnce() -> T) -> bool {
+        let hash = hash_id(id, self.seed);
+        let index = self.seen;
+        self.seen += 1;
+        if self.slots.len() >= self.capacity {
+            match self.slots.peek() {
+                Some(top) if hash < top.hash => {
+                    self.slots.pop();
+                }
+                _ => return false,
+            }
+        }
+        self.slots.push(Slot {
+            hash,
+            index,
+            item: item(),
+        });
+        true
+    }
+
+    /// Number of reads offered so far
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    /// The reads kept, in the order they were offered
+    pub fn into_items(self) -> Vec<T> {
+        let mut slots = self.slots.into_vec();
+        slots.sort_unstable_by_key(|slot| slot.index);
+        slots.into_iter().map(|slot| slot.item).collect()
+    }
+}
+
+/// What was seen and kept while subsampling, pairs counting as one read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubsampleCounts {
+    pub seen: usize,
+    pub kept: usize,
+    /// Number of bases kept, both mates included
+    pub kept_bases: u64,
+}
+
+/// Fills a reservoir of `count` reads with the records of `reader`
+fn reservoir_sample(
+    reader: &mut dyn FastxReader,
+    count: usize,
+    seed: u64,
+) -> Result<Reservoir<OwnedRecord>, ParseError> {
+    let mut reservoir = Reservoir::new(count, seed);
+    while let Some(record) = reader.next() {
+        let record = record?;
+        reservoir.offer(record.id(), || record.to_owned_record());
+    }
+    Ok(reservoir)
+}
+
+/// Returns the reads of `reader` selected by `mode`, in input order
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::sample::{subsample_reader, SubsampleMode};
+///
+/// let mut fastq = Vec::new();
+/// for i in 0..1000 {
+///     fastq.extend_from_slice(format!("@r{i}\nACGT\n+\nIIII\n").as_bytes());
+/// }
+/// let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+/// let kept = subsample_reader(&mut reader, SubsampleMode::Count(100), 42).unwrap();
+/// assert_eq!(kept.len(), 100);
+/// ```
+pub fn subsample_reader(
+    reader: &mut dyn FastxReader,
+    mode: SubsampleMode,
+    seed: u64,
+) -> Result<Vec<OwnedRecord>, ParseError> {
+    if let SubsampleMode::Count(count) = mode {
+        return Ok(reservoir_sample(reader, count, seed)?.into_items());
+    }
+    let sampler = Sampler::new(mode.fraction().unwrap_or(1.), seed);
+    let mut kept = Vec::new();
+    while let Some(record) = reader.next() {
+        let record = record?;
+        if sampler.keep(record.id()) {
+            kept.push(record.to_owned_record());
+        }
+    }
+    Ok(kept)
+}
+
+/// Writes the reads of `reader` selected by `mode` to `writer`, in input order.
+///
+/// With [`SubsampleMode::Count`], nothing is written before the whole input was read.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::sample::{subsample_to_writer, SubsampleMode};
+///
+/// let mut fasta = Vec::new();
+/// for i in 0..1000 {
+///     fasta.extend_from_slice(format!(">{i}\nACGT\n").as_bytes());
+/// }
+/// let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+/// // 10x of a 100bp genome out of 4000 bases
+/// let mode = SubsampleMode::Coverage { genome_size: 100, coverage: 10., total_bases: 4000 };
+/// let counts = subsample_to_writer(&mut reader, mode, 42, &mut Vec::new()).unwrap();
+/// assert!((200..300).contains(&counts.kept));
+/// ```
+pub fn subsample_to_writer(
+    reader: &mut dyn FastxReader,
+    mode: SubsampleMode,
+    seed: u64,
+    writer: &mut dyn Write,
+) -> Result<SubsampleCounts, ParseError> {
+    if let SubsampleMode::Count(count) = mode {
+        let reservoir = reservoir_sample(reader, count, seed)?;
+        let mut counts = SubsampleCounts {
+            seen: reservoir.seen(),
+            ..Default::default()
+        };
+        for record in reservoir.into_items() {
+            record.write(writer, None)?;
+            counts.kept += 1;
+            counts.kept_bases += record.num_bases() as u64;
+        }
+        return Ok(counts);
+    }
+    let sampler = Sampler::new(mode.fraction().unwrap_or(1.), seed);
+    let mut counts = SubsampleCounts::default();
+    while let Some(record) = reader.next() {
+        let record = record?;
+        counts.seen += 1;
+        if sampler.keep(record.id()) {
+            record.write(writer, None)?;
+            counts.kept += 1;
+            counts.kept_bases += record.num_bases() as u64;
+        }
+    }
+    Ok(counts)
+}
+
+fn unpaired_error(record: &SequenceRecord) -> ParseError {
+    ParseError::new_unpaired_records(
+        ErrorPosition {
+            line: record.start_line_number(),
+            id: Some(String::from_utf8_lossy(record.id()).into()),
+        },
+        record.format(),
+    )
+}
+
+/// The next record of each reader, an error of kind `UnpairedRecords` if only one of
+/// them has one
+fn next_pair<'a, 'b>(
+    reader1: &'a mut dyn FastxReader,
+    reader2: &'b mut dyn FastxReader,
+) -> Result<Option<(SequenceRecord<'a>, SequenceRecord<'b>)>, ParseError> {
+    match (reader1.next(), reader2.next()) {
+        (None, None) => Ok(None),
+        (Some(r1), Some(r2)) => Ok(Some((r1?, r2?))),
+        (Some(r), None) | (None, Some(r)) => Err(unpaired_error(&r?)),
+    }
+}
+
+/// Fills a reservoir of `count` read pairs, chosen on the id of R1
+fn reservoir_sample_paired(
+    reader1: &mut dyn FastxReader,
+    reader2: &mut dyn FastxReader,
+    count: usize,
+    seed: u64,
+) -> Result<Reservoir<(OwnedRecord, OwnedRecord)>, ParseError> {
+    let mut reservoir = Reservoir::new(count, seed);
+    while let Some((record1, record2)) = next_pair(reader1, reader2)? {
+        reservoir.offer(record1.id(), || {
+            (record1.to_owned_record(), record2.to_owned_record())
+        });
+    }
+    Ok(reservoir)
+}
+
+/// Paired-end version of [`subsample_reader`]: the decision is made on the id of R1
+/// and both mates are kept or dropped together.
+///
+/// Returns an error of kind `UnpairedRecords` if one of the readers runs out of
+/// records before the other.
+pub fn subsample_paired_reader(
+    reader1: &mut dyn FastxReader,
+    reader2: &mut dyn FastxReader,
+    mode: SubsampleMode,
+    seed: u64,
+) -> Result<Vec<(OwnedRecord, OwnedRecord)>, ParseError> {
+    if let SubsampleMode::Count(count) = mode {
+        return Ok(reservoir_sample_paired(reader1, reader2, count, seed)?.into_items());
+    }
+    let sampler = Sampler::new(mode.fraction().unwrap_or(1.), seed);
+    let mut kept = Vec::new();
+    while let Some((record1, record2)) = next_pair(reader1, reader2)? {
+        if sampler.keep(record1.id()) {
+            kept.push((record1.to_owned_record(), record2.to_owned_record()));
+        }
+    }
+    Ok(kept)
+}
+
+/// Paired-end version of [`subsample_to_writer`]: the decision is made on the id of R1
+/// and both mates are written (R1 to the first writer, R2 to the second one) or dropped.
+///
+/// Returns an error of kind `UnpairedRecords` if one of the readers runs out of
+/// records before the other.
+pub fn subsample_paired_to_writers(
+    reader1: &mut dyn FastxReader,
+    reader2: &mut dyn FastxReader,
+    mode: SubsampleMode,
+    seed: u64,
+    writers: (&mut dyn Write, &mut dyn Write),
+) -> Result<SubsampleCounts, ParseError> {
+    if let SubsampleMode::Count(count) = mode {
+        let reservoir = reservoir_sample_paired(reader1, reader2, count, seed)?;
+        let mut counts = SubsampleCounts {
+            seen: reservoir.seen(),
+            ..Default::default()
+        };
+        for (record1, record2) in reservoir.into_items() {
+            record1.write(writers.0, None)?;
+            record2.write(writers.1, None)?;
+            counts.kept += 1;
+            counts.kept_bases += (record1.num_bases() + record2.num_bases()) as u64;
+        }
+        return Ok(counts);
+    }
+    let sampler = Sampler::new(mode.fraction().unwrap_or(1.), seed);
+    let mut counts = SubsampleCounts::default();
+    while let Some((record1, record2)) = next_pair(reader1, reader2)? {
+        counts.seen += 1;
+        if sampler.keep(record1.id()) {
+            record1.write(writers.0, None)?;
+            record2.write(writers.1, None)?;
+            counts.kept += 1;
+            counts.kept_bases += (record1.num_bases() + record2.num_bases()) as u64;
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_sampler() {
+        let sampler = Sampler::new(0.5, 1);
+        assert_eq!(sampler.keep(b"a/1"), sampler.keep(b"a/2 2:N:0"));
+        let kept = (0..10_000)
+            .filter(|i| sampler.keep(format!("read{i}").as_bytes()))
+            .count();
+        assert!((4800..5200).contains(&kept), "{kept}");
+
+        // another seed gives another subset
+        let other = Sampler::new(0.5, 2);
+        assert!((0..100).any(|i| {
+            let id = format!("read{i}");
+            sampler.keep(id.as_bytes()) != other.keep(id.as_bytes())
+        }));
+
+        assert!(Sampler::new(1., 0).keep(b"anything"));
+        assert!(!Sampler::new(0., 0).keep(b"anything"));
+    }
+
+    #[test]
+    fn test_subsample_paired_to_writers() {
+        let mut fastq1 = Vec::new();
+        let mut fastq2 = Vec::new();
+        for i in 0..100 {
+            fastq1.extend_from_slice(format!("@r{i}/1\nACGT\n+\nIIII\n").as_bytes());
+            fastq2.extend_from_slice(format!("@r{i}/2\nTT\n+\nII\n").as_bytes());
+        }
+        let mode = SubsampleMode::Coverage {
+            genome_size: 60,
+            coverage: 5.,
+            total_bases: 600,
+        };
+        assert_eq!(mode.fraction(), Some(0.5));
+
+        let (mut out1, mut out2) = (Vec::new(), Vec::new());
+        let mut reader1 = parse_fastx_reader(&fastq1[..]).unwrap();
+        let mut reader2 = parse_fastx_reader(&fastq2[..]).unwrap();
+        let counts = subsample_paired_to_writers(
+            &mut reader1,
+            &mut reader2,
+            mode,
+            7,
+            (&mut out1, &mut out2),
+        )
+        .unwrap();
+        assert_eq!(counts.seen, 100);
+        assert_eq!(counts.kept_bases, counts.kept as u64 * 6);
+
+        // same mates on both sides
+        let ids = |out: &[u8]| -> Vec<Vec<u8>> {
+            let mut reader = parse_fastx_reader(out).unwrap();
+            let mut ids = Vec::new();
+            while let Some(r) = reader.next() {
+                ids.push(mate_key(r.unwrap().id()).to_vec());
+            }
+            ids
+        };
+        assert_eq!(ids(&out1).len(), counts.kept);
+        assert_eq!(ids(&out1), ids(&out2));
+    }
+
+    #[test]
+    fn test_subsample_count() {
+        let mut fastq1 = Vec::new();
+        let mut fastq2 = Vec::new();
+        for i in 0..500 {
+            fastq1.extend_from_slice(format!("@r{i}/1\nACGT\n+\nIIII\n").as_bytes());
+            fastq2.extend_from_slice(format!("@r{i}/2\nTT\n+\nII\n").as_bytes());
+        }
+        let sample = |count, seed| {
+            let mut reader = parse_fastx_reader(&fastq1[..]).unwrap();
+            subsample_reader(&mut reader, SubsampleMode::Count(count), seed).unwrap()
+        };
+        let kept = sample(50, 3);
+        assert_eq!(kept.len(), 50);
+        assert_eq!(kept, sample(50, 3));
+        assert_ne!(kept, sample(50, 4));
+        // kept in input order
+        let numbers: Vec<usize> = kept
+            .iter()
+            .map(|r| {
+                std::str::from_utf8(&r.id[1..r.id.len() - 2])
+                    .unwrap()
+                    .parse()
+                    .unwrap()
+            })
+            .collect();
+        assert!(numbers.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sample(1000, 3).len(), 500);
+        assert!(sample(0, 3).is_empty());
+
+        let pairs = subsample_paired_reader(
+            &mut parse_fastx_reader(&fastq1[..]).unwrap(),
+            &mut parse_fastx_reader(&fastq2[..]).unwrap(),
+            SubsampleMode::Count(50),
+            3,
+        )
+        .unwrap();
+        // the same reads as R1 alone since the decision only depends on the id
+        assert_eq!(pairs.len(), 50);
+        assert!(pairs
+            .iter()
+            .zip(&kept)
+            .all(|((r1, r2), r)| r1 == r && mate_key(&r1.id) == mate_key(&r2.id)));
+
+        let (mut out1, mut out2) = (Vec::new(), Vec::new());
+        let counts = subsample_paired_to_writers(
+            &mut parse_fastx_reader(&fastq1[..]).unwrap(),
+            &mut parse_fastx_reader(&fastq2[..]).unwrap(),
+            SubsampleMode::Count(50),
+            3,
+            (&mut out1, &mut out2),
+        )
+        .unwrap();
+        assert_eq!(
+            counts,
+            SubsampleCounts {
+                seen: 500,
+                kept: 50,
+                kept_bases: 300
+            }
+        );
+        let mut written = Vec::new();
+        for record in &kept {
+            record.write(&mut written, None).unwrap();
+        }
+        assert_eq!(out1, written);
+
+        let err = subsample_paired_reader(
+            &mut parse_fastx_reader(&fastq1[..]).unwrap(),
+            &mut parse_fastx_reader(&fastq2[..fastq2.len() - 16]).unwrap(),
+            SubsampleMode::Count(10),
+            3,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, crate::errors::ParseErrorKind::UnpairedRecords);
+    }
+}
@@ -0,0 +1,114 @@
+//! Screening reads for known kmer fingerprints (e.g. PhiX or adapter sequences)
+//!
+//! This is a quick QC pass: a set of probe kmers is built once from the
+//! contaminant references and every read is checked for how many of its
+//! kmers hit that set.
+use std::collections::HashSet;
+
+use crate::bitkmer::BitKmer;
+use crate::errors::ParseError;
+use crate::parser::FastxReader;
+use crate::Sequence;
+
+/// A read that contained at least `min_hits` probe kmers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenHit {
+    /// 0-based index of the record in the stream
+    pub record_index: usize,
+    /// ID of the record
+    pub id: Vec<u8>,
+    /// Number of kmers of the record found in the probe set
+    pub hits: usize,
+}
+
+/// Build a set of canonical probe kmers of size `k` from a sequence, typically
+/// the reference of the contaminant you're screening for.
+///
+/// Kmers containing non-ACGT bases are skipped.
+pub fn probe_kmers(seq: &[u8], k: u8) -> HashSet<BitKmer> {
+    seq.strip_returns()
+        .bit_kmers(k, true)
+        .map(|(_, kmer, _)| kmer)
+        .collect()
+}
+
+/// Stream all the records of `reader` and report the ones containing at least
+/// `min_hits` kmers from `probe_kmers`.
+///
+/// The probe kmers are expected to be canonical (see [`probe_kmers`]) and to all
+/// have the same size; the size of the first one is used to kmerize the reads.
+/// An empty probe set never matches anything.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::screen::{contains_kmers, probe_kmers};
+///
+/// let probes = probe_kmers(b"GAGTTTTATCGCTTCCATGA", 11);
+/// let mut reader = parse_fastx_reader(&b">a\nACGATCGATCAG\n>b\nTTTTATCGCTTCCA\n"[..]).unwrap();
+/// let hits = contains_kmers(&mut reader, &probes, 2).unwrap();
+/// assert_eq!(hits.len(), 1);
+/// assert_eq!(hits[0].id, b"b");
+/// ```
+pub fn contains_kmers(
+    reader: &mut dyn FastxReader,
+    probe_kmers: &HashSet<BitKmer>,
+    min_hits: usize,
+) -> Result<Vec<ScreenHit>, ParseError> {
+    let mut found = Vec::new();
+    let k = match probe_kmers.iter().next() {
+        Some((_, k)) => *k,
+        None => {
+            // we still go through the reader so parsing errors are surfaced
+            while let Some(record) = reader.next() {
+                record?;
+            }
+            return Ok(found);
+        }
+    };
+
+    let mut record_index = 0;
+    while let Some(record) = reader.next() {
+        let record = record?;
+        let seq = record.strip_returns();
+        let hits = seq
+            .bit_kmers(k, true)
+            .filter(|(_, kmer, _)| probe_kmers.contains(kmer))
+            .count();
+        if hits >= min_hits && hits > 0 {
+            found.push(ScreenHit {
+                record_index,
+                id: record.id().to_vec(),
+                hits,
+            });
+        }
+        record_index += 1;
+    }
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn test_screen_finds_reads_on_both_strands() {
+        let probes = probe_kmers(b"ACGTTGCAAGGCTTAC", 5);
+        let input = b"@fwd\nACGTTGCAAG\n+\nIIIIIIIIII\n@none\nAAAAAAAAAA\n+\nIIIIIIIIII\n@rev\nGTAAGCCTTG\n+\nIIIIIIIIII\n";
+        let mut reader = parse_fastx_reader(&input[..]).unwrap();
+        let hits = contains_kmers(&mut reader, &probes, 3).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].record_index, 0);
+        assert_eq!(hits[0].hits, 6);
+        assert_eq!(hits[1].id, b"rev");
+        assert_eq!(hits[1].record_index, 2);
+    }
+
+    #[test]
+    fn test_screen_empty_probes() {
+        let mut reader = parse_fastx_reader(&b">a\nACGT\n"[..]).unwrap();
+        let hits = contains_kmers(&mut reader, &HashSet::new(), 0).unwrap();
+        assert!(hits.is_empty());
+    }
+}
@@ -0,0 +1,528 @@
+//! Searching for primers and other short patterns in sequences
+#[cfg(feature = "search")]
+use aho_corasick::AhoCorasick;
+
+use crate::sequence::{complement, Strand};
+use crate::Sequence;
+
+/// Bitmask of the bases each (upper or lowercase) IUPAC code can stand for,
+/// with A = 1, C = 2, G = 4 and T/U = 8. Anything else is 0 and never matches.
+const IUPAC_MASK: [u8; 256] = {
+    let mut lookup = [0; 256];
+    let codes: [(u8, u8); 16] = [
+        (b'A', 0b0001),
+        (b'C', 0b0010),
+        (b'G', 0b0100),
+        (b'T', 0b1000),
+        (b'U', 0b1000),
+        (b'R', 0b0101),
+        (b'Y', 0b1010),
+        (b'S', 0b0110),
+        (b'W', 0b1001),
+        (b'K', 0b1100),
+        (b'M', 0b0011),
+        (b'B', 0b1110),
+        (b'D', 0b1101),
+        (b'H', 0b1011),
+        (b'V', 0b0111),
+        (b'N', 0b1111),
+    ];
+    let mut i = 0;
+    while i < codes.len() {
+        let (code, mask) = codes[i];
+        lookup[code as usize] = mask;
+        lookup[code.to_ascii_lowercase() as usize] = mask;
+        i += 1;
+    }
+    lookup
+};
+
+#[inline]
+fn base_matches(base: u8, pattern_mask: u8) -> bool {
+    let base_mask = IUPAC_MASK[base as usize];
+    // every base the sequence character can stand for needs to be allowed by the pattern
+    base_mask != 0 && base_mask & !pattern_mask == 0
+}
+
+/// Returns whether `seq` matches the (possibly degenerate) `primer` exactly,
+/// e.g. an `R` in the primer matches both `A` and `G` in the sequence.
+///
+/// Ambiguous bases in `seq` only match if the primer allows all the bases they
+/// can stand for: an `N` in the sequence only matches an `N` in the primer.
+/// Comparison is case-insensitive.
+///
+/// ```
+/// use needletail::search::match_degenerate;
+///
+/// assert!(match_degenerate(b"ACGT", b"RCKN"));
+/// assert!(!match_degenerate(b"CCGT", b"RCKN"));
+/// ```
+pub fn match_degenerate(seq: &[u8], primer_iupac: &[u8]) -> bool {
+    seq.len() == primer_iupac.len()
+        && seq
+            .iter()
+            .zip(primer_iupac)
+            .all(|(b, p)| base_matches(*b, IUPAC_MASK[*p as usize]))
+}
+
+/// Returns the start positions of all the (possibly overlapping) exact matches
+/// of the degenerate `primer_iupac` in `seq`.
+pub fn find_degenerate(seq: &[u8], primer_iupac: &[u8]) -> Vec<usize> {
+    DegeneratePattern::new(primer_iupac)
+        .find_iter(seq)
+        .collect()
+}
+
+/// A degenerate pattern with its per-position bitmasks precomputed, to use
+/// when searching the same primer in many records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DegeneratePattern {
+    masks: Vec<u8>,
+}
+
+impl DegeneratePattern {
+    pub fn new(primer_iupac: &[u8]) -> Self {
+        Self {
+            masks: primer_iupac
+                .iter()
+                .map(|p| IUPAC_MASK[*p as usize])
+                .collect(),
+        }
+    }
+
+    /// The pattern of the reverse complement of the primer, to search the
+    /// other strand.
+    pub fn reverse_complement(primer_iupac: &[u8]) -> Self {
+        let rc: Vec<u8> = primer_iupac.iter().rev().map(|b| complement(*b)).collect();
+        Self::new(&rc)
+    }
+
+    /// Length of the pattern
+    pub fn len(&self) -> usize {
+        self.masks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    /// Whether the pattern matches `seq` starting at `pos`
+    #[inline]
+    pub fn matches_at(&self, seq: &[u8], pos: usize) -> bool {
+        match seq.get(pos..pos + self.masks.len()) {
+            Some(window) => window
+                .iter()
+                .zip(&self.masks)
+                .all(|(b, m)| base_matches(*b, *m)),
+            None => false,
+        }
+    }
+
+    /// Iterates over the start positions of all the matches in `seq`
+    pub fn find_iter<'a>(&'a self, seq: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let end = if self.is_empty() {
+            0
+        } else {
+            (seq.len() + 1).saturating_sub(self.masks.len())
+        };
+        (0..end).filter(move |pos| self.matches_at(seq, *pos))
+    }
+}
+
+/// A match of a pattern in a sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PatternMatch {
+    /// Index of the pattern, in the order they were given
+    pub pattern: usize,
+    /// Position of the first base of the match, on the forward strand
+    pub start: usize,
+    /// Position after the last base of the match, on the forward strand
+    pub end: usize,
+    /// `Reverse` if it's the reverse complement of the pattern that matched
+    pub strand: Strand,
+    /// Number of edits (substitutions, insertions or deletions) in the match
+    pub distance: usize,
+}
+
+fn sort_matches(matches: &mut [PatternMatch]) {
+    matches.sort_unstable_by_key(|m| (m.start, m.end, m.strand == Strand::Reverse, m.pattern));
+}
+
+/// Exact search of many patterns at once with an Aho-Corasick automaton, ignoring case.
+/// Unlike [`DegeneratePattern`], IUPAC codes in the patterns only match themselves.
+/// Requires the `search` feature.
+///
+/// ```
+/// use needletail::search::MultiPattern;
+/// use needletail::sequence::Strand;
+///
+/// let patterns = MultiPattern::new(&[&b"ACGT"[..], b"GGA"]).both_strands();
+/// let matches = patterns.find_all(&b"TTacgtTCCAA"[..]);
+/// let found: Vec<_> = matches.iter().map(|m| (m.pattern, m.start, m.strand)).collect();
+/// // ACGT is its own reverse complement, TCC is the one of GGA
+/// assert_eq!(found, [(0, 2, Strand::Forward), (0, 2, Strand::Reverse), (1, 6, Strand::Reverse)]);
+/// ```
+#[cfg(feature = "search")]
+#[derive(Debug, Clone)]
+pub struct MultiPattern {
+    patterns: Vec<Vec<u8>>,
+    both_strands: bool,
+    automaton: AhoCorasick,
+}
+
+#[cfg(feature = "search")]
+impl MultiPattern {
+    /// Searches `patterns` on the forward strand
+    ///
+    /// # Panics
+    ///
+    /// Panics if the patterns are too large for the automaton to be built.
+    pub fn new<P: AsRef<[u8]>>(patterns: &[P]) -> Self {
+        Self::build(
+            patterns.iter().map(|p| p.as_ref().to_vec()).collect(),
+            false,
+        )
+    }
+
+    fn build(patterns: Vec<Vec<u8>>, both_strands: bool) -> Self {
+        let mut searched = patterns.clone();
+        if both_strands {
+            searched.extend(patterns.iter().map(|p| p.reverse_complement()));
+        }
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(&searched)
+            .expect("the patterns are too large to search");
+        Self {
+            patterns,
+            both_strands,
+            automaton,
+        }
+    }
+
+    /// Also searches the reverse complement of the patterns
+    pub fn both_strands(self) -> Self {
+        if self.both_strands {
+            return self;
+        }
+        Self::build(self.patterns, true)
+    }
+
+    /// All the (possibly overlapping) matches in `seq`, sorted by position. Line
+    /// endings are removed from `seq` first.
+    pub fn find_all<'a, S: Sequence<'a> + ?Sized>(&self, seq: &'a S) -> Vec<PatternMatch> {
+        let seq = seq.strip_returns();
+        let num_patterns = self.patterns.len();
+        let mut matches: Vec<_> = self
+            .automaton
+            .find_overlapping_iter(&*seq)
+            .map(|m| {
+                let index = m.pattern().as_usize();
+                let (pattern, strand) = if index < num_patterns {
+                    (index, Strand::Forward)
+                } else {
+                    (index - num_patterns, Strand::Reverse)
+                };
+                PatternMatch {
+                    pattern,
+                    start: m.start(),
+                    end: m.end(),
+                    strand,
+                    distance: 0,
+                }
+            })
+            .collect();
+        sort_matches(&mut matches);
+        matches
+    }
+}
+
+/// A degenerate pattern compiled for Myers' bit-parallel algorithm, one bit per
+/// position of the pattern for each byte of the sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MyersPattern {
+    len: usize,
+    peq: Vec<u64>,
+}
+
+impl MyersPattern {
+    fn new(masks: &[u8]) -> Self {
+        let mut peq = vec![0; 256];
+        for (byte, eq) in peq.iter_mut().enumerate() {
+            for (i, mask) in masks.iter().enumerate() {
+                if base_matches(byte as u8, *mask) {
+                    *eq |= 1 << i;
+                }
+            }
+        }
+        Self {
+            len: masks.len(),
+            peq,
+        }
+    }
+
+    /// The end (exclusive) and distance of the best match of each region of `seq`
+    /// matching with at most `max_distance` edits
+    fn match_ends(&self, seq: &[u8], max_distance: usize) -> Vec<(usize, usize)> {
+        let high = 1 << (self.len - 1);
+        let (mut pv, mut mv) = (u64::MAX, 0u64);
+        let mut score = self.len;
+        let mut ends: Vec<(usize, usize)> = Vec::new();
+        let mut in_region = false;
+        for (j, base) in seq.iter().enumerate() {
+            let eq = self.peq[*base as usize];
+            let xv = eq | mv;
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+            if ph & high != 0 {
+                score += 1;
+            } else if mh & high != 0 {
+                score -= 1;
+            }
+            // a match can start anywhere in the sequence, so the first row stays at 0
+            ph <<= 1;
+            mh <<= 1;
+            pv = mh | !(xv | ph);
+            mv = ph & xv;
+
+            if score <= max_distance {
+                // consecutive ends are the same match with more or fewer edits at its
+                // end: only the best one is kept
+                match ends.last_mut() {
+                    Some(last) if in_region => {
+                        if score < last.1 {
+                            *last = (j + 1, score);
+                        }
+                    }
+                    _ => ends.push((j + 1, score)),
+                }
+                in_region = true;
+            } else {
+                in_region = false;
+            }
+        }
+        ends
+    }
+}
+
+/// Start of the alignment of `masks` ending at `end` in `seq` with `distance` edits,
+/// the one closest in length to the pattern if there are several, found by aligning
+/// both backwards from `end`
+fn match_start(masks: &[u8], seq: &[u8], end: usize, distance: usize) -> usize {
+    let max_len = (masks.len() + distance).min(end);
+    // prev[j]: edits to align the last i bases of the pattern with the last j bases
+    // before `end`
+    let mut prev: Vec<usize> = (0..=max_len).collect();
+    let mut row = vec![0; max_len + 1];
+    for (i, mask) in masks.iter().rev().enumerate() {
+        row[0] = i + 1;
+        for j in 1..=max_len {
+            let cost = usize::from(!base_matches(seq[end - j], *mask));
+            row[j] = (prev[j - 1] + cost).min(prev[j] + 1).min(row[j - 1] + 1);
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    let len = (0..=max_len)
+        .min_by_key(|j| (prev[*j].max(distance), j.abs_diff(masks.len())))
+        .unwrap_or(0);
+    end - len
+}
+
+/// Approximate search of a degenerate pattern (e.g. a primer with IUPAC codes) with
+/// Myers' bit-parallel algorithm, allowing substitutions, insertions and deletions.
+/// Bases match like in [`match_degenerate`].
+///
+/// Each region of the sequence close enough to the pattern gives a single match, the
+/// one with the fewest edits.
+///
+/// ```
+/// use needletail::search::ApproxPattern;
+/// use needletail::sequence::Strand;
+///
+/// let primer = ApproxPattern::new(b"GTGYCAGCMGCCGCGGTAA", 2).both_strands();
+/// // one substitution and a deleted base
+/// let read = b"NNNNGTGCCAGCTGCGCGGTAANNNN";
+/// let matches = primer.find_all(&read[..]);
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!((matches[0].start, matches[0].end), (4, 22));
+/// assert_eq!((matches[0].strand, matches[0].distance), (Strand::Forward, 2));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApproxPattern {
+    masks: Vec<u8>,
+    max_distance: usize,
+    forward: MyersPattern,
+    reverse: Option<(Vec<u8>, MyersPattern)>,
+}
+
+impl ApproxPattern {
+    /// Searches the forward strand for `pattern_iupac` with at most `max_distance` edits
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is empty or longer than 64 bases.
+    pub fn new(pattern_iupac: &[u8], max_distance: usize) -> Self {
+        assert!(
+            (1..=64).contains(&pattern_iupac.len()),
+            "the pattern must have between 1 and 64 bases, got {}",
+            pattern_iupac.len()
+        );
+        let masks: Vec<u8> = pattern_iupac
+            .iter()
+            .map(|p| IUPAC_MASK[*p as usize])
+            .collect();
+        Self {
+            forward: MyersPattern::new(&masks),
+            masks,
+            max_distance,
+            reverse: None,
+        }
+    }
+
+    /// Also searches the reverse complement of the pattern
+    pub fn both_strands(mut self) -> Self {
+        let rc: Vec<u8> = self
+            .masks
+            .iter()
+            .rev()
+            // complementing a mask swaps A and T (bits 0 and 3), C and G (bits 1 and 2)
+            .map(|m| (m & 1) << 3 | (m & 2) << 1 | (m & 4) >> 1 | (m & 8) >> 3)
+            .collect();
+        self.reverse = Some((rc.clone(), MyersPattern::new(&rc)));
+        self
+    }
+
+    /// Length of the pattern
+    pub fn len(&self) -> usize {
+        self.masks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    /// All the matches in `seq`, sorted by position. Line endings are removed from
+    /// `seq` first. The `pattern` of the matches is always 0.
+    pub fn find_all<'a, S: Sequence<'a> + ?Sized>(&self, seq: &'a S) -> Vec<PatternMatch> {
+        let seq = seq.strip_returns();
+        let mut strands = vec![(&self.masks, &self.forward, Strand::Forward)];
+        if let Some((masks, pattern)) = &self.reverse {
+            strands.push((masks, pattern, Strand::Reverse));
+        }
+        let mut matches = Vec::new();
+        for (masks, pattern, strand) in strands {
+            for (end, distance) in pattern.match_ends(&seq, self.max_distance) {
+                matches.push(PatternMatch {
+                    pattern: 0,
+                    start: match_start(masks, &seq, end, distance),
+                    end,
+                    strand,
+                    distance,
+                });
+            }
+        }
+        sort_matches(&mut matches);
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_degenerate() {
+        assert!(match_degenerate(b"acgt", b"ACGT"));
+        assert!(match_degenerate(b"AGCTT", b"RSYWU"));
+        assert!(match_degenerate(b"ACGT", b"NNNN"));
+        assert!(!match_degenerate(b"ACGN", b"ACGT"));
+        assert!(match_degenerate(b"ACGN", b"ACGN"));
+        assert!(!match_degenerate(b"ACG", b"ACGT"));
+        assert!(!match_degenerate(b"AC-T", b"ACNT"));
+    }
+
+    #[test]
+    fn test_find_degenerate() {
+        assert_eq!(find_degenerate(b"AAGAAAGA", b"ARA"), vec![1, 3, 5]);
+        assert_eq!(find_degenerate(b"AC", b"ACG"), Vec::<usize>::new());
+        assert_eq!(find_degenerate(b"AC", b""), Vec::<usize>::new());
+
+        // GTYA reverse complemented is TRAC
+        let rc = DegeneratePattern::reverse_complement(b"GTYA");
+        assert_eq!(rc.find_iter(b"CCTGACTAAC").collect::<Vec<_>>(), vec![2, 6]);
+    }
+
+    #[cfg(feature = "search")]
+    #[test]
+    fn test_multi_pattern() {
+        let patterns = MultiPattern::new(&["AAA", "AAC"]);
+        let starts: Vec<_> = patterns
+            .find_all(&b"AAAAC\nAAA"[..])
+            .iter()
+            .map(|m| (m.pattern, m.start, m.end))
+            .collect();
+        assert_eq!(starts, [(0, 0, 3), (0, 1, 4), (1, 2, 5), (0, 5, 8)]);
+        let reverse = MultiPattern::new(&["GTT"]).both_strands().both_strands();
+        let matches = reverse.find_all(&b"AACGTT"[..]);
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].start, matches[0].strand), (0, Strand::Reverse));
+        assert_eq!((matches[1].start, matches[1].strand), (3, Strand::Forward));
+    }
+
+    /// Lowest number of edits to align `pattern` with a substring of `seq` ending at
+    /// each position
+    fn naive_distances(pattern: &[u8], seq: &[u8]) -> Vec<usize> {
+        let mut prev = vec![0; seq.len() + 1];
+        for (i, p) in pattern.iter().enumerate() {
+            let mut row = vec![i + 1; seq.len() + 1];
+            for j in 1..=seq.len() {
+                let cost = usize::from(!base_matches(seq[j - 1], IUPAC_MASK[*p as usize]));
+                row[j] = (prev[j - 1] + cost).min(prev[j] + 1).min(row[j - 1] + 1);
+            }
+            prev = row;
+        }
+        prev
+    }
+
+    #[test]
+    fn test_approx_pattern() {
+        let seq: Vec<u8> = (0..300u64)
+            .map(|i| b"ACGT"[(i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 62) as usize])
+            .collect();
+        for pattern in [&b"ACGTRC"[..], b"TTGCANNA", b"GATTACAGATTACA"] {
+            let distances = naive_distances(pattern, &seq);
+            for max_distance in 0..3 {
+                let matches = ApproxPattern::new(pattern, max_distance).find_all(&seq[..]);
+                for m in &matches {
+                    assert_eq!(distances[m.end], m.distance);
+                    assert!(m.distance <= max_distance);
+                    let aligned = naive_distances(pattern, &seq[m.start..m.end]);
+                    assert_eq!(aligned[m.end - m.start], m.distance);
+                }
+                // every end close enough is part of a match
+                let regions = distances
+                    .windows(2)
+                    .filter(|w| w[0] > max_distance && w[1] <= max_distance)
+                    .count()
+                    + usize::from(distances[0] <= max_distance);
+                assert_eq!(matches.len(), regions, "{pattern:?} {max_distance}");
+            }
+        }
+
+        // ACCGT is also a single substitution away from ACGGT
+        let primer = ApproxPattern::new(b"ACGGT", 0).both_strands();
+        let matches = primer.find_all(&b"TTACCGTTT"[..]);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0],
+            PatternMatch {
+                pattern: 0,
+                start: 2,
+                end: 7,
+                strand: Strand::Reverse,
+                distance: 0
+            }
+        );
+    }
+}
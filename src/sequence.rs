@@ -3,8 +3,40 @@ use std::borrow::Cow;
 
 use memchr::memchr2;
 
+use crate::alphabet::Alphabet;
 use crate::bitkmer::BitNuclKmer;
-use crate::kmer::{CanonicalKmers, Kmers};
+use crate::errors::ParseError;
+use crate::kmer::{CanonicalKmers, Kmers, NormalizedKmers, OwnedCanonicalKmers};
+// kept here for backwards compatibility, it now lives in `needletail::quality`
+pub use crate::quality::QualitySequence;
+
+pub mod tables;
+
+/// How to normalize sequences, see [`normalize_with`]. The default options are the
+/// ones of [`normalize`] without IUPAC bases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// Keep lowercase bases lowercase instead of uppercasing everything. Characters
+    /// replaced by an N get an `n` if they were lowercase.
+    pub keep_case: bool,
+    /// Keep `.` and `~` as they are instead of converting them to `-`
+    pub keep_gap_chars: bool,
+    /// Convert U to T (true by default)
+    pub convert_u: bool,
+    /// Keep the IUPAC ambiguity codes instead of replacing them with N
+    pub iupac: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            keep_case: false,
+            keep_gap_chars: false,
+            convert_u: true,
+            iupac: false,
+        }
+    }
+}
 
 /// Transform a nucleic acid sequence into its "normalized" form.
 ///
@@ -16,39 +48,38 @@ use crate::kmer::{CanonicalKmers, Kmers};
 ///  - some other punctuation is converted to gaps
 ///  - IUPAC bases may be converted to N's depending on the parameter passed in
 ///  - everything else is considered a N
+///
+/// This is `normalize_with` with the default options, apart from `iupac`.
 pub fn normalize(seq: &[u8], allow_iupac: bool) -> Option<Vec<u8>> {
+    normalize_with(
+        seq,
+        &NormalizeOptions {
+            iupac: allow_iupac,
+            ..Default::default()
+        },
+    )
+}
+
+/// Same as `normalize` with some of its conversions turned off, e.g. to keep the case
+/// and gap characters of aligned sequences. Returns `None` if nothing was changed.
+///
+/// ```
+/// use needletail::sequence::{normalize_with, NormalizeOptions};
+///
+/// let options = NormalizeOptions {
+///     keep_case: true,
+///     keep_gap_chars: true,
+///     ..Default::default()
+/// };
+/// assert_eq!(normalize_with(b"ac.GU~x", &options).unwrap(), b"ac.GT~n");
+/// assert_eq!(normalize_with(b"ac.gu", &Default::default()).unwrap(), b"AC-GT");
+/// ```
+pub fn normalize_with(seq: &[u8], options: &NormalizeOptions) -> Option<Vec<u8>> {
     let mut buf: Vec<u8> = Vec::with_capacity(seq.len());
     let mut changed: bool = false;
 
     for n in seq {
-        let (new_char, char_changed) = match (*n, allow_iupac) {
-            c @ (b'A' | b'C' | b'G' | b'T' | b'N' | b'-', _) => (c.0, false),
-            (b'a', _) => (b'A', true),
-            (b'c', _) => (b'C', true),
-            (b'g', _) => (b'G', true),
-            // normalize uridine to thymine
-            (b't' | b'u' | b'U', _) => (b'T', true),
-            // normalize gaps
-            (b'.' | b'~', _) => (b'-', true),
-            // logic for IUPAC bases (a little messy)
-            c @ (b'B' | b'D' | b'H' | b'V' | b'R' | b'Y' | b'S' | b'W' | b'K' | b'M', true) => {
-                (c.0, false)
-            }
-            (b'b', true) => (b'B', true),
-            (b'd', true) => (b'D', true),
-            (b'h', true) => (b'H', true),
-            (b'v', true) => (b'V', true),
-            (b'r', true) => (b'R', true),
-            (b'y', true) => (b'Y', true),
-            (b's', true) => (b'S', true),
-            (b'w', true) => (b'W', true),
-            (b'k', true) => (b'K', true),
-            (b'm', true) => (b'M', true),
-            // remove all whitespace and line endings
-            (b' ' | b'\t' | b'\r' | b'\n', _) => (b' ', true),
-            // everything else is an N
-            _ => (b'N', true),
-        };
+        let (new_char, char_changed) = normalize_char(*n, options);
         changed = changed || char_changed;
         if new_char != b' ' {
             buf.push(new_char);
@@ -61,11 +92,134 @@ pub fn normalize(seq: &[u8], allow_iupac: bool) -> Option<Vec<u8>> {
     }
 }
 
+/// Normalize a single character, see `normalize_with`. Returns the new character (a space if
+/// it should be removed) and whether it was changed.
+#[inline]
+fn normalize_char(n: u8, options: &NormalizeOptions) -> (u8, bool) {
+    let upper = n.to_ascii_uppercase();
+    let new_char = match upper {
+        b'A' | b'C' | b'G' | b'T' | b'N' | b'-' => upper,
+        // normalize uridine to thymine
+        b'U' if options.convert_u => b'T',
+        b'U' => b'U',
+        // normalize gaps
+        b'.' | b'~' if options.keep_gap_chars => n,
+        b'.' | b'~' => b'-',
+        b'B' | b'D' | b'H' | b'V' | b'R' | b'Y' | b'S' | b'W' | b'K' | b'M' if options.iupac => {
+            upper
+        }
+        // remove all whitespace and line endings
+        b' ' | b'\t' | b'\r' | b'\n' => return (b' ', true),
+        // everything else is an N
+        _ => b'N',
+    };
+    let new_char = if options.keep_case && n.is_ascii_lowercase() {
+        new_char.to_ascii_lowercase()
+    } else {
+        new_char
+    };
+    (new_char, new_char != n)
+}
+
+/// Maps positions between a sequence and its normalized form (see `normalize_with_map`).
+///
+/// Normalization only ever removes characters (whitespace and line endings) so
+/// only the runs of removed characters are stored.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PositionMap {
+    // (position in the normalized sequence right after a run of removed characters,
+    //  total number of characters removed up to there)
+    removed: Vec<(usize, usize)>,
+    normalized_len: usize,
+}
+
+impl PositionMap {
+    #[inline]
+    fn removed_before(&self, normalized_pos: usize) -> usize {
+        let idx = self
+            .removed
+            .partition_point(|(pos, _)| *pos <= normalized_pos);
+        if idx == 0 {
+            0
+        } else {
+            self.removed[idx - 1].1
+        }
+    }
+
+    /// Translate a position of the normalized sequence into one of the original sequence
+    pub fn to_original(&self, normalized_pos: usize) -> usize {
+        normalized_pos + self.removed_before(normalized_pos)
+    }
+
+    /// Translate a position of the original sequence into one of the normalized sequence.
+    /// Returns `None` if the character at that position was removed during normalization.
+    pub fn to_normalized(&self, original_pos: usize) -> Option<usize> {
+        // last run of removed characters starting at or before `original_pos`
+        let idx = self
+            .removed
+            .partition_point(|(pos, removed)| pos + removed <= original_pos);
+        let removed = if idx == 0 { 0 } else { self.removed[idx - 1].1 };
+        let normalized_pos = original_pos - removed;
+        if normalized_pos >= self.normalized_len {
+            return None;
+        }
+        // is it inside the next run of removed characters?
+        match self.removed.get(idx) {
+            Some((pos, _)) if normalized_pos >= *pos => None,
+            _ => Some(normalized_pos),
+        }
+    }
+}
+
+/// Same as `normalize` but always returns the normalized sequence along with a
+/// `PositionMap` translating coordinates between the original and normalized
+/// sequences, e.g. to report variants found in the normalized sequence.
+///
+/// ```
+/// use needletail::sequence::normalize_with_map;
+///
+/// let (seq, map) = normalize_with_map(b"acg\nTT\r\nu", false);
+/// assert_eq!(seq, b"ACGTTT");
+/// assert_eq!(map.to_original(3), 4);
+/// assert_eq!(map.to_original(5), 8);
+/// assert_eq!(map.to_normalized(8), Some(5));
+/// assert_eq!(map.to_normalized(3), None);
+/// ```
+pub fn normalize_with_map(seq: &[u8], allow_iupac: bool) -> (Vec<u8>, PositionMap) {
+    let mut buf: Vec<u8> = Vec::with_capacity(seq.len());
+    let mut map = PositionMap::default();
+    let mut removed = 0;
+    let mut in_removed_run = false;
+    let options = NormalizeOptions {
+        iupac: allow_iupac,
+        ..Default::default()
+    };
+
+    for n in seq {
+        let (new_char, _) = normalize_char(*n, &options);
+        if new_char == b' ' {
+            removed += 1;
+            in_removed_run = true;
+        } else {
+            if in_removed_run {
+                map.removed.push((buf.len(), removed));
+                in_removed_run = false;
+            }
+            buf.push(new_char);
+        }
+    }
+    if in_removed_run {
+        map.removed.push((buf.len(), removed));
+    }
+    map.normalized_len = buf.len();
+    (buf, map)
+}
+
 /// Returns the complementary base for a given IUPAC base code.
 ///
 /// Does not work for RNA sequences (maybe we should raise an error or something?)
 #[inline]
-pub fn complement(n: u8) -> u8 {
+pub const fn complement(n: u8) -> u8 {
     match n {
         b'a' => b't',
         b'A' => b'T',
@@ -104,40 +258,198 @@ pub fn complement(n: u8) -> u8 {
     }
 }
 
-/// Taking in a sequence string, return the canonical form of the sequence
-/// (e.g. the lexigraphically lowest of either the original sequence or its
-/// reverse complement)
-pub fn canonical(seq: &[u8]) -> Cow<[u8]> {
-    let mut buf: Vec<u8> = Vec::with_capacity(seq.len());
-    // enough just keeps our comparisons from happening after they need to
-    let mut enough = false;
-    let mut original_was_canonical = false;
-
-    // loop through the kmer and its reverse complement simultaneously
-    for (rn, n) in seq.iter().rev().map(|n| complement(*n)).zip(seq.iter()) {
-        buf.push(rn);
-        if !enough && (*n < rn) {
-            original_was_canonical = true;
-            break;
-        } else if !enough && (rn < *n) {
-            enough = true;
+/// Number of bases reverse complemented at once by [`extend_reverse_complement`]
+const REVCOMP_CHUNK: usize = 32;
+
+/// Replaces `seq` by its reverse complement, without allocating
+///
+/// ```
+/// use needletail::sequence::reverse_complement_in_place;
+///
+/// let mut seq = b"AACCgN".to_vec();
+/// reverse_complement_in_place(&mut seq);
+/// assert_eq!(seq, b"NcGGTT");
+/// ```
+pub fn reverse_complement_in_place(seq: &mut [u8]) {
+    seq.reverse();
+    for base in seq {
+        *base = tables::COMPLEMENT[*base as usize];
+    }
+}
+
+/// Appends the reverse complement of `seq` to `out`, each base being complemented
+/// by [`complement`].
+///
+/// This is what `Sequence::reverse_complement` uses: the bases go through
+/// [`tables::COMPLEMENT`] in fixed-size chunks, which the compiler unrolls without
+/// bounds checks, rather than one at a time through `complement`.
+///
+/// ```
+/// use needletail::sequence::extend_reverse_complement;
+///
+/// let mut out = b">".to_vec();
+/// extend_reverse_complement(&mut out, b"AACCgtN");
+/// assert_eq!(out, b">NacGGTT");
+/// ```
+pub fn extend_reverse_complement(out: &mut Vec<u8>, seq: &[u8]) {
+    let start = out.len();
+    out.resize(start + seq.len(), 0);
+    let mut dst_chunks = out[start..].chunks_exact_mut(REVCOMP_CHUNK);
+    let mut src_chunks = seq.rchunks_exact(REVCOMP_CHUNK);
+    for (dst, src) in (&mut dst_chunks).zip(&mut src_chunks) {
+        let src: &[u8; REVCOMP_CHUNK] = src.try_into().unwrap();
+        for (d, s) in dst.iter_mut().zip(src.iter().rev()) {
+            *d = tables::COMPLEMENT[*s as usize];
+        }
+    }
+    // the start of `seq` ends up at the end of `out`
+    let src_rest = src_chunks.remainder().iter().rev();
+    for (d, s) in dst_chunks.into_remainder().iter_mut().zip(src_rest) {
+        *d = tables::COMPLEMENT[*s as usize];
+    }
+}
+
+const IS_BASE: [bool; 256] = {
+    let mut table = tables::IUPAC_DNA;
+    table[b'U' as usize] = true;
+    table[b'u' as usize] = true;
+    table
+};
+
+/// A nucleotide symbol: `A`, `C`, `G`, `T`, `U`, `N` or another IUPAC code, in upper
+/// or lower case. Unlike a raw `u8` it can't be a quality character or a gap.
+///
+/// ```
+/// use needletail::sequence::Base;
+///
+/// let base = Base::new(b'r').unwrap();
+/// assert_eq!(base.complement().to_uppercase(), Base::new(b'Y').unwrap());
+/// assert!(base.is_ambiguous());
+/// assert!(Base::T.is_acgt());
+/// assert_eq!(Base::new(b'I'), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Base(u8);
+
+impl Base {
+    pub const A: Base = Base(b'A');
+    pub const C: Base = Base(b'C');
+    pub const G: Base = Base(b'G');
+    pub const T: Base = Base(b'T');
+    pub const N: Base = Base(b'N');
+
+    /// Returns `None` if `c` isn't a nucleotide symbol
+    pub const fn new(c: u8) -> Option<Self> {
+        if IS_BASE[c as usize] {
+            Some(Self(c))
+        } else {
+            None
+        }
+    }
+
+    /// The symbol, as it was given
+    pub const fn symbol(self) -> u8 {
+        self.0
+    }
+
+    pub const fn to_uppercase(self) -> Self {
+        Self(self.0.to_ascii_uppercase())
+    }
+
+    /// The complementary base (see [`complement`]), `U` is its own complement
+    pub const fn complement(self) -> Self {
+        Self(complement(self.0))
+    }
+
+    /// Whether the base is one of `ACGT` in any case
+    pub const fn is_acgt(self) -> bool {
+        matches!(self.0.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T')
+    }
+
+    /// Whether the base stands for several bases, `N` included
+    pub const fn is_ambiguous(self) -> bool {
+        !matches!(
+            self.0.to_ascii_uppercase(),
+            b'A' | b'C' | b'G' | b'T' | b'U'
+        )
+    }
+}
+
+impl From<Base> for u8 {
+    fn from(base: Base) -> u8 {
+        base.0
+    }
+}
+
+/// Which strand of a sequence is the canonical one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strand {
+    /// The sequence itself (also used for reverse complement palindromes)
+    Forward,
+    /// Its reverse complement
+    Reverse,
+}
+
+/// Returns which of `seq` or its reverse complement is lexicographically lowest,
+/// without allocating. The sequences are compared 8 bytes at a time.
+pub fn canonical_strand(seq: &[u8]) -> Strand {
+    let len = seq.len();
+    let mut i = 0;
+    // past the middle the comparison is the mirror of what was already compared
+    let half = len.div_ceil(2);
+    while i + 8 <= half {
+        let mut rc = [0; 8];
+        for (j, b) in rc.iter_mut().enumerate() {
+            *b = complement(seq[len - 1 - i - j]);
+        }
+        let fwd = u64::from_be_bytes(seq[i..i + 8].try_into().unwrap());
+        let rc = u64::from_be_bytes(rc);
+        if fwd != rc {
+            return if fwd < rc {
+                Strand::Forward
+            } else {
+                Strand::Reverse
+            };
         }
-        // unstated if branch: if rn == n, keep comparing
+        i += 8;
     }
-    match (original_was_canonical, enough) {
-        (true, true) => panic!("Bug: should never set original_was_canonical if enough == true"),
-        (true, false) => seq.into(),
-        (false, true) => buf.into(),
-        // the sequences were completely equal, return the ref
-        (false, false) => seq.into(),
+    for i in i..half {
+        let rc = complement(seq[len - 1 - i]);
+        if seq[i] != rc {
+            return if seq[i] < rc {
+                Strand::Forward
+            } else {
+                Strand::Reverse
+            };
+        }
+    }
+    Strand::Forward
+}
+
+/// Taking in a sequence string, return the canonical form of the sequence
+/// (e.g. the lexigraphically lowest of either the original sequence or its
+/// reverse complement) along with the strand it comes from.
+///
+/// The sequence is only copied if its reverse complement is the canonical one.
+pub fn canonical_with_strand(seq: &[u8]) -> (Cow<'_, [u8]>, Strand) {
+    match canonical_strand(seq) {
+        Strand::Forward => (seq.into(), Strand::Forward),
+        Strand::Reverse => (seq.reverse_complement().into(), Strand::Reverse),
     }
 }
 
+/// Taking in a sequence string, return the canonical form of the sequence
+/// (e.g. the lexigraphically lowest of either the original sequence or its
+/// reverse complement)
+pub fn canonical(seq: &[u8]) -> Cow<'_, [u8]> {
+    canonical_with_strand(seq).0
+}
+
 /// Find the lexigraphically smallest substring of `seq` of length `length`
 ///
 /// There's probably a faster algorithm for this somewhere...
-pub fn minimizer(seq: &[u8], length: usize) -> Cow<[u8]> {
-    let reverse_complement: Vec<u8> = seq.iter().rev().map(|n| complement(*n)).collect();
+pub fn minimizer(seq: &[u8], length: usize) -> Cow<'_, [u8]> {
+    let reverse_complement = seq.reverse_complement();
     let mut minmer = Cow::Borrowed(&seq[..length]);
 
     for (kmer, rc_kmer) in seq.windows(length).zip(reverse_complement.windows(length)) {
@@ -190,6 +502,27 @@ pub trait Sequence<'a> {
         new_buf.into()
     }
 
+    /// Same as `strip_returns` but always writes the sequence into `buf` (after
+    /// clearing it) to reuse its allocation across records.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// let mut buf = Vec::new();
+    /// assert_eq!(b"AC\r\nGT\n".strip_returns_into(&mut buf), b"ACGT");
+    /// ```
+    fn strip_returns_into<'b>(&'a self, buf: &'b mut Vec<u8>) -> &'b [u8] {
+        buf.clear();
+        let seq = self.sequence();
+        let mut i = 0;
+        while let Some(match_pos) = memchr2(b'\r', b'\n', &seq[i..]) {
+            buf.extend_from_slice(&seq[i..i + match_pos]);
+            i += match_pos + 1;
+        }
+        buf.extend_from_slice(&seq[i..]);
+        buf
+    }
+
     /// Returns the reverse complement of a sequence. Biologically this is
     /// equivalent to the sequence of the strand opposite the one you pass
     /// in.
@@ -200,17 +533,30 @@ pub trait Sequence<'a> {
     /// assert_eq!(b"AACC".reverse_complement(), b"GGTT");
     /// ```
     fn reverse_complement(&'a self) -> Vec<u8> {
-        self.sequence()
-            .iter()
-            .rev()
-            .map(|n| complement(*n))
-            .collect()
+        let mut rc = Vec::new();
+        extend_reverse_complement(&mut rc, self.sequence());
+        rc
+    }
+
+    /// Same as `reverse_complement` but writes into `buf` (after clearing it) to
+    /// reuse its allocation across records.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// let mut buf = Vec::new();
+    /// assert_eq!(b"AACC".reverse_complement_into(&mut buf), b"GGTT");
+    /// ```
+    fn reverse_complement_into<'b>(&'a self, buf: &'b mut Vec<u8>) -> &'b [u8] {
+        buf.clear();
+        extend_reverse_complement(buf, self.sequence());
+        buf
     }
 
     /// [Nucleic Acids] Normalizes the sequence. See documentation for
     /// `needletail::sequence::normalize`. Do not use on amino acid
-    /// sequences. Note that this returns a Cow so you may have to coerce
-    /// to a Vec<u8> or &[u8] as necessary.
+    /// sequences (see `normalize_with_alphabet` for those). Note that this
+    /// returns a Cow so you may have to coerce to a Vec<u8> or &[u8] as necessary.
     ///
     /// ```
     /// use needletail::Sequence;
@@ -231,6 +577,37 @@ pub trait Sequence<'a> {
         }
     }
 
+    /// [Nucleic Acids] Same as `normalize` but always writes the normalized
+    /// sequence into `buf` (after clearing it) to reuse its allocation across records.
+    fn normalize_into<'b>(&'a self, buf: &'b mut Vec<u8>, iupac: bool) -> &'b [u8] {
+        buf.clear();
+        let options = NormalizeOptions {
+            iupac,
+            ..Default::default()
+        };
+        for n in self.sequence() {
+            let (new_char, _) = normalize_char(*n, &options);
+            if new_char != b' ' {
+                buf.push(new_char);
+            }
+        }
+        buf
+    }
+
+    /// Normalizes the sequence for `alphabet`, see [`Alphabet::normalize`]. Unlike
+    /// `normalize`, this works for protein sequences: their residues are kept instead
+    /// of being replaced by N's.
+    ///
+    /// ```
+    /// use needletail::alphabet::Alphabet;
+    /// use needletail::Sequence;
+    ///
+    /// assert_eq!(b"mkwv\nY".normalize_with_alphabet(&Alphabet::Protein).as_ref(), b"MKWVY");
+    /// ```
+    fn normalize_with_alphabet(&'a self, alphabet: &Alphabet) -> Cow<'a, [u8]> {
+        alphabet.normalize(self.sequence())
+    }
+
     /// [Nucleic Acids] Returns an iterator over the sequence that skips
     /// non-ACGT bases and returns a tuple containing (position, the
     /// canonicalized kmer, if the sequence is the complement of the original).
@@ -238,6 +615,20 @@ pub trait Sequence<'a> {
         CanonicalKmers::new(self.sequence(), reverse_complement, k)
     }
 
+    /// [Nucleic Acids] Same as `canonical_kmers` without having to compute the
+    /// reverse complement of the sequence first, returning a lending iterator. See
+    /// `needletail::kmer::OwnedCanonicalKmers`.
+    fn canonical_kmers_owned(&'a self, k: u8) -> OwnedCanonicalKmers<'a> {
+        OwnedCanonicalKmers::new(self.sequence(), k)
+    }
+
+    /// [Nucleic Acids] Returns a lending iterator over the kmers of the
+    /// normalized sequence without allocating a normalized copy (or reverse
+    /// complement) of the sequence first. See `needletail::kmer::NormalizedKmers`.
+    fn normalized_kmers(&'a self, k: u8, canonical: bool) -> NormalizedKmers<'a> {
+        NormalizedKmers::new(self.sequence(), k, canonical)
+    }
+
     /// Returns an iterator that returns a sliding window of k-sized
     /// sequences (k-mers). Does not skip whitespace or correct bases in the
     /// original sequence so `.normalize` or `.strip_returns` may be
@@ -252,6 +643,62 @@ pub trait Sequence<'a> {
     }
 }
 
+/// Reusable buffers for the `*_into` methods of `Sequence` and `QualitySequence`,
+/// so that processing millions of records doesn't allocate for each of them.
+///
+/// The buffers are public fields so several can be borrowed at once, e.g. to
+/// hold a normalized sequence while computing its reverse complement.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::sequence::{Sequence, Workspace};
+///
+/// let mut reader = parse_fastx_reader(&b">a\nacgT\n>b\nAAG\n"[..]).unwrap();
+/// let mut ws = Workspace::new();
+/// while let Some(record) = reader.next() {
+///     let record = record.unwrap();
+///     let (norm, rc) = ws.normalize_and_reverse_complement(&record, false);
+///     for (_, kmer, _) in norm.canonical_kmers(2, rc) {
+///         assert_eq!(kmer.len(), 2);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Workspace {
+    pub normalized: Vec<u8>,
+    pub reverse_complement: Vec<u8>,
+    pub masked: Vec<u8>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Normalizes the sequence and computes the reverse complement of the result,
+    /// as needed by `canonical_kmers`
+    pub fn normalize_and_reverse_complement<'a, S: Sequence<'a> + ?Sized>(
+        &mut self,
+        seq: &'a S,
+        iupac: bool,
+    ) -> (&[u8], &[u8]) {
+        seq.normalize_into(&mut self.normalized, iupac);
+        self.normalized
+            .reverse_complement_into(&mut self.reverse_complement);
+        (&self.normalized, &self.reverse_complement)
+    }
+
+    /// Replaces the bases with a quality below `score` by N's, see
+    /// `QualitySequence::quality_mask_into`
+    pub fn quality_mask<'a, S: QualitySequence<'a> + ?Sized>(
+        &mut self,
+        seq: &'a S,
+        score: u8,
+    ) -> Result<&[u8], ParseError> {
+        seq.quality_mask_into(&mut self.masked, score)
+    }
+}
+
 impl<'a> Sequence<'a> for &'a [u8] {
     fn sequence(&'a self) -> &'a [u8] {
         self
@@ -270,44 +717,6 @@ impl<'a> Sequence<'a> for Cow<'a, [u8]> {
     }
 }
 
-/// [⚠️Unstable] A trait to wrap over sequence data that has associated
-/// quality information.
-///
-/// Will be stabilized once we figure out a good way to handle sequences that
-/// have _optional_ quality information (like `SequenceRecord`) because the
-/// return trait requires a slice from an immutable reference and
-/// `SequenceRecords` can't return that without modifying themselves.
-pub trait QualitySequence<'a>: Sequence<'a> {
-    fn quality(&'a self) -> &'a [u8];
-
-    /// Given a `SeqRecord` and a quality cutoff, mask out low-quality bases with
-    /// `N` characters.
-    fn quality_mask(&'a self, score: u8) -> Cow<'a, [u8]> {
-        let qual = self.quality();
-        // could maybe speed this up by doing a copy of base and then
-        // iterating though qual and masking?
-        let seq: Vec<u8> = self
-            .sequence()
-            .iter()
-            .zip(qual.iter())
-            .map(|(base, qual)| if *qual < score { b'N' } else { *base })
-            .collect();
-        seq.into()
-    }
-}
-
-impl<'a> Sequence<'a> for (&'a [u8], &'a [u8]) {
-    fn sequence(&'a self) -> &'a [u8] {
-        self.0
-    }
-}
-
-impl<'a> QualitySequence<'a> for (&'a [u8], &'a [u8]) {
-    fn quality(&'a self) -> &'a [u8] {
-        self.1
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -343,6 +752,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_normalize_with() {
+        let keep_all = NormalizeOptions {
+            keep_case: true,
+            keep_gap_chars: true,
+            convert_u: false,
+            iupac: true,
+        };
+        assert_eq!(
+            normalize_with(b"acgu.~-ACGU ryN", &keep_all).as_deref(),
+            Some(b"acgu.~-ACGUryN".as_slice())
+        );
+        assert_eq!(normalize_with(b"acgu.~-ACGUryN", &keep_all), None);
+        let keep_case = NormalizeOptions {
+            keep_case: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_with(b"ryRYu", &keep_case).as_deref(),
+            Some(b"nnNNt".as_slice())
+        );
+        assert_eq!(normalize_with(b"ACGT", &keep_case), None);
+    }
+
+    #[test]
+    fn test_normalize_with_map() {
+        let original = b" \tAC\n\nGT-u\r\n";
+        let (seq, map) = normalize_with_map(original, false);
+        assert_eq!(seq, b"ACGT-T");
+        for (pos, base) in seq.iter().enumerate() {
+            let original_pos = map.to_original(pos);
+            assert_eq!(
+                normalize_char(original[original_pos], &Default::default()).0,
+                *base
+            );
+            assert_eq!(map.to_normalized(original_pos), Some(pos));
+        }
+        for original_pos in [0, 1, 4, 5, 10, 11, 12] {
+            assert_eq!(map.to_normalized(original_pos), None);
+        }
+
+        let (seq, map) = normalize_with_map(b"ACGT", false);
+        assert_eq!(seq, b"ACGT");
+        assert_eq!(map.to_original(2), 2);
+        assert_eq!(map.to_normalized(3), Some(3));
+    }
+
+    #[test]
+    fn test_into_variants_reuse_buffer() {
+        let mut buf = Vec::with_capacity(16);
+        let ptr = buf.as_ptr();
+        assert_eq!(b"AC\ngu.".normalize_into(&mut buf, false), b"ACGT-");
+        assert_eq!(b"ACGT".normalize_into(&mut buf, false), b"ACGT");
+        assert_eq!(b"AAC".reverse_complement_into(&mut buf), b"GTT");
+        assert_eq!(b"A\nC\r\n".strip_returns_into(&mut buf), b"AC");
+        assert_eq!(b"ACGT".strip_returns_into(&mut buf), b"ACGT");
+        assert_eq!(buf.as_ptr(), ptr);
+
+        let mut seq = b"ACGTTn".to_vec();
+        reverse_complement_in_place(&mut seq);
+        assert_eq!(seq, b"nAACGT");
+
+        let mut ws = Workspace::new();
+        let (norm, rc) = ws.normalize_and_reverse_complement(&b"aaCG\nN"[..], false);
+        assert_eq!(norm, b"AACGN");
+        assert_eq!(rc, b"NCGTT");
+        let record = (&b"ACGT"[..], &b"I#I#"[..]);
+        assert_eq!(ws.quality_mask(&record, b'5').unwrap(), b"ANGN");
+    }
+
+    #[test]
+    fn test_extend_reverse_complement() {
+        let bases = b"ACGTacgtNnRyU-.";
+        for len in [0, 1, 31, 32, 33, 64, 100] {
+            // only ACGT, then with other bytes now and then
+            for stride in [4, 7, bases.len()] {
+                let seq: Vec<u8> = (0..len).map(|i| bases[(i * 5 + i / 3) % stride]).collect();
+                let expected: Vec<u8> = seq.iter().rev().map(|b| complement(*b)).collect();
+                let mut out = b"x".to_vec();
+                extend_reverse_complement(&mut out, &seq);
+                assert_eq!(out[1..], expected);
+            }
+        }
+    }
+
     #[test]
     fn test_complement() {
         assert_eq!(complement(b'a'), b't');
@@ -351,6 +845,23 @@ mod tests {
         assert_eq!(complement(b'n'), b'n');
     }
 
+    #[test]
+    fn test_base() {
+        let bases: Vec<_> = b"ACGTUNacgtun".iter().map(|c| Base::new(*c)).collect();
+        assert!(bases.iter().all(Option::is_some));
+        for c in [b'-', b'.', b'I', b'E', b'X', b' '] {
+            assert_eq!(Base::new(c), None);
+        }
+        assert_eq!(
+            Base::new(b'a').unwrap().complement(),
+            Base::new(b't').unwrap()
+        );
+        assert_eq!(Base::N.complement(), Base::N);
+        assert!(Base::N.is_ambiguous() && !Base::N.is_acgt());
+        assert!(!Base::new(b'u').unwrap().is_ambiguous());
+        assert_eq!(u8::from(Base::new(b'g').unwrap().to_uppercase()), b'G');
+    }
+
     #[test]
     fn can_canonicalize() {
         assert_eq!(canonical(b"A").as_ref(), b"A");
@@ -358,18 +869,41 @@ mod tests {
         assert_eq!(canonical(b"AAGT").as_ref(), b"AAGT");
         assert_eq!(canonical(b"ACTT").as_ref(), b"AAGT");
         assert_eq!(canonical(b"GC").as_ref(), b"GC");
+        assert_eq!(canonical(b"").as_ref(), b"");
     }
 
     #[test]
-    fn can_minimize() {
-        let minmer = minimizer(&b"ATTTCG"[..], 3);
-        assert_eq!(&minmer[..], b"AAA");
+    fn can_canonicalize_with_strand() {
+        // differences past the first 8 bytes and in the middle of odd lengths
+        let seq = b"ACGTACGTACGTTTTT";
+        let (canon, strand) = canonical_with_strand(seq);
+        assert_eq!(strand, Strand::Reverse);
+        assert_eq!(canon.as_ref(), seq.reverse_complement().as_slice());
+        assert!(matches!(canon, Cow::Owned(_)));
+
+        let (canon, strand) = canonical_with_strand(b"AAAAAAAAAACTTTTTTTTTT");
+        assert_eq!(strand, Strand::Forward);
+        assert!(matches!(canon, Cow::Borrowed(_)));
+        assert_eq!(canonical_strand(b"AAAAAAAAAAGTTTTTTTTTT"), Strand::Reverse);
+
+        // reverse complement palindromes are their own canonical form
+        assert_eq!(canonical_strand(b"ACGTACGTACGTACGT"), Strand::Forward);
+
+        // same result as a naive comparison
+        for seq in [
+            &b"GATTACAGATTACAGATTACA"[..],
+            b"TGCATGCATGCATGCAAA",
+            b"NNACGTnn",
+        ] {
+            let rc = seq.reverse_complement();
+            let expected = if rc.as_slice() < seq { &rc[..] } else { seq };
+            assert_eq!(canonical(seq).as_ref(), expected);
+        }
     }
 
     #[test]
-    fn test_quality_mask() {
-        let seq_rec = (&b"AGCT"[..], &b"AAA0"[..]);
-        let filtered_rec = seq_rec.quality_mask(b'5');
-        assert_eq!(&filtered_rec[..], &b"AGCN"[..]);
+    fn can_minimize() {
+        let minmer = minimizer(&b"ATTTCG"[..], 3);
+        assert_eq!(&minmer[..], b"AAA");
     }
 }
@@ -0,0 +1,75 @@
+//! Lookup tables indexed by byte, following the rules of the functions of
+//! `needletail::sequence`, for tools working on bytes in their own hot loops.
+use super::complement;
+
+/// The complement of each byte, as returned by [`complement`]: the complementary
+/// base of the IUPAC DNA codes keeping their case, other bytes (`U`, `N`, gaps...)
+/// unchanged.
+///
+/// ```
+/// use needletail::sequence::tables::COMPLEMENT;
+///
+/// let rc: Vec<u8> = b"ACGtnR".iter().rev().map(|b| COMPLEMENT[*b as usize]).collect();
+/// assert_eq!(rc, b"YnaCGT");
+/// ```
+pub const COMPLEMENT: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = complement(i as u8);
+        i += 1;
+    }
+    table
+};
+
+/// Whether each byte is an IUPAC DNA code in upper or lower case: `ACGT`, `N` and
+/// the ambiguity codes `RYSWKMBDHV`. `U` and gaps are not included.
+pub const IUPAC_DNA: [bool; 256] = {
+    let symbols = b"ACGTNRYSWKMBDHV";
+    let mut table = [false; 256];
+    let mut i = 0;
+    while i < symbols.len() {
+        table[symbols[i] as usize] = true;
+        table[symbols[i].to_ascii_lowercase() as usize] = true;
+        i += 1;
+    }
+    table
+};
+
+/// The 2-bit encoding of each byte used by the kmers of `needletail::bitkmer`:
+/// `A` is 0, `C` 1, `G` 2 and `T` 3 in either case, everything else (`N` included)
+/// is `None`.
+pub const NUC2BIT: [Option<u8>; 256] = {
+    let mut table = [None; 256];
+    table[b'A' as usize] = Some(0);
+    table[b'C' as usize] = Some(1);
+    table[b'G' as usize] = Some(2);
+    table[b'T' as usize] = Some(3);
+    table[b'a' as usize] = Some(0);
+    table[b'c' as usize] = Some(1);
+    table[b'g' as usize] = Some(2);
+    table[b't' as usize] = Some(3);
+    table
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::normalize;
+
+    #[test]
+    fn test_tables_follow_normalize() {
+        for c in 0..=255u8 {
+            let normalized =
+                normalize(&[c], true).map_or(c, |n| n.first().copied().unwrap_or(b' '));
+            if IUPAC_DNA[c as usize] {
+                assert_eq!(normalized, c.to_ascii_uppercase());
+                assert!(IUPAC_DNA[COMPLEMENT[c as usize] as usize]);
+            }
+            assert_eq!(
+                NUC2BIT[c as usize].is_some(),
+                b"ACGT".contains(&normalized) && c != b'u' && c != b'U'
+            );
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! MinHash sketches of sequences, to estimate how similar sequences are without
+//! comparing all their kmers.
+use std::collections::BTreeSet;
+
+use crate::bitkmer::bitmer_hash;
+use crate::errors::ParseError;
+use crate::parser::FastxReader;
+use crate::Sequence;
+
+/// A bottom-k MinHash sketch: the `size` smallest hashes of the canonical kmers
+/// of a sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinHash {
+    k: u8,
+    size: usize,
+    // sorted and deduplicated
+    hashes: Vec<u64>,
+}
+
+impl MinHash {
+    /// An empty sketch keeping the `size` smallest hashes of kmers of size `k`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` isn't between 1 and 32 or `size` is 0.
+    pub fn new(k: u8, size: usize) -> Self {
+        assert!((1..=32).contains(&k), "k must be between 1 and 32, got {k}");
+        assert!(size > 0, "the sketch size needs to be at least 1");
+        Self {
+            k,
+            size,
+            hashes: Vec::with_capacity(size + 1),
+        }
+    }
+
+    /// Sketches a sequence. Kmers with non-ACGT bases are skipped.
+    pub fn from_seq<'a, S: Sequence<'a> + ?Sized>(seq: &'a S, k: u8, size: usize) -> Self {
+        let mut sketch = Self::new(k, size);
+        sketch.add_seq(seq);
+        sketch
+    }
+
+    /// Adds the kmers of a sequence to the sketch
+    pub fn add_seq<'a, S: Sequence<'a> + ?Sized>(&mut self, seq: &'a S) {
+        for (_, kmer, _) in seq.strip_returns().bit_kmers(self.k, true) {
+            self.add_hash(bitmer_hash(kmer));
+        }
+    }
+
+    /// Adds the kmers of all the records of `reader` to the sketch
+    pub fn add_reader(&mut self, reader: &mut dyn FastxReader) -> Result<(), ParseError> {
+        while let Some(record) = reader.next() {
+            self.add_seq(&record?);
+        }
+        Ok(())
+    }
+
+    /// Adds a hash to the sketch
+    pub fn add_hash(&mut self, hash: u64) {
+        if self.hashes.len() == self.size && hash >= self.hashes[self.size - 1] {
+            return;
+        }
+        if let Err(pos) = self.hashes.binary_search(&hash) {
+            self.hashes.insert(pos, hash);
+            self.hashes.truncate(self.size);
+        }
+    }
+
+    /// The hashes of the sketch, in increasing order
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    /// Number of hashes shared with `other` and size of the union
+    /// among the smallest hashes of both sketches
+    fn shared_in_union(&self, other: &Self) -> (usize, usize) {
+        self.check_compatible(other);
+        let size = self.size.min(other.size);
+        let (mut i, mut j) = (0, 0);
+        let (mut shared, mut union) = (0, 0);
+        while union < size && i < self.hashes.len() && j < other.hashes.len() {
+            match self.hashes[i].cmp(&other.hashes[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    shared += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+            union += 1;
+        }
+        // one of the sketches ran out of hashes: the rest of the other one is in the union
+        union += (self.hashes.len() - i)
+            .max(other.hashes.len() - j)
+            .min(size - union);
+        (shared, union)
+    }
+
+    fn check_compatible(&self, other: &Self) {
+        assert_eq!(
+            self.k, other.k,
+            "can't compare sketches of kmers of different sizes"
+        );
+    }
+
+    /// Estimated Jaccard similarity of the kmers of both sketched sequences. Sketches
+    /// of different sizes are compared on the hashes of the smallest one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sketches are of kmers of different sizes.
+    pub fn jaccard(&self, other: &Self) -> f64 {
+        let (shared, union) = self.shared_in_union(other);
+        if union == 0 {
+            return 0.;
+        }
+        shared as f64 / union as f64
+    }
+
+    /// Estimated fraction of the kmers of this sequence also found in `other`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sketches are of kmers of different sizes.
+    pub fn containment(&self, other: &Self) -> f64 {
+        self.check_compatible(other);
+        // only the hashes in the range covered by the other sketch can be compared
+        let max = match other.hashes.last() {
+            Some(max) if other.hashes.len() == other.size => *max,
+            Some(_) => u64::MAX,
+            None => return 0.,
+        };
+        let comparable: Vec<_> = self.hashes.iter().filter(|h| **h <= max).collect();
+        if comparable.is_empty() {
+            return 0.;
+        }
+        let shared = comparable
+            .iter()
+            .filter(|h| other.hashes.binary_search(h).is_ok())
+            .count();
+        shared as f64 / comparable.len() as f64
+    }
+}
+
+/// A FracMinHash (scaled) sketch: all the hashes of the canonical kmers below
+/// `u64::MAX / scaled`, i.e. about one kmer in `scaled`. Unlike with [`MinHash`], the
+/// size of the sketch grows with the number of distinct kmers, which makes
+/// containment estimates between sequences of very different sizes accurate.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::sketch::FracMinHash;
+///
+/// let genome = b">genome\nACGTTGCATGCAGTCGATCGATCGTAGCTAGCTAGCTGATCGATGCTAGCTAGCATCGAT\n";
+/// let mut sketch = FracMinHash::new(7, 2);
+/// sketch.add_reader(&mut *parse_fastx_reader(&genome[..]).unwrap()).unwrap();
+///
+/// let read = FracMinHash::from_seq(&b"GCAGTCGATCGATCGTAGCT"[..], 7, 2);
+/// assert_eq!(read.containment(&sketch), 1.);
+/// assert!(sketch.containment(&read) < 0.5);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FracMinHash {
+    k: u8,
+    scaled: u64,
+    hashes: BTreeSet<u64>,
+}
+
+impl FracMinHash {
+    /// An empty sketch of the kmers of size `k`, keeping about one hash in `scaled`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` isn't between 1 and 32 or `scaled` is 0.
+    pub fn new(k: u8, scaled: u64) -> Self {
+        assert!((1..=32).contains(&k), "k must be between 1 and 32, got {k}");
+        assert!(scaled > 0, "the scaled factor needs to be at least 1");
+        Self {
+            k,
+            scaled,
+            hashes: BTreeSet::new(),
+        }
+    }
+
+    /// Sketches a sequence. Kmers with non-ACGT bases are skipped.
+    pub fn from_seq<'a, S: Sequence<'a> + ?Sized>(seq: &'a S, k: u8, scaled: u64) -> Self {
+        let mut sketch = Self::new(k, scaled);
+        sketch.add_seq(seq);
+        sketch
+    }
+
+    /// Adds the kmers of a sequence to the sketch
+    pub fn add_seq<'a, S: Sequence<'a> + ?Sized>(&mut self, seq: &'a S) {
+        for (_, kmer, _) in seq.strip_returns().bit_kmers(self.k, true) {
+            self.add_hash(bitmer_hash(kmer));
+        }
+    }
+
+    /// Adds the kmers of all the records of `reader` to the sketch
+    pub fn add_reader(&mut self, reader: &mut dyn FastxReader) -> Result<(), ParseError> {
+        while let Some(record) = reader.next() {
+            self.add_seq(&record?);
+        }
+        Ok(())
+    }
+
+    /// Adds a hash to the sketch if it's below the threshold
+    pub fn add_hash(&mut self, hash: u64) {
+        if hash <= self.max_hash() {
+            self.hashes.insert(hash);
+        }
+    }
+
+    /// The largest hash kept in the sketch
+    pub fn max_hash(&self) -> u64 {
+        u64::MAX / self.scaled
+    }
+
+    /// The hashes of the sketch, in increasing order
+    pub fn hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.hashes.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    pub fn scaled(&self) -> u64 {
+        self.scaled
+    }
+
+    /// Estimated number of distinct kmers of the sketched sequences
+    pub fn estimated_kmers(&self) -> u64 {
+        self.hashes.len() as u64 * self.scaled
+    }
+
+    /// Number of hashes shared with `other` and number of hashes of each sketch, among
+    /// the hashes both sketches keep when they have different scaled factors
+    fn compare(&self, other: &Self) -> (usize, usize, usize) {
+        assert_eq!(
+            self.k, other.k,
+            "can't compare sketches of kmers of different sizes"
+        );
+        let max = self.max_hash().min(other.max_hash());
+        let ours = self.hashes.range(..=max);
+        let theirs = other.hashes.range(..=max);
+        let shared = ours.clone().filter(|h| other.hashes.contains(h)).count();
+        (shared, ours.count(), theirs.count())
+    }
+
+    /// Estimated Jaccard similarity of the kmers of both sketched sequences. Sketches
+    /// with different scaled factors are compared on the hashes kept by the coarsest one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sketches are of kmers of different sizes.
+    pub fn jaccard(&self, other: &Self) -> f64 {
+        let (shared, ours, theirs) = self.compare(other);
+        let union = ours + theirs - shared;
+        if union == 0 {
+            return 0.;
+        }
+        shared as f64 / union as f64
+    }
+
+    /// Estimated fraction of the kmers of this sequence also found in `other`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sketches are of kmers of different sizes.
+    pub fn containment(&self, other: &Self) -> f64 {
+        let (shared, ours, _) = self.compare(other);
+        if ours == 0 {
+            return 0.;
+        }
+        shared as f64 / ours as f64
+    }
+}
+
+/// Converts a Jaccard similarity of the kmers of size `k` of two sequences into the
+/// Mash distance, an estimate of their mutation rate (0 for identical sequences, 1
+/// when no kmers are shared).
+///
+/// ```
+/// use needletail::sketch::mash_distance;
+///
+/// assert_eq!(mash_distance(1., 21), 0.);
+/// assert_eq!(mash_distance(0., 21), 1.);
+/// assert!((mash_distance(0.5, 21) - 0.0193).abs() < 1e-4);
+/// ```
+pub fn mash_distance(jaccard: f64, k: u8) -> f64 {
+    if jaccard <= 0. {
+        return 1.;
+    }
+    (-(2. * jaccard / (1. + jaccard)).ln() / f64::from(k)).clamp(0., 1.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minhash() {
+        let seq = b"ACGTTGCATGCAGTCGATCGATCGTAGCTAGCTAGCTGATCGATGCTAGCTAGCATCGAT";
+        let sketch = MinHash::from_seq(&seq[..], 7, 10);
+        assert_eq!(sketch.hashes().len(), 10);
+        assert!(sketch.hashes().windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sketch.jaccard(&sketch), 1.);
+
+        // the reverse complement has the same canonical kmers
+        let rc = MinHash::from_seq(&seq.reverse_complement()[..], 7, 10);
+        assert_eq!(sketch, rc);
+
+        let half = MinHash::from_seq(&seq[..30], 7, 100);
+        let full = MinHash::from_seq(&seq[..], 7, 100);
+        assert_eq!(half.containment(&full), 1.);
+        assert!(full.containment(&half) < 0.6);
+        let jaccard = half.jaccard(&full);
+        assert!(jaccard > 0.3 && jaccard < 0.6, "{jaccard}");
+
+        let other = MinHash::from_seq(&b"TTTTTTTTTTTT"[..], 7, 100);
+        assert_eq!(other.jaccard(&full), 0.);
+    }
+
+    #[test]
+    #[should_panic(expected = "k must be between 1 and 32")]
+    fn test_minhash_invalid_k() {
+        MinHash::from_seq(&b"ACGTTGCA"[..], 0, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "kmers of different sizes")]
+    fn test_minhash_different_k() {
+        let seq = b"ACGTTGCATGCAGTCGATCGATCG";
+        MinHash::from_seq(&seq[..], 7, 10).jaccard(&MinHash::from_seq(&seq[..], 8, 10));
+    }
+
+    #[test]
+    fn test_frac_minhash() {
+        // a pseudo-random sequence, without repeated kmers
+        let seq: Vec<u8> = (0..2000u64)
+            .map(|i| b"ACGT"[(bitmer_hash((i, 32)) >> 62) as usize])
+            .collect();
+        let full = FracMinHash::from_seq(&seq[..], 11, 1);
+        let all_kmers = full.len();
+        assert_eq!(full.jaccard(&full), 1.);
+        assert_eq!(
+            full,
+            FracMinHash::from_seq(&seq.reverse_complement()[..], 11, 1)
+        );
+
+        let scaled = FracMinHash::from_seq(&seq[..], 11, 4);
+        assert!(scaled.len() < all_kmers);
+        assert!(scaled.hashes().all(|h| h <= scaled.max_hash()));
+        // comparing with different scaled factors uses the hashes both keep
+        assert_eq!(scaled.jaccard(&full), 1.);
+        assert_eq!(full.containment(&scaled), 1.);
+
+        let half = FracMinHash::from_seq(&seq[..1000], 11, 1);
+        assert_eq!(half.containment(&full), 1.);
+        let containment = full.containment(&half);
+        assert!(containment > 0.4 && containment < 0.6, "{containment}");
+        assert_eq!(FracMinHash::new(11, 1).containment(&full), 0.);
+    }
+
+    #[test]
+    #[should_panic(expected = "k must be between 1 and 32")]
+    fn test_frac_minhash_invalid_k() {
+        FracMinHash::new(33, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "kmers of different sizes")]
+    fn test_frac_minhash_different_k() {
+        let seq = b"ACGTTGCATGCAGTCGATCGATCG";
+        FracMinHash::from_seq(&seq[..], 7, 1).containment(&FracMinHash::from_seq(&seq[..], 8, 1));
+    }
+}
@@ -0,0 +1,534 @@
+//! Statistics computed over sequences, e.g. for genome QC plots
+use std::collections::BTreeMap;
+
+use crate::errors::ParseError;
+use crate::parser::FastxReader;
+
+fn gc_counts(seq: &[u8]) -> (i64, i64) {
+    let mut g = 0;
+    let mut c = 0;
+    for base in seq {
+        match base {
+            b'G' | b'g' => g += 1,
+            b'C' | b'c' => c += 1,
+            _ => {}
+        }
+    }
+    (g, c)
+}
+
+/// Iterator over the GC skew of windows of a sequence, see [`gc_skew`]
+#[derive(Debug, Clone)]
+pub struct GcSkew<'a> {
+    seq: &'a [u8],
+    window: usize,
+    step: usize,
+    pos: usize,
+    // number of G and C in the current window, if it was already counted
+    counts: Option<(i64, i64)>,
+}
+
+impl Iterator for GcSkew<'_> {
+    type Item = (usize, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.pos.checked_add(self.window)?;
+        if end > self.seq.len() {
+            return None;
+        }
+        let (g, c) = match self.counts {
+            Some(counts) => counts,
+            None => gc_counts(&self.seq[self.pos..end]),
+        };
+        let pos = self.pos;
+        self.pos += self.step;
+
+        // overlapping windows: only count the bases that changed
+        self.counts = if self.step < self.window && self.pos + self.window <= self.seq.len() {
+            let (g_out, c_out) = gc_counts(&self.seq[pos..self.pos]);
+            let (g_in, c_in) = gc_counts(&self.seq[end..end + self.step]);
+            Some((g - g_out + g_in, c - c_out + c_in))
+        } else {
+            None
+        };
+
+        let skew = if g + c == 0 {
+            0.
+        } else {
+            (g - c) as f64 / (g + c) as f64
+        };
+        Some((pos, skew))
+    }
+}
+
+/// Iterates over the GC skew, `(G - C) / (G + C)`, of windows of `window` bases moving
+/// by `step` bases, along with the position of their start. Windows without any G or C
+/// have a skew of 0 and trailing bases that don't fill a whole window are ignored.
+///
+/// The sign of the skew changes around the origin and terminus of replication
+/// of bacterial genomes, see [`cumulative_gc_skew`] to locate them.
+/// Panics if `window` or `step` is 0.
+///
+/// ```
+/// use needletail::stats::gc_skew;
+///
+/// let skews: Vec<_> = gc_skew(b"GGGCAACCCG", 4, 3).collect();
+/// assert_eq!(skews, vec![(0, 0.5), (3, -1.0), (6, -0.5)]);
+/// ```
+pub fn gc_skew(seq: &[u8], window: usize, step: usize) -> GcSkew<'_> {
+    assert!(window > 0, "window needs to be at least 1");
+    assert!(step > 0, "step needs to be at least 1");
+    GcSkew {
+        seq,
+        window,
+        step,
+        pos: 0,
+        counts: None,
+    }
+}
+
+/// Running sum of the GC skews of [`gc_skew`]: its minimum and maximum are at
+/// the origin and terminus of replication of bacterial genomes.
+///
+/// ```
+/// use needletail::stats::cumulative_gc_skew;
+///
+/// let cumulative: Vec<_> = cumulative_gc_skew(b"GGGCAACCCG", 4, 3).collect();
+/// assert_eq!(cumulative, vec![(0, 0.5), (3, -0.5), (6, -1.0)]);
+/// ```
+pub fn cumulative_gc_skew(
+    seq: &[u8],
+    window: usize,
+    step: usize,
+) -> impl Iterator<Item = (usize, f64)> + '_ {
+    gc_skew(seq, window, step).scan(0., |total, (pos, skew)| {
+        *total += skew;
+        Some((pos, *total))
+    })
+}
+
+/// Totals over the records of a file, see [`parallel_file_stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileStats {
+    pub records: u64,
+    pub bases: u64,
+    /// Length of the shortest record, 0 if there are none
+    pub min_length: u64,
+    pub max_length: u64,
+    /// Number of `G`/`C` bases, in either case
+    pub gc_bases: u64,
+    /// Number of `N` bases, in either case
+    pub n_bases: u64,
+    /// Sum of the Phred+33 scores of all the bases, 0 for FASTA
+    pub quality_sum: u64,
+}
+
+impl FileStats {
+    /// Adds a record to the totals
+    pub fn add(&mut self, seq: &[u8], qual: Option<&[u8]>) {
+        let length = seq.len() as u64;
+        self.min_length = if self.records == 0 {
+            length
+        } else {
+            self.min_length.min(length)
+        };
+        self.max_length = self.max_length.max(length);
+        self.records += 1;
+        self.bases += length;
+        for base in seq {
+            match base {
+                b'G' | b'g' | b'C' | b'c' => self.gc_bases += 1,
+                b'N' | b'n' => self.n_bases += 1,
+                _ => {}
+            }
+        }
+        if let Some(qual) = qual {
+            self.quality_sum += qual
+                .iter()
+                .map(|q| u64::from(q.saturating_sub(33)))
+                .sum::<u64>();
+        }
+    }
+
+    /// Adds the totals of `other`, e.g. computed over another part of the file
+    pub fn merge(&mut self, other: &FileStats) {
+        if other.records == 0 {
+            return;
+        }
+        self.min_length = if self.records == 0 {
+            other.min_length
+        } else {
+            self.min_length.min(other.min_length)
+        };
+        self.max_length = self.max_length.max(other.max_length);
+        self.records += other.records;
+        self.bases += other.bases;
+        self.gc_bases += other.gc_bases;
+        self.n_bases += other.n_bases;
+        self.quality_sum += other.quality_sum;
+    }
+
+    /// Fraction of G/C among the bases, 0 if there are none
+    pub fn gc_content(&self) -> f64 {
+        if self.bases == 0 {
+            0.0
+        } else {
+            self.gc_bases as f64 / self.bases as f64
+        }
+    }
+
+    /// Mean Phred score of the bases, 0 if there are none
+    pub fn mean_quality(&self) -> f64 {
+        if self.bases == 0 {
+            0.0
+        } else {
+            self.quality_sum as f64 / self.bases as f64
+        }
+    }
+}
+
+/// Summary of all the records of a file computed in a single pass by [`summarize`]:
+/// the [`FileStats`] totals along with the distribution of the lengths, for N50 and
+/// the like, and the mean quality at each position of the reads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SummaryStats {
+    pub totals: FileStats,
+    /// Number of records of each length
+    length_counts: BTreeMap<u64, u64>,
+    /// Sum of the Phred+33 scores and number of bases at each position of the reads
+    position_quality_sums: Vec<u64>,
+    position_bases: Vec<u64>,
+}
+
+impl SummaryStats {
+    /// Adds a record to the statistics
+    pub fn add(&mut self, seq: &[u8], qual: Option<&[u8]>) {
+        self.totals.add(seq, qual);
+        *self.length_counts.entry(seq.len() as u64).or_default() += 1;
+        if let Some(qual) = qual {
+            if qual.len() > self.position_bases.len() {
+                self.position_quality_sums.resize(qual.len(), 0);
+                self.position_bases.resize(qual.len(), 0);
+            }
+            for (i, q) in qual.iter().enumerate() {
+                self.position_quality_sums[i] += u64::from(q.saturating_sub(33));
+                self.position_bases[i] += 1;
+            }
+        }
+    }
+
+    /// Adds the statistics of `other`, e.g. computed over another file
+    pub fn merge(&mut self, other: &SummaryStats) {
+        self.totals.merge(&other.totals);
+        for (length, count) in &other.length_counts {
+            *self.length_counts.entry(*length).or_default() += count;
+        }
+        if other.position_bases.len() > self.position_bases.len() {
+            self.position_quality_sums
+                .resize(other.position_bases.len(), 0);
+            self.position_bases.resize(other.position_bases.len(), 0);
+        }
+        for (i, (sum, bases)) in other
+            .position_quality_sums
+            .iter()
+            .zip(&other.position_bases)
+            .enumerate()
+        {
+            self.position_quality_sums[i] += sum;
+            self.position_bases[i] += bases;
+        }
+    }
+
+    /// Mean length of the records, 0 if there are none
+    pub fn mean_length(&self) -> f64 {
+        if self.totals.records == 0 {
+            0.0
+        } else {
+            self.totals.bases as f64 / self.totals.records as f64
+        }
+    }
+
+    /// Length of the shortest record such that the records at least as long hold at
+    /// least `x`% of the bases (e.g. `nx(50)` is the N50), 0 if there are no bases.
+    /// `x` is capped at 100.
+    pub fn nx(&self, x: u8) -> u64 {
+        let target = self.totals.bases * u64::from(x.min(100));
+        let mut covered = 0;
+        for (length, count) in self.length_counts.iter().rev() {
+            covered += length * count;
+            if covered * 100 >= target {
+                return *length;
+            }
+        }
+        0
+    }
+
+    /// Length of the shortest record such that the records at least as long hold at
+    /// least half of the bases
+    pub fn n50(&self) -> u64 {
+        self.nx(50)
+    }
+
+    /// Same as [`SummaryStats::n50`] for 90% of the bases
+    pub fn n90(&self) -> u64 {
+        self.nx(90)
+    }
+
+    /// Number of records of each length, from the shortest to the longest
+    pub fn length_counts(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.length_counts.iter().map(|(l, c)| (*l, *c))
+    }
+
+    /// Mean Phred score of the bases at each position of the reads (the first base
+    /// of all the reads, then the second one...), empty for FASTA
+    pub fn position_quality_means(&self) -> Vec<f64> {
+        self.position_quality_sums
+            .iter()
+            .zip(&self.position_bases)
+            .map(|(sum, bases)| *sum as f64 / *bases as f64)
+            .collect()
+    }
+}
+
+/// Computes the [`SummaryStats`] of all the remaining records of `reader`
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::stats::summarize;
+///
+/// let fastq = b"@r1\nACGTAC\n+\nIII555\n@r2\nGGN\n+\nI++\n@r3\nAT\n+\nII\n";
+/// let stats = summarize(&mut parse_fastx_reader(&fastq[..]).unwrap()).unwrap();
+/// assert_eq!((stats.totals.records, stats.totals.bases), (3, 11));
+/// assert_eq!((stats.totals.min_length, stats.totals.max_length), (2, 6));
+/// assert_eq!((stats.n50(), stats.n90()), (6, 2));
+/// assert_eq!(stats.totals.gc_content(), 5.0 / 11.0);
+/// assert_eq!(stats.position_quality_means()[..3], [40.0, 30.0, 25.0]);
+/// ```
+pub fn summarize(reader: &mut dyn FastxReader) -> Result<SummaryStats, ParseError> {
+    let mut stats = SummaryStats::default();
+    while let Some(record) = reader.next() {
+        let record = record?;
+        stats.add(&record.seq(), record.qual());
+    }
+    Ok(stats)
+}
+
+/// Computes the [`FileStats`] of a (possibly compressed) FASTA/FASTQ file using all the
+/// `n_threads` given, for large files on fast disks where a single thread is the
+/// bottleneck.
+///
+/// The work is split in a pipeline: one thread decompresses the file (gzip, bzip2, xz
+/// and zstd streams can't be decompressed in parallel), the calling thread parses it
+/// into batches of records and `n_threads` worker threads compute the totals of the
+/// batches, merged at the end. Use 1 thread for plain files on slow disks.
+///
+/// ```no_run
+/// use needletail::stats::parallel_file_stats;
+///
+/// let stats = parallel_file_stats("reads.fastq.gz", 8).unwrap();
+/// println!("{} reads, mean quality {:.1}", stats.records, stats.mean_quality());
+/// ```
+#[cfg(feature = "parallel")]
+pub fn parallel_file_stats<P: AsRef<std::path::Path>>(
+    path: P,
+    n_threads: usize,
+) -> Result<FileStats, ParseError> {
+    parallel_reader_stats(crate::parser::open_file(path.as_ref())?, n_threads)
+}
+
+/// Same as [`parallel_file_stats`] for any stream, e.g. a network one
+///
+/// ```
+/// use needletail::stats::parallel_reader_stats;
+///
+/// let fastq = b"@r1\nACGN\n+\n5555\n@r2\nGG\n+\n++\n";
+/// let stats = parallel_reader_stats(&fastq[..], 2).unwrap();
+/// assert_eq!((stats.records, stats.bases, stats.min_length), (2, 6, 2));
+/// assert_eq!((stats.gc_bases, stats.n_bases), (4, 1));
+/// assert_eq!(stats.mean_quality(), 100.0 / 6.0);
+/// ```
+#[cfg(feature = "parallel")]
+pub fn parallel_reader_stats<R: std::io::Read + Send>(
+    reader: R,
+    n_threads: usize,
+) -> Result<FileStats, ParseError> {
+    use std::io::{self, Read};
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+
+    use crate::parse_fastx_reader;
+    use crate::parser::{decompress_reader, BoundedBatcher, OwnedRecord};
+
+    const CHUNK_BYTES: usize = 1024 * 1024;
+    const BATCH_BYTES: usize = 4 * 1024 * 1024;
+
+    /// Reads the decompressed chunks sent by the decompression thread
+    struct ChunkReader {
+        receiver: mpsc::Receiver<io::Result<Vec<u8>>>,
+        chunk: io::Cursor<Vec<u8>>,
+    }
+
+    impl Read for ChunkReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            loop {
+                let n = self.chunk.read(buf)?;
+                if n > 0 || buf.is_empty() {
+                    return Ok(n);
+                }
+                match self.receiver.recv() {
+                    Ok(chunk) => self.chunk = io::Cursor::new(chunk?),
+                    // the whole file was read
+                    Err(_) => return Ok(0),
+                }
+            }
+        }
+    }
+
+    let n_threads = n_threads.max(1);
+    let (decompressed, _) = decompress_reader(reader)?;
+    let (chunk_sender, chunk_receiver) = mpsc::sync_channel(4);
+    let (batch_sender, batch_receiver) = mpsc::sync_channel::<Vec<OwnedRecord>>(2 * n_threads);
+    let batch_receiver = Mutex::new(batch_receiver);
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut decompressed = decompressed;
+            loop {
+                let mut chunk = Vec::with_capacity(CHUNK_BYTES);
+                match (&mut decompressed)
+                    .take(CHUNK_BYTES as u64)
+                    .read_to_end(&mut chunk)
+                {
+                    Ok(0) => return,
+                    // stop if the parser stopped
+                    Ok(_) => {
+                        if chunk_sender.send(Ok(chunk)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = chunk_sender.send(Err(e));
+                        return;
+                    }
+                }
+            }
+        });
+
+        let workers: Vec<_> = (0..n_threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut stats = FileStats::default();
+                    loop {
+                        // the lock is released as soon as a batch is received
+                        let batch = match batch_receiver.lock().unwrap().recv() {
+                            Ok(batch) => batch,
+                            Err(_) => return stats,
+                        };
+                        for record in &batch {
+                            stats.add(&record.seq, record.qual.as_deref());
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let chunks = ChunkReader {
+            receiver: chunk_receiver,
+            chunk: io::Cursor::new(Vec::new()),
+        };
+        let read_result = parse_fastx_reader(chunks).and_then(|mut reader| {
+            let mut batcher = BoundedBatcher::new(BATCH_BYTES);
+            while let Some(batch) = batcher.next_batch(&mut reader)? {
+                if batch_sender.send(batch).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        // closing the channels stops the other threads once they're done
+        drop(batch_sender);
+
+        let mut stats = FileStats::default();
+        for worker in workers {
+            stats.merge(&worker.join().expect("stats thread panicked"));
+        }
+        read_result.map(|_| stats)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gc_skew_matches_naive_computation() {
+        let seq = b"ATGCGGGCTTAGCCCCgcgNNNAAGGGCTTacg";
+        for (window, step) in [(1, 1), (5, 1), (5, 2), (4, 4), (3, 7)] {
+            let expected: Vec<_> = (0..)
+                .map(|i| i * step)
+                .take_while(|pos| pos + window <= seq.len())
+                .map(|pos| {
+                    let (g, c) = gc_counts(&seq[pos..pos + window]);
+                    let skew = if g + c == 0 {
+                        0.
+                    } else {
+                        (g - c) as f64 / (g + c) as f64
+                    };
+                    (pos, skew)
+                })
+                .collect();
+            assert_eq!(gc_skew(seq, window, step).collect::<Vec<_>>(), expected);
+        }
+        assert_eq!(gc_skew(b"ACG", 4, 1).count(), 0);
+    }
+
+    #[test]
+    fn test_summary_stats() {
+        let mut stats = SummaryStats::default();
+        assert_eq!((stats.n50(), stats.mean_length()), (0, 0.0));
+        for length in [2, 3, 4, 5, 6] {
+            stats.add(&vec![b'A'; length], None);
+        }
+        assert_eq!(stats.mean_length(), 4.0);
+        // 20 bases: 6 + 5 >= 10 and 6 + 5 + 4 + 3 >= 18
+        assert_eq!(
+            (stats.nx(0), stats.n50(), stats.n90(), stats.nx(100)),
+            (6, 5, 3, 2)
+        );
+        assert!(stats.position_quality_means().is_empty());
+
+        let mut other = SummaryStats::default();
+        other.add(b"ACG", Some(b"+5I"));
+        other.add(b"A", Some(b"I"));
+        stats.merge(&other);
+        assert_eq!(stats.totals.records, 7);
+        assert_eq!(
+            stats.length_counts().collect::<Vec<_>>(),
+            [(1, 1), (2, 1), (3, 2), (4, 1), (5, 1), (6, 1)]
+        );
+        assert_eq!(stats.position_quality_means(), [25.0, 20.0, 40.0]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_file_stats() {
+        use crate::parse_fastx_file;
+
+        for path in [
+            "tests/data/PRJNA271013_head.fq",
+            "tests/data/28S.fasta",
+            "tests/data/test.fa.gz",
+        ] {
+            let mut expected = FileStats::default();
+            let mut reader = parse_fastx_file(path).unwrap();
+            while let Some(record) = reader.next() {
+                let record = record.unwrap();
+                expected.add(&record.seq(), record.qual());
+            }
+            for n_threads in [1, 4] {
+                assert_eq!(parallel_file_stats(path, n_threads).unwrap(), expected);
+            }
+        }
+        assert!(parallel_file_stats("tests/data/bad_header.fastq", 2).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Trimming reads
+use crate::parser::OwnedRecord;
+use crate::sequence::complement;
+
+/// Adapters found at the 3' end of R1 and R2 when the read runs past the insert
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdapterPair<'a> {
+    pub r1: &'a [u8],
+    pub r2: &'a [u8],
+}
+
+/// Illumina TruSeq adapters
+pub const TRUSEQ: AdapterPair<'static> = AdapterPair {
+    r1: b"AGATCGGAAGAGCACACGTCTGAACTCCAGTCA",
+    r2: b"AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGT",
+};
+
+/// Illumina Nextera adapters
+pub const NEXTERA: AdapterPair<'static> = AdapterPair {
+    r1: b"CTGTCTCTTATACACATCT",
+    r2: b"CTGTCTCTTATACACATCT",
+};
+
+/// Shortest insert detected by [`read_through`]
+const MIN_INSERT: usize = 12;
+/// Mismatches allowed per 10 bases in the overlap and in the adapters
+const MISMATCHES_PER_10: usize = 1;
+
+/// Whether `a` and `b` differ in at most 1 base every 10
+fn similar(a: &[u8], b: impl Iterator<Item = u8>) -> bool {
+    let max = a.len() * MISMATCHES_PER_10 / 10;
+    let mut mismatches = 0;
+    for (x, y) in a.iter().zip(b) {
+        if !x.eq_ignore_ascii_case(&y) {
+            mismatches += 1;
+            if mismatches > max {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Whether `seq` starts with (a prefix of) `adapter`
+fn starts_with_adapter(seq: &[u8], adapter: &[u8]) -> bool {
+    let len = seq.len().min(adapter.len());
+    similar(&seq[..len], adapter[..len].iter().copied())
+}
+
+fn truncate(record: &mut OwnedRecord, len: usize) {
+    record.seq.truncate(len);
+    if let Some(qual) = record.qual.as_mut() {
+        qual.truncate(len);
+    }
+}
+
+/// Detects a pair of mates read through a fragment shorter than them, and trims both
+/// to the fragment. Returns the length of the fragment if they were trimmed.
+///
+/// The mates read through if the start of R1 is the reverse complement of the start
+/// of R2 over the length of the fragment (at least 12 bases) and both continue with
+/// their adapter. Up to 1 mismatch every 10 bases is allowed in both checks.
+///
+/// ```
+/// use needletail::parser::OwnedRecord;
+/// use needletail::trim::{read_through, TRUSEQ};
+///
+/// let read = |seq: &[u8]| OwnedRecord { id: b"r".to_vec(), seq: seq.to_vec(), qual: None };
+/// // a 16 bases fragment read by 30 bases mates
+/// let mut r1 = read(b"ACGTTGCATGCAGTCAAGATCGGAAGAGCA");
+/// let mut r2 = read(b"TGACTGCATGCAACGTAGATCGGAAGAGCG");
+/// assert_eq!(read_through((&mut r1, &mut r2), &TRUSEQ), Some(16));
+/// assert_eq!(r1.seq, b"ACGTTGCATGCAGTCA");
+/// assert_eq!(r2.seq, b"TGACTGCATGCAACGT");
+/// ```
+pub fn read_through(
+    pair: (&mut OwnedRecord, &mut OwnedRecord),
+    adapters: &AdapterPair,
+) -> Option<usize> {
+    let (r1, r2) = pair;
+    let max_insert = r1.seq.len().min(r2.seq.len()).checked_sub(1)?;
+    // longest overlaps first: short ones are more likely to match by chance
+    let insert = (MIN_INSERT..=max_insert).rev().find(|len| {
+        similar(
+            &r1.seq[..*len],
+            r2.seq[..*len].iter().rev().map(|b| complement(*b)),
+        ) && starts_with_adapter(&r1.seq[*len..], adapters.r1)
+            && starts_with_adapter(&r2.seq[*len..], adapters.r2)
+    })?;
+    truncate(r1, insert);
+    truncate(r2, insert);
+    Some(insert)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sequence;
+
+    #[test]
+    fn test_read_through() {
+        let fragment = b"ACGTTGCATGCAGTCGATCGGTACCA";
+        let read = |fragment: &[u8], adapter: &[u8], qual: bool| {
+            let mut seq = fragment.to_vec();
+            seq.extend_from_slice(adapter);
+            seq.truncate(40);
+            OwnedRecord {
+                id: b"r".to_vec(),
+                qual: qual.then(|| vec![b'I'; seq.len()]),
+                seq,
+            }
+        };
+        let mut r1 = read(fragment, NEXTERA.r1, true);
+        let mut r2 = read(&fragment.reverse_complement(), NEXTERA.r2, true);
+        // a sequencing error in the fragment and one in the adapter
+        r1.seq[3] = b'A';
+        r2.seq[30] = b'A';
+        assert_eq!(read_through((&mut r1, &mut r2), &NEXTERA), Some(26));
+        assert_eq!(r1.qual.unwrap().len(), 26);
+        assert_eq!(r2.seq, fragment.reverse_complement());
+
+        // the mates overlap but there is no adapter
+        let mut r1 = read(fragment, b"TTTTTTTTTTTTTTTTTT", false);
+        let mut r2 = read(&fragment.reverse_complement(), NEXTERA.r2, false);
+        assert_eq!(read_through((&mut r1, &mut r2), &NEXTERA), None);
+        assert_eq!(r1.seq.len(), 40);
+
+        // fragment longer than the reads
+        let mut r1 = read(b"ACGT", b"", false);
+        let mut r2 = read(b"ACGT", b"", false);
+        assert_eq!(read_through((&mut r1, &mut r2), &NEXTERA), None);
+    }
+}
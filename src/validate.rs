@@ -0,0 +1,225 @@
+//! Quick structural checks of files, much faster than parsing every record when
+//! all you want to know is whether a file is well-formed and how many reads it has.
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use memchr::memchr;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::{decompress_reader, open_file, Format};
+
+const BUFSIZE: usize = 1024 * 1024;
+
+/// What [`fastq_quick`] found in a valid file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Number of records
+    pub records: u64,
+    /// Total number of bases
+    pub bases: u64,
+    /// Number of lines, blank ones included
+    pub lines: u64,
+}
+
+/// State of the scanner, kept across buffer boundaries
+struct Scanner {
+    summary: Summary,
+    // which line of the record we're on: header, sequence, separator or quality
+    record_line: u8,
+    // length and first/last bytes of the line being read
+    line_len: usize,
+    first_byte: Option<u8>,
+    last_byte: Option<u8>,
+    // header of the current record, for error messages
+    header: Vec<u8>,
+    seq_len: usize,
+}
+
+impl Scanner {
+    fn new() -> Self {
+        Self {
+            summary: Summary::default(),
+            record_line: 0,
+            line_len: 0,
+            first_byte: None,
+            last_byte: None,
+            header: Vec::new(),
+            seq_len: 0,
+        }
+    }
+
+    /// Adds a piece of the current line
+    fn extend_line(&mut self, piece: &[u8]) {
+        if piece.is_empty() {
+            return;
+        }
+        if self.line_len == 0 {
+            self.first_byte = Some(piece[0]);
+        }
+        self.line_len += piece.len();
+        self.last_byte = piece.last().copied();
+        if self.record_line == 0 {
+            self.header.extend_from_slice(piece);
+        }
+    }
+
+    fn error_position(&self, with_id: bool) -> ErrorPosition {
+        let id = if with_id {
+            let header = self.header.strip_prefix(b"@").unwrap_or(&self.header);
+            let id = header.split(|b| *b == b' ').next().unwrap_or_default();
+            Some(String::from_utf8_lossy(id.trim_ascii_end()).into())
+        } else {
+            None
+        };
+        ErrorPosition {
+            line: self.summary.lines,
+            id,
+        }
+    }
+
+    /// Checks the line that was just read in full
+    fn end_line(&mut self) -> Result<(), ParseError> {
+        self.summary.lines += 1;
+        let len = self.line_len - usize::from(self.last_byte == Some(b'\r'));
+        let first_byte = self.first_byte;
+        self.line_len = 0;
+        self.first_byte = None;
+        self.last_byte = None;
+
+        match self.record_line {
+            0 => {
+                // blank lines are allowed between records
+                if len == 0 {
+                    self.header.clear();
+                    return Ok(());
+                }
+                if first_byte != Some(b'@') {
+                    return Err(ParseError::new_invalid_start(
+                        first_byte.unwrap_or_default(),
+                        self.error_position(false),
+                        Format::Fastq,
+                    ));
+                }
+            }
+            1 => self.seq_len = len,
+            2 => {
+                if first_byte != Some(b'+') {
+                    return Err(ParseError::new_invalid_separator(
+                        first_byte.unwrap_or_default(),
+                        self.error_position(true),
+                    ));
+                }
+            }
+            _ => {
+                if len != self.seq_len {
+                    return Err(ParseError::new_unequal_length(
+                        self.seq_len,
+                        len,
+                        self.error_position(true),
+                    ));
+                }
+                self.summary.records += 1;
+                self.summary.bases += len as u64;
+                self.header.clear();
+            }
+        }
+        self.record_line = (self.record_line + 1) % 4;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Summary, ParseError> {
+        if self.line_len > 0 {
+            // last line without a line ending
+            self.end_line()?;
+        }
+        if self.record_line != 0 {
+            return Err(ParseError::new_unexpected_end(
+                self.error_position(true),
+                Format::Fastq,
+            ));
+        }
+        Ok(self.summary)
+    }
+}
+
+/// Same as [`fastq_quick`] for anything implementing `Read`
+pub fn fastq_quick_reader<R: io::Read + Send>(reader: R) -> Result<Summary, ParseError> {
+    let (reader, _) = decompress_reader(reader)?;
+    let mut reader = io::BufReader::with_capacity(BUFSIZE, reader);
+    let mut scanner = Scanner::new();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        let len = buf.len();
+        let mut start = 0;
+        while let Some(end) = memchr(b'\n', &buf[start..]) {
+            scanner.extend_line(&buf[start..start + end]);
+            scanner.end_line()?;
+            start += end + 1;
+        }
+        scanner.extend_line(&buf[start..]);
+        reader.consume(len);
+    }
+    scanner.finish()
+}
+
+/// Checks that a (possibly compressed) FASTQ file is structurally valid and counts its
+/// records, without parsing them: every record needs to be 4 lines, starting with `@`
+/// and `+`, with sequence and quality of the same length. Their content isn't checked.
+///
+/// The error returned for an invalid file has the line number of the first
+/// offending line, and the id of its record if it could be read.
+///
+/// ```
+/// use needletail::validate::fastq_quick_reader;
+///
+/// let summary = fastq_quick_reader(&b"@a\nACGT\n+\nIIII\n@b\nAC\n+\nII\n"[..]).unwrap();
+/// assert_eq!((summary.records, summary.bases), (2, 6));
+///
+/// let err = fastq_quick_reader(&b"@a\nACGT\n+\nIIII\n@b\nAC\n+\nI\n"[..]).unwrap_err();
+/// assert_eq!(err.position.line, 8);
+/// assert_eq!(err.position.id.as_deref(), Some("b"));
+/// ```
+pub fn fastq_quick<P: AsRef<Path>>(path: P) -> Result<Summary, ParseError> {
+    fastq_quick_reader(open_file(path.as_ref())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+
+    #[test]
+    fn test_fastq_quick() {
+        let summary =
+            fastq_quick_reader(&b"@a x\r\nACGT\r\n+\r\nIIII\r\n\n@b\nAC\n+\nII"[..]).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                records: 2,
+                bases: 6,
+                lines: 9
+            }
+        );
+
+        let summary = fastq_quick("tests/data/PRJNA271013_head.fq").unwrap();
+        assert_eq!(summary.records, 2000);
+
+        for (data, kind, line) in [
+            (&b">a\nACGT\n"[..], ParseErrorKind::InvalidStart, 1),
+            (b"@a\nACGT\n-\nIIII\n", ParseErrorKind::InvalidSeparator, 3),
+            (b"@a\nACGT\n+\n", ParseErrorKind::UnexpectedEnd, 3),
+            (
+                b"@a\nACGT\n+\nIIII\nACGT\n",
+                ParseErrorKind::InvalidStart,
+                5,
+            ),
+        ] {
+            let err = fastq_quick_reader(data).unwrap_err();
+            assert_eq!(err.kind, kind);
+            assert_eq!(err.position.line, line);
+        }
+    }
+}